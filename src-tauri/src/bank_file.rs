@@ -0,0 +1,337 @@
+//! Bulk bank payment files.
+//!
+//! Builds the transfer files banks accept for bulk uploads: a generic CSV and an
+//! ISO 20022 `pain.001.001.03` customer credit transfer initiation. Payments are
+//! checked with [`validate`] first so a file is never written with missing or
+//! malformed bank details.
+
+use crate::models::{BankPayment, Settings};
+
+/// What the payments are for; sets the pain.001 category purpose code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Purpose {
+    Salary,
+    Supplier,
+}
+
+impl Purpose {
+    fn code(self) -> &'static str {
+        match self {
+            Purpose::Salary => "SALA",
+            Purpose::Supplier => "SUPP",
+        }
+    }
+
+    /// Message ids must be unique per upload; the time suffix keeps re-exports apart.
+    fn message_id(self, execution_date: &str) -> String {
+        let prefix = if self == Purpose::Salary { "SAL" } else { "SUP" };
+        format!("{}-{}-{}", prefix, execution_date.replace('-', ""), chrono::Local::now().format("%H%M%S"))
+    }
+}
+
+/// The business account the payments are drawn from, taken from the bank details in settings.
+#[derive(Debug, Clone)]
+pub struct Debtor {
+    pub name: String,
+    pub bank_name: Option<String>,
+    pub bank_branch: Option<String>,
+    pub account_no: Option<String>,
+}
+
+impl Debtor {
+    pub fn from_settings(s: &Settings) -> Self {
+        Debtor {
+            name: s.bank_account_name.clone()
+                .filter(|n| !n.trim().is_empty())
+                .unwrap_or_else(|| s.business_name.clone()),
+            bank_name: s.bank_name.clone(),
+            bank_branch: s.bank_branch.clone(),
+            account_no: s.bank_account_no.clone(),
+        }
+    }
+}
+
+fn blank(v: &Option<String>) -> bool {
+    v.as_deref().is_none_or(|s| s.trim().is_empty())
+}
+
+/// Account numbers as sent to the bank: spaces removed.
+fn account_id(v: &Option<String>) -> String {
+    v.as_deref().unwrap_or("").chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+fn valid_account_no(v: &Option<String>) -> bool {
+    let id = account_id(v);
+    !id.is_empty() && id.len() <= 34 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Checks every payment (and, for pain.001, the debtor and currency) and returns
+/// all problems at once rather than stopping at the first.
+pub fn validate(
+    format: &str,
+    payments: &[BankPayment],
+    debtor: &Debtor,
+    currency: &str,
+    execution_date: &str,
+) -> Result<(), String> {
+    let mut errors = Vec::new();
+    if format != "CSV" && format != "pain.001" {
+        return Err(format!("Unsupported payment file format: {}", format));
+    }
+    if payments.is_empty() {
+        errors.push("There are no payments to export".to_string());
+    }
+    if chrono::NaiveDate::parse_from_str(execution_date, "%Y-%m-%d").is_err() {
+        errors.push(format!("Invalid payment date {} (expected YYYY-MM-DD)", execution_date));
+    }
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+        errors.push(format!("Currency must be a 3-letter ISO code such as LKR or USD, not \"{}\"", currency));
+    }
+    if format == "pain.001" {
+        if debtor.name.trim().is_empty() {
+            errors.push("Business bank account name is missing in settings".to_string());
+        }
+        if blank(&debtor.bank_name) {
+            errors.push("Business bank name is missing in settings".to_string());
+        }
+        if !valid_account_no(&debtor.account_no) {
+            errors.push("Business bank account number in settings is missing or invalid".to_string());
+        }
+    }
+
+    let mut references = std::collections::HashSet::new();
+    for p in payments {
+        let who = if p.payee_name.trim().is_empty() { "(unnamed payee)" } else { p.payee_name.trim() };
+        if p.payee_name.trim().is_empty() {
+            errors.push("A payment has no payee name".to_string());
+        } else if p.payee_name.chars().count() > 140 {
+            errors.push(format!("{}: payee name is longer than 140 characters", who));
+        }
+        if blank(&p.bank_name) {
+            errors.push(format!("{}: bank name is missing", who));
+        }
+        if !valid_account_no(&p.account_no) {
+            errors.push(format!("{}: bank account number is missing or invalid", who));
+        }
+        if !p.amount.is_finite() || p.amount <= 0.0 {
+            errors.push(format!("{}: amount must be greater than zero", who));
+        } else if (p.amount * 100.0 - (p.amount * 100.0).round()).abs() > 1e-6 {
+            errors.push(format!("{}: amount has more than 2 decimal places", who));
+        }
+        if p.reference.is_empty() || p.reference.len() > 35 {
+            errors.push(format!("{}: payment reference must be 1-35 characters", who));
+        } else if !references.insert(p.reference.as_str()) {
+            errors.push(format!("{}: duplicate payment reference {}", who, p.reference));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Payment file not written:\n{}", errors.join("\n")))
+    }
+}
+
+pub fn write_csv(path: &str, payments: &[BankPayment], currency: &str, execution_date: &str) -> Result<(), String> {
+    let mut wtr = csv::Writer::from_path(path).map_err(|e| e.to_string())?;
+    wtr.write_record(["Payee Name", "Bank", "Branch", "Account Number", "Amount", "Currency", "Payment Date", "Reference", "Remittance Info"])
+        .map_err(|e| e.to_string())?;
+    for p in payments {
+        wtr.write_record([
+            p.payee_name.trim(),
+            p.bank_name.as_deref().unwrap_or("").trim(),
+            p.bank_branch.as_deref().unwrap_or("").trim(),
+            &account_id(&p.account_no),
+            &format!("{:.2}", p.amount),
+            currency,
+            execution_date,
+            &p.reference,
+            p.remittance_info.as_deref().unwrap_or(""),
+        ]).map_err(|e| e.to_string())?;
+    }
+    wtr.flush().map_err(|e| e.to_string())
+}
+
+fn xml_escape(s: &str) -> String {
+    s.trim()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Trims free text to the schema's Max140Text before escaping.
+fn text140(s: &str) -> String {
+    xml_escape(&s.trim().chars().take(140).collect::<String>())
+}
+
+fn agent(bank_name: &Option<String>, branch: &Option<String>) -> String {
+    let branch = match branch.as_deref().map(str::trim).filter(|b| !b.is_empty()) {
+        Some(b) => format!("<BrnchId><Nm>{}</Nm></BrnchId>", text140(b)),
+        None => String::new(),
+    };
+    format!("<FinInstnId><Nm>{}</Nm></FinInstnId>{}", text140(bank_name.as_deref().unwrap_or("")), branch)
+}
+
+/// Renders a pain.001.001.03 document with one payment information block.
+pub fn pain001(
+    message_id: &str,
+    payments: &[BankPayment],
+    debtor: &Debtor,
+    currency: &str,
+    execution_date: &str,
+    purpose: Purpose,
+) -> String {
+    let count = payments.len();
+    let total: f64 = payments.iter().map(|p| p.amount).sum();
+    let created = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S");
+    let message_id = xml_escape(message_id);
+    let debtor_name = text140(&debtor.name);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<Document xmlns=\"urn:iso:std:iso:20022:tech:xsd:pain.001.001.03\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n");
+    xml.push_str("  <CstmrCdtTrfInitn>\n");
+    xml.push_str(&format!(
+        "    <GrpHdr>\n      <MsgId>{}</MsgId>\n      <CreDtTm>{}</CreDtTm>\n      <NbOfTxs>{}</NbOfTxs>\n      <CtrlSum>{:.2}</CtrlSum>\n      <InitgPty><Nm>{}</Nm></InitgPty>\n    </GrpHdr>\n",
+        message_id, created, count, total, debtor_name
+    ));
+    xml.push_str("    <PmtInf>\n");
+    xml.push_str(&format!("      <PmtInfId>{}</PmtInfId>\n", message_id));
+    xml.push_str("      <PmtMtd>TRF</PmtMtd>\n");
+    xml.push_str(&format!("      <NbOfTxs>{}</NbOfTxs>\n      <CtrlSum>{:.2}</CtrlSum>\n", count, total));
+    xml.push_str(&format!("      <PmtTpInf><CtgyPurp><Cd>{}</Cd></CtgyPurp></PmtTpInf>\n", purpose.code()));
+    xml.push_str(&format!("      <ReqdExctnDt>{}</ReqdExctnDt>\n", execution_date));
+    xml.push_str(&format!("      <Dbtr><Nm>{}</Nm></Dbtr>\n", debtor_name));
+    xml.push_str(&format!("      <DbtrAcct><Id><Othr><Id>{}</Id></Othr></Id><Ccy>{}</Ccy></DbtrAcct>\n", xml_escape(&account_id(&debtor.account_no)), currency));
+    xml.push_str(&format!("      <DbtrAgt>{}</DbtrAgt>\n", agent(&debtor.bank_name, &debtor.bank_branch)));
+    xml.push_str("      <ChrgBr>SLEV</ChrgBr>\n");
+    for p in payments {
+        xml.push_str("      <CdtTrfTxInf>\n");
+        xml.push_str(&format!("        <PmtId><EndToEndId>{}</EndToEndId></PmtId>\n", xml_escape(&p.reference)));
+        xml.push_str(&format!("        <Amt><InstdAmt Ccy=\"{}\">{:.2}</InstdAmt></Amt>\n", currency, p.amount));
+        xml.push_str(&format!("        <CdtrAgt>{}</CdtrAgt>\n", agent(&p.bank_name, &p.bank_branch)));
+        xml.push_str(&format!("        <Cdtr><Nm>{}</Nm></Cdtr>\n", text140(&p.payee_name)));
+        xml.push_str(&format!("        <CdtrAcct><Id><Othr><Id>{}</Id></Othr></Id></CdtrAcct>\n", xml_escape(&account_id(&p.account_no))));
+        if let Some(info) = p.remittance_info.as_deref().filter(|s| !s.trim().is_empty()) {
+            xml.push_str(&format!("        <RmtInf><Ustrd>{}</Ustrd></RmtInf>\n", text140(info)));
+        }
+        xml.push_str("      </CdtTrfTxInf>\n");
+    }
+    xml.push_str("    </PmtInf>\n");
+    xml.push_str("  </CstmrCdtTrfInitn>\n");
+    xml.push_str("</Document>\n");
+    xml
+}
+
+/// Validates the payments and, only if they pass, writes the file in `format`
+/// ("CSV" or "pain.001"). Returns the total amount written.
+pub fn export(
+    path: &str,
+    format: &str,
+    payments: &[BankPayment],
+    debtor: &Debtor,
+    currency: &str,
+    execution_date: &str,
+    purpose: Purpose,
+) -> Result<f64, String> {
+    validate(format, payments, debtor, currency, execution_date)?;
+    if format == "CSV" {
+        write_csv(path, payments, currency, execution_date)?;
+    } else {
+        let xml = pain001(&purpose.message_id(execution_date), payments, debtor, currency, execution_date, purpose);
+        std::fs::write(path, xml).map_err(|e| e.to_string())?;
+    }
+    Ok((payments.iter().map(|p| p.amount).sum::<f64>() * 100.0).round() / 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payment(name: &str, amount: f64, reference: &str) -> BankPayment {
+        BankPayment {
+            payee_name: name.to_string(),
+            bank_name: Some("Commercial Bank".to_string()),
+            bank_branch: None,
+            account_no: Some("8001 2345 67".to_string()),
+            amount,
+            reference: reference.to_string(),
+            remittance_info: None,
+            transaction_id: None,
+        }
+    }
+
+    fn debtor() -> Debtor {
+        Debtor {
+            name: "Acme & Sons".to_string(),
+            bank_name: Some("People's Bank".to_string()),
+            bank_branch: Some("Main".to_string()),
+            account_no: Some("1002003004".to_string()),
+        }
+    }
+
+    #[test]
+    fn xml_escape_covers_markup_characters() {
+        assert_eq!(xml_escape(" A & B <C> \"d\" 'e' "), "A &amp; B &lt;C&gt; &quot;d&quot; &apos;e&apos;");
+        // Truncation happens before escaping, so an entity is never cut in half.
+        let long = format!("{}&", "x".repeat(139));
+        assert_eq!(text140(&long), format!("{}&amp;", "x".repeat(139)));
+        assert_eq!(text140(&"y".repeat(200)).len(), 140);
+    }
+
+    #[test]
+    fn validate_accepts_a_clean_file() {
+        let payments = [payment("Nimal", 1500.5, "PAY-1"), payment("Kamala", 20.0, "PAY-2")];
+        assert_eq!(validate("pain.001", &payments, &debtor(), "LKR", "2026-01-31"), Ok(()));
+        assert_eq!(validate("CSV", &payments, &debtor(), "LKR", "2026-01-31"), Ok(()));
+    }
+
+    #[test]
+    fn validate_reports_every_problem() {
+        let payments = [
+            payment("Nimal", 10.005, "PAY-1"),
+            payment("Kamala", 0.0, "PAY-1"),
+            BankPayment { account_no: Some("12/34".to_string()), ..payment("Sunil", 5.0, "PAY-3") },
+        ];
+        let err = validate("pain.001", &payments, &debtor(), "lkr", "31/01/2026").unwrap_err();
+        assert!(err.contains("Nimal: amount has more than 2 decimal places"));
+        assert!(err.contains("Kamala: amount must be greater than zero"));
+        assert!(err.contains("Kamala: duplicate payment reference PAY-1"));
+        assert!(err.contains("Sunil: bank account number is missing or invalid"));
+        assert!(err.contains("Currency must be a 3-letter ISO code"));
+        assert!(err.contains("Invalid payment date 31/01/2026"));
+    }
+
+    #[test]
+    fn validate_checks_the_debtor_only_for_pain001() {
+        let no_bank = Debtor { bank_name: None, account_no: None, ..debtor() };
+        let payments = [payment("Nimal", 10.0, "PAY-1")];
+        assert_eq!(validate("CSV", &payments, &no_bank, "LKR", "2026-01-31"), Ok(()));
+        let err = validate("pain.001", &payments, &no_bank, "LKR", "2026-01-31").unwrap_err();
+        assert!(err.contains("Business bank name is missing"));
+        assert!(err.contains("Business bank account number"));
+        assert!(validate("MT101", &payments, &debtor(), "LKR", "2026-01-31").is_err());
+        assert!(validate("CSV", &[], &debtor(), "LKR", "2026-01-31").unwrap_err().contains("no payments"));
+    }
+
+    #[test]
+    fn pain001_totals_and_escapes_payments() {
+        let payments = [
+            BankPayment { remittance_info: Some("Salary <Jan>".to_string()), ..payment("Silva & Co", 1000.1, "PAY-1") },
+            payment("Perera", 0.2, "PAY-2"),
+        ];
+        let xml = pain001("SAL-20260131-120000", &payments, &debtor(), "LKR", "2026-01-31", Purpose::Salary);
+        assert_eq!(xml.matches("<NbOfTxs>2</NbOfTxs>").count(), 2);
+        assert_eq!(xml.matches("<CtrlSum>1000.30</CtrlSum>").count(), 2);
+        assert_eq!(xml.matches("<CdtTrfTxInf>").count(), 2);
+        assert!(xml.contains("<InstdAmt Ccy=\"LKR\">1000.10</InstdAmt>"));
+        assert!(xml.contains("<Cdtr><Nm>Silva &amp; Co</Nm></Cdtr>"));
+        assert!(xml.contains("<Ustrd>Salary &lt;Jan&gt;</Ustrd>"));
+        assert!(xml.contains("<Dbtr><Nm>Acme &amp; Sons</Nm></Dbtr>"));
+        assert!(xml.contains("<Id>8001234567</Id>"));
+        assert!(xml.contains("<Cd>SALA</Cd>"));
+        assert!(xml.contains("<BrnchId><Nm>Main</Nm></BrnchId>"));
+    }
+}
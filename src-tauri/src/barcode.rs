@@ -0,0 +1,189 @@
+//! Barcode validation and encoding for product labels.
+//!
+//! Codes of exactly 13 digits are treated as EAN-13 (check digit verified);
+//! anything else printable is encoded as Code 128 (code set B).
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symbology {
+    Ean13,
+    Code128,
+}
+
+/// Longest value accepted for Code 128 so a label still scans at a sensible module width.
+const CODE128_MAX_LEN: usize = 40;
+
+/// Bar/space widths for Code 128 values 0..=105, followed by the stop pattern.
+const CODE128_PATTERNS: [&str; 107] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212", "221213",
+    "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221", "223211", "221132",
+    "221231", "213212", "223112", "312131", "311222", "321122", "321221", "312212", "322112", "322211",
+    "212123", "212321", "232121", "111323", "131123", "131321", "112313", "132113", "132311", "211313",
+    "231113", "231311", "112133", "112331", "132131", "113123", "113321", "133121", "313121", "211331",
+    "231131", "213113", "213311", "213131", "311123", "311321", "331121", "312113", "312311", "332111",
+    "314111", "221411", "431111", "111224", "111422", "121124", "121421", "141122", "141221", "112214",
+    "112412", "122114", "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111",
+    "111242", "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311", "113141",
+    "114131", "311141", "411131", "211412", "211214", "211232", "2331112",
+];
+const CODE128_START_B: usize = 104;
+const CODE128_STOP: usize = 106;
+
+/// EAN-13 left-hand odd parity ("L") patterns; "R" and "G" are derived from these.
+const EAN_L: [&str; 10] = [
+    "0001101", "0011001", "0010011", "0111101", "0100011",
+    "0110001", "0101111", "0111011", "0110111", "0001011",
+];
+/// Parity of the six left-hand digits, selected by the first (implicit) digit.
+const EAN_PARITY: [&str; 10] = [
+    "LLLLLL", "LLGLGG", "LLGGLG", "LLGGGL", "LGLLGG",
+    "LGGLLG", "LGGGLL", "LGLGLG", "LGLGGL", "LGGLGL",
+];
+
+/// EAN-13 check digit for the first twelve digits.
+pub fn ean13_check_digit(digits: &[u8]) -> u8 {
+    let sum: u32 = digits.iter().take(12).enumerate()
+        .map(|(i, d)| *d as u32 * if i % 2 == 0 { 1 } else { 3 })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
+/// Works out which symbology a code will be printed with, rejecting values that
+/// cannot be encoded (bad EAN-13 check digit, non-printable characters, too long).
+pub fn validate_barcode(code: &str) -> Result<Symbology, String> {
+    if code.is_empty() {
+        return Err("Barcode cannot be empty".to_string());
+    }
+    if code.len() == 13 && code.chars().all(|c| c.is_ascii_digit()) {
+        let digits: Vec<u8> = code.bytes().map(|b| b - b'0').collect();
+        if ean13_check_digit(&digits) != digits[12] {
+            return Err(format!("{} is not a valid EAN-13 code (check digit should be {})", code, ean13_check_digit(&digits)));
+        }
+        return Ok(Symbology::Ean13);
+    }
+    if code.len() > CODE128_MAX_LEN {
+        return Err(format!("Barcode cannot be longer than {} characters", CODE128_MAX_LEN));
+    }
+    if !code.chars().all(|c| (' '..='~').contains(&c)) {
+        return Err("Barcode may only contain printable ASCII characters".to_string());
+    }
+    Ok(Symbology::Code128)
+}
+
+/// Encodes a code into its module sequence (`true` = bar), without quiet zones.
+pub fn encode(code: &str) -> Result<(Symbology, Vec<bool>), String> {
+    let symbology = validate_barcode(code)?;
+    let modules = match symbology {
+        Symbology::Ean13 => encode_ean13(code),
+        Symbology::Code128 => encode_code128(code),
+    };
+    Ok((symbology, modules))
+}
+
+fn push_bits(modules: &mut Vec<bool>, bits: &str) {
+    modules.extend(bits.chars().map(|c| c == '1'));
+}
+
+fn encode_ean13(code: &str) -> Vec<bool> {
+    let digits: Vec<usize> = code.bytes().map(|b| (b - b'0') as usize).collect();
+    let parity = EAN_PARITY[digits[0]].as_bytes();
+    let mut modules = Vec::with_capacity(95);
+
+    push_bits(&mut modules, "101");
+    for (i, d) in digits[1..7].iter().enumerate() {
+        let l = EAN_L[*d];
+        if parity[i] == b'L' {
+            push_bits(&mut modules, l);
+        } else {
+            // G pattern is the mirror image of the inverted L pattern
+            modules.extend(l.chars().rev().map(|c| c == '0'));
+        }
+    }
+    push_bits(&mut modules, "01010");
+    for d in &digits[7..13] {
+        // R pattern is the inverted L pattern
+        modules.extend(EAN_L[*d].chars().map(|c| c == '0'));
+    }
+    push_bits(&mut modules, "101");
+    modules
+}
+
+/// Symbol values for a code: start B, the characters, checksum and stop.
+fn code128_values(code: &str) -> Vec<usize> {
+    let mut values = vec![CODE128_START_B];
+    values.extend(code.bytes().map(|b| (b - b' ') as usize));
+    let checksum = values.iter().enumerate()
+        .map(|(i, v)| if i == 0 { *v } else { i * v })
+        .sum::<usize>() % 103;
+    values.push(checksum);
+    values.push(CODE128_STOP);
+    values
+}
+
+fn encode_code128(code: &str) -> Vec<bool> {
+    let mut modules = Vec::new();
+    for v in code128_values(code) {
+        for (i, w) in CODE128_PATTERNS[v].bytes().enumerate() {
+            let bar = i % 2 == 0;
+            modules.extend(std::iter::repeat_n(bar, (w - b'0') as usize));
+        }
+    }
+    modules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digits(code: &str) -> Vec<u8> {
+        code.bytes().map(|b| b - b'0').collect()
+    }
+
+    #[test]
+    fn ean13_check_digit_matches_known_codes() {
+        assert_eq!(ean13_check_digit(&digits("4006381333931")), 1);
+        assert_eq!(ean13_check_digit(&digits("5901234123457")), 7);
+        // A weighted sum that is a multiple of ten gives 0, not 10.
+        assert_eq!(ean13_check_digit(&digits("000000000000")), 0);
+        assert_eq!(ean13_check_digit(&digits("978014300723")), 4);
+    }
+
+    #[test]
+    fn validate_barcode_picks_symbology() {
+        assert_eq!(validate_barcode("4006381333931"), Ok(Symbology::Ean13));
+        assert!(validate_barcode("4006381333932").unwrap_err().contains("check digit should be 1"));
+        // Twelve or fourteen digits are not EAN-13 and fall back to Code 128.
+        assert_eq!(validate_barcode("400638133393"), Ok(Symbology::Code128));
+        assert_eq!(validate_barcode("SKU-001"), Ok(Symbology::Code128));
+        assert!(validate_barcode("").is_err());
+        assert!(validate_barcode("caf\u{e9}").is_err());
+        assert!(validate_barcode(&"X".repeat(CODE128_MAX_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn ean13_encodes_to_95_modules_with_guards() {
+        let (symbology, modules) = encode("5901234123457").unwrap();
+        assert_eq!(symbology, Symbology::Ean13);
+        assert_eq!(modules.len(), 95);
+        assert_eq!(&modules[..3], &[true, false, true]);
+        assert_eq!(&modules[45..50], &[false, true, false, true, false]);
+        assert_eq!(&modules[92..], &[true, false, true]);
+    }
+
+    #[test]
+    fn code128_checksum_is_weighted_sum_mod_103() {
+        // Start B (104) + P*1 + J*2 + J*3 + 1*4 + 2*5 + 3*6 + C*7 = 879, 879 % 103 = 55.
+        assert_eq!(code128_values("PJJ123C"), vec![104, 48, 42, 42, 17, 18, 19, 35, 55, CODE128_STOP]);
+        // The checksum wraps around: start B alone plus "~" (94) is 198 % 103 = 95.
+        assert_eq!(code128_values("~"), vec![104, 94, 95, CODE128_STOP]);
+    }
+
+    #[test]
+    fn code128_module_count() {
+        // 11 modules per symbol (start, data, checksum) plus a 13-module stop.
+        let (symbology, modules) = encode("PJJ123C").unwrap();
+        assert_eq!(symbology, Symbology::Code128);
+        assert_eq!(modules.len(), (1 + 7 + 1) * 11 + 13);
+        assert!(modules[0] && *modules.last().unwrap());
+    }
+}
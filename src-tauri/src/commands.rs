@@ -1,30 +1,139 @@
 use tauri::{State, Manager};
 use crate::db::AppDb;
+use crate::encryption::{DbFiles, KeySource};
 use crate::models::*;
+use crate::permissions::Permission;
 use crate::AuthState;
-use crate::SessionUser;
+use crate::{LoginResult, PendingLogin, Session, SessionUser};
+use std::time::{Duration, Instant};
 
 // ── Helpers ─────────────────────────────────────────────
 
-fn check_permission(auth: &State<'_, AuthState>, permission: &str) -> Result<(), String> {
-    let user = auth.user.lock().unwrap();
-    if let Some(u) = &*user {
-        if u.role == "Admin" || u.permissions.contains(&permission.to_string()) {
-            return Ok(());
-        }
+fn check_permission(auth: &State<'_, AuthState>, permission: Permission) -> Result<(), String> {
+    check_any_permission(auth, &[permission])
+}
+
+/// Passes when the current user holds at least one of `permissions`.
+fn check_any_permission(auth: &State<'_, AuthState>, permissions: &[Permission]) -> Result<(), String> {
+    match &current_user(auth) {
+        None => Err("Not logged in".to_string()),
+        Some(u) if u.locked => Err(crate::session::SESSION_LOCKED.to_string()),
+        Some(u) if u.must_change_password => Err("You must change your password before continuing".to_string()),
+        Some(u) if u.must_enroll_two_factor => Err(TWO_FACTOR_ENROLL_REQUIRED.to_string()),
+        Some(u) if u.role == "Admin" || permissions.iter().any(|p| u.permissions.iter().any(|g| g == p.name())) => Ok(()),
+        Some(_) => Err("Permission denied".to_string()),
+    }
+}
+
+fn has_permission(auth: &State<'_, AuthState>, permissions: &[Permission]) -> bool {
+    check_any_permission(auth, permissions).is_ok()
+}
+
+/// For commands open to every logged-in user.
+fn require_session(auth: &State<'_, AuthState>) -> Result<(), String> {
+    match &current_user(auth) {
+        Some(u) if u.locked => Err(crate::session::SESSION_LOCKED.to_string()),
+        Some(u) if u.must_change_password => Err("You must change your password before continuing".to_string()),
+        Some(u) if u.must_enroll_two_factor => Err(TWO_FACTOR_ENROLL_REQUIRED.to_string()),
+        Some(_) => Ok(()),
+        None => Err("Not logged in".to_string()),
+    }
+}
+
+const TWO_FACTOR_ENROLL_REQUIRED: &str = "You must set up two-factor authentication before continuing";
+
+/// Salaries, payslips and everything else in payroll.
+const PAYROLL_ACCESS: &[Permission] = &[Permission::ViewPayroll, Permission::ManagePayroll];
+
+/// Re-reads the user's role and permissions from the database before checking,
+/// so a revoked grant takes effect at once instead of at the next login. Used
+/// by commands that touch users, payroll, settings, audit logs or delete data.
+fn check_current_permission(db: &State<'_, AppDb>, auth: &State<'_, AuthState>, permission: Permission) -> Result<(), String> {
+    refresh_session_permissions(db, auth)?;
+    check_permission(auth, permission)
+}
+
+fn check_current_any_permission(db: &State<'_, AppDb>, auth: &State<'_, AuthState>, permissions: &[Permission]) -> Result<(), String> {
+    refresh_session_permissions(db, auth)?;
+    check_any_permission(auth, permissions)
+}
+
+fn refresh_session_permissions(db: &State<'_, AppDb>, auth: &State<'_, AuthState>) -> Result<(), String> {
+    let Some(user_id) = get_current_user_id(auth) else { return Ok(()) };
+    let Some(user) = db.get_user(user_id).map_err(|e| e.to_string())?.filter(|u| u.is_active) else {
+        // The account was deleted or deactivated while logged in.
+        *auth.session.lock().unwrap() = None;
+        return Err("Not logged in".to_string());
+    };
+    let permissions = db.get_effective_permissions(user_id).map_err(|e| e.to_string())?;
+    // A new grant may bring the user under the mandatory two-factor policy.
+    let policy = db.get_password_policy().map_err(|e| e.to_string())?;
+    let must_enroll_two_factor = !user.totp_enabled && two_factor_required(&policy, &user.role, &permissions);
+    update_current_user(auth, |u| {
+        u.role = user.role;
+        u.permissions = permissions;
+        u.must_enroll_two_factor = must_enroll_two_factor;
+    });
+    Ok(())
+}
+
+fn current_user(auth: &State<'_, AuthState>) -> Option<SessionUser> {
+    auth.session.lock().unwrap().as_ref().map(|s| s.user.clone())
+}
+
+fn update_current_user(auth: &State<'_, AuthState>, f: impl FnOnce(&mut SessionUser)) {
+    if let Some(s) = auth.session.lock().unwrap().as_mut() {
+        f(&mut s.user);
     }
-    Err("Permission denied".to_string())
 }
 
 fn get_current_user_id(auth: &State<'_, AuthState>) -> Option<i64> {
-    auth.user.lock().unwrap().as_ref().map(|u| u.id)
+    auth.session.lock().unwrap().as_ref().map(|s| s.user.id)
+}
+
+/// Falls back to the current user's default location when none is given and
+/// makes sure stock is only moved at a location that exists and is active.
+fn resolve_location(db: &State<'_, AppDb>, auth: &State<'_, AuthState>, location_id: Option<i64>) -> Result<i64, String> {
+    let location_id = match location_id {
+        Some(id) => id,
+        None => db.default_location_for_user(get_current_user_id(auth)).map_err(|e| e.to_string())?,
+    };
+    match db.stock_location_status(location_id).map_err(|e| e.to_string())? {
+        Some(true) => Ok(location_id),
+        Some(false) => Err("Stock location is inactive".to_string()),
+        None => Err("Stock location not found".to_string()),
+    }
+}
+
+/// Trims SKU and barcode, validates the barcode can be printed and makes sure
+/// neither code is already used by another product.
+fn normalize_product_codes(
+    db: &State<'_, AppDb>,
+    sku: Option<String>,
+    barcode: Option<String>,
+    exclude_id: Option<i64>,
+) -> Result<(Option<String>, Option<String>), String> {
+    let sku = sku.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let barcode = barcode.map(|b| b.trim().to_string()).filter(|b| !b.is_empty());
+    if let Some(ref b) = barcode {
+        crate::barcode::validate_barcode(b)?;
+    }
+    if sku.is_some() && sku == barcode {
+        return Err("SKU and barcode must differ".to_string());
+    }
+    for code in sku.iter().chain(barcode.iter()) {
+        if db.product_code_in_use(code, exclude_id).map_err(|e| e.to_string())? {
+            return Err(format!("{} is already used by another product", code));
+        }
+    }
+    Ok((sku, barcode))
 }
 
 // ── Customers ──────────────────────────────────────────
 
 #[tauri::command]
-pub fn get_customers(db: State<'_, AppDb>) -> Result<Vec<Customer>, String> {
-    // No specific permission required to view customers for now, or add 'view_customers'
+pub fn get_customers(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<Customer>, String> {
+    check_any_permission(&auth, &[Permission::ViewCustomers, Permission::CreateInvoices])?;
     db.get_customers().map_err(|e| e.to_string())
 }
 
@@ -38,8 +147,9 @@ pub fn create_customer(
     email: Option<String>,
     address: Option<String>,
     tax_id: Option<String>,
+    price_list_id: Option<i64>,
 ) -> Result<i64, String> {
-    check_permission(&auth, "create_customers")?;
+    check_permission(&auth, Permission::CreateCustomers)?;
     
     let c = Customer {
         id: None,
@@ -49,6 +159,8 @@ pub fn create_customer(
         email,
         address,
         tax_id,
+        price_list_id,
+        price_list_name: None,
         created_at: None,
     };
     let id = db.create_customer(&c).map_err(|e| e.to_string())?;
@@ -75,8 +187,9 @@ pub fn update_customer(
     email: Option<String>,
     address: Option<String>,
     tax_id: Option<String>,
+    price_list_id: Option<i64>,
 ) -> Result<(), String> {
-    check_permission(&auth, "edit_customers")?;
+    check_permission(&auth, Permission::EditCustomers)?;
     let c = Customer {
         id: Some(id),
         name: name.clone(),
@@ -85,6 +198,8 @@ pub fn update_customer(
         email,
         address,
         tax_id,
+        price_list_id,
+        price_list_name: None,
         created_at: None,
     };
     db.update_customer(&c).map_err(|e| e.to_string())?;
@@ -102,7 +217,7 @@ pub fn update_customer(
 
 #[tauri::command]
 pub fn delete_customer(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64) -> Result<(), String> {
-    check_permission(&auth, "manage_customers")?;
+    check_current_permission(&db, &auth, Permission::DeleteCustomers)?;
     db.delete_customer(id).map_err(|e| e.to_string())?;
     
     db.log_activity(
@@ -116,10 +231,151 @@ pub fn delete_customer(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64
     Ok(())
 }
 
+// ── Price Lists ────────────────────────────────────────
+
+fn validate_price_list_items(items: &[PriceListItem]) -> Result<(), String> {
+    for (i, item) in items.iter().enumerate() {
+        if item.min_quantity < 0.0 {
+            return Err("Tier quantities cannot be negative".to_string());
+        }
+        if item.unit_price < 0.0 {
+            return Err("Prices cannot be negative".to_string());
+        }
+        if items[..i].iter().any(|o| o.product_id == item.product_id && o.min_quantity == item.min_quantity) {
+            return Err("Each product can only have one price per quantity tier".to_string());
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_price_lists(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<PriceList>, String> {
+    check_any_permission(&auth, &[Permission::ViewProducts, Permission::CreateInvoices])?;
+    db.get_price_lists().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_price_list_detail(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64) -> Result<PriceList, String> {
+    check_permission(&auth, Permission::ViewProducts)?;
+    db.get_price_list_detail(id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn create_price_list(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    name: String,
+    description: Option<String>,
+    discount_percent: Option<f64>,
+    items: Vec<PriceListItem>,
+) -> Result<i64, String> {
+    check_permission(&auth, Permission::EditProducts)?;
+    if name.trim().is_empty() {
+        return Err("Price list name is required".to_string());
+    }
+    let discount_percent = discount_percent.unwrap_or(0.0);
+    if !(0.0..=100.0).contains(&discount_percent) {
+        return Err("Discount must be between 0 and 100 percent".to_string());
+    }
+    validate_price_list_items(&items)?;
+    let pl = PriceList {
+        id: None,
+        name: name.trim().to_string(),
+        description,
+        discount_percent,
+        is_active: true,
+        created_at: None,
+        items: None,
+    };
+    let id = db.create_price_list(&pl, &items).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "CREATE",
+        "Price List",
+        Some(&id.to_string()),
+        &format!("Created price list: {}", pl.name)
+    ).ok();
+
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn update_price_list(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    id: i64,
+    name: String,
+    description: Option<String>,
+    discount_percent: Option<f64>,
+    is_active: Option<bool>,
+    items: Vec<PriceListItem>,
+) -> Result<(), String> {
+    check_permission(&auth, Permission::EditProducts)?;
+    let existing = db.get_price_list_detail(id).map_err(|e| e.to_string())?;
+    if name.trim().is_empty() {
+        return Err("Price list name is required".to_string());
+    }
+    let discount_percent = discount_percent.unwrap_or(existing.discount_percent);
+    if !(0.0..=100.0).contains(&discount_percent) {
+        return Err("Discount must be between 0 and 100 percent".to_string());
+    }
+    validate_price_list_items(&items)?;
+    let pl = PriceList {
+        id: Some(id),
+        name: name.trim().to_string(),
+        description,
+        discount_percent,
+        is_active: is_active.unwrap_or(existing.is_active),
+        created_at: None,
+        items: None,
+    };
+    db.update_price_list(&pl, &items).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE",
+        "Price List",
+        Some(&id.to_string()),
+        &format!("Updated price list: {}", pl.name)
+    ).ok();
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_price_list(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64) -> Result<(), String> {
+    check_permission(&auth, Permission::EditProducts)?;
+    db.delete_price_list(id).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "DELETE",
+        "Price List",
+        Some(&id.to_string()),
+        "Deleted price list"
+    ).ok();
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn resolve_price(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    product_id: i64,
+    customer_id: Option<i64>,
+    quantity: f64,
+) -> Result<ResolvedPrice, String> {
+    check_any_permission(&auth, &[Permission::ViewProducts, Permission::CreateInvoices])?;
+    db.resolve_price(product_id, customer_id, quantity).map_err(|e| e.to_string())
+}
+
 // ── Products ───────────────────────────────────────────
 
 #[tauri::command]
-pub fn get_products(db: State<'_, AppDb>) -> Result<Vec<Product>, String> {
+pub fn get_products(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<Product>, String> {
+    check_any_permission(&auth, &[Permission::ViewProducts, Permission::CreateInvoices])?;
     db.get_products().map_err(|e| e.to_string())
 }
 
@@ -131,16 +387,36 @@ pub fn create_product(
     description: Option<String>,
     unit_price: f64,
     tax_percent: f64,
+    track_stock: Option<bool>,
+    opening_stock: Option<f64>,
+    opening_unit_cost: Option<f64>,
+    reorder_level: Option<f64>,
+    location_id: Option<i64>,
+    unit: Option<String>,
+    sku: Option<String>,
+    barcode: Option<String>,
+    category: Option<String>,
 ) -> Result<i64, String> {
-    check_permission(&auth, "manage_products")?;
+    check_permission(&auth, Permission::CreateProducts)?;
+    let location_id = resolve_location(&db, &auth, location_id)?;
+    let (sku, barcode) = normalize_product_codes(&db, sku, barcode, None)?;
     let p = Product {
         id: None,
         name: name.clone(),
         description,
         unit_price,
         tax_percent,
+        track_stock: track_stock.unwrap_or(false),
+        stock_on_hand: opening_stock.unwrap_or(0.0),
+        reorder_level: reorder_level.unwrap_or(0.0),
+        average_cost: opening_unit_cost.unwrap_or(0.0),
+        unit: unit.filter(|u| !u.trim().is_empty()),
+        sku,
+        barcode,
+        category: category.filter(|c| !c.trim().is_empty()),
+        is_active: true,
     };
-    let id = db.create_product(&p).map_err(|e| e.to_string())?;
+    let id = db.create_product(&p, location_id, get_current_user_id(&auth)).map_err(|e| e.to_string())?;
     
     db.log_activity(
         get_current_user_id(&auth),
@@ -162,14 +438,34 @@ pub fn update_product(
     description: Option<String>,
     unit_price: f64,
     tax_percent: f64,
+    track_stock: Option<bool>,
+    reorder_level: Option<f64>,
+    unit: Option<String>,
+    sku: Option<String>,
+    barcode: Option<String>,
+    category: Option<String>,
+    is_active: Option<bool>,
 ) -> Result<(), String> {
-    check_permission(&auth, "edit_products")?;
+    check_permission(&auth, Permission::EditProducts)?;
+    let existing = db.get_product(id).map_err(|e| e.to_string())?;
+    // Omitted text fields keep their stored value; an empty string clears them.
+    let (sku, barcode) = normalize_product_codes(&db, sku.or(existing.sku.clone()), barcode.or(existing.barcode.clone()), Some(id))?;
+    // On-hand quantity only changes through the stock ledger, never by editing the product
     let p = Product {
         id: Some(id),
         name: name.clone(),
         description,
         unit_price,
         tax_percent,
+        track_stock: track_stock.unwrap_or(existing.track_stock),
+        stock_on_hand: existing.stock_on_hand,
+        reorder_level: reorder_level.unwrap_or(existing.reorder_level),
+        average_cost: existing.average_cost,
+        unit: unit.or(existing.unit.clone()).filter(|u| !u.trim().is_empty()),
+        sku,
+        barcode,
+        category: category.or(existing.category.clone()).filter(|c| !c.trim().is_empty()),
+        is_active: is_active.unwrap_or(existing.is_active),
     };
     db.update_product(&p).map_err(|e| e.to_string())?;
     
@@ -186,7 +482,7 @@ pub fn update_product(
 
 #[tauri::command]
 pub fn delete_product(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64) -> Result<(), String> {
-    check_permission(&auth, "delete_products")?;
+    check_current_permission(&db, &auth, Permission::DeleteProducts)?;
     db.delete_product(id).map_err(|e| e.to_string())?;
     
     db.log_activity(
@@ -200,164 +496,556 @@ pub fn delete_product(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64)
     Ok(())
 }
 
-// ── Invoices ───────────────────────────────────────────
-
 #[tauri::command]
-pub fn get_invoices(db: State<'_, AppDb>) -> Result<Vec<Invoice>, String> {
-    db.get_invoices().map_err(|e| e.to_string())
+pub fn find_product_by_code(db: State<'_, AppDb>, auth: State<'_, AuthState>, code: String) -> Result<Option<Product>, String> {
+    check_any_permission(&auth, &[Permission::ViewProducts, Permission::CreateInvoices])?;
+    let code = code.trim();
+    if code.is_empty() {
+        return Ok(None);
+    }
+    db.find_product_by_code(code).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub fn get_invoice_detail(db: State<'_, AppDb>, id: i64) -> Result<Invoice, String> {
-    db.get_invoice_detail(id).map_err(|e| e.to_string())
+pub fn export_barcode_labels_pdf(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    product_ids: Vec<i64>,
+    copies: Option<u32>,
+    file_path: String,
+) -> Result<String, String> {
+    check_permission(&auth, Permission::ViewProducts)?;
+    let copies = copies.unwrap_or(1).max(1) as usize;
+    let mut labels = Vec::new();
+    for id in product_ids {
+        let product = db.get_product(id).map_err(|e| e.to_string())?;
+        labels.extend(std::iter::repeat_n(product, copies));
+    }
+    if labels.is_empty() {
+        return Err("Select at least one product to print labels for".to_string());
+    }
+    let settings = db.get_settings().map_err(|e| e.to_string())?;
+    crate::pdf::generate_barcode_labels_pdf(&labels, &settings, &file_path)
 }
 
+// ── Inventory ──────────────────────────────────────────
+
 #[tauri::command]
-pub fn create_invoice(
+pub fn adjust_stock(
     db: State<'_, AppDb>,
     auth: State<'_, AuthState>,
-    customer_id: i64,
-    status: String,
-    issue_date: String,
-    due_date: String,
+    product_id: i64,
+    location_id: Option<i64>,
+    quantity: f64,
     notes: Option<String>,
-    discount: f64,
-    discount_percent: f64,
-    advance: f64,
-    items: Vec<InvoiceItem>,
-) -> Result<i64, String> {
-    check_permission(&auth, "create_invoice")?;
-    let inv = Invoice {
-        id: None,
-        invoice_number: None,
-        customer_id,
-        customer_name: None,
-        customer_phone: None,
-        status,
-        issue_date,
-        due_date,
-        notes,
-        subtotal: 0.0,
-        tax: 0.0,
-        discount,
-        discount_percent,
-        advance,
-        total: 0.0,
-        created_at: None,
-        items: None,
-    };
-    let id = db.create_invoice(&inv, &items).map_err(|e| e.to_string())?;
-    
+) -> Result<(), String> {
+    check_permission(&auth, Permission::ManageInventory)?;
+    if quantity == 0.0 {
+        return Err("Adjustment quantity cannot be zero".to_string());
+    }
+    let location_id = resolve_location(&db, &auth, location_id)?;
+    let product = db.get_product(product_id).map_err(|e| e.to_string())?;
+    if !product.track_stock {
+        return Err(format!("Stock is not tracked for {}", product.name));
+    }
+    db.adjust_stock(product_id, location_id, quantity, notes.as_deref(), get_current_user_id(&auth))
+        .map_err(|e| e.to_string())?;
+
     db.log_activity(
         get_current_user_id(&auth),
-        "CREATE",
-        "Invoice",
-        Some(&id.to_string()),
-        "Created new invoice"
+        "ADJUST_STOCK",
+        "Inventory",
+        Some(&product_id.to_string()),
+        &format!("Adjusted stock of {} by {}", product.name, quantity)
     ).ok();
 
-    Ok(id)
+    Ok(())
 }
 
 #[tauri::command]
-pub fn update_invoice_status(
+pub fn record_stock_return(
     db: State<'_, AppDb>,
     auth: State<'_, AuthState>,
-    id: i64,
-    status: String,
+    invoice_id: i64,
+    product_id: i64,
+    quantity: f64,
+    notes: Option<String>,
 ) -> Result<(), String> {
-    check_permission(&auth, "edit_invoices")?; // Edit invoice permission
-    db.update_invoice_status(id, &status)
+    check_permission(&auth, Permission::ManageInventory)?;
+    if quantity <= 0.0 {
+        return Err("Return quantity must be greater than zero".to_string());
+    }
+    let returnable = db.returnable_quantity(invoice_id, product_id).map_err(|e| e.to_string())?;
+    if quantity > returnable {
+        return Err(format!("Only {} unit(s) can be returned against this invoice", returnable));
+    }
+    db.record_stock_return(invoice_id, product_id, quantity, notes.as_deref(), get_current_user_id(&auth))
         .map_err(|e| e.to_string())?;
-    
+
     db.log_activity(
         get_current_user_id(&auth),
-        "UPDATE_STATUS",
-        "Invoice",
-        Some(&id.to_string()),
-        &format!("Updated invoice status to {}", status)
+        "RETURN",
+        "Inventory",
+        Some(&invoice_id.to_string()),
+        &format!("Returned {} unit(s) of product ID {} to stock", quantity, product_id)
     ).ok();
 
     Ok(())
 }
 
 #[tauri::command]
-pub fn delete_invoice(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64) -> Result<(), String> {
-    check_permission(&auth, "delete_invoices")?;
-    db.delete_invoice(id).map_err(|e| e.to_string())?;
-    
+pub fn create_purchase_receipt(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    product_id: i64,
+    location_id: Option<i64>,
+    quantity: f64,
+    unit_cost: f64,
+    supplier: Option<String>,
+    reference: Option<String>,
+    received_date: String,
+    notes: Option<String>,
+) -> Result<i64, String> {
+    check_permission(&auth, Permission::ManageInventory)?;
+    if quantity <= 0.0 {
+        return Err("Received quantity must be greater than zero".to_string());
+    }
+    if unit_cost < 0.0 {
+        return Err("Unit cost cannot be negative".to_string());
+    }
+    let location_id = resolve_location(&db, &auth, location_id)?;
+    let product = db.get_product(product_id).map_err(|e| e.to_string())?;
+    if !product.track_stock {
+        return Err(format!("Stock is not tracked for {}", product.name));
+    }
+    let r = PurchaseReceipt {
+        id: None,
+        product_id,
+        product_name: None,
+        location_id,
+        quantity,
+        unit_cost,
+        supplier,
+        reference,
+        received_date,
+        notes,
+        created_at: None,
+    };
+    let id = db.create_purchase_receipt(&r, get_current_user_id(&auth)).map_err(|e| e.to_string())?;
+
     db.log_activity(
         get_current_user_id(&auth),
-        "DELETE",
-        "Invoice",
+        "CREATE",
+        "Inventory",
         Some(&id.to_string()),
-        "Deleted invoice"
+        &format!("Received {} unit(s) of {} at {:.2}", quantity, product.name, unit_cost)
     ).ok();
 
-    Ok(())
+    Ok(id)
 }
 
-// ── Dashboard ──────────────────────────────────────────
+#[tauri::command]
+pub fn get_purchase_receipts(db: State<'_, AppDb>, auth: State<'_, AuthState>, product_id: Option<i64>) -> Result<Vec<PurchaseReceipt>, String> {
+    check_any_permission(&auth, &[Permission::ViewProducts, Permission::ManageInventory])?;
+    db.get_purchase_receipts(product_id).map_err(|e| e.to_string())
+}
 
 #[tauri::command]
-pub fn get_dashboard_stats(db: State<'_, AppDb>) -> Result<DashboardStats, String> {
-    db.get_dashboard_stats().map_err(|e| e.to_string())
+pub fn get_stock_movements(db: State<'_, AppDb>, auth: State<'_, AuthState>, product_id: Option<i64>, location_id: Option<i64>, limit: i64) -> Result<Vec<StockMovement>, String> {
+    check_any_permission(&auth, &[Permission::ViewProducts, Permission::ManageInventory])?;
+    db.get_stock_movements(product_id, location_id, limit).map_err(|e| e.to_string())
 }
 
-// ── Settings ───────────────────────────────────────────
+#[tauri::command]
+pub fn get_stock_report(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<StockReport, String> {
+    check_permission(&auth, Permission::ViewReports)?;
+    db.get_stock_report().map_err(|e| e.to_string())
+}
+
+// ── Stock Locations ────────────────────────────────────
 
 #[tauri::command]
-pub fn get_settings(db: State<'_, AppDb>) -> Result<Settings, String> {
-    db.get_settings().map_err(|e| e.to_string())
+pub fn get_stock_locations(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<StockLocation>, String> {
+    require_session(&auth)?;
+    db.get_stock_locations().map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub fn update_settings(
+pub fn create_stock_location(
     db: State<'_, AppDb>,
     auth: State<'_, AuthState>,
-    business_name: String,
-    business_address: Option<String>,
-    business_phone: Option<String>,
-    business_email: Option<String>,
-    currency_symbol: String,
-    tax_label: String,
-    logo_path: Option<String>,
-    default_footer: Option<String>,
-    template_type: String,
-    signature_path: Option<String>,
-    bank_name: Option<String>,
-    bank_account_name: Option<String>,
-    bank_account_no: Option<String>,
-    bank_branch: Option<String>,
-    business_tagline: Option<String>,
-    qr_code_path: Option<String>,
-) -> Result<(), String> {
-    check_permission(&auth, "manage_settings")?;
-    let s = Settings {
-        business_name: business_name.clone(),
-        business_address,
-        business_phone,
-        business_email,
-        currency_symbol,
-        tax_label,
-        logo_path,
-        default_footer,
-        template_type,
-        signature_path,
-        bank_name,
-        bank_account_name,
-        bank_account_no,
-        bank_branch,
-        business_tagline,
-        qr_code_path,
+    name: String,
+    address: Option<String>,
+) -> Result<i64, String> {
+    check_permission(&auth, Permission::ManageInventory)?;
+    if name.trim().is_empty() {
+        return Err("Location name is required".to_string());
+    }
+    let l = StockLocation {
+        id: None,
+        name: name.trim().to_string(),
+        address,
+        is_active: true,
     };
-    db.update_settings(&s).map_err(|e| e.to_string())?;
-    
+    let id = db.create_stock_location(&l).map_err(|e| e.to_string())?;
+
     db.log_activity(
         get_current_user_id(&auth),
-        "UPDATE",
-        "Settings",
-        None,
+        "CREATE",
+        "Stock Location",
+        Some(&id.to_string()),
+        &format!("Created stock location: {}", l.name)
+    ).ok();
+
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn update_stock_location(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    id: i64,
+    name: String,
+    address: Option<String>,
+    is_active: bool,
+) -> Result<(), String> {
+    check_permission(&auth, Permission::ManageInventory)?;
+    if name.trim().is_empty() {
+        return Err("Location name is required".to_string());
+    }
+    if !is_active {
+        let settings = db.get_settings().map_err(|e| e.to_string())?;
+        if settings.default_location_id.unwrap_or(1) == id {
+            return Err("The default stock location cannot be deactivated".to_string());
+        }
+    }
+    let l = StockLocation {
+        id: Some(id),
+        name: name.trim().to_string(),
+        address,
+        is_active,
+    };
+    db.update_stock_location(&l).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE",
+        "Stock Location",
+        Some(&id.to_string()),
+        &format!("Updated stock location: {}", l.name)
+    ).ok();
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_location_stock(db: State<'_, AppDb>, auth: State<'_, AuthState>, location_id: Option<i64>) -> Result<Vec<LocationStock>, String> {
+    check_any_permission(&auth, &[Permission::ViewProducts, Permission::ManageInventory])?;
+    db.get_location_stock(location_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn set_user_default_location(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    user_id: i64,
+    location_id: Option<i64>,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    if let Some(lid) = location_id {
+        resolve_location(&db, &auth, Some(lid))?;
+    }
+    db.set_user_default_location(user_id, location_id).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE",
+        "User",
+        Some(&user_id.to_string()),
+        &format!("Set default stock location to {:?}", location_id)
+    ).ok();
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn create_stock_transfer(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    from_location_id: i64,
+    to_location_id: i64,
+    transfer_date: String,
+    notes: Option<String>,
+    items: Vec<StockTransferItem>,
+) -> Result<i64, String> {
+    check_permission(&auth, Permission::ManageInventory)?;
+    if from_location_id == to_location_id {
+        return Err("Source and destination locations must differ".to_string());
+    }
+    resolve_location(&db, &auth, Some(from_location_id))?;
+    resolve_location(&db, &auth, Some(to_location_id))?;
+    if items.is_empty() {
+        return Err("A transfer needs at least one item".to_string());
+    }
+    for item in &items {
+        if item.quantity <= 0.0 {
+            return Err("Transfer quantities must be greater than zero".to_string());
+        }
+        let product = db.get_product(item.product_id).map_err(|e| e.to_string())?;
+        if !product.track_stock {
+            return Err(format!("Stock is not tracked for {}", product.name));
+        }
+    }
+    let t = StockTransfer {
+        id: None,
+        transfer_number: None,
+        from_location_id,
+        from_location_name: None,
+        to_location_id,
+        to_location_name: None,
+        transfer_date,
+        notes,
+        user_id: None,
+        username: None,
+        created_at: None,
+        items: None,
+    };
+    let id = db.create_stock_transfer(&t, &items, get_current_user_id(&auth))?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "TRANSFER",
+        "Inventory",
+        Some(&id.to_string()),
+        &format!("Transferred {} item(s) between locations {} and {}", items.len(), from_location_id, to_location_id)
+    ).ok();
+
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn get_stock_transfers(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<StockTransfer>, String> {
+    check_any_permission(&auth, &[Permission::ViewProducts, Permission::ManageInventory])?;
+    db.get_stock_transfers().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_stock_transfer_detail(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64) -> Result<StockTransfer, String> {
+    check_any_permission(&auth, &[Permission::ViewProducts, Permission::ManageInventory])?;
+    db.get_stock_transfer_detail(id).map_err(|e| e.to_string())
+}
+
+// ── Invoices ───────────────────────────────────────────
+
+#[tauri::command]
+pub fn get_invoices(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<Invoice>, String> {
+    check_permission(&auth, Permission::ViewInvoices)?;
+    db.get_invoices().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_invoice_detail(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64) -> Result<Invoice, String> {
+    check_permission(&auth, Permission::ViewInvoices)?;
+    db.get_invoice_detail(id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn create_invoice(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    customer_id: i64,
+    status: String,
+    issue_date: String,
+    due_date: String,
+    notes: Option<String>,
+    discount: f64,
+    discount_percent: f64,
+    advance: f64,
+    mut items: Vec<InvoiceItem>,
+    location_id: Option<i64>,
+    charges: Option<Vec<InvoiceCharge>>,
+) -> Result<i64, String> {
+    check_permission(&auth, Permission::CreateInvoices)?;
+    if items.iter().any(|i| !i.quantity.is_finite() || i.quantity <= 0.0) {
+        return Err("Item quantities must be greater than zero".to_string());
+    }
+    if items.iter().any(|i| i.discount < 0.0 || !(0.0..=100.0).contains(&i.discount_percent)) {
+        return Err("Line discounts must be a positive amount or a percentage up to 100".to_string());
+    }
+    let charges = charges.unwrap_or_default();
+    for charge in &charges {
+        if charge.description.trim().is_empty() {
+            return Err("Each additional charge needs a description".to_string());
+        }
+        if charge.amount < 0.0 || charge.tax_percent < 0.0 {
+            return Err("Charge amounts and tax rates cannot be negative".to_string());
+        }
+    }
+    // Catalogue lines are billed at the customer's effective price and tax; users who
+    // may edit invoices can override them by hand
+    let may_override_price = has_permission(&auth, &[Permission::EditInvoices]);
+    for item in items.iter_mut() {
+        if let Some(product_id) = item.product_id {
+            let product = db.get_product(product_id).map_err(|e| e.to_string())?;
+            if !product.is_active {
+                return Err(format!("{} is archived and cannot be invoiced", product.name));
+            }
+            if !may_override_price {
+                let price = db.resolve_price(product_id, Some(customer_id), item.quantity).map_err(|e| e.to_string())?;
+                item.unit_price = price.unit_price;
+                item.tax_percent = price.tax_percent;
+            }
+        }
+    }
+    let location_id = resolve_location(&db, &auth, location_id)?;
+    let inv = Invoice {
+        id: None,
+        invoice_number: None,
+        customer_id,
+        customer_name: None,
+        customer_phone: None,
+        status,
+        issue_date,
+        due_date,
+        notes,
+        subtotal: 0.0,
+        tax: 0.0,
+        discount,
+        discount_percent,
+        advance,
+        total: 0.0,
+        location_id: Some(location_id),
+        created_at: None,
+        items: None,
+        charges: None,
+    };
+    let id = db.create_invoice(&inv, &items, &charges, get_current_user_id(&auth)).map_err(|e| e.to_string())?;
+    
+    db.log_activity(
+        get_current_user_id(&auth),
+        "CREATE",
+        "Invoice",
+        Some(&id.to_string()),
+        "Created new invoice"
+    ).ok();
+
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn update_invoice_status(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    id: i64,
+    status: String,
+) -> Result<(), String> {
+    check_permission(&auth, Permission::EditInvoices)?; // Edit invoice permission
+    db.update_invoice_status(id, &status)
+        .map_err(|e| e.to_string())?;
+    
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE_STATUS",
+        "Invoice",
+        Some(&id.to_string()),
+        &format!("Updated invoice status to {}", status)
+    ).ok();
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_invoice(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::DeleteInvoices)?;
+    db.delete_invoice(id, get_current_user_id(&auth)).map_err(|e| e.to_string())?;
+    
+    db.log_activity(
+        get_current_user_id(&auth),
+        "DELETE",
+        "Invoice",
+        Some(&id.to_string()),
+        "Deleted invoice"
+    ).ok();
+
+    Ok(())
+}
+
+// ── Dashboard ──────────────────────────────────────────
+
+#[tauri::command]
+pub fn get_dashboard_stats(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<DashboardStats, String> {
+    check_permission(&auth, Permission::ViewReports)?;
+    db.get_dashboard_stats().map_err(|e| e.to_string())
+}
+
+// ── Settings ───────────────────────────────────────────
+
+#[tauri::command]
+pub fn get_settings(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Settings, String> {
+    require_session(&auth)?;
+    db.get_settings().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn update_settings(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    business_name: String,
+    business_address: Option<String>,
+    business_phone: Option<String>,
+    business_email: Option<String>,
+    currency_symbol: String,
+    tax_label: String,
+    logo_path: Option<String>,
+    default_footer: Option<String>,
+    template_type: String,
+    signature_path: Option<String>,
+    bank_name: Option<String>,
+    bank_account_name: Option<String>,
+    bank_account_no: Option<String>,
+    bank_branch: Option<String>,
+    business_tagline: Option<String>,
+    qr_code_path: Option<String>,
+    inventory_valuation_method: Option<String>,
+    default_location_id: Option<i64>,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManageSettings)?;
+    let existing = db.get_settings().map_err(|e| e.to_string())?;
+    let inventory_valuation_method = inventory_valuation_method.unwrap_or(existing.inventory_valuation_method);
+    if !["FIFO", "WeightedAverage"].contains(&inventory_valuation_method.as_str()) {
+        return Err("Valuation method must be FIFO or WeightedAverage".to_string());
+    }
+    let default_location_id = match default_location_id {
+        Some(id) => Some(resolve_location(&db, &auth, Some(id))?),
+        None => existing.default_location_id,
+    };
+    let s = Settings {
+        business_name: business_name.clone(),
+        business_address,
+        business_phone,
+        business_email,
+        currency_symbol,
+        tax_label,
+        logo_path,
+        default_footer,
+        template_type,
+        signature_path,
+        bank_name,
+        bank_account_name,
+        bank_account_no,
+        bank_branch,
+        business_tagline,
+        qr_code_path,
+        inventory_valuation_method,
+        default_location_id,
+    };
+    db.update_settings(&s).map_err(|e| e.to_string())?;
+    
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE",
+        "Settings",
+        None,
         &format!("Updated business settings for {}", business_name)
     ).ok();
 
@@ -369,9 +1057,11 @@ pub fn update_settings(
 #[tauri::command]
 pub fn export_invoice_pdf(
     db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
     invoice_id: i64,
     file_path: String,
 ) -> Result<String, String> {
+    check_permission(&auth, Permission::ExportInvoices)?;
     let invoice = db.get_invoice_detail(invoice_id).map_err(|e| e.to_string())?;
     let settings = db.get_settings().map_err(|e| e.to_string())?;
     
@@ -402,7 +1092,7 @@ pub fn register(db: State<'_, AppDb>, auth: State<'_, AuthState>, username: Stri
     // Role assignment
     let has_users = db.has_any_user().map_err(|e| e.to_string())?;
     let role = if !has_users { "Admin" } else { 
-        check_permission(&auth, "manage_users")?;
+        check_current_permission(&db, &auth, Permission::ManageUsers)?;
         "User" 
     };
 
@@ -411,10 +1101,13 @@ pub fn register(db: State<'_, AppDb>, auth: State<'_, AuthState>, username: Stri
         return Err("Username already exists".to_string());
     }
 
-    // Hash password
-    let hash = bcrypt::hash(password, bcrypt::DEFAULT_COST).map_err(|e| e.to_string())?;
+    let policy = db.get_password_policy().map_err(|e| e.to_string())?;
+    crate::password::validate(&policy, &password)?;
+    let hash = crate::password::hash(&password)?;
 
-    let id = db.create_user(&username, &hash, role).map_err(|e| e.to_string())?;
+    // Accounts made by an admin start with a password the admin knows, so the
+    // new user has to pick their own at first login.
+    let id = db.create_user(&username, &hash, role, has_users).map_err(|e| e.to_string())?;
     
     db.log_activity(
         get_current_user_id(&auth),
@@ -428,512 +1121,3047 @@ pub fn register(db: State<'_, AppDb>, auth: State<'_, AuthState>, username: Stri
 }
 
 #[tauri::command]
-pub fn login(db: State<'_, AppDb>, auth: State<'_, AuthState>, username: String, password: String) -> Result<SessionUser, String> {
-    let user = db
-        .get_user_by_username(&username)
-        .map_err(|e| e.to_string())?
-        .ok_or_else(|| "Invalid username or password".to_string())?;
+pub fn login(db: State<'_, AppDb>, auth: State<'_, AuthState>, username: String, password: String) -> Result<LoginResult, String> {
+    *auth.pending_login.lock().unwrap() = None;
+
+    let user = match db.get_user_by_username(&username).map_err(|e| e.to_string())? {
+        Some(user) => user,
+        None => {
+            log_login_failure(&db, None, &username, "unknown username");
+            return Err("Invalid username or password".to_string());
+        }
+    };
+    let user_id = user.id.unwrap();
+
+    check_not_locked(&db, &user)?;
 
-    let valid = bcrypt::verify(password, &user.password_hash).map_err(|e| e.to_string())?;
+    let valid = bcrypt::verify(&password, &user.password_hash).map_err(|e| e.to_string())?;
 
     if !valid {
-        return Err("Invalid username or password".to_string());
+        return Err(record_login_failure(&db, &user, "wrong password", "Invalid username or password")?.0);
     }
+    check_active(&db, &user)?;
 
-    let user_id = user.id.unwrap();
-    let permissions = db.get_user_permissions(user_id).map_err(|e| e.to_string())?;
-    
-    let session_user = SessionUser {
-        id: user_id,
-        username: user.username.clone(),
-        role: user.role.clone(),
-        permissions,
-    };
+    if user.totp_enabled {
+        *auth.pending_login.lock().unwrap() = Some(PendingLogin { user_id, started: Instant::now() });
+        return Ok(LoginResult { session: None, two_factor_required: true });
+    }
 
-    // Store in global state
-    let mut current_user = auth.user.lock().unwrap();
-    *current_user = Some(session_user.clone());
+    let session_user = start_session(&db, &auth, &user)?;
+    Ok(LoginResult { session: Some(session_user), two_factor_required: false })
+}
 
-    db.log_activity(
+/// The second login step: accepts a code from the authenticator app or an
+/// unused recovery code. Wrong codes count towards the lockout like wrong
+/// passwords do.
+#[tauri::command]
+pub fn verify_two_factor(db: State<'_, AppDb>, auth: State<'_, AuthState>, code: String) -> Result<SessionUser, String> {
+    let pending = auth.pending_login.lock().unwrap().take()
+        .ok_or("No login is waiting for a two-factor code. Please log in again.")?;
+    if pending.started.elapsed() > PENDING_LOGIN_TTL {
+        return Err("The two-factor step timed out. Please log in again.".to_string());
+    }
+
+    let user = db.get_user(pending.user_id).map_err(|e| e.to_string())?.ok_or("User not found")?;
+    check_not_locked(&db, &user)?;
+    check_active(&db, &user)?;
+    let secret = db.get_totp_secret(pending.user_id).map_err(|e| e.to_string())?
+        .filter(|_| user.totp_enabled)
+        .ok_or("Two-factor authentication is not set up for this account")?;
+
+    if !accept_totp_code(&db, &user, &secret, &code)? {
+        let unused = db.get_unused_recovery_codes(pending.user_id).map_err(|e| e.to_string())?;
+        match crate::two_factor::match_recovery_code(&code, &unused) {
+            Some(code_id) => {
+                db.use_recovery_code(code_id).map_err(|e| e.to_string())?;
+                db.log_activity(
+                    Some(pending.user_id),
+                    "TWO_FACTOR_RECOVERY",
+                    "User",
+                    Some(&pending.user_id.to_string()),
+                    &format!("User {} logged in with a recovery code ({} left)", user.username, unused.len() - 1)
+                ).ok();
+            }
+            None => {
+                let (error, locked) = record_login_failure(&db, &user, "invalid two-factor code", "Invalid authentication code")?;
+                if !locked {
+                    *auth.pending_login.lock().unwrap() = Some(pending);
+                }
+                return Err(error);
+            }
+        }
+    }
+
+    start_session(&db, &auth, &user)
+}
+
+/// How long a login may wait for its two-factor code.
+const PENDING_LOGIN_TTL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Checks a TOTP code and marks its time step used, so each code is accepted
+/// only once.
+fn accept_totp_code(db: &State<'_, AppDb>, user: &User, secret: &str, code: &str) -> Result<bool, String> {
+    let user_id = user.id.unwrap();
+    let last_step = db.get_totp_last_step(user_id).map_err(|e| e.to_string())?;
+    match crate::two_factor::verify_code(secret, &user.username, code, last_step)? {
+        Some(step) => db.record_totp_step(user_id, step).map_err(|e| e.to_string()),
+        None => Ok(false),
+    }
+}
+
+fn start_session(db: &State<'_, AppDb>, auth: &State<'_, AuthState>, user: &User) -> Result<SessionUser, String> {
+    let user_id = user.id.unwrap();
+    if user.failed_login_attempts > 0 || user.locked_until.is_some() {
+        db.clear_login_failures(user_id).map_err(|e| e.to_string())?;
+    }
+
+    let permissions = db.get_effective_permissions(user_id).map_err(|e| e.to_string())?;
+    let policy = db.get_password_policy().map_err(|e| e.to_string())?;
+
+    let session_user = SessionUser {
+        id: user_id,
+        username: user.username.clone(),
+        role: user.role.clone(),
+        must_change_password: user.must_change_password,
+        must_enroll_two_factor: !user.totp_enabled && two_factor_required(&policy, &user.role, &permissions),
+        permissions,
+        token: uuid::Uuid::new_v4().to_string(),
+        locked: false,
+    };
+
+    // Store in global state
+    let now = Instant::now();
+    *auth.session.lock().unwrap() = Some(Session {
+        user: session_user.clone(),
+        started: now,
+        last_active: now,
+        idle_timeout: Duration::from_secs(policy.idle_timeout_minutes.max(0) as u64 * 60),
+        lifetime: Duration::from_secs(policy.session_lifetime_hours.max(1) as u64 * 3600),
+    });
+
+    db.log_activity(
         Some(user_id),
         "LOGIN",
         "Session",
         None,
-        &format!("User {} logged in", username)
+        &format!("User {} logged in", user.username)
     ).ok();
 
     Ok(session_user)
 }
 
+/// Whether the policy makes two-factor mandatory for someone with this role
+/// and these effective permissions.
+fn two_factor_required(policy: &PasswordPolicy, role: &str, permissions: &[String]) -> bool {
+    policy.require_two_factor && (
+        role == "Admin"
+        || [Permission::ManagePayroll, Permission::ManageUsers].iter()
+            .any(|p| permissions.iter().any(|g| g == p.name()))
+    )
+}
+
+fn log_login_failure(db: &State<'_, AppDb>, user_id: Option<i64>, username: &str, reason: &str) {
+    db.log_activity(
+        user_id,
+        "LOGIN_FAILED",
+        "Session",
+        user_id.map(|id| id.to_string()).as_deref(),
+        &format!("Failed login for {}: {}", username, reason)
+    ).ok();
+}
+
+/// Deactivated accounts are only told so once their password was accepted.
+fn check_active(db: &State<'_, AppDb>, user: &User) -> Result<(), String> {
+    if user.is_active {
+        return Ok(());
+    }
+    log_login_failure(db, user.id, &user.username, "account deactivated");
+    Err("This account has been deactivated. Contact an administrator.".to_string())
+}
+
+fn check_not_locked(db: &State<'_, AppDb>, user: &User) -> Result<(), String> {
+    let now = chrono::Utc::now().naive_utc();
+    match lock_expiry(user) {
+        Some(until) if until > now => {
+            log_login_failure(db, user.id, &user.username, "account locked");
+            Err(account_locked_message(until, now))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Counts a wrong password or code against the lockout policy, audits it and
+/// returns the message for the login screen (`error`, or the locked message
+/// once this attempt locks the account) and whether it did lock it.
+fn record_login_failure(db: &State<'_, AppDb>, user: &User, reason: &str, error: &str) -> Result<(String, bool), String> {
+    let user_id = user.id.unwrap();
+    let policy = db.get_password_policy().map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().naive_utc();
+    let until = now + chrono::Duration::minutes(policy.lockout_minutes.max(1));
+    let (attempts, locked) = db
+        .record_failed_login(user_id, policy.max_failed_logins, &until.format(LOCK_TIME_FORMAT).to_string())
+        .map_err(|e| e.to_string())?;
+    if locked {
+        log_login_failure(db, Some(user_id), &user.username, &format!("{}, account locked for {} minute(s)", reason, policy.lockout_minutes.max(1)));
+        return Ok((account_locked_message(until, now), true));
+    }
+    if policy.max_failed_logins > 0 {
+        log_login_failure(db, Some(user_id), &user.username, &format!("{} (attempt {} of {})", reason, attempts, policy.max_failed_logins));
+    } else {
+        log_login_failure(db, Some(user_id), &user.username, reason);
+    }
+    Ok((error.to_string(), false))
+}
+
+const LOCK_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+fn lock_expiry(user: &User) -> Option<chrono::NaiveDateTime> {
+    user.locked_until.as_deref()
+        .and_then(|t| chrono::NaiveDateTime::parse_from_str(t, LOCK_TIME_FORMAT).ok())
+}
+
+/// Kept distinct from the bad-credentials message so the login screen can say
+/// why the account cannot sign in.
+fn account_locked_message(until: chrono::NaiveDateTime, now: chrono::NaiveDateTime) -> String {
+    let minutes = ((until - now).num_seconds() + 59) / 60;
+    format!(
+        "Account locked after too many failed login attempts. Try again in {} minute(s) or ask an administrator to unlock it.",
+        minutes.max(1)
+    )
+}
+
 #[tauri::command]
 pub fn logout(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<(), String> {
-    let user_id = get_current_user_id(&auth);
-    let mut current_user = auth.user.lock().unwrap();
+    *auth.pending_login.lock().unwrap() = None;
+    let mut session = auth.session.lock().unwrap();
     
-    if let Some(u) = &*current_user {
+    if let Some(s) = &*session {
         db.log_activity(
-            user_id,
+            Some(s.user.id),
             "LOGOUT",
             "Session",
             None,
-            &format!("User {} logged out", u.username)
+            &format!("User {} logged out", s.user.username)
         ).ok();
     }
     
-    *current_user = None;
+    *session = None;
     Ok(())
 }
 
+/// Locks the session until the password is entered again with `unlock_session`.
 #[tauri::command]
-pub fn get_current_session(auth: State<'_, AuthState>) -> Result<Option<SessionUser>, String> {
-    let user = auth.user.lock().unwrap();
-    Ok(user.clone())
-}
+pub fn lock_session(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<(), String> {
+    let user = current_user(&auth).ok_or("Not logged in")?;
+    update_current_user(&auth, |u| u.locked = true);
 
-// ── Admin Commands ──────────────────────────────────────
+    db.log_activity(
+        Some(user.id),
+        "SESSION_LOCK",
+        "Session",
+        None,
+        &format!("User {} locked their session", user.username)
+    ).ok();
 
+    Ok(())
+}
+
+/// Wrong passwords here count towards the login lockout; once the account
+/// locks, the session is ended.
 #[tauri::command]
-pub fn get_users(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<User>, String> {
-    check_permission(&auth, "manage_users")?;
-    db.get_users().map_err(|e| e.to_string())
+pub fn unlock_session(db: State<'_, AppDb>, auth: State<'_, AuthState>, password: String) -> Result<SessionUser, String> {
+    let session_user = current_user(&auth).ok_or("Not logged in")?;
+    let Some(user) = db.get_user(session_user.id).map_err(|e| e.to_string())?.filter(|u| u.is_active) else {
+        *auth.session.lock().unwrap() = None;
+        return Err("Not logged in".to_string());
+    };
+
+    if !bcrypt::verify(&password, &user.password_hash).map_err(|e| e.to_string())? {
+        let (error, locked) = record_login_failure(&db, &user, "wrong password on lock screen", "Password is incorrect")?;
+        if locked {
+            *auth.session.lock().unwrap() = None;
+        }
+        return Err(error);
+    }
+    if user.failed_login_attempts > 0 || user.locked_until.is_some() {
+        db.clear_login_failures(session_user.id).map_err(|e| e.to_string())?;
+    }
+
+    let permissions = db.get_effective_permissions(session_user.id).map_err(|e| e.to_string())?;
+    let mut session = auth.session.lock().unwrap();
+    let s = session.as_mut().ok_or("Not logged in")?;
+    s.user.locked = false;
+    s.user.role = user.role;
+    s.user.permissions = permissions;
+    s.last_active = Instant::now();
+
+    db.log_activity(
+        Some(session_user.id),
+        "SESSION_UNLOCK",
+        "Session",
+        None,
+        &format!("User {} unlocked their session", session_user.username)
+    ).ok();
+
+    Ok(s.user.clone())
 }
 
 #[tauri::command]
-pub fn get_user_permissions(db: State<'_, AppDb>, auth: State<'_, AuthState>, user_id: i64) -> Result<Vec<String>, String> {
-    check_permission(&auth, "manage_users")?;
-    db.get_user_permissions(user_id).map_err(|e| e.to_string())
+pub fn get_current_session(auth: State<'_, AuthState>) -> Result<Option<SessionUser>, String> {
+    Ok(current_user(&auth))
 }
 
+/// Lets the logged-in user replace their own password. This is the one command
+/// allowed while a password change is still required.
 #[tauri::command]
-pub fn update_user_permissions(
+pub fn change_password(
     db: State<'_, AppDb>,
     auth: State<'_, AuthState>,
-    user_id: i64,
-    permissions: Vec<String>
+    current_password: String,
+    new_password: String,
 ) -> Result<(), String> {
-    check_permission(&auth, "manage_users")?;
-    db.update_user_permissions(user_id, permissions).map_err(|e| e.to_string())?;
-    
+    let user_id = get_current_user_id(&auth).ok_or("Not logged in")?;
+    let user = db.get_user(user_id).map_err(|e| e.to_string())?.ok_or("User not found")?;
+    // Wrong guesses count toward the lockout, as on the lock screen
+    if !bcrypt::verify(&current_password, &user.password_hash).map_err(|e| e.to_string())? {
+        let (error, locked) = record_login_failure(&db, &user, "wrong current password on password change", "Current password is incorrect")?;
+        if locked {
+            *auth.session.lock().unwrap() = None;
+        }
+        return Err(error);
+    }
+    if user.failed_login_attempts > 0 || user.locked_until.is_some() {
+        db.clear_login_failures(user_id).map_err(|e| e.to_string())?;
+    }
+    let policy = db.get_password_policy().map_err(|e| e.to_string())?;
+    crate::password::validate(&policy, &new_password)?;
+    let previous = db.get_password_hashes(user_id, policy.history_count).map_err(|e| e.to_string())?;
+    crate::password::check_history(&policy, &new_password, &previous)?;
+
+    let hash = crate::password::hash(&new_password)?;
+    db.set_password(user_id, &hash, false).map_err(|e| e.to_string())?;
+    update_current_user(&auth, |u| u.must_change_password = false);
+
     db.log_activity(
-        get_current_user_id(&auth),
-        "UPDATE_PERMISSIONS",
+        Some(user_id),
+        "PASSWORD_CHANGE",
         "User",
         Some(&user_id.to_string()),
-        &format!("Updated permissions for user ID: {}", user_id)
+        &format!("User {} changed their password", user.username)
     ).ok();
 
     Ok(())
 }
 
+// ── Two-Factor Authentication ───────────────────────────
+//
+// These only need a session, so a user the policy forces to enroll can still
+// reach them.
+
 #[tauri::command]
-pub fn get_audit_logs(
-    db: State<'_, AppDb>,
-    auth: State<'_, AuthState>,
-    limit: i64,
-    offset: i64,
-    module: Option<String>,
-    user_id: Option<i64>,
-    date: Option<String>,
-    month: Option<String>,
-) -> Result<Vec<AuditLog>, String> {
-    check_permission(&auth, "view_activity_logs")?;
-    db.get_audit_logs(limit, offset, module, user_id, date, month).map_err(|e| e.to_string())
+pub fn get_two_factor_status(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<TwoFactorStatus, String> {
+    let session = current_user(&auth).ok_or("Not logged in")?;
+    let user = db.get_user(session.id).map_err(|e| e.to_string())?.ok_or("User not found")?;
+    let policy = db.get_password_policy().map_err(|e| e.to_string())?;
+    let recovery_codes_remaining = if user.totp_enabled {
+        db.get_unused_recovery_codes(session.id).map_err(|e| e.to_string())?.len() as i64
+    } else {
+        0
+    };
+    Ok(TwoFactorStatus {
+        enabled: user.totp_enabled,
+        required: two_factor_required(&policy, &session.role, &session.permissions),
+        recovery_codes_remaining,
+    })
 }
+
+/// Generates a new secret for the current user. Two-factor stays off until a
+/// code from it is confirmed with `confirm_two_factor_setup`.
 #[tauri::command]
-pub fn upload_logo(
-    app: tauri::AppHandle,
-    auth: State<'_, AuthState>,
-    source_path: String,
-) -> Result<String, String> {
-    check_permission(&auth, "manage_settings")?;
-    
-    let app_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
-    let branding_dir = app_dir.join("branding");
-    std::fs::create_dir_all(&branding_dir).map_err(|e| e.to_string())?;
-    
-    let extension = std::path::Path::new(&source_path)
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("png");
-        
-    let target_path = branding_dir.join(format!("logo.{}", extension));
-    
-    std::fs::copy(&source_path, &target_path).map_err(|e| e.to_string())?;
-    
-    Ok(target_path.to_str().unwrap().to_string())
+pub fn begin_two_factor_setup(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<TwoFactorSetup, String> {
+    let user_id = get_current_user_id(&auth).ok_or("Not logged in")?;
+    let user = db.get_user(user_id).map_err(|e| e.to_string())?.ok_or("User not found")?;
+    if user.totp_enabled {
+        return Err("Two-factor authentication is already on. Turn it off first to enroll a new device.".to_string());
+    }
+
+    let secret = crate::two_factor::new_secret();
+    let (otpauth_uri, qr_code) = crate::two_factor::provisioning(&secret, &user.username)?;
+    db.set_pending_totp_secret(user_id, &secret).map_err(|e| e.to_string())?;
+
+    Ok(TwoFactorSetup { secret, otpauth_uri, qr_code })
 }
 
+/// Turns two-factor on once the user proves their app produces valid codes.
+/// Returns the recovery codes; they are only stored hashed, so this is the one
+/// time they can be shown.
 #[tauri::command]
-pub fn upload_signature(
-    app: tauri::AppHandle,
-    auth: State<'_, AuthState>,
-    source_path: String,
-) -> Result<String, String> {
-    check_permission(&auth, "manage_settings")?;
-    
-    let app_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
-    let branding_dir = app_dir.join("branding");
-    std::fs::create_dir_all(&branding_dir).map_err(|e| e.to_string())?;
-    
-    let extension = std::path::Path::new(&source_path)
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("png");
-        
-    let target_path = branding_dir.join(format!("signature.{}", extension));
-    
-    std::fs::copy(&source_path, &target_path).map_err(|e| e.to_string())?;
-    
-    Ok(target_path.to_str().unwrap().to_string())
+pub fn confirm_two_factor_setup(db: State<'_, AppDb>, auth: State<'_, AuthState>, code: String) -> Result<Vec<String>, String> {
+    let user_id = get_current_user_id(&auth).ok_or("Not logged in")?;
+    let user = db.get_user(user_id).map_err(|e| e.to_string())?.ok_or("User not found")?;
+    if user.totp_enabled {
+        return Err("Two-factor authentication is already on".to_string());
+    }
+    let secret = db.get_totp_secret(user_id).map_err(|e| e.to_string())?
+        .ok_or("Start two-factor setup first")?;
+    if !accept_totp_code(&db, &user, &secret, &code)? {
+        return Err("Invalid authentication code. Check the time on your device and try again.".to_string());
+    }
+
+    let codes = crate::two_factor::generate_recovery_codes();
+    let hashes = codes.iter()
+        .map(|c| crate::two_factor::hash_recovery_code(c))
+        .collect::<Result<Vec<_>, _>>()?;
+    db.enable_totp(user_id, &hashes).map_err(|e| e.to_string())?;
+    update_current_user(&auth, |u| u.must_enroll_two_factor = false);
+
+    db.log_activity(
+        Some(user_id),
+        "TWO_FACTOR_ENABLE",
+        "User",
+        Some(&user_id.to_string()),
+        &format!("User {} turned on two-factor authentication", user.username)
+    ).ok();
+
+    Ok(codes)
 }
 
+/// Replaces all of the current user's recovery codes with new ones.
 #[tauri::command]
-pub fn upload_qr_code(
-    app: tauri::AppHandle,
-    auth: State<'_, AuthState>,
-    source_path: String,
-) -> Result<String, String> {
-    check_permission(&auth, "manage_settings")?;
-    
-    let app_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
-    let branding_dir = app_dir.join("branding");
-    std::fs::create_dir_all(&branding_dir).map_err(|e| e.to_string())?;
-    
-    let extension = std::path::Path::new(&source_path)
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("png");
-        
-    let target_path = branding_dir.join(format!("qrcode.{}", extension));
-    
-    std::fs::copy(&source_path, &target_path).map_err(|e| e.to_string())?;
-    
-    Ok(target_path.to_str().unwrap().to_string())
+pub fn regenerate_recovery_codes(db: State<'_, AppDb>, auth: State<'_, AuthState>, password: String) -> Result<Vec<String>, String> {
+    let user_id = get_current_user_id(&auth).ok_or("Not logged in")?;
+    let user = db.get_user(user_id).map_err(|e| e.to_string())?.ok_or("User not found")?;
+    if !bcrypt::verify(&password, &user.password_hash).map_err(|e| e.to_string())? {
+        return Err("Password is incorrect".to_string());
+    }
+    if !user.totp_enabled {
+        return Err("Two-factor authentication is not on".to_string());
+    }
+
+    let codes = crate::two_factor::generate_recovery_codes();
+    let hashes = codes.iter()
+        .map(|c| crate::two_factor::hash_recovery_code(c))
+        .collect::<Result<Vec<_>, _>>()?;
+    db.replace_recovery_codes(user_id, &hashes).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        Some(user_id),
+        "TWO_FACTOR_CODES",
+        "User",
+        Some(&user_id.to_string()),
+        &format!("User {} generated new recovery codes", user.username)
+    ).ok();
+
+    Ok(codes)
 }
-// ── Categories ─────────────────────────────────────────
 
 #[tauri::command]
-pub fn get_categories(db: State<'_, AppDb>) -> Result<Vec<Category>, String> {
-    db.get_categories().map_err(|e| e.to_string())
+pub fn disable_two_factor(db: State<'_, AppDb>, auth: State<'_, AuthState>, password: String) -> Result<(), String> {
+    let session = current_user(&auth).ok_or("Not logged in")?;
+    let user = db.get_user(session.id).map_err(|e| e.to_string())?.ok_or("User not found")?;
+    if !bcrypt::verify(&password, &user.password_hash).map_err(|e| e.to_string())? {
+        return Err("Password is incorrect".to_string());
+    }
+    let policy = db.get_password_policy().map_err(|e| e.to_string())?;
+    if two_factor_required(&policy, &session.role, &session.permissions) {
+        return Err("Two-factor authentication is required for your account and cannot be turned off".to_string());
+    }
+    db.disable_totp(session.id).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        Some(session.id),
+        "TWO_FACTOR_DISABLE",
+        "User",
+        Some(&session.id.to_string()),
+        &format!("User {} turned off two-factor authentication", user.username)
+    ).ok();
+
+    Ok(())
 }
 
+/// Sets another user's password. Unless `require_change` is false the user
+/// must replace it at their next login. Your own password is changed with
+/// `change_password`, which checks the current one and the history.
 #[tauri::command]
-pub fn create_category(
+pub fn admin_reset_password(
     db: State<'_, AppDb>,
     auth: State<'_, AuthState>,
-    name: String,
-    category_type: String,
-) -> Result<i64, String> {
-    check_permission(&auth, "manage_settings")?;
-    let c = Category { id: None, name: name.clone(), category_type };
-    let id = db.create_category(&c).map_err(|e| e.to_string())?;
-    
+    user_id: i64,
+    new_password: String,
+    require_change: Option<bool>,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    if get_current_user_id(&auth) == Some(user_id) {
+        return Err("Use Change Password to change your own password".to_string());
+    }
+    let user = db.get_user(user_id).map_err(|e| e.to_string())?.ok_or("User not found")?;
+    check_user_change(&db, &auth, &user, false)?;
+    let policy = db.get_password_policy().map_err(|e| e.to_string())?;
+    crate::password::validate(&policy, &new_password)?;
+
+    let hash = crate::password::hash(&new_password)?;
+    db.set_password(user_id, &hash, require_change.unwrap_or(true)).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "PASSWORD_RESET",
+        "User",
+        Some(&user_id.to_string()),
+        &format!("Reset password for user {}", user.username)
+    ).ok();
+
+    Ok(())
+}
+
+fn current_user_is_admin(auth: &State<'_, AuthState>) -> bool {
+    current_user(auth).map(|u| u.role == "Admin").unwrap_or(false)
+}
+
+/// Rules shared by the user lifecycle commands: only an Admin may change an
+/// Admin's account, and there must always be an active Admin left.
+fn check_user_change(
+    db: &State<'_, AppDb>,
+    auth: &State<'_, AuthState>,
+    target: &User,
+    removes_admin: bool,
+) -> Result<(), String> {
+    if target.role == "Admin" && !current_user_is_admin(auth) {
+        return Err("Only an administrator can change an administrator's account".to_string());
+    }
+    if removes_admin && target.role == "Admin" && target.is_active
+        && db.count_active_admins().map_err(|e| e.to_string())? <= 1
+    {
+        return Err("Cannot remove the last active administrator".to_string());
+    }
+    Ok(())
+}
+
+/// Deactivated users cannot log in; their records and audit history stay.
+#[tauri::command]
+pub fn set_user_active(db: State<'_, AppDb>, auth: State<'_, AuthState>, user_id: i64, active: bool) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    let user = db.get_user(user_id).map_err(|e| e.to_string())?.ok_or("User not found")?;
+    if !active && get_current_user_id(&auth) == Some(user_id) {
+        return Err("You cannot deactivate your own account".to_string());
+    }
+    check_user_change(&db, &auth, &user, !active)?;
+    db.set_user_active(user_id, active).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        if active { "ACTIVATE" } else { "DEACTIVATE" },
+        "User",
+        Some(&user_id.to_string()),
+        &format!("{} user {}", if active { "Reactivated" } else { "Deactivated" }, user.username)
+    ).ok();
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn update_user_role(db: State<'_, AppDb>, auth: State<'_, AuthState>, user_id: i64, role: String) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    if role != "Admin" && role != "User" {
+        return Err(format!("Unknown role: {}", role));
+    }
+    if get_current_user_id(&auth) == Some(user_id) {
+        return Err("You cannot change your own role".to_string());
+    }
+    let user = db.get_user(user_id).map_err(|e| e.to_string())?.ok_or("User not found")?;
+    if user.role == role {
+        return Ok(());
+    }
+    if role == "Admin" && !current_user_is_admin(&auth) {
+        return Err("Only an administrator can make someone an administrator".to_string());
+    }
+    check_user_change(&db, &auth, &user, true)?;
+    db.set_user_role(user_id, &role).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "CHANGE_ROLE",
+        "User",
+        Some(&user_id.to_string()),
+        &format!("Changed role of {} from {} to {}", user.username, user.role, role)
+    ).ok();
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn rename_user(db: State<'_, AppDb>, auth: State<'_, AuthState>, user_id: i64, username: String) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    let username = username.trim().to_string();
+    if username.is_empty() {
+        return Err("Username is required".to_string());
+    }
+    let user = db.get_user(user_id).map_err(|e| e.to_string())?.ok_or("User not found")?;
+    if user.username == username {
+        return Ok(());
+    }
+    check_user_change(&db, &auth, &user, false)?;
+    if db.get_user_by_username(&username).map_err(|e| e.to_string())?.is_some() {
+        return Err("Username already exists".to_string());
+    }
+    db.rename_user(user_id, &username).map_err(|e| e.to_string())?;
+    if get_current_user_id(&auth) == Some(user_id) {
+        update_current_user(&auth, |u| u.username = username.clone());
+    }
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "RENAME",
+        "User",
+        Some(&user_id.to_string()),
+        &format!("Renamed user {} to {}", user.username, username)
+    ).ok();
+
+    Ok(())
+}
+
+/// Removes the account for good. Audit log entries are kept under the old
+/// username; deactivating is the gentler option for departed staff.
+#[tauri::command]
+pub fn delete_user(db: State<'_, AppDb>, auth: State<'_, AuthState>, user_id: i64) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    if get_current_user_id(&auth) == Some(user_id) {
+        return Err("You cannot delete your own account".to_string());
+    }
+    let user = db.get_user(user_id).map_err(|e| e.to_string())?.ok_or("User not found")?;
+    check_user_change(&db, &auth, &user, true)?;
+    db.delete_user(user_id).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "DELETE",
+        "User",
+        Some(&user_id.to_string()),
+        &format!("Deleted user {}", user.username)
+    ).ok();
+
+    Ok(())
+}
+
+/// Lifts a login lockout and clears the failed attempt count.
+#[tauri::command]
+pub fn unlock_user(db: State<'_, AppDb>, auth: State<'_, AuthState>, user_id: i64) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    let user = db.get_user(user_id).map_err(|e| e.to_string())?.ok_or("User not found")?;
+    check_user_change(&db, &auth, &user, false)?;
+    db.clear_login_failures(user_id).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UNLOCK",
+        "User",
+        Some(&user_id.to_string()),
+        &format!("Unlocked user {}", user.username)
+    ).ok();
+
+    Ok(())
+}
+
+/// Removes another user's two-factor enrollment, e.g. after a lost phone. If
+/// the policy requires it they will have to enroll again at their next login.
+#[tauri::command]
+pub fn reset_two_factor(db: State<'_, AppDb>, auth: State<'_, AuthState>, user_id: i64) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    let user = db.get_user(user_id).map_err(|e| e.to_string())?.ok_or("User not found")?;
+    check_user_change(&db, &auth, &user, false)?;
+    db.disable_totp(user_id).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "TWO_FACTOR_RESET",
+        "User",
+        Some(&user_id.to_string()),
+        &format!("Reset two-factor authentication for user {}", user.username)
+    ).ok();
+
+    Ok(())
+}
+
+/// The policy is shown on the password change screen, so any session may read it.
+#[tauri::command]
+pub fn get_password_policy(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<PasswordPolicy, String> {
+    get_current_user_id(&auth).ok_or("Not logged in")?;
+    db.get_password_policy().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn update_password_policy(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    policy: PasswordPolicy,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    if !(4..=128).contains(&policy.min_length) {
+        return Err("Minimum length must be between 4 and 128".to_string());
+    }
+    if !(0..=24).contains(&policy.history_count) {
+        return Err("Password history must be between 0 and 24".to_string());
+    }
+    if !(0..=480).contains(&policy.idle_timeout_minutes) {
+        return Err("Idle timeout must be between 0 and 480 minutes".to_string());
+    }
+    if !(1..=168).contains(&policy.session_lifetime_hours) {
+        return Err("Session lifetime must be between 1 and 168 hours".to_string());
+    }
+    if !(0..=100).contains(&policy.max_failed_logins) {
+        return Err("Failed login limit must be between 0 and 100".to_string());
+    }
+    if !(1..=1440).contains(&policy.lockout_minutes) {
+        return Err("Lockout duration must be between 1 and 1440 minutes".to_string());
+    }
+    db.update_password_policy(&policy).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE",
+        "PasswordPolicy",
+        None,
+        &format!(
+            "Password policy: min {} chars, upper {}, lower {}, digit {}, symbol {}, history {}, lock after {} failures for {} min, require 2FA {}, idle lock {} min, session {} h",
+            policy.min_length, policy.require_uppercase, policy.require_lowercase,
+            policy.require_digit, policy.require_symbol, policy.history_count,
+            policy.max_failed_logins, policy.lockout_minutes, policy.require_two_factor,
+            policy.idle_timeout_minutes, policy.session_lifetime_hours
+        )
+    ).ok();
+
+    Ok(())
+}
+
+// ── Database Encryption ─────────────────────────────────
+
+/// Works before login and before the database is open, so the app knows
+/// whether to ask for the passphrase or keyfile first.
+#[tauri::command]
+pub fn get_database_status(app: tauri::AppHandle, files: State<'_, DbFiles>) -> Result<DatabaseStatus, String> {
+    let encrypted = files.is_encrypted();
+    let source = encrypted.then(|| files.key_source());
+    Ok(DatabaseStatus {
+        encrypted,
+        unlocked: app.try_state::<AppDb>().is_some(),
+        key_source: source.as_ref().map(|s| match s {
+            KeySource::Passphrase => "passphrase".to_string(),
+            KeySource::Keyfile { .. } => "keyfile".to_string(),
+        }),
+        keyfile_path: match source {
+            Some(KeySource::Keyfile { path }) => Some(path),
+            _ => None,
+        },
+    })
+}
+
+/// Opens the encrypted database with the passphrase, or with a keyfile. A
+/// keyfile at a new path (say, a different drive letter) is remembered.
+#[tauri::command]
+pub fn unlock_database(
+    app: tauri::AppHandle,
+    files: State<'_, DbFiles>,
+    passphrase: Option<String>,
+    keyfile_path: Option<String>,
+) -> Result<(), String> {
+    if app.try_state::<AppDb>().is_some() {
+        return Ok(());
+    }
+    let (key, wrong_key) = match &keyfile_path {
+        Some(path) => (crate::encryption::read_keyfile(path)?, "This keyfile does not unlock the database"),
+        None => (passphrase.ok_or("Passphrase is required")?, "Passphrase is incorrect"),
+    };
+    let db = AppDb::new(files.db_path_str(), Some(&key)).map_err(|e| match e {
+        rusqlite::Error::SqliteFailure(f, _) if f.code == rusqlite::ErrorCode::NotADatabase => wrong_key.to_string(),
+        e => e.to_string(),
+    })?;
+
+    if let Some(path) = keyfile_path {
+        let source = KeySource::Keyfile { path };
+        if files.key_source() != source {
+            files.save_key_source(&source)?;
+        }
+    }
+    db.log_activity(None, "UNLOCK", "Database", None, "Encrypted database unlocked").ok();
+    app.manage(db);
+    Ok(())
+}
+
+fn check_database_admin(db: &State<'_, AppDb>, auth: &State<'_, AuthState>) -> Result<(), String> {
+    check_current_permission(db, auth, Permission::ManageSettings)?;
+    if !current_user_is_admin(auth) {
+        return Err("Only an administrator can manage database encryption".to_string());
+    }
+    Ok(())
+}
+
+fn new_key_source(key_source: &str, keyfile_path: Option<String>) -> Result<KeySource, String> {
+    match key_source {
+        "passphrase" => Ok(KeySource::Passphrase),
+        "keyfile" => {
+            let path = keyfile_path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty())
+                .ok_or("Keyfile path is required")?;
+            Ok(KeySource::Keyfile { path })
+        }
+        _ => Err(format!("Unknown key source: {}", key_source)),
+    }
+}
+
+/// Encrypts the existing plaintext database. With a keyfile, an existing file
+/// at the path is used as the key; otherwise a new random one is written there.
+#[tauri::command]
+pub fn encrypt_database(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    files: State<'_, DbFiles>,
+    key_source: String,
+    passphrase: Option<String>,
+    keyfile_path: Option<String>,
+) -> Result<(), String> {
+    check_database_admin(&db, &auth)?;
+    if files.is_encrypted() {
+        return Err("The database is already encrypted".to_string());
+    }
+    let source = new_key_source(&key_source, keyfile_path)?;
+    let key = crate::encryption::resolve_new_key(&source, passphrase.as_deref())?;
+
+    // Saved first: it is ignored while the file is still plaintext.
+    files.save_key_source(&source)?;
+    db.encrypt(files.db_path_str(), &key)?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "ENCRYPT",
+        "Database",
+        None,
+        &format!("Encrypted the database with a {}", key_source)
+    ).ok();
+
+    Ok(())
+}
+
+/// Re-encrypts the database with a new passphrase or keyfile. The current
+/// passphrase must be given when one is in use.
+#[tauri::command]
+pub fn change_database_key(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    files: State<'_, DbFiles>,
+    current_passphrase: Option<String>,
+    key_source: String,
+    passphrase: Option<String>,
+    keyfile_path: Option<String>,
+) -> Result<(), String> {
+    check_database_admin(&db, &auth)?;
+    if !files.is_encrypted() {
+        return Err("The database is not encrypted".to_string());
+    }
+    let current_key = match files.key_source() {
+        KeySource::Passphrase => current_passphrase.ok_or("Current passphrase is required")?,
+        KeySource::Keyfile { path } => crate::encryption::read_keyfile(&path)?,
+    };
+    if !AppDb::key_matches(files.db_path_str(), &current_key) {
+        return Err("Current passphrase is incorrect".to_string());
+    }
+
+    let source = new_key_source(&key_source, keyfile_path)?;
+    let key = crate::encryption::resolve_new_key(&source, passphrase.as_deref())?;
+    if key == current_key {
+        return Err("The new key must differ from the current one".to_string());
+    }
+    db.rekey(&key).map_err(|e| e.to_string())?;
+    files.save_key_source(&source)
+        .map_err(|e| format!("The database key was changed. {}", e))?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "CHANGE_KEY",
+        "Database",
+        None,
+        &format!("Changed the database key to a {}", key_source)
+    ).ok();
+
+    Ok(())
+}
+
+// ── Admin Commands ──────────────────────────────────────
+
+#[tauri::command]
+pub fn get_users(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<User>, String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    db.get_users().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_user_permissions(db: State<'_, AppDb>, auth: State<'_, AuthState>, user_id: i64) -> Result<Vec<String>, String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    db.get_user_permissions(user_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn update_user_permissions(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    user_id: i64,
+    permissions: Vec<String>
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    crate::permissions::validate_names(&permissions)?;
+    db.update_user_permissions(user_id, permissions).map_err(|e| e.to_string())?;
+    
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE_PERMISSIONS",
+        "User",
+        Some(&user_id.to_string()),
+        &format!("Updated permissions for user ID: {}", user_id)
+    ).ok();
+
+    Ok(())
+}
+
+/// The permission registry, for building grant forms.
+#[tauri::command]
+pub fn get_permissions(auth: State<'_, AuthState>) -> Result<Vec<PermissionInfo>, String> {
+    check_permission(&auth, Permission::ManageUsers)?;
+    Ok(Permission::ALL.iter().map(|p| PermissionInfo {
+        name: p.name().to_string(),
+        group: p.group().to_string(),
+        description: p.description().to_string(),
+    }).collect())
+}
+
+// ── Roles ───────────────────────────────────────────────
+
+fn validate_role(db: &AppDb, r: &Role) -> Result<(), String> {
+    if r.name.is_empty() {
+        return Err("Role name is required".to_string());
+    }
+    if r.name.contains('\n') {
+        return Err("Role name must be a single line".to_string());
+    }
+    crate::permissions::validate_names(&r.permissions)?;
+    let existing = db.get_roles().map_err(|e| e.to_string())?;
+    if existing.iter().any(|o| o.id != r.id && o.name.eq_ignore_ascii_case(&r.name)) {
+        return Err(format!("A role named {} already exists", r.name));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_roles(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<Role>, String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    db.get_roles().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn create_role(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    name: String,
+    description: Option<String>,
+    permissions: Vec<String>,
+) -> Result<i64, String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    let r = Role {
+        id: None,
+        name: name.trim().to_string(),
+        description: description.filter(|d| !d.trim().is_empty()),
+        permissions,
+        user_count: 0,
+        created_at: None,
+    };
+    validate_role(&db, &r)?;
+    let id = db.save_role(&r).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "CREATE",
+        "Role",
+        Some(&id.to_string()),
+        &format!("Created role {} with permissions: {}", r.name, r.permissions.join(", "))
+    ).ok();
+
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn update_role(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    id: i64,
+    name: Option<String>,
+    description: Option<String>,
+    permissions: Option<Vec<String>>,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    let existing = db.get_role(id).map_err(|e| e.to_string())?.ok_or("Role not found")?;
+    let r = Role {
+        name: name.map(|n| n.trim().to_string()).unwrap_or_else(|| existing.name.clone()),
+        description: match description {
+            Some(d) => Some(d).filter(|d| !d.trim().is_empty()),
+            None => existing.description.clone(),
+        },
+        permissions: permissions.unwrap_or_else(|| existing.permissions.clone()),
+        ..existing
+    };
+    validate_role(&db, &r)?;
+    db.save_role(&r).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE",
+        "Role",
+        Some(&id.to_string()),
+        &format!("Updated role {} with permissions: {}", r.name, r.permissions.join(", "))
+    ).ok();
+
+    Ok(())
+}
+
+/// Deletes a role; users holding it keep their other roles and direct grants.
+#[tauri::command]
+pub fn delete_role(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    let existing = db.get_role(id).map_err(|e| e.to_string())?.ok_or("Role not found")?;
+    db.delete_role(id).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "DELETE",
+        "Role",
+        Some(&id.to_string()),
+        &format!("Deleted role {} (assigned to {} user(s))", existing.name, existing.user_count)
+    ).ok();
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_user_roles(db: State<'_, AppDb>, auth: State<'_, AuthState>, user_id: i64) -> Result<Vec<i64>, String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    db.get_user_role_ids(user_id).map_err(|e| e.to_string())
+}
+
+/// Replaces the roles assigned to a user. A logged-in user's session picks the
+/// change up at its next permission re-check (see `check_current_permission`).
+#[tauri::command]
+pub fn update_user_roles(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    user_id: i64,
+    role_ids: Vec<i64>,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManageUsers)?;
+    let roles = db.get_roles().map_err(|e| e.to_string())?;
+    let mut names = Vec::new();
+    for id in &role_ids {
+        let role = roles.iter().find(|r| r.id == Some(*id)).ok_or_else(|| format!("Role {} not found", id))?;
+        names.push(role.name.clone());
+    }
+    db.update_user_roles(user_id, &role_ids).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE_ROLES",
+        "User",
+        Some(&user_id.to_string()),
+        &format!("Assigned roles to user ID {}: {}", user_id, if names.is_empty() { "none".to_string() } else { names.join(", ") })
+    ).ok();
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_audit_logs(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    limit: i64,
+    offset: i64,
+    module: Option<String>,
+    user_id: Option<i64>,
+    date: Option<String>,
+    month: Option<String>,
+) -> Result<Vec<AuditLog>, String> {
+    check_current_permission(&db, &auth, Permission::ViewActivityLogs)?;
+    db.get_audit_logs(limit, offset, module, user_id, date, month).map_err(|e| e.to_string())
+}
+#[tauri::command]
+pub fn upload_logo(
+    app: tauri::AppHandle,
+    auth: State<'_, AuthState>,
+    source_path: String,
+) -> Result<String, String> {
+    check_permission(&auth, Permission::ManageSettings)?;
+    
+    let app_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let branding_dir = app_dir.join("branding");
+    std::fs::create_dir_all(&branding_dir).map_err(|e| e.to_string())?;
+    
+    let extension = std::path::Path::new(&source_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("png");
+        
+    let target_path = branding_dir.join(format!("logo.{}", extension));
+    
+    std::fs::copy(&source_path, &target_path).map_err(|e| e.to_string())?;
+    
+    Ok(target_path.to_str().unwrap().to_string())
+}
+
+#[tauri::command]
+pub fn upload_signature(
+    app: tauri::AppHandle,
+    auth: State<'_, AuthState>,
+    source_path: String,
+) -> Result<String, String> {
+    check_permission(&auth, Permission::ManageSettings)?;
+    
+    let app_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let branding_dir = app_dir.join("branding");
+    std::fs::create_dir_all(&branding_dir).map_err(|e| e.to_string())?;
+    
+    let extension = std::path::Path::new(&source_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("png");
+        
+    let target_path = branding_dir.join(format!("signature.{}", extension));
+    
+    std::fs::copy(&source_path, &target_path).map_err(|e| e.to_string())?;
+    
+    Ok(target_path.to_str().unwrap().to_string())
+}
+
+#[tauri::command]
+pub fn upload_qr_code(
+    app: tauri::AppHandle,
+    auth: State<'_, AuthState>,
+    source_path: String,
+) -> Result<String, String> {
+    check_permission(&auth, Permission::ManageSettings)?;
+    
+    let app_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let branding_dir = app_dir.join("branding");
+    std::fs::create_dir_all(&branding_dir).map_err(|e| e.to_string())?;
+    
+    let extension = std::path::Path::new(&source_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("png");
+        
+    let target_path = branding_dir.join(format!("qrcode.{}", extension));
+    
+    std::fs::copy(&source_path, &target_path).map_err(|e| e.to_string())?;
+    
+    Ok(target_path.to_str().unwrap().to_string())
+}
+// ── Categories ─────────────────────────────────────────
+
+#[tauri::command]
+pub fn get_categories(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<Category>, String> {
+    require_session(&auth)?;
+    db.get_categories().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn create_category(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    name: String,
+    category_type: String,
+) -> Result<i64, String> {
+    check_current_permission(&db, &auth, Permission::ManageSettings)?;
+    let c = Category { id: None, name: name.clone(), category_type };
+    let id = db.create_category(&c).map_err(|e| e.to_string())?;
+    
+    db.log_activity(
+        get_current_user_id(&auth),
+        "CREATE",
+        "Category",
+        Some(&id.to_string()),
+        &format!("Created category: {}", name)
+    ).ok();
+
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn delete_category(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManageSettings)?;
+    db.delete_category(id).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// ── Accounts ───────────────────────────────────────────
+
+#[tauri::command]
+pub fn get_accounts(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<Account>, String> {
+    check_any_permission(&auth, &[Permission::ViewTransactions, Permission::CreateTransactions, Permission::ManagePayroll, Permission::ManageSettings])?;
+    db.get_accounts().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn create_account(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    name: String,
+    account_type: String,
+    balance: f64,
+    currency: String,
+) -> Result<i64, String> {
+    check_current_permission(&db, &auth, Permission::ManageSettings)?;
+    let a = Account { id: None, name: name.clone(), account_type, balance, currency };
+    let id = db.create_account(&a).map_err(|e| e.to_string())?;
+    
+    db.log_activity(
+        get_current_user_id(&auth),
+        "CREATE",
+        "Account",
+        Some(&id.to_string()),
+        &format!("Created account: {}", name)
+    ).ok();
+
+    Ok(id)
+}
+
+// ── Transactions ───────────────────────────────────────
+
+#[tauri::command]
+pub fn get_transactions(db: State<'_, AppDb>, auth: State<'_, AuthState>, limit: i64) -> Result<Vec<Transaction>, String> {
+    check_permission(&auth, Permission::ViewTransactions)?;
+    let mut transactions = db.get_transactions(limit).map_err(|e| e.to_string())?;
+    if !has_permission(&auth, PAYROLL_ACCESS) {
+        transactions.iter_mut().for_each(hide_payroll_posting);
+    }
+    Ok(transactions)
+}
+
+/// Salary and remittance postings name the employee they pay; users without
+/// payroll access only see them as payroll.
+fn hide_payroll_posting(t: &mut Transaction) {
+    let reference = t.reference_id.as_deref().unwrap_or("");
+    if reference.starts_with("PAY-") || reference.starts_with("PAYL-") {
+        t.description = Some("Payroll".to_string());
+    }
+}
+
+#[tauri::command]
+pub fn create_transaction(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    account_id: i64,
+    category_id: Option<i64>,
+    amount: f64,
+    transaction_type: String,
+    description: Option<String>,
+    date: String,
+    reference_id: Option<String>,
+) -> Result<i64, String> {
+    check_permission(&auth, Permission::CreateTransactions)?;
+    let t = Transaction {
+        id: None,
+        account_id,
+        category_id,
+        amount,
+        transaction_type: transaction_type.clone(),
+        description,
+        date,
+        reference_id,
+        created_at: None,
+    };
+    let id = db.create_transaction(&t).map_err(|e| e.to_string())?;
+    
+    db.log_activity(
+        get_current_user_id(&auth),
+        "CREATE",
+        "Transaction",
+        Some(&id.to_string()),
+        &format!("Recorded {} transaction of {}", transaction_type, amount)
+    ).ok();
+
+    Ok(id)
+}
+
+// ── Employees & Payroll ────────────────────────────────
+
+#[tauri::command]
+pub fn get_employees(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<Employee>, String> {
+    check_any_permission(&auth, &[Permission::ViewEmployees, Permission::ViewPayroll, Permission::ManagePayroll])?;
+    let mut employees = db.get_employees().map_err(|e| e.to_string())?;
+    if !has_permission(&auth, PAYROLL_ACCESS) {
+        employees.iter_mut().for_each(hide_pay_details);
+    }
+    Ok(employees)
+}
+
+/// Blanks the pay and bank fields of an employee for users without payroll access.
+fn hide_pay_details(e: &mut Employee) {
+    e.salary = 0.0;
+    e.allowances = 0.0;
+    e.overtime_rate = 0.0;
+    e.late_penalty = 0.0;
+    e.bank_name = None;
+    e.bank_branch = None;
+    e.bank_account_no = None;
+}
+
+fn validate_employee_schedule(e: &Employee) -> Result<(), String> {
+    if e.overtime_rate < 0.0 || e.late_penalty < 0.0 {
+        return Err("Overtime rate and late penalty cannot be negative".to_string());
+    }
+    if !(0.0..=24.0).contains(&e.standard_hours) || e.standard_hours == 0.0 {
+        return Err("Standard hours must be between 0 and 24".to_string());
+    }
+    if let Some(ref t) = e.shift_start {
+        parse_clock_time(t)?;
+    }
+    Ok(())
+}
+
+const PAY_FREQUENCIES: [&str; 3] = ["Monthly", "BiWeekly", "Weekly"];
+
+fn validate_pay_frequency(pay_frequency: &str) -> Result<(), String> {
+    if !PAY_FREQUENCIES.contains(&pay_frequency) {
+        return Err(format!("Unknown pay frequency: {}", pay_frequency));
+    }
+    Ok(())
+}
+
+fn validate_employment(e: &Employee) -> Result<(), String> {
+    if !["Active", "Suspended", "Terminated"].contains(&e.status.as_str()) {
+        return Err(format!("Unknown employment status: {}", e.status));
+    }
+    validate_pay_frequency(&e.pay_frequency)?;
+    let join = e.join_date.as_deref().map(parse_date).transpose()?;
+    let exit = e.exit_date.as_deref().map(parse_date).transpose()?;
+    if let (Some(join), Some(exit)) = (join, exit) {
+        if exit < join {
+            return Err("Exit date cannot be before the join date".to_string());
+        }
+    }
+    if e.status == "Terminated" && exit.is_none() {
+        return Err("Terminated employees need an exit date".to_string());
+    }
+    Ok(())
+}
+
+fn parse_clock_time(t: &str) -> Result<chrono::NaiveTime, String> {
+    chrono::NaiveTime::parse_from_str(t, "%H:%M").map_err(|_| format!("Invalid time {} (expected HH:MM)", t))
+}
+
+fn parse_date(d: &str) -> Result<chrono::NaiveDate, String> {
+    chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| format!("Invalid date {} (expected YYYY-MM-DD)", d))
+}
+
+#[tauri::command]
+pub fn create_employee(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    name: String,
+    role: Option<String>,
+    email: Option<String>,
+    phone: Option<String>,
+    salary: f64,
+    allowances: Option<f64>,
+    overtime_rate: Option<f64>,
+    standard_hours: Option<f64>,
+    shift_start: Option<String>,
+    late_penalty: Option<f64>,
+    bank_name: Option<String>,
+    bank_branch: Option<String>,
+    bank_account_no: Option<String>,
+    department: Option<String>,
+    status: Option<String>,
+    join_date: Option<String>,
+    exit_date: Option<String>,
+    pay_frequency: Option<String>,
+) -> Result<i64, String> {
+    check_permission(&auth, Permission::CreateEmployees)?;
+    let mut e = Employee {
+        id: None, name: name.clone(), role, email, phone, salary,
+        allowances: allowances.unwrap_or(0.0),
+        overtime_rate: overtime_rate.unwrap_or(0.0),
+        standard_hours: standard_hours.unwrap_or(8.0),
+        shift_start: shift_start.filter(|s| !s.is_empty()),
+        late_penalty: late_penalty.unwrap_or(0.0),
+        bank_name: bank_name.filter(|s| !s.is_empty()),
+        bank_branch: bank_branch.filter(|s| !s.is_empty()),
+        bank_account_no: bank_account_no.filter(|s| !s.is_empty()),
+        department: department.filter(|s| !s.is_empty()),
+        status: status.unwrap_or_else(|| "Active".to_string()),
+        join_date: join_date.filter(|s| !s.is_empty()),
+        exit_date: exit_date.filter(|s| !s.is_empty()),
+        pay_frequency: pay_frequency.unwrap_or_else(|| "Monthly".to_string()),
+        created_at: None,
+    };
+    // Pay is set by payroll staff; others can only add the employee record.
+    if !has_permission(&auth, PAYROLL_ACCESS) {
+        hide_pay_details(&mut e);
+    }
+    validate_employee_schedule(&e)?;
+    validate_employment(&e)?;
+    let id = db.create_employee(&e).map_err(|e| e.to_string())?;
+    
+    db.log_activity(
+        get_current_user_id(&auth),
+        "CREATE",
+        "Employee",
+        Some(&id.to_string()),
+        &format!("Added employee: {}", name)
+    ).ok();
+
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn update_employee(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    id: i64,
+    name: String,
+    role: Option<String>,
+    email: Option<String>,
+    phone: Option<String>,
+    salary: f64,
+    allowances: Option<f64>,
+    overtime_rate: Option<f64>,
+    standard_hours: Option<f64>,
+    shift_start: Option<String>,
+    late_penalty: Option<f64>,
+    bank_name: Option<String>,
+    bank_branch: Option<String>,
+    bank_account_no: Option<String>,
+    department: Option<String>,
+    status: Option<String>,
+    join_date: Option<String>,
+    exit_date: Option<String>,
+    pay_frequency: Option<String>,
+) -> Result<(), String> {
+    check_permission(&auth, Permission::EditEmployees)?;
+    let existing = db.get_employee(id).map_err(|e| e.to_string())?
+        .ok_or("Employee not found")?;
+    // Without payroll access the (hidden) pay fields are left as they were.
+    let (salary, allowances, overtime_rate, late_penalty, bank_name, bank_branch, bank_account_no) =
+        if has_permission(&auth, PAYROLL_ACCESS) {
+            (salary, allowances, overtime_rate, late_penalty, bank_name, bank_branch, bank_account_no)
+        } else {
+            (existing.salary, Some(existing.allowances), None, None, None, None, None)
+        };
+    let e = Employee {
+        id: Some(id),
+        name: name.clone(),
+        role,
+        email,
+        phone,
+        salary,
+        allowances: allowances.unwrap_or(0.0),
+        overtime_rate: overtime_rate.unwrap_or(existing.overtime_rate),
+        standard_hours: standard_hours.unwrap_or(existing.standard_hours),
+        shift_start: shift_start.or(existing.shift_start).filter(|s| !s.is_empty()),
+        late_penalty: late_penalty.unwrap_or(existing.late_penalty),
+        bank_name: bank_name.or(existing.bank_name).filter(|s| !s.is_empty()),
+        bank_branch: bank_branch.or(existing.bank_branch).filter(|s| !s.is_empty()),
+        bank_account_no: bank_account_no.or(existing.bank_account_no).filter(|s| !s.is_empty()),
+        department: department.or(existing.department).filter(|s| !s.is_empty()),
+        status: status.unwrap_or(existing.status),
+        join_date: join_date.or(existing.join_date).filter(|s| !s.is_empty()),
+        exit_date: exit_date.or(existing.exit_date).filter(|s| !s.is_empty()),
+        pay_frequency: pay_frequency.unwrap_or(existing.pay_frequency),
+        created_at: None,
+    };
+    validate_employee_schedule(&e)?;
+    validate_employment(&e)?;
+    db.update_employee(&e).map_err(|e| e.to_string())?;
+    
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE",
+        "Employee",
+        Some(&id.to_string()),
+        &format!("Updated employee: {}", name)
+    ).ok();
+
+    Ok(())
+}
+
+/// The account payroll is paid from, defaulting to Cash.
+fn payroll_account(db: &AppDb, account_id: Option<i64>) -> Result<i64, String> {
+    let account_id = account_id.unwrap_or(1);
+    let accounts = db.get_accounts().map_err(|e| e.to_string())?;
+    if !accounts.iter().any(|a| a.id == Some(account_id)) {
+        return Err("Account not found".to_string());
+    }
+    Ok(account_id)
+}
+
+/// Amounts entered over the top of what attendance derives for the period.
+#[derive(Default)]
+struct PayAdjustments {
+    overtime_pay: Option<f64>,
+    late_penalties: Option<f64>,
+    absences: Option<f64>,
+    /// Payroll record being recalculated; its own loan instalments don't count as recovered.
+    replacing: Option<i64>,
+}
+
+/// Share of a pay period `emp` was employed, from their join and exit dates.
+fn employment_proration(emp: &Employee, start: chrono::NaiveDate, end: chrono::NaiveDate) -> Result<f64, String> {
+    let join = emp.join_date.as_deref().map(parse_date).transpose()?;
+    let exit = emp.exit_date.as_deref().map(parse_date).transpose()?;
+    Ok(crate::payroll::employed_fraction(start, end, join, exit))
+}
+
+/// Builds the pay figures for `emp` over a pay period: overtime, late penalties
+/// and absences are derived from attendance (unless overridden), then the active
+/// payroll rules are applied and any loan instalments due are deducted. The
+/// employee's fixed allowances are always included, prorated for a mid-period
+/// join or exit. Without an explicit `base_salary` the saved salary is prorated
+/// the same way. Callers fill in the payment date and status.
+fn build_payroll(
+    db: &AppDb,
+    emp: &Employee,
+    base_salary: Option<f64>,
+    bonuses: f64,
+    pay_period_start: &str,
+    pay_period_end: &str,
+    adjust: &PayAdjustments,
+) -> Result<PayrollPreview, String> {
+    let start = parse_date(pay_period_start)?;
+    let end = parse_date(pay_period_end)?;
+    if end < start {
+        return Err("Pay period end must not be before its start".to_string());
+    }
+    let proration = employment_proration(emp, start, end)?;
+    if proration <= 0.0 {
+        return Err(format!("{} was not employed during {} to {}", emp.name, pay_period_start, pay_period_end));
+    }
+    let round2 = |v: f64| (v * 100.0).round() / 100.0;
+    let full_salary = base_salary.unwrap_or(emp.salary);
+    let base_salary = base_salary.unwrap_or_else(|| round2(emp.salary * proration));
+
+    let employee_id = emp.id.unwrap_or(0);
+    let records = db.get_attendance(Some(employee_id), pay_period_start, pay_period_end).map_err(|e| e.to_string())?;
+    let leave_types = db.get_leave_types().map_err(|e| e.to_string())?;
+    // Absences are priced at the full-period daily rate
+    let mut attendance = crate::payroll::summarize_attendance(emp, full_salary, start, end, &records, &leave_types);
+    attendance.overtime_pay = adjust.overtime_pay.unwrap_or(attendance.overtime_pay);
+    attendance.late_penalties = adjust.late_penalties.unwrap_or(attendance.late_penalties);
+    attendance.absences = adjust.absences.unwrap_or(attendance.absences);
+
+    let components = db.get_payroll_components(true).map_err(|e| e.to_string())?;
+    let brackets = db.get_tax_brackets().map_err(|e| e.to_string())?;
+    let loan_instalments = db.get_loan_instalments(employee_id, pay_period_start, pay_period_end, adjust.replacing)
+        .map_err(|e| e.to_string())?;
+    let input = crate::payroll::PayInput {
+        base_salary,
+        allowances: round2(emp.allowances * proration),
+        overtime_pay: attendance.overtime_pay,
+        bonuses,
+        late_penalties: attendance.late_penalties,
+        absences: attendance.absences,
+        loan_instalments,
+    };
+    let pay = crate::payroll::compute(&input, &components, &brackets);
+    let record = PayrollRecord {
+        id: None,
+        employee_id,
+        employee_name: Some(emp.name.clone()),
+        employee_role: emp.role.clone(),
+        base_salary,
+        overtime_pay: input.overtime_pay,
+        bonuses,
+        allowances: pay.allowances,
+        gross_salary: pay.gross_salary,
+        tax: pay.tax,
+        late_penalties: pay.late_penalties,
+        absences: pay.absences,
+        other_deductions: pay.other_deductions,
+        total_deductions: pay.total_deductions,
+        net_pay: pay.net_pay,
+        pay_period_start: pay_period_start.to_string(),
+        pay_period_end: pay_period_end.to_string(),
+        payment_date: String::new(),
+        status: "Pending".to_string(),
+        notes: None,
+        employer_contributions: pay.employer_contributions,
+        lines: Some(pay.lines),
+        account_id: None,
+        run_id: None,
+    };
+    Ok(PayrollPreview { record, attendance, proration })
+}
+
+/// Shows what `create_payroll` would save for an employee and period, including
+/// the attendance figures behind overtime, late penalties and absences.
+#[tauri::command]
+pub fn preview_payroll(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    employee_id: i64,
+    base_salary: Option<f64>,
+    bonuses: Option<f64>,
+    pay_period_start: String,
+    pay_period_end: String,
+) -> Result<PayrollPreview, String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let emp = db.get_employee(employee_id).map_err(|e| e.to_string())?
+        .ok_or("Employee not found")?;
+    build_payroll(
+        &db, &emp, base_salary, bonuses.unwrap_or(0.0),
+        &pay_period_start, &pay_period_end, &PayAdjustments::default(),
+    )
+}
+
+#[tauri::command]
+pub fn create_payroll(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    employee_id: i64,
+    base_salary: f64,
+    bonuses: f64,
+    pay_period_start: String,
+    pay_period_end: String,
+    payment_date: String,
+    status: String,
+    notes: Option<String>,
+    overtime_pay: Option<f64>,
+    late_penalties: Option<f64>,
+    absences: Option<f64>,
+    account_id: Option<i64>,
+) -> Result<i64, String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    if !["Paid", "Pending"].contains(&status.as_str()) {
+        return Err(format!("Unknown payroll status: {}", status));
+    }
+    let account_id = payroll_account(&db, account_id)?;
+    if [overtime_pay, late_penalties, absences].iter().flatten().any(|v| *v < 0.0) {
+        return Err("Overtime, late penalties and absences cannot be negative".to_string());
+    }
+    let emp = db.get_employee(employee_id).map_err(|e| e.to_string())?
+        .ok_or("Employee not found")?;
+    if db.employees_paid_in_period(&pay_period_start, &pay_period_end).map_err(|e| e.to_string())?.contains(&employee_id) {
+        return Err(format!("{} already has payroll for part of this period", emp.name));
+    }
+    let adjust = PayAdjustments { overtime_pay, late_penalties, absences, replacing: None };
+    let preview = build_payroll(&db, &emp, Some(base_salary), bonuses, &pay_period_start, &pay_period_end, &adjust)?;
+    let p = PayrollRecord {
+        payment_date,
+        status,
+        notes,
+        account_id: Some(account_id),
+        ..preview.record
+    };
+    let id = db.create_payroll(&p).map_err(|e| e.to_string())?;
+    
+    db.log_activity(
+        get_current_user_id(&auth),
+        "CREATE",
+        "Payroll",
+        Some(&id.to_string()),
+        &format!("Recorded payroll payment for employee ID: {}", employee_id)
+    ).ok();
+
+    Ok(id)
+}
+
+/// Pays a Pending record made by `create_payroll`: net pay is expensed and its
+/// liabilities recorded, as for a record saved as Paid. Records in a run are
+/// paid with the run. The date and account default to those on the record.
+#[tauri::command]
+pub fn pay_payroll(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    id: i64,
+    payment_date: Option<String>,
+    account_id: Option<i64>,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let record = db.get_payroll_detail(id).map_err(|e| e.to_string())?;
+    if record.run_id.is_some() {
+        return Err("This record is part of a payroll run; pay the run instead".to_string());
+    }
+    if record.status != "Pending" {
+        return Err(format!("Only pending payroll can be paid (this record is {})", record.status));
+    }
+    let payment_date = payment_date.filter(|d| !d.is_empty()).unwrap_or(record.payment_date);
+    parse_date(&payment_date)?;
+    let account_id = payroll_account(&db, account_id.or(record.account_id))?;
+    db.pay_payroll(id, account_id, &payment_date).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "PAY",
+        "Payroll",
+        Some(&id.to_string()),
+        &format!("Paid payroll for {} on {}", record.employee_name.as_deref().unwrap_or("employee"), payment_date)
+    ).ok();
+
+    Ok(())
+}
+
+// ── Payroll Runs ───────────────────────────────────────
+
+fn load_payroll_run(db: &AppDb, id: i64) -> Result<PayrollRun, String> {
+    db.get_payroll_run_detail(id).map_err(|e| e.to_string())?
+        .ok_or_else(|| "Payroll run not found".to_string())
+}
+
+/// Loads a run that must still be a draft, with the record being adjusted.
+fn draft_run_record(db: &AppDb, run_id: i64, payroll_id: i64) -> Result<(PayrollRun, PayrollRecord), String> {
+    let run = load_payroll_run(db, run_id)?;
+    if run.status != "Draft" {
+        return Err("Only draft payroll runs can be changed".to_string());
+    }
+    let record = run.records.iter().flatten()
+        .find(|r| r.id == Some(payroll_id))
+        .cloned()
+        .ok_or("This employee is not part of the payroll run")?;
+    Ok((run, record))
+}
+
+#[tauri::command]
+pub fn get_payroll_runs(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<PayrollRun>, String> {
+    check_current_any_permission(&db, &auth, PAYROLL_ACCESS)?;
+    db.get_payroll_runs().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_payroll_run_detail(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64) -> Result<PayrollRun, String> {
+    check_current_any_permission(&db, &auth, PAYROLL_ACCESS)?;
+    load_payroll_run(&db, id)
+}
+
+/// Drafts a payroll run for every employee from their saved salary, attendance
+/// and the payroll rules. Nothing is posted until the run is approved and paid.
+/// Employees who already have payroll for part of the period are left out.
+#[tauri::command]
+pub fn create_payroll_run(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    pay_period_start: String,
+    pay_period_end: String,
+    payment_date: String,
+    bonuses: Option<f64>,
+    account_id: Option<i64>,
+    notes: Option<String>,
+    pay_frequency: Option<String>,
+) -> Result<i64, String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    parse_date(&payment_date)?;
+    let (start, end) = (parse_date(&pay_period_start)?, parse_date(&pay_period_end)?);
+    let account_id = payroll_account(&db, account_id)?;
+    let pay_frequency = pay_frequency.unwrap_or_else(|| "Monthly".to_string());
+    validate_pay_frequency(&pay_frequency)?;
+    // Active employees on this pay frequency, plus leavers whose exit falls in the period
+    let mut employees = Vec::new();
+    for emp in db.get_employees().map_err(|e| e.to_string())? {
+        if emp.pay_frequency == pay_frequency && emp.status != "Suspended" && employment_proration(&emp, start, end)? > 0.0 {
+            employees.push(emp);
+        }
+    }
+    if employees.is_empty() {
+        return Err(format!("No {} employees were employed during this pay period", pay_frequency.to_lowercase()));
+    }
+    // Anyone already paid (or drafted) for part of the period is left out, so they are not paid twice
+    let already_paid = db.employees_paid_in_period(&pay_period_start, &pay_period_end).map_err(|e| e.to_string())?;
+    employees.retain(|emp| !already_paid.contains(&emp.id.unwrap_or(0)));
+    if employees.is_empty() {
+        return Err("Every employee in this pay period already has payroll for it".to_string());
+    }
+    let mut records = Vec::new();
+    for emp in &employees {
+        let preview = build_payroll(
+            &db, emp, None, bonuses.unwrap_or(0.0), &pay_period_start, &pay_period_end, &PayAdjustments::default(),
+        )?;
+        records.push(PayrollRecord {
+            payment_date: payment_date.clone(),
+            account_id: Some(account_id),
+            ..preview.record
+        });
+    }
+    let run = PayrollRun {
+        id: None,
+        run_number: None,
+        pay_period_start,
+        pay_period_end,
+        payment_date,
+        account_id,
+        status: "Draft".to_string(),
+        notes,
+        created_by: get_current_user_id(&auth),
+        created_by_name: None,
+        approved_by: None,
+        approved_by_name: None,
+        approved_at: None,
+        paid_at: None,
+        void_reason: None,
+        created_at: None,
+        employee_count: 0,
+        total_gross: 0.0,
+        total_net: 0.0,
+        records: None,
+    };
+    let id = db.create_payroll_run(&run, &records).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "CREATE",
+        "PayrollRun",
+        Some(&id.to_string()),
+        &format!("Drafted payroll run for {} employees ({} to {})", records.len(), run.pay_period_start, run.pay_period_end)
+    ).ok();
+
+    Ok(id)
+}
+
+/// The one-step bulk payroll, kept for existing callers: drafts a run for the
+/// monthly-paid employees and returns how many it covers. The run is approved
+/// by a second user and paid like any other before anything is posted.
+#[tauri::command]
+pub fn create_bulk_payroll(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    pay_period_start: String,
+    pay_period_end: String,
+    payment_date: String,
+    bonuses: f64,
+    account_id: Option<i64>,
+) -> Result<i64, String> {
+    let run_id = create_payroll_run(
+        db.clone(), auth, pay_period_start, pay_period_end, payment_date, Some(bonuses), account_id, None, None,
+    )?;
+    Ok(load_payroll_run(&db, run_id)?.employee_count)
+}
+
+/// Recalculates one employee's pay in a draft run with the given adjustments.
+#[tauri::command]
+pub fn adjust_payroll_run_record(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    run_id: i64,
+    payroll_id: i64,
+    base_salary: Option<f64>,
+    bonuses: Option<f64>,
+    overtime_pay: Option<f64>,
+    late_penalties: Option<f64>,
+    absences: Option<f64>,
+    notes: Option<String>,
+) -> Result<PayrollRecord, String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    if [base_salary, bonuses, overtime_pay, late_penalties, absences].iter().flatten().any(|v| *v < 0.0) {
+        return Err("Payroll amounts cannot be negative".to_string());
+    }
+    let (run, record) = draft_run_record(&db, run_id, payroll_id)?;
+    let emp = db.get_employee(record.employee_id).map_err(|e| e.to_string())?
+        .ok_or("Employee not found")?;
+    let adjust = PayAdjustments {
+        overtime_pay: overtime_pay.or(Some(record.overtime_pay)),
+        late_penalties: late_penalties.or(Some(record.late_penalties)),
+        absences: absences.or(Some(record.absences)),
+        replacing: Some(payroll_id),
+    };
+    let preview = build_payroll(
+        &db, &emp,
+        Some(base_salary.unwrap_or(record.base_salary)), bonuses.unwrap_or(record.bonuses),
+        &run.pay_period_start, &run.pay_period_end, &adjust,
+    )?;
+    let updated = PayrollRecord {
+        id: record.id,
+        notes: notes.or(record.notes),
+        payment_date: record.payment_date,
+        account_id: record.account_id,
+        run_id: record.run_id,
+        ..preview.record
+    };
+    db.replace_payroll_record(payroll_id, &updated).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE",
+        "PayrollRun",
+        Some(&run_id.to_string()),
+        &format!("Adjusted pay for {} in {}", emp.name, run.run_number.as_deref().unwrap_or("payroll run"))
+    ).ok();
+
+    Ok(updated)
+}
+
+#[tauri::command]
+pub fn remove_payroll_run_record(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    run_id: i64,
+    payroll_id: i64,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let (run, record) = draft_run_record(&db, run_id, payroll_id)?;
+    db.delete_payroll_record(payroll_id).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE",
+        "PayrollRun",
+        Some(&run_id.to_string()),
+        &format!("Removed {} from {}", record.employee_name.as_deref().unwrap_or("employee"), run.run_number.as_deref().unwrap_or("payroll run"))
+    ).ok();
+
+    Ok(())
+}
+
+/// Approval must come from a different user than the one who drafted the run.
+#[tauri::command]
+pub fn approve_payroll_run(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    id: i64,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let run = load_payroll_run(&db, id)?;
+    if run.status != "Draft" {
+        return Err(format!("Only draft payroll runs can be approved (this one is {})", run.status));
+    }
+    if run.employee_count == 0 {
+        return Err("This payroll run has no employees".to_string());
+    }
+    let user_id = get_current_user_id(&auth);
+    if user_id.is_none() || user_id == run.created_by {
+        return Err("A payroll run must be approved by a different user than the one who prepared it".to_string());
+    }
+    db.approve_payroll_run(id, user_id).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        user_id,
+        "APPROVE",
+        "PayrollRun",
+        Some(&id.to_string()),
+        &format!("Approved {} ({} employees, net {:.2})", run.run_number.as_deref().unwrap_or("payroll run"), run.employee_count, run.total_net)
+    ).ok();
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn pay_payroll_run(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    id: i64,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let run = load_payroll_run(&db, id)?;
+    if run.status != "Approved" {
+        return Err("Only approved payroll runs can be paid".to_string());
+    }
+    db.pay_payroll_run(id).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE",
+        "PayrollRun",
+        Some(&id.to_string()),
+        &format!("Paid {} ({} employees, net {:.2})", run.run_number.as_deref().unwrap_or("payroll run"), run.employee_count, run.total_net)
+    ).ok();
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn void_payroll_run(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    id: i64,
+    reason: Option<String>,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let run = load_payroll_run(&db, id)?;
+    if run.status == "Void" {
+        return Err("This payroll run is already void".to_string());
+    }
+    db.void_payroll_run(id, reason.as_deref().filter(|r| !r.is_empty())).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "VOID",
+        "PayrollRun",
+        Some(&id.to_string()),
+        &format!("Voided {}{}", run.run_number.as_deref().unwrap_or("payroll run"), reason.map(|r| format!(": {}", r)).unwrap_or_default())
+    ).ok();
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_payroll_summary(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<PayrollRecord>, String> {
+    check_current_any_permission(&db, &auth, PAYROLL_ACCESS)?;
+    db.get_payroll_summary().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_payroll_detail(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64) -> Result<PayrollRecord, String> {
+    check_current_any_permission(&db, &auth, PAYROLL_ACCESS)?;
+    db.get_payroll_detail(id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn void_payroll(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    id: i64,
+    reason: Option<String>,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let record = db.get_payroll_detail(id).map_err(|e| e.to_string())?;
+    if record.status == "Void" {
+        return Err("This payroll record is already void".to_string());
+    }
+    if record.run_id.is_some() && record.status != "Paid" {
+        return Err("Remove the employee from the draft run, or void the whole run, instead".to_string());
+    }
+    db.void_payroll(id, reason.as_deref().filter(|r| !r.is_empty())).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "VOID",
+        "Payroll",
+        Some(&id.to_string()),
+        &format!("Voided payroll for {}{}",
+            record.employee_name.as_deref().unwrap_or("employee"),
+            reason.map(|r| format!(": {}", r)).unwrap_or_default())
+    ).ok();
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_payroll_liabilities(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    status: Option<String>,
+) -> Result<Vec<PayrollLiability>, String> {
+    check_current_any_permission(&db, &auth, PAYROLL_ACCESS)?;
+    db.get_payroll_liabilities(status.as_deref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn remit_payroll_liabilities(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    mut liability_ids: Vec<i64>,
+    account_id: i64,
+    date: String,
+    reference: Option<String>,
+) -> Result<f64, String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    liability_ids.sort_unstable();
+    liability_ids.dedup();
+    if liability_ids.is_empty() {
+        return Err("Select at least one liability to remit".to_string());
+    }
+    parse_date(&date)?;
+    let account_id = payroll_account(&db, Some(account_id))?;
+    let outstanding = db.get_payroll_liabilities(Some("Outstanding")).map_err(|e| e.to_string())?;
+    if let Some(id) = liability_ids.iter().find(|id| !outstanding.iter().any(|l| l.id == Some(**id))) {
+        return Err(format!("Liability {} is not outstanding", id));
+    }
+    let reference = reference.filter(|r| !r.is_empty());
+    let total = db.remit_payroll_liabilities(&liability_ids, account_id, &date, reference.as_deref())
+        .map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE",
+        "Payroll",
+        None,
+        &format!("Remitted {} payroll liabilit{} totalling {:.2}", liability_ids.len(), if liability_ids.len() == 1 { "y" } else { "ies" }, total)
+    ).ok();
+
+    Ok(total)
+}
+
+// ── Bank Payment Files ─────────────────────────────────
+
+/// Writes a bulk transfer file paying net salary for the payroll in a pay period.
+/// `format` is "CSV" or "pain.001". Returns the total amount in the file.
+#[tauri::command]
+pub fn export_payroll_payment_file(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    pay_period_start: String,
+    pay_period_end: String,
+    payment_date: String,
+    format: String,
+    currency: String,
+    file_path: String,
+) -> Result<f64, String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    if parse_date(&pay_period_end)? < parse_date(&pay_period_start)? {
+        return Err("Pay period end must not be before its start".to_string());
+    }
+    let payments = db.get_payroll_bank_payments(&pay_period_start, &pay_period_end).map_err(|e| e.to_string())?;
+    let settings = db.get_settings().map_err(|e| e.to_string())?;
+    let total = crate::bank_file::export(
+        &file_path,
+        &format,
+        &payments,
+        &crate::bank_file::Debtor::from_settings(&settings),
+        currency.trim(),
+        &payment_date,
+        crate::bank_file::Purpose::Salary,
+    )?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "EXPORT",
+        "Payroll",
+        None,
+        &format!("Exported {} salary payment file for {} to {}: {} payment(s) totalling {:.2}", format, pay_period_start, pay_period_end, payments.len(), total)
+    ).ok();
+
+    Ok(total)
+}
+
+/// Writes a bulk transfer file for selected expense transactions. The payee and
+/// bank details come from the caller; amounts and references are taken from the
+/// transactions themselves.
+#[tauri::command]
+pub fn export_supplier_payment_file(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    mut payments: Vec<BankPayment>,
+    payment_date: String,
+    format: String,
+    currency: String,
+    file_path: String,
+) -> Result<f64, String> {
+    check_permission(&auth, Permission::EditTransactions)?;
+    for p in payments.iter_mut() {
+        let id = p.transaction_id.ok_or("Each supplier payment must be linked to an expense transaction")?;
+        let t = db.get_transaction(id).map_err(|e| e.to_string())?
+            .ok_or(format!("Transaction {} not found", id))?;
+        if t.transaction_type != "Expense" {
+            return Err(format!("Transaction {} is not an expense", id));
+        }
+        p.amount = t.amount;
+        p.reference = format!("TXN-{}", id);
+        p.remittance_info = p.remittance_info.take().filter(|s| !s.trim().is_empty()).or(t.description);
+    }
+    let settings = db.get_settings().map_err(|e| e.to_string())?;
+    let total = crate::bank_file::export(
+        &file_path,
+        &format,
+        &payments,
+        &crate::bank_file::Debtor::from_settings(&settings),
+        currency.trim(),
+        &payment_date,
+        crate::bank_file::Purpose::Supplier,
+    )?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "EXPORT",
+        "Transaction",
+        None,
+        &format!("Exported {} supplier payment file: {} payment(s) totalling {:.2}", format, payments.len(), total)
+    ).ok();
+
+    Ok(total)
+}
+
+#[tauri::command]
+pub fn export_payslip_pdf(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    payroll_id: i64,
+    file_path: String,
+) -> Result<String, String> {
+    check_current_any_permission(&db, &auth, PAYROLL_ACCESS)?;
+    let payroll = db.get_payroll_detail(payroll_id).map_err(|e| e.to_string())?;
+    let settings = db.get_settings().map_err(|e| e.to_string())?;
+    crate::pdf::generate_payslip_pdf(&payroll, &settings, &file_path)
+}
+
+fn payroll_for_period(db: &AppDb, start: &str, end: &str) -> Result<Vec<PayrollRecord>, String> {
+    if parse_date(end)? < parse_date(start)? {
+        return Err("Pay period end must not be before its start".to_string());
+    }
+    let records = db.get_payroll_for_period(start, end).map_err(|e| e.to_string())?;
+    if records.is_empty() {
+        return Err(format!("No payroll found for {} to {}", start, end));
+    }
+    Ok(records)
+}
+
+/// File-system safe name for a per-employee payslip.
+fn payslip_file_name(p: &PayrollRecord) -> String {
+    let name: String = p.employee_name.as_deref().unwrap_or("employee").chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    format!("payslip-{}-{}-{}.pdf", name, p.pay_period_end, p.id.unwrap_or(0))
+}
+
+/// Renders every payslip in a pay period, either as pages of one PDF at `path`
+/// (`mode` "single") or as one PDF per record inside the folder `path` (`mode`
+/// "folder"). Returns the number of payslips written.
+#[tauri::command]
+pub fn export_payslips(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    pay_period_start: String,
+    pay_period_end: String,
+    mode: String,
+    path: String,
+) -> Result<usize, String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let records = payroll_for_period(&db, &pay_period_start, &pay_period_end)?;
+    let settings = db.get_settings().map_err(|e| e.to_string())?;
+    match mode.as_str() {
+        "single" => {
+            crate::pdf::generate_payslips_pdf(&records, &settings, &path)?;
+        }
+        "folder" => {
+            let dir = std::path::Path::new(&path);
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            for p in &records {
+                let file = dir.join(payslip_file_name(p));
+                crate::pdf::generate_payslip_pdf(p, &settings, &file.to_string_lossy())?;
+            }
+        }
+        _ => return Err("Mode must be single or folder".to_string()),
+    }
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "EXPORT",
+        "Payroll",
+        None,
+        &format!("Exported {} payslip(s) for {} to {}", records.len(), pay_period_start, pay_period_end)
+    ).ok();
+
+    Ok(records.len())
+}
+
+/// Writes the payroll register for a pay period as "PDF" or "XLSX". Returns the
+/// number of employees listed.
+#[tauri::command]
+pub fn export_payroll_register(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    pay_period_start: String,
+    pay_period_end: String,
+    format: String,
+    file_path: String,
+) -> Result<usize, String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let records = payroll_for_period(&db, &pay_period_start, &pay_period_end)?;
+    let (rows, totals) = crate::payroll::payroll_register(&records);
+    match format.as_str() {
+        "PDF" => {
+            let settings = db.get_settings().map_err(|e| e.to_string())?;
+            crate::pdf::generate_payroll_register_pdf(&rows, &totals, &pay_period_start, &pay_period_end, &settings, &file_path)?;
+        }
+        "XLSX" => {
+            let mut workbook = rust_xlsxwriter::Workbook::new();
+            let worksheet = workbook.add_worksheet();
+            let bold = rust_xlsxwriter::Format::new().set_bold();
+            let money = rust_xlsxwriter::Format::new().set_num_format("#,##0.00");
+            let money_bold = rust_xlsxwriter::Format::new().set_bold().set_num_format("#,##0.00");
+
+            worksheet.write_string_with_format(0, 0, format!("Payroll Register {} to {}", pay_period_start, pay_period_end), &bold).map_err(|e| e.to_string())?;
+            worksheet.write_string_with_format(2, 0, "Employee", &bold).map_err(|e| e.to_string())?;
+            worksheet.write_string_with_format(2, 1, "Role", &bold).map_err(|e| e.to_string())?;
+            for (i, (label, _)) in crate::payroll::REGISTER_COLUMNS.iter().enumerate() {
+                worksheet.write_string_with_format(2, (i + 2) as u16, *label, &bold).map_err(|e| e.to_string())?;
+            }
+            for (n, row) in rows.iter().enumerate() {
+                let r = (n + 3) as u32;
+                worksheet.write_string(r, 0, &row.employee_name).map_err(|e| e.to_string())?;
+                worksheet.write_string(r, 1, row.employee_role.as_deref().unwrap_or("")).map_err(|e| e.to_string())?;
+                for (i, amount) in row.amounts.iter().enumerate() {
+                    worksheet.write_number_with_format(r, (i + 2) as u16, *amount, &money).map_err(|e| e.to_string())?;
+                }
+            }
+            let total_row = (rows.len() + 3) as u32;
+            worksheet.write_string_with_format(total_row, 0, "Total", &bold).map_err(|e| e.to_string())?;
+            for (i, amount) in totals.iter().enumerate() {
+                worksheet.write_number_with_format(total_row, (i + 2) as u16, *amount, &money_bold).map_err(|e| e.to_string())?;
+            }
+            worksheet.set_column_width(0, 28).map_err(|e| e.to_string())?;
+            worksheet.set_column_width(1, 18).map_err(|e| e.to_string())?;
+            for i in 0..crate::payroll::REGISTER_COLUMNS.len() {
+                worksheet.set_column_width((i + 2) as u16, 14).map_err(|e| e.to_string())?;
+            }
+            workbook.save(&file_path).map_err(|e| e.to_string())?;
+        }
+        _ => return Err("Format must be PDF or XLSX".to_string()),
+    }
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "EXPORT",
+        "Payroll",
+        None,
+        &format!("Exported {} payroll register for {} to {}", format, pay_period_start, pay_period_end)
+    ).ok();
+
+    Ok(rows.len())
+}
+
+/// Paid payroll for the tax year starting on the first of `start_month` (default
+/// January) in `year`, summed per employee.
+fn year_end_for(db: &AppDb, year: i32, start_month: Option<u32>) -> Result<Vec<YearEndSummary>, String> {
+    let (start, end) = crate::payroll::tax_year_bounds(year, start_month.unwrap_or(1))
+        .ok_or("Tax year must start in a month between 1 and 12")?;
+    let (start, end) = (start.format("%Y-%m-%d").to_string(), end.format("%Y-%m-%d").to_string());
+    let records = db.get_paid_payroll_between(&start, &end).map_err(|e| e.to_string())?;
+    Ok(crate::payroll::year_end_summaries(&records, &start, &end))
+}
+
+#[tauri::command]
+pub fn get_year_end_summary(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    year: i32,
+    start_month: Option<u32>,
+) -> Result<Vec<YearEndSummary>, String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    year_end_for(&db, year, start_month)
+}
+
+/// Writes year-end earnings certificates, for one employee or everyone paid in
+/// the tax year, as pages of one PDF (`mode` "single") or one PDF per employee in
+/// the folder `path` (`mode` "folder"). Returns the number of certificates written.
+#[tauri::command]
+pub fn export_earnings_certificates(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    year: i32,
+    start_month: Option<u32>,
+    employee_id: Option<i64>,
+    mode: String,
+    path: String,
+) -> Result<usize, String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let mut summaries = year_end_for(&db, year, start_month)?;
+    if let Some(id) = employee_id {
+        summaries.retain(|s| s.employee_id == id);
+    }
+    let first = summaries.first().ok_or("No paid payroll found for this tax year")?;
+    let period = format!("{} to {}", first.tax_year_start, first.tax_year_end);
+    let settings = db.get_settings().map_err(|e| e.to_string())?;
+    match mode.as_str() {
+        "single" => {
+            crate::pdf::generate_earnings_certificates_pdf(&summaries, &settings, &path)?;
+        }
+        "folder" => {
+            let dir = std::path::Path::new(&path);
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            for s in &summaries {
+                let name: String = s.employee_name.as_deref().unwrap_or("employee").chars()
+                    .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+                    .collect();
+                let file = dir.join(format!("earnings-certificate-{}-{}-{}.pdf", name, s.tax_year_end, s.employee_id));
+                crate::pdf::generate_earnings_certificates_pdf(std::slice::from_ref(s), &settings, &file.to_string_lossy())?;
+            }
+        }
+        _ => return Err("Mode must be single or folder".to_string()),
+    }
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "EXPORT",
+        "Payroll",
+        employee_id.map(|id| id.to_string()).as_deref(),
+        &format!("Exported {} earnings certificate(s) for tax year {}", summaries.len(), period)
+    ).ok();
+
+    Ok(summaries.len())
+}
+
+/// Writes the consolidated year-end workbook: one row per employee with the
+/// standard totals, a column per earning/deduction component and per employer
+/// contribution, and a totals row. Returns the number of employees listed.
+#[tauri::command]
+pub fn export_year_end_xlsx(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    year: i32,
+    start_month: Option<u32>,
+    file_path: String,
+) -> Result<usize, String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let summaries = year_end_for(&db, year, start_month)?;
+    let first = summaries.first().ok_or("No paid payroll found for this tax year")?;
+    let period = format!("{} to {}", first.tax_year_start, first.tax_year_end);
+
+    let fixed: [crate::payroll::AmountColumn<YearEndSummary>; 12] = [
+        ("Payslips", |s| s.payslips as f64),
+        ("Basic Salary", |s| s.base_salary),
+        ("Allowances", |s| s.allowances),
+        ("Overtime", |s| s.overtime_pay),
+        ("Bonuses", |s| s.bonuses),
+        ("Gross", |s| s.gross_salary),
+        ("Tax", |s| s.tax),
+        ("Late Penalties", |s| s.late_penalties),
+        ("Absences", |s| s.absences),
+        ("Other Deductions", |s| s.other_deductions),
+        ("Total Deductions", |s| s.total_deductions),
+        ("Net Pay", |s| s.net_pay),
+    ];
+    // Component columns in first-seen order (summaries already sort them by type).
+    let mut components: Vec<(String, String)> = Vec::new();
+    let mut employer: Vec<String> = Vec::new();
+    for s in &summaries {
+        for c in &s.components {
+            let key = (c.line_type.clone(), c.name.clone());
+            if c.amount != 0.0 && !components.contains(&key) {
+                components.push(key);
+            }
+            if c.employer_amount != 0.0 && !employer.contains(&c.name) {
+                employer.push(c.name.clone());
+            }
+        }
+    }
+
+    let mut workbook = rust_xlsxwriter::Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    let bold = rust_xlsxwriter::Format::new().set_bold();
+    let money = rust_xlsxwriter::Format::new().set_num_format("#,##0.00");
+    let money_bold = rust_xlsxwriter::Format::new().set_bold().set_num_format("#,##0.00");
+
+    worksheet.write_string_with_format(0, 0, format!("Payroll Year-End Summary {}", period), &bold).map_err(|e| e.to_string())?;
+    let mut headers: Vec<String> = vec!["Employee".to_string(), "Role".to_string()];
+    headers.extend(fixed.iter().map(|(label, _)| label.to_string()));
+    headers.extend(components.iter().map(|(t, n)| format!("{} ({})", n, t)));
+    headers.extend(employer.iter().map(|n| format!("{} (Employer)", n)));
+    for (i, h) in headers.iter().enumerate() {
+        worksheet.write_string_with_format(2, i as u16, h, &bold).map_err(|e| e.to_string())?;
+    }
+
+    let mut totals = vec![0.0; headers.len() - 2];
+    for (n, s) in summaries.iter().enumerate() {
+        let r = (n + 3) as u32;
+        worksheet.write_string(r, 0, s.employee_name.as_deref().unwrap_or("")).map_err(|e| e.to_string())?;
+        worksheet.write_string(r, 1, s.employee_role.as_deref().unwrap_or("")).map_err(|e| e.to_string())?;
+        let mut values: Vec<f64> = fixed.iter().map(|(_, f)| f(s)).collect();
+        values.extend(components.iter().map(|(t, name)| {
+            s.components.iter().filter(|c| &c.line_type == t && &c.name == name).map(|c| c.amount).sum::<f64>()
+        }));
+        values.extend(employer.iter().map(|name| {
+            s.components.iter().filter(|c| &c.name == name).map(|c| c.employer_amount).sum::<f64>()
+        }));
+        for (i, v) in values.iter().enumerate() {
+            totals[i] += v;
+            if i == 0 {
+                worksheet.write_number(r, 2, *v).map_err(|e| e.to_string())?;
+            } else {
+                worksheet.write_number_with_format(r, (i + 2) as u16, *v, &money).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    let total_row = (summaries.len() + 3) as u32;
+    worksheet.write_string_with_format(total_row, 0, "Total", &bold).map_err(|e| e.to_string())?;
+    for (i, v) in totals.iter().enumerate() {
+        if i == 0 {
+            worksheet.write_number_with_format(total_row, 2, *v, &bold).map_err(|e| e.to_string())?;
+        } else {
+            worksheet.write_number_with_format(total_row, (i + 2) as u16, (v * 100.0).round() / 100.0, &money_bold).map_err(|e| e.to_string())?;
+        }
+    }
+    worksheet.set_column_width(0, 28).map_err(|e| e.to_string())?;
+    worksheet.set_column_width(1, 18).map_err(|e| e.to_string())?;
+    for i in 2..headers.len() {
+        worksheet.set_column_width(i as u16, 14).map_err(|e| e.to_string())?;
+    }
+    workbook.save(&file_path).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "EXPORT",
+        "Payroll",
+        None,
+        &format!("Exported year-end payroll summary for tax year {}", period)
+    ).ok();
+
+    Ok(summaries.len())
+}
+
+// ── Employee Loans ─────────────────────────────────────
+
+fn validate_employee_loan(l: &EmployeeLoan) -> Result<(), String> {
+    if !["Loan", "Advance"].contains(&l.loan_type.as_str()) {
+        return Err(format!("Unknown loan type: {}", l.loan_type));
+    }
+    if l.principal <= 0.0 {
+        return Err("Principal must be greater than zero".to_string());
+    }
+    if l.instalment <= 0.0 {
+        return Err("Instalment must be greater than zero".to_string());
+    }
+    if l.instalment > l.principal {
+        return Err("Instalment cannot exceed the principal".to_string());
+    }
+    parse_date(&l.start_date)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_employee_loans(db: State<'_, AppDb>, auth: State<'_, AuthState>, employee_id: Option<i64>) -> Result<Vec<EmployeeLoan>, String> {
+    check_current_any_permission(&db, &auth, PAYROLL_ACCESS)?;
+    db.get_employee_loans(employee_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn create_employee_loan(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    employee_id: i64,
+    loan_type: String,
+    principal: f64,
+    instalment: f64,
+    start_date: String,
+    notes: Option<String>,
+) -> Result<i64, String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let emp = db.get_employee(employee_id).map_err(|e| e.to_string())?
+        .ok_or("Employee not found")?;
+    let l = EmployeeLoan {
+        id: None,
+        employee_id,
+        employee_name: None,
+        loan_type,
+        principal,
+        instalment,
+        start_date,
+        status: "Active".to_string(),
+        recovered: 0.0,
+        balance: principal,
+        notes: notes.filter(|n| !n.is_empty()),
+        created_at: None,
+    };
+    validate_employee_loan(&l)?;
+    let id = db.create_employee_loan(&l).map_err(|e| e.to_string())?;
+
     db.log_activity(
         get_current_user_id(&auth),
         "CREATE",
-        "Category",
+        "EmployeeLoan",
         Some(&id.to_string()),
-        &format!("Created category: {}", name)
+        &format!("Issued {} of {:.2} to {} ({:.2} per period)", l.loan_type.to_lowercase(), principal, emp.name, instalment)
     ).ok();
 
     Ok(id)
 }
 
+/// Edits a loan's terms. The principal cannot drop below what has already been
+/// recovered; `status` may switch between "Active" and "Cancelled" to stop or
+/// resume deductions.
 #[tauri::command]
-pub fn delete_category(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64) -> Result<(), String> {
-    check_permission(&auth, "manage_settings")?;
-    db.delete_category(id).map_err(|e| e.to_string())?;
-    Ok(())
-}
+pub fn update_employee_loan(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    id: i64,
+    loan_type: Option<String>,
+    principal: Option<f64>,
+    instalment: Option<f64>,
+    start_date: Option<String>,
+    status: Option<String>,
+    notes: Option<String>,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let existing = db.get_employee_loan(id).map_err(|e| e.to_string())?
+        .ok_or("Loan not found")?;
+    let status = match status.as_deref() {
+        None => if existing.status == "Cancelled" { "Cancelled" } else { "Active" }.to_string(),
+        Some("Active") | Some("Cancelled") => status.unwrap_or_default(),
+        Some(other) => return Err(format!("Unknown loan status: {}", other)),
+    };
+    let l = EmployeeLoan {
+        loan_type: loan_type.unwrap_or_else(|| existing.loan_type.clone()),
+        principal: principal.unwrap_or(existing.principal),
+        instalment: instalment.unwrap_or(existing.instalment),
+        start_date: start_date.unwrap_or_else(|| existing.start_date.clone()),
+        status,
+        notes: notes.or_else(|| existing.notes.clone()).filter(|n| !n.is_empty()),
+        ..existing.clone()
+    };
+    validate_employee_loan(&l)?;
+    if l.principal < existing.recovered {
+        return Err(format!("Principal cannot be less than the {:.2} already recovered", existing.recovered));
+    }
+    db.update_employee_loan(&l).map_err(|e| e.to_string())?;
 
-// ── Accounts ───────────────────────────────────────────
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE",
+        "EmployeeLoan",
+        Some(&id.to_string()),
+        &format!("Updated {} for {} ({})", l.loan_type.to_lowercase(), existing.employee_name.as_deref().unwrap_or("employee"), l.status)
+    ).ok();
 
-#[tauri::command]
-pub fn get_accounts(db: State<'_, AppDb>) -> Result<Vec<Account>, String> {
-    db.get_accounts().map_err(|e| e.to_string())
+    Ok(())
 }
 
 #[tauri::command]
-pub fn create_account(
+pub fn delete_employee_loan(
     db: State<'_, AppDb>,
     auth: State<'_, AuthState>,
-    name: String,
-    account_type: String,
-    balance: f64,
-    currency: String,
-) -> Result<i64, String> {
-    check_permission(&auth, "manage_settings")?;
-    let a = Account { id: None, name: name.clone(), account_type, balance, currency };
-    let id = db.create_account(&a).map_err(|e| e.to_string())?;
-    
+    id: i64,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let existing = db.get_employee_loan(id).map_err(|e| e.to_string())?
+        .ok_or("Loan not found")?;
+    if existing.recovered > 0.0 {
+        return Err("This loan has instalments on payroll; cancel it instead".to_string());
+    }
+    db.delete_employee_loan(id).map_err(|e| e.to_string())?;
+
     db.log_activity(
         get_current_user_id(&auth),
-        "CREATE",
-        "Account",
+        "DELETE",
+        "EmployeeLoan",
         Some(&id.to_string()),
-        &format!("Created account: {}", name)
+        &format!("Deleted {} of {:.2} for {}", existing.loan_type.to_lowercase(), existing.principal, existing.employee_name.as_deref().unwrap_or("employee"))
     ).ok();
 
-    Ok(id)
+    Ok(())
 }
 
-// ── Transactions ───────────────────────────────────────
+// ── Attendance & Leave ─────────────────────────────────
 
 #[tauri::command]
-pub fn get_transactions(db: State<'_, AppDb>, limit: i64) -> Result<Vec<Transaction>, String> {
-    db.get_transactions(limit).map_err(|e| e.to_string())
+pub fn get_attendance(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    employee_id: Option<i64>,
+    start_date: String,
+    end_date: String,
+) -> Result<Vec<AttendanceRecord>, String> {
+    check_current_any_permission(&db, &auth, PAYROLL_ACCESS)?;
+    db.get_attendance(employee_id, &start_date, &end_date).map_err(|e| e.to_string())
 }
 
+/// Records a day's attendance, replacing any existing entry for that day.
+/// Paid leave is only accepted while the employee has balance left.
 #[tauri::command]
-pub fn create_transaction(
+pub fn record_attendance(
     db: State<'_, AppDb>,
     auth: State<'_, AuthState>,
-    account_id: i64,
-    category_id: Option<i64>,
-    amount: f64,
-    transaction_type: String,
-    description: Option<String>,
+    employee_id: i64,
     date: String,
-    reference_id: Option<String>,
+    status: String,
+    check_in: Option<String>,
+    check_out: Option<String>,
+    leave_type_id: Option<i64>,
+    notes: Option<String>,
 ) -> Result<i64, String> {
-    check_permission(&auth, "create_transactions")?;
-    let t = Transaction {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    if !["Present", "Absent", "HalfDay", "Leave", "Holiday"].contains(&status.as_str()) {
+        return Err(format!("Unknown attendance status: {}", status));
+    }
+    let day = parse_date(&date)?;
+    let check_in = check_in.filter(|t| !t.is_empty());
+    let check_out = check_out.filter(|t| !t.is_empty());
+    if let (Some(a), Some(b)) = (&check_in, &check_out) {
+        if parse_clock_time(b)? < parse_clock_time(a)? {
+            return Err("Check-out must be after check-in".to_string());
+        }
+    }
+    for t in check_in.iter().chain(check_out.iter()) {
+        parse_clock_time(t)?;
+    }
+    db.get_employee(employee_id).map_err(|e| e.to_string())?
+        .ok_or("Employee not found")?;
+
+    let leave_type_id = if status == "Leave" {
+        let type_id = leave_type_id.ok_or("Choose a leave type")?;
+        let leave_type = db.get_leave_types().map_err(|e| e.to_string())?
+            .into_iter()
+            .find(|t| t.id == Some(type_id))
+            .ok_or("Leave type not found")?;
+        if leave_type.is_paid {
+            use chrono::Datelike;
+            let balance = db.get_leave_balances(employee_id, day.year(), day.month()).map_err(|e| e.to_string())?
+                .into_iter()
+                .find(|b| b.leave_type_id == type_id)
+                .map_or(0.0, |b| b.balance);
+            // Re-saving a day already booked against this leave type doesn't use more leave
+            let already_booked = db.get_attendance(Some(employee_id), &date, &date).map_err(|e| e.to_string())?
+                .iter()
+                .any(|a| a.status == "Leave" && a.leave_type_id == Some(type_id));
+            if !already_booked && balance < 1.0 {
+                return Err(format!("Not enough {} leave left ({} day(s) available)", leave_type.name, balance));
+            }
+        }
+        Some(type_id)
+    } else {
+        None
+    };
+
+    let a = AttendanceRecord {
         id: None,
-        account_id,
-        category_id,
-        amount,
-        transaction_type: transaction_type.clone(),
-        description,
-        date,
-        reference_id,
-        created_at: None,
+        employee_id,
+        employee_name: None,
+        date: date.clone(),
+        status: status.clone(),
+        check_in,
+        check_out,
+        leave_type_id,
+        leave_type_name: None,
+        notes,
     };
-    let id = db.create_transaction(&t).map_err(|e| e.to_string())?;
-    
+    let id = db.record_attendance(&a).map_err(|e| e.to_string())?;
+
     db.log_activity(
         get_current_user_id(&auth),
-        "CREATE",
-        "Transaction",
+        "UPDATE",
+        "Attendance",
         Some(&id.to_string()),
-        &format!("Recorded {} transaction of {}", transaction_type, amount)
+        &format!("Marked employee ID {} as {} on {}", employee_id, status, date)
     ).ok();
 
     Ok(id)
 }
 
-// ── Employees & Payroll ────────────────────────────────
+#[tauri::command]
+pub fn delete_attendance(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    id: i64,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    db.delete_attendance(id).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "DELETE",
+        "Attendance",
+        Some(&id.to_string()),
+        "Deleted attendance record"
+    ).ok();
+
+    Ok(())
+}
 
 #[tauri::command]
-pub fn get_employees(db: State<'_, AppDb>) -> Result<Vec<Employee>, String> {
-    db.get_employees().map_err(|e| e.to_string())
+pub fn get_leave_types(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<LeaveType>, String> {
+    check_current_any_permission(&db, &auth, PAYROLL_ACCESS)?;
+    db.get_leave_types().map_err(|e| e.to_string())
+}
+
+fn validate_leave_type(t: &LeaveType) -> Result<(), String> {
+    if t.name.trim().is_empty() {
+        return Err("Leave type name is required".to_string());
+    }
+    if t.annual_days < 0.0 || t.annual_days > 366.0 {
+        return Err("Annual entitlement must be between 0 and 366 days".to_string());
+    }
+    if !["Annual", "Monthly"].contains(&t.accrual.as_str()) {
+        return Err(format!("Unknown accrual: {}", t.accrual));
+    }
+    Ok(())
 }
 
 #[tauri::command]
-pub fn create_employee(
+pub fn create_leave_type(
     db: State<'_, AppDb>,
     auth: State<'_, AuthState>,
     name: String,
-    role: Option<String>,
-    email: Option<String>,
-    phone: Option<String>,
-    salary: f64,
-    allowances: Option<f64>,
+    annual_days: f64,
+    accrual: Option<String>,
+    is_paid: Option<bool>,
 ) -> Result<i64, String> {
-    check_permission(&auth, "manage_payroll")?;
-    let e = Employee {
-        id: None, name: name.clone(), role, email, phone, salary,
-        allowances: allowances.unwrap_or(0.0),
-        created_at: None,
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let t = LeaveType {
+        id: None,
+        name: name.trim().to_string(),
+        annual_days,
+        accrual: accrual.unwrap_or_else(|| "Annual".to_string()),
+        is_paid: is_paid.unwrap_or(true),
+        is_active: true,
     };
-    let id = db.create_employee(&e).map_err(|e| e.to_string())?;
-    
+    validate_leave_type(&t)?;
+    let id = db.create_leave_type(&t).map_err(|e| e.to_string())?;
+
     db.log_activity(
         get_current_user_id(&auth),
         "CREATE",
-        "Employee",
+        "LeaveType",
         Some(&id.to_string()),
-        &format!("Added employee: {}", name)
+        &format!("Added leave type: {}", t.name)
     ).ok();
 
     Ok(id)
 }
 
 #[tauri::command]
-pub fn update_employee(
+pub fn update_leave_type(
     db: State<'_, AppDb>,
     auth: State<'_, AuthState>,
     id: i64,
-    name: String,
-    role: Option<String>,
-    email: Option<String>,
-    phone: Option<String>,
-    salary: f64,
-    allowances: Option<f64>,
+    name: Option<String>,
+    annual_days: Option<f64>,
+    accrual: Option<String>,
+    is_paid: Option<bool>,
+    is_active: Option<bool>,
 ) -> Result<(), String> {
-    check_permission(&auth, "manage_payroll")?;
-    let e = Employee {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let existing = db.get_leave_types().map_err(|e| e.to_string())?
+        .into_iter()
+        .find(|t| t.id == Some(id))
+        .ok_or("Leave type not found")?;
+    let t = LeaveType {
         id: Some(id),
-        name: name.clone(),
-        role,
-        email,
-        phone,
-        salary,
-        allowances: allowances.unwrap_or(0.0),
-        created_at: None,
+        name: name.map(|n| n.trim().to_string()).unwrap_or(existing.name),
+        annual_days: annual_days.unwrap_or(existing.annual_days),
+        accrual: accrual.unwrap_or(existing.accrual),
+        is_paid: is_paid.unwrap_or(existing.is_paid),
+        is_active: is_active.unwrap_or(existing.is_active),
     };
-    db.update_employee(&e).map_err(|e| e.to_string())?;
-    
+    validate_leave_type(&t)?;
+    db.update_leave_type(&t).map_err(|e| e.to_string())?;
+
     db.log_activity(
         get_current_user_id(&auth),
         "UPDATE",
-        "Employee",
+        "LeaveType",
         Some(&id.to_string()),
-        &format!("Updated employee: {}", name)
+        &format!("Updated leave type: {}", t.name)
     ).ok();
 
     Ok(())
 }
 
+/// Leave balances for a year. Monthly accruals are counted to the current month
+/// for the current year and in full for past years.
 #[tauri::command]
-pub fn create_payroll(
+pub fn get_leave_balances(
     db: State<'_, AppDb>,
     auth: State<'_, AuthState>,
     employee_id: i64,
-    base_salary: f64,
-    bonuses: f64,
-    pay_period_start: String,
-    pay_period_end: String,
-    payment_date: String,
-    status: String,
-    notes: Option<String>,
+    year: Option<i32>,
+) -> Result<Vec<LeaveBalance>, String> {
+    check_current_any_permission(&db, &auth, PAYROLL_ACCESS)?;
+    use chrono::Datelike;
+    let today = chrono::Local::now().date_naive();
+    let year = year.unwrap_or(today.year());
+    let through_month = match year.cmp(&today.year()) {
+        std::cmp::Ordering::Less => 12,
+        std::cmp::Ordering::Equal => today.month(),
+        std::cmp::Ordering::Greater => 0,
+    };
+    db.get_leave_balances(employee_id, year, through_month).map_err(|e| e.to_string())
+}
+
+// ── Payroll Rules ──────────────────────────────────────
+
+fn validate_payroll_component(db: &AppDb, c: &PayrollComponent) -> Result<(), String> {
+    if c.name.trim().is_empty() {
+        return Err("Component name is required".to_string());
+    }
+    if !["Earning", "Deduction", "Contribution"].contains(&c.component_type.as_str()) {
+        return Err(format!("Unknown component type: {}", c.component_type));
+    }
+    if !["Fixed", "Percent"].contains(&c.calculation.as_str()) {
+        return Err(format!("Unknown calculation: {}", c.calculation));
+    }
+    if !["Basic", "Gross"].contains(&c.basis.as_str()) {
+        return Err(format!("Unknown basis: {}", c.basis));
+    }
+    if c.component_type == "Earning" && c.basis == "Gross" {
+        return Err("Earnings can only be a percentage of basic pay".to_string());
+    }
+    if c.amount < 0.0 || c.employer_rate < 0.0 {
+        return Err("Amounts and rates cannot be negative".to_string());
+    }
+    if c.calculation == "Percent" && (c.amount > 100.0 || c.employer_rate > 100.0) {
+        return Err("Percentages cannot exceed 100".to_string());
+    }
+    let existing = db.get_payroll_components(false).map_err(|e| e.to_string())?;
+    if existing.iter().any(|o| o.id != c.id && o.name.eq_ignore_ascii_case(c.name.trim())) {
+        return Err(format!("A payroll component named {} already exists", c.name.trim()));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_payroll_components(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<PayrollComponent>, String> {
+    check_current_any_permission(&db, &auth, PAYROLL_ACCESS)?;
+    db.get_payroll_components(false).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn create_payroll_component(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    name: String,
+    component_type: String,
+    calculation: String,
+    amount: f64,
+    employer_rate: Option<f64>,
+    basis: Option<String>,
+    taxable: Option<bool>,
+    sort_order: Option<i64>,
 ) -> Result<i64, String> {
-    check_permission(&auth, "manage_payroll")?;
-    let net_pay = base_salary + bonuses;
-    let p = PayrollRecord {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let c = PayrollComponent {
         id: None,
-        employee_id,
-        employee_name: None,
-        employee_role: None,
-        base_salary,
-        overtime_pay: 0.0,
-        bonuses,
-        allowances: 0.0,
-        gross_salary: net_pay,
-        tax: 0.0,
-        late_penalties: 0.0,
-        absences: 0.0,
-        other_deductions: 0.0,
-        total_deductions: 0.0,
-        net_pay,
-        pay_period_start,
-        pay_period_end,
-        payment_date,
-        status,
-        notes,
+        name: name.trim().to_string(),
+        component_type,
+        calculation,
+        amount,
+        employer_rate: employer_rate.unwrap_or(0.0),
+        basis: basis.unwrap_or_else(|| "Basic".to_string()),
+        taxable: taxable.unwrap_or(true),
+        is_active: true,
+        sort_order: sort_order.unwrap_or(0),
     };
-    let id = db.create_payroll(&p).map_err(|e| e.to_string())?;
-    
+    validate_payroll_component(&db, &c)?;
+    let id = db.create_payroll_component(&c).map_err(|e| e.to_string())?;
+
     db.log_activity(
         get_current_user_id(&auth),
         "CREATE",
-        "Payroll",
+        "PayrollComponent",
         Some(&id.to_string()),
-        &format!("Recorded payroll payment for employee ID: {}", employee_id)
+        &format!("Added payroll component: {}", c.name)
     ).ok();
 
     Ok(id)
 }
 
 #[tauri::command]
-pub fn create_bulk_payroll(
+pub fn update_payroll_component(
     db: State<'_, AppDb>,
     auth: State<'_, AuthState>,
-    pay_period_start: String,
-    pay_period_end: String,
-    payment_date: String,
-    bonuses: f64,
-) -> Result<i64, String> {
-    check_permission(&auth, "manage_payroll")?;
-    let employees = db.get_employees().map_err(|e| e.to_string())?;
-    if employees.is_empty() {
-        return Err("No employees found".to_string());
-    }
-    let mut count: i64 = 0;
-    for emp in &employees {
-        let base = emp.salary;
-        let net_pay = base + bonuses;
-        let p = PayrollRecord {
-            id: None,
-            employee_id: emp.id.unwrap_or(0),
-            employee_name: None,
-            employee_role: None,
-            base_salary: base,
-            overtime_pay: 0.0,
-            bonuses,
-            allowances: 0.0,
-            gross_salary: net_pay,
-            tax: 0.0,
-            late_penalties: 0.0,
-            absences: 0.0,
-            other_deductions: 0.0,
-            total_deductions: 0.0,
-            net_pay,
-            pay_period_start: pay_period_start.clone(),
-            pay_period_end: pay_period_end.clone(),
-            payment_date: payment_date.clone(),
-            status: "Paid".to_string(),
-            notes: None,
-        };
-        db.create_payroll(&p).map_err(|e| e.to_string())?;
-        count += 1;
-    }
+    id: i64,
+    name: Option<String>,
+    component_type: Option<String>,
+    calculation: Option<String>,
+    amount: Option<f64>,
+    employer_rate: Option<f64>,
+    basis: Option<String>,
+    taxable: Option<bool>,
+    is_active: Option<bool>,
+    sort_order: Option<i64>,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    let existing = db.get_payroll_components(false).map_err(|e| e.to_string())?
+        .into_iter()
+        .find(|c| c.id == Some(id))
+        .ok_or("Payroll component not found")?;
+    let c = PayrollComponent {
+        id: Some(id),
+        name: name.map(|n| n.trim().to_string()).unwrap_or(existing.name),
+        component_type: component_type.unwrap_or(existing.component_type),
+        calculation: calculation.unwrap_or(existing.calculation),
+        amount: amount.unwrap_or(existing.amount),
+        employer_rate: employer_rate.unwrap_or(existing.employer_rate),
+        basis: basis.unwrap_or(existing.basis),
+        taxable: taxable.unwrap_or(existing.taxable),
+        is_active: is_active.unwrap_or(existing.is_active),
+        sort_order: sort_order.unwrap_or(existing.sort_order),
+    };
+    validate_payroll_component(&db, &c)?;
+    db.update_payroll_component(&c).map_err(|e| e.to_string())?;
 
     db.log_activity(
         get_current_user_id(&auth),
-        "CREATE",
-        "Payroll",
-        None,
-        &format!("Bulk payroll processed for {} employees", count)
+        "UPDATE",
+        "PayrollComponent",
+        Some(&id.to_string()),
+        &format!("Updated payroll component: {}", c.name)
     ).ok();
 
-    Ok(count)
+    Ok(())
 }
 
 #[tauri::command]
-pub fn get_payroll_summary(db: State<'_, AppDb>) -> Result<Vec<PayrollRecord>, String> {
-    db.get_payroll_summary().map_err(|e| e.to_string())
+pub fn delete_payroll_component(
+    db: State<'_, AppDb>,
+    auth: State<'_, AuthState>,
+    id: i64,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    db.delete_payroll_component(id).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "DELETE",
+        "PayrollComponent",
+        Some(&id.to_string()),
+        "Deleted payroll component"
+    ).ok();
+
+    Ok(())
 }
 
 #[tauri::command]
-pub fn get_payroll_detail(db: State<'_, AppDb>, id: i64) -> Result<PayrollRecord, String> {
-    db.get_payroll_detail(id).map_err(|e| e.to_string())
+pub fn get_tax_brackets(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<TaxBracket>, String> {
+    check_current_any_permission(&db, &auth, PAYROLL_ACCESS)?;
+    db.get_tax_brackets().map_err(|e| e.to_string())
 }
 
+/// Replaces the tax table. Brackets must not overlap; only the last may be open-ended.
 #[tauri::command]
-pub fn export_payslip_pdf(
+pub fn save_tax_brackets(
     db: State<'_, AppDb>,
-    payroll_id: i64,
-    file_path: String,
-) -> Result<String, String> {
-    let payroll = db.get_payroll_detail(payroll_id).map_err(|e| e.to_string())?;
-    let settings = db.get_settings().map_err(|e| e.to_string())?;
-    crate::pdf::generate_payslip_pdf(&payroll, &settings, &file_path)
+    auth: State<'_, AuthState>,
+    mut brackets: Vec<TaxBracket>,
+) -> Result<(), String> {
+    check_current_permission(&db, &auth, Permission::ManagePayroll)?;
+    brackets.sort_by(|a, b| a.lower_bound.total_cmp(&b.lower_bound));
+    for (i, b) in brackets.iter().enumerate() {
+        if b.lower_bound < 0.0 || !(0.0..=100.0).contains(&b.rate) {
+            return Err("Bracket bounds cannot be negative and rates must be between 0 and 100".to_string());
+        }
+        match b.upper_bound {
+            Some(upper) if upper <= b.lower_bound => {
+                return Err(format!("Bracket starting at {} must end above its start", b.lower_bound));
+            }
+            None if i + 1 < brackets.len() => {
+                return Err("Only the highest bracket can have no upper limit".to_string());
+            }
+            _ => {}
+        }
+        if let Some(next) = brackets.get(i + 1) {
+            if b.upper_bound.is_some_and(|u| u > next.lower_bound) {
+                return Err(format!("Brackets starting at {} and {} overlap", b.lower_bound, next.lower_bound));
+            }
+        }
+    }
+    db.save_tax_brackets(&brackets).map_err(|e| e.to_string())?;
+
+    db.log_activity(
+        get_current_user_id(&auth),
+        "UPDATE",
+        "TaxBrackets",
+        None,
+        &format!("Saved {} tax bracket(s)", brackets.len())
+    ).ok();
+
+    Ok(())
 }
 
 // ── Reports & Export ─────────────────────────────────────
 
 #[tauri::command]
 pub fn get_cash_flow_report(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<CashFlowEntry>, String> {
-    check_permission(&auth, "view_reports")?;
+    check_permission(&auth, Permission::ViewReports)?;
     db.get_cash_flow_report().map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub fn get_category_report(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<CategoryReportEntry>, String> {
-    check_permission(&auth, "view_reports")?;
+    check_permission(&auth, Permission::ViewReports)?;
     db.get_category_report().map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub fn get_gross_margin_report(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<GrossMarginEntry>, String> {
+    check_permission(&auth, Permission::ViewReports)?;
+    db.get_gross_margin_report().map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn export_data_csv(
     db: State<'_, AppDb>,
@@ -941,7 +4169,7 @@ pub fn export_data_csv(
     module: String,
     path: String,
 ) -> Result<(), String> {
-    check_permission(&auth, "manage_settings")?;
+    check_current_permission(&db, &auth, Permission::ManageSettings)?;
     
     let mut wtr = csv::Writer::from_path(&path).map_err(|e| e.to_string())?;
 
@@ -954,7 +4182,10 @@ pub fn export_data_csv(
             }
         },
         "Transactions" => {
-            let data = db.get_transactions(10000).map_err(|e| e.to_string())?;
+            let mut data = db.get_transactions(10000).map_err(|e| e.to_string())?;
+            if !has_permission(&auth, PAYROLL_ACCESS) {
+                data.iter_mut().for_each(hide_payroll_posting);
+            }
             wtr.write_record(&["ID", "Date", "Type", "Amount", "Description", "Ref ID"]).map_err(|e| e.to_string())?;
             for t in data {
                 wtr.serialize((t.id, t.date, t.transaction_type, t.amount, t.description, t.reference_id)).map_err(|e| e.to_string())?;
@@ -981,14 +4212,17 @@ pub fn export_data_xlsx(
     module: String,
     path: String,
 ) -> Result<(), String> {
-    check_permission(&auth, "manage_settings")?;
+    check_current_permission(&db, &auth, Permission::ManageSettings)?;
     
     let mut workbook = rust_xlsxwriter::Workbook::new();
     let worksheet = workbook.add_worksheet();
 
     match module.as_str() {
         "Transactions" => {
-            let data = db.get_transactions(10000).map_err(|e| e.to_string())?;
+            let mut data = db.get_transactions(10000).map_err(|e| e.to_string())?;
+            if !has_permission(&auth, PAYROLL_ACCESS) {
+                data.iter_mut().for_each(hide_payroll_posting);
+            }
             worksheet.write_string(0, 0, "ID").map_err(|e| e.to_string())?;
             worksheet.write_string(0, 1, "Date").map_err(|e| e.to_string())?;
             worksheet.write_string(0, 2, "Type").map_err(|e| e.to_string())?;
@@ -1136,12 +4370,14 @@ pub fn verify_offline_activation(db: State<'_, AppDb>) -> Result<bool, String> {
 // ── Custom Templates ──────────────────────────────────
 
 #[tauri::command]
-pub fn get_custom_templates(db: State<'_, AppDb>) -> Result<Vec<CustomTemplate>, String> {
+pub fn get_custom_templates(db: State<'_, AppDb>, auth: State<'_, AuthState>) -> Result<Vec<CustomTemplate>, String> {
+    require_session(&auth)?;
     db.get_custom_templates().map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub fn get_custom_template(db: State<'_, AppDb>, id: i64) -> Result<CustomTemplate, String> {
+pub fn get_custom_template(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64) -> Result<CustomTemplate, String> {
+    require_session(&auth)?;
     db.get_custom_template(id).map_err(|e| e.to_string())
 }
 
@@ -1167,7 +4403,7 @@ pub fn create_custom_template(
     border_style: String,
     border_color: String,
 ) -> Result<i64, String> {
-    check_permission(&auth, "manage_templates")?;
+    check_permission(&auth, Permission::ManageTemplates)?;
     let template = CustomTemplate {
         id: None,
         name, header_bg_color, header_text_color, accent_color,
@@ -1202,7 +4438,7 @@ pub fn update_custom_template(
     border_style: String,
     border_color: String,
 ) -> Result<(), String> {
-    check_permission(&auth, "manage_templates")?;
+    check_permission(&auth, Permission::ManageTemplates)?;
     let template = CustomTemplate {
         id: Some(id),
         name, header_bg_color, header_text_color, accent_color,
@@ -1216,6 +4452,6 @@ pub fn update_custom_template(
 
 #[tauri::command]
 pub fn delete_custom_template(db: State<'_, AppDb>, auth: State<'_, AuthState>, id: i64) -> Result<(), String> {
-    check_permission(&auth, "manage_templates")?;
+    check_permission(&auth, Permission::ManageTemplates)?;
     db.delete_custom_template(id).map_err(|e| e.to_string())
 }
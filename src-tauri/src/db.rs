@@ -2,15 +2,17 @@ use rusqlite::{params, Connection, Result as SqlResult};
 use std::sync::Mutex;
 
 use crate::models::*;
+use crate::permissions::Permission;
 
 pub struct AppDb {
     pub conn: Mutex<Connection>,
 }
 
 impl AppDb {
-    pub fn new(db_path: &str) -> SqlResult<Self> {
-        let conn = Connection::open(db_path)?;
-        conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA foreign_keys=ON;")?;
+    /// Opens the database, unlocking it with `key` when it is encrypted. A
+    /// wrong key fails with `NotADatabase`.
+    pub fn new(db_path: &str, key: Option<&str>) -> SqlResult<Self> {
+        let conn = Self::connect(db_path, key)?;
         let db = AppDb {
             conn: Mutex::new(conn),
         };
@@ -18,8 +20,90 @@ impl AppDb {
         Ok(db)
     }
 
+    fn connect(db_path: &str, key: Option<&str>) -> SqlResult<Connection> {
+        let conn = Connection::open(db_path)?;
+        if let Some(key) = key {
+            conn.pragma_update(None, "key", key)?;
+        }
+        conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA foreign_keys=ON;")?;
+        Ok(conn)
+    }
+
+    /// Encrypts a plaintext database in place: exports it to an encrypted copy,
+    /// swaps the copy in for the original and reopens it with `key`. If any step
+    /// fails the plaintext database is reopened and stays in use.
+    pub fn encrypt(&self, db_path: &str, key: &str) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let encrypted_path = format!("{}.encrypting", db_path);
+        let _ = std::fs::remove_file(&encrypted_path);
+
+        conn.execute("ATTACH DATABASE ?1 AS encrypted KEY ?2", params![encrypted_path, key])
+            .map_err(|e| e.to_string())?;
+        let exported = conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()));
+        conn.execute("DETACH DATABASE encrypted", []).map_err(|e| e.to_string())?;
+        if let Err(e) = exported {
+            let _ = std::fs::remove_file(&encrypted_path);
+            return Err(e.to_string());
+        }
+
+        // Close the plaintext connection so its WAL is checkpointed and the
+        // file can be replaced. A connection that will not close is put back.
+        let plaintext = std::mem::replace(&mut *conn, Connection::open_in_memory().map_err(|e| e.to_string())?);
+        if let Err((plaintext, e)) = plaintext.close() {
+            *conn = plaintext;
+            let _ = std::fs::remove_file(&encrypted_path);
+            return Err(e.to_string());
+        }
+        for suffix in ["-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", db_path, suffix));
+        }
+
+        // The plaintext file is kept aside until the encrypted one opens, so
+        // every failure from here on can go back to it.
+        let plaintext_path = format!("{}.plaintext", db_path);
+        let reopen_plaintext = |conn: &mut Connection, error: String| -> Result<(), String> {
+            let _ = std::fs::remove_file(&encrypted_path);
+            *conn = Self::connect(db_path, None).map_err(|e| e.to_string())?;
+            Err(error)
+        };
+        if let Err(e) = std::fs::rename(db_path, &plaintext_path) {
+            return reopen_plaintext(&mut conn, format!("Could not replace the database file: {}", e));
+        }
+        if let Err(e) = std::fs::rename(&encrypted_path, db_path) {
+            let _ = std::fs::rename(&plaintext_path, db_path);
+            return reopen_plaintext(&mut conn, format!("Could not replace the database file: {}", e));
+        }
+        match Self::connect(db_path, Some(key)) {
+            Ok(encrypted) => {
+                *conn = encrypted;
+                let _ = std::fs::remove_file(&plaintext_path);
+                Ok(())
+            }
+            Err(e) => {
+                let _ = std::fs::rename(&plaintext_path, db_path);
+                reopen_plaintext(&mut conn, e.to_string())
+            }
+        }
+    }
+
+    /// Whether `key` opens the encrypted database at `db_path`.
+    pub fn key_matches(db_path: &str, key: &str) -> bool {
+        Self::connect(db_path, Some(key)).is_ok()
+    }
+
+    /// Re-encrypts an encrypted database with a new key.
+    pub fn rekey(&self, key: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.pragma_update(None, "rekey", key)
+    }
+
     fn init_tables(&self) -> SqlResult<()> {
         let conn = self.conn.lock().unwrap();
+        let has_roles_table: bool = conn.query_row(
+            "SELECT count(*) FROM sqlite_master WHERE type='table' AND name='roles'",
+            [],
+            |row| row.get(0),
+        ).unwrap_or(0) > 0;
         conn.execute_batch(
             "
             CREATE TABLE IF NOT EXISTS customers (
@@ -30,15 +114,129 @@ impl AppDb {
                 email TEXT,
                 address TEXT,
                 tax_id TEXT,
+                price_list_id INTEGER,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS price_lists (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT UNIQUE NOT NULL,
+                description TEXT,
+                discount_percent REAL DEFAULT 0,
+                is_active INTEGER DEFAULT 1,
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP
             );
 
+            CREATE TABLE IF NOT EXISTS price_list_items (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                price_list_id INTEGER NOT NULL,
+                product_id INTEGER NOT NULL,
+                min_quantity REAL NOT NULL DEFAULT 0,
+                unit_price REAL NOT NULL,
+                tax_percent REAL,
+                UNIQUE (price_list_id, product_id, min_quantity),
+                FOREIGN KEY (price_list_id) REFERENCES price_lists(id) ON DELETE CASCADE,
+                FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
+            );
+
             CREATE TABLE IF NOT EXISTS products (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 name TEXT NOT NULL,
                 description TEXT,
                 unit_price REAL NOT NULL DEFAULT 0,
-                tax_percent REAL DEFAULT 0
+                tax_percent REAL DEFAULT 0,
+                track_stock INTEGER DEFAULT 0,
+                stock_on_hand REAL DEFAULT 0,
+                reorder_level REAL DEFAULT 0,
+                average_cost REAL DEFAULT 0,
+                unit TEXT DEFAULT 'pcs',
+                sku TEXT,
+                barcode TEXT,
+                category TEXT,
+                is_active INTEGER DEFAULT 1
+            );
+
+            CREATE TABLE IF NOT EXISTS stock_movements (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                product_id INTEGER NOT NULL,
+                movement_type TEXT NOT NULL, -- 'Opening', 'Sale', 'Return', 'Adjustment', 'Reversal'
+                quantity REAL NOT NULL,      -- signed: positive adds stock
+                unit_cost REAL DEFAULT 0,
+                location_id INTEGER NOT NULL DEFAULT 1,
+                invoice_id INTEGER,          -- kept without FK so history survives invoice deletion
+                receipt_id INTEGER,
+                transfer_id INTEGER,
+                notes TEXT,
+                user_id INTEGER,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS stock_locations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT UNIQUE NOT NULL,
+                address TEXT,
+                is_active INTEGER DEFAULT 1
+            );
+
+            CREATE TABLE IF NOT EXISTS product_stock (
+                product_id INTEGER NOT NULL,
+                location_id INTEGER NOT NULL,
+                quantity REAL NOT NULL DEFAULT 0,
+                PRIMARY KEY (product_id, location_id),
+                FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE,
+                FOREIGN KEY (location_id) REFERENCES stock_locations(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS stock_transfers (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                transfer_number TEXT UNIQUE,
+                from_location_id INTEGER NOT NULL,
+                to_location_id INTEGER NOT NULL,
+                transfer_date TEXT NOT NULL,
+                notes TEXT,
+                user_id INTEGER,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (from_location_id) REFERENCES stock_locations(id),
+                FOREIGN KEY (to_location_id) REFERENCES stock_locations(id),
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS stock_transfer_items (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                transfer_id INTEGER NOT NULL,
+                product_id INTEGER NOT NULL,
+                quantity REAL NOT NULL,
+                FOREIGN KEY (transfer_id) REFERENCES stock_transfers(id) ON DELETE CASCADE,
+                FOREIGN KEY (product_id) REFERENCES products(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS purchase_receipts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                product_id INTEGER NOT NULL,
+                location_id INTEGER NOT NULL DEFAULT 1,
+                quantity REAL NOT NULL,
+                unit_cost REAL NOT NULL DEFAULT 0,
+                supplier TEXT,
+                reference TEXT,
+                received_date TEXT NOT NULL,
+                notes TEXT,
+                user_id INTEGER,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL
+            );
+
+            -- Remaining quantity per receipt, consumed oldest-first for FIFO costing
+            CREATE TABLE IF NOT EXISTS cost_layers (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                product_id INTEGER NOT NULL,
+                movement_id INTEGER NOT NULL,
+                quantity_remaining REAL NOT NULL,
+                unit_cost REAL NOT NULL DEFAULT 0,
+                FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE,
+                FOREIGN KEY (movement_id) REFERENCES stock_movements(id) ON DELETE CASCADE
             );
 
             CREATE TABLE IF NOT EXISTS invoices (
@@ -55,6 +253,7 @@ impl AppDb {
                 discount_percent REAL DEFAULT 0,
                 advance REAL DEFAULT 0,
                 total REAL DEFAULT 0,
+                location_id INTEGER,
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
                 FOREIGN KEY(customer_id) REFERENCES customers(id)
             );
@@ -62,10 +261,25 @@ impl AppDb {
             CREATE TABLE IF NOT EXISTS invoice_items (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 invoice_id INTEGER,
+                product_id INTEGER,
                 product_name TEXT,
                 description TEXT,
-                quantity INTEGER DEFAULT 1,
+                quantity REAL DEFAULT 1,
+                unit TEXT,
                 unit_price REAL DEFAULT 0,
+                discount REAL DEFAULT 0,
+                discount_percent REAL DEFAULT 0,
+                tax_percent REAL DEFAULT 0,
+                line_total REAL DEFAULT 0,
+                cost_total REAL DEFAULT 0,
+                FOREIGN KEY(invoice_id) REFERENCES invoices(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS invoice_charges (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                invoice_id INTEGER,
+                description TEXT NOT NULL,
+                amount REAL DEFAULT 0,
                 tax_percent REAL DEFAULT 0,
                 line_total REAL DEFAULT 0,
                 FOREIGN KEY(invoice_id) REFERENCES invoices(id) ON DELETE CASCADE
@@ -88,14 +302,25 @@ impl AppDb {
                 bank_account_no TEXT,
                 bank_branch TEXT,
                 business_tagline TEXT,
-                qr_code_path TEXT
+                qr_code_path TEXT,
+                inventory_valuation_method TEXT DEFAULT 'FIFO',
+                default_location_id INTEGER
             );
 
             CREATE TABLE IF NOT EXISTS users (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 username TEXT UNIQUE NOT NULL,
                 password_hash TEXT NOT NULL,
-                role TEXT NOT NULL DEFAULT 'User'
+                role TEXT NOT NULL DEFAULT 'User',
+                default_location_id INTEGER,
+                must_change_password INTEGER NOT NULL DEFAULT 0,
+                password_changed_at TEXT,
+                failed_login_attempts INTEGER NOT NULL DEFAULT 0,
+                locked_until TEXT,
+                totp_secret TEXT,
+                totp_enabled INTEGER NOT NULL DEFAULT 0,
+                totp_last_step INTEGER,
+                is_active INTEGER NOT NULL DEFAULT 1
             );
 
             CREATE TABLE IF NOT EXISTS permissions (
@@ -112,6 +337,60 @@ impl AppDb {
                 FOREIGN KEY (permission_id) REFERENCES permissions(id) ON DELETE CASCADE
             );
 
+            CREATE TABLE IF NOT EXISTS password_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id INTEGER NOT NULL,
+                password_hash TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS password_policy (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                min_length INTEGER NOT NULL DEFAULT 8,
+                require_uppercase INTEGER NOT NULL DEFAULT 1,
+                require_lowercase INTEGER NOT NULL DEFAULT 1,
+                require_digit INTEGER NOT NULL DEFAULT 1,
+                require_symbol INTEGER NOT NULL DEFAULT 0,
+                history_count INTEGER NOT NULL DEFAULT 3,
+                max_failed_logins INTEGER NOT NULL DEFAULT 5,
+                lockout_minutes INTEGER NOT NULL DEFAULT 15,
+                require_two_factor INTEGER NOT NULL DEFAULT 0,
+                idle_timeout_minutes INTEGER NOT NULL DEFAULT 15,
+                session_lifetime_hours INTEGER NOT NULL DEFAULT 12
+            );
+
+            CREATE TABLE IF NOT EXISTS recovery_codes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id INTEGER NOT NULL,
+                code_hash TEXT NOT NULL,
+                used_at TEXT,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS roles (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT UNIQUE NOT NULL,
+                description TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS role_permissions (
+                role_id INTEGER NOT NULL,
+                permission_id INTEGER NOT NULL,
+                PRIMARY KEY (role_id, permission_id),
+                FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE,
+                FOREIGN KEY (permission_id) REFERENCES permissions(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS user_roles (
+                user_id INTEGER NOT NULL,
+                role_id INTEGER NOT NULL,
+                PRIMARY KEY (user_id, role_id),
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+                FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+            );
+
             CREATE TABLE IF NOT EXISTS categories (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 name TEXT NOT NULL,
@@ -148,9 +427,44 @@ impl AppDb {
                 phone TEXT,
                 salary REAL DEFAULT 0,
                 allowances REAL DEFAULT 0,
+                overtime_rate REAL DEFAULT 0,
+                standard_hours REAL DEFAULT 8,
+                shift_start TEXT,
+                late_penalty REAL DEFAULT 0,
+                bank_name TEXT,
+                bank_branch TEXT,
+                bank_account_no TEXT,
+                department TEXT,
+                status TEXT NOT NULL DEFAULT 'Active',
+                join_date TEXT,
+                exit_date TEXT,
+                pay_frequency TEXT NOT NULL DEFAULT 'Monthly',
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP
             );
 
+            CREATE TABLE IF NOT EXISTS leave_types (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                annual_days REAL NOT NULL DEFAULT 0,
+                accrual TEXT NOT NULL DEFAULT 'Annual',
+                is_paid INTEGER NOT NULL DEFAULT 1,
+                is_active INTEGER NOT NULL DEFAULT 1
+            );
+
+            CREATE TABLE IF NOT EXISTS attendance (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                employee_id INTEGER NOT NULL,
+                date TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'Present',
+                check_in TEXT,
+                check_out TEXT,
+                leave_type_id INTEGER,
+                notes TEXT,
+                UNIQUE (employee_id, date),
+                FOREIGN KEY (employee_id) REFERENCES employees(id),
+                FOREIGN KEY (leave_type_id) REFERENCES leave_types(id)
+            );
+
             CREATE TABLE IF NOT EXISTS payroll (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 employee_id INTEGER NOT NULL,
@@ -170,6 +484,90 @@ impl AppDb {
                 payment_date TEXT NOT NULL,
                 status TEXT DEFAULT 'Paid',
                 notes TEXT,
+                employer_contributions REAL DEFAULT 0,
+                account_id INTEGER,
+                void_reason TEXT,
+                run_id INTEGER,
+                FOREIGN KEY (employee_id) REFERENCES employees(id),
+                FOREIGN KEY (run_id) REFERENCES payroll_runs(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS payroll_runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                run_number TEXT UNIQUE,
+                pay_period_start TEXT NOT NULL,
+                pay_period_end TEXT NOT NULL,
+                payment_date TEXT NOT NULL,
+                account_id INTEGER NOT NULL DEFAULT 1,
+                status TEXT NOT NULL DEFAULT 'Draft',
+                notes TEXT,
+                created_by INTEGER,
+                approved_by INTEGER,
+                approved_at DATETIME,
+                paid_at DATETIME,
+                void_reason TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (account_id) REFERENCES accounts(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS payroll_liabilities (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                payroll_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                employee_amount REAL NOT NULL DEFAULT 0,
+                employer_amount REAL NOT NULL DEFAULT 0,
+                amount REAL NOT NULL DEFAULT 0,
+                status TEXT NOT NULL DEFAULT 'Outstanding',
+                remitted_date TEXT,
+                account_id INTEGER,
+                transaction_id INTEGER,
+                reference TEXT,
+                FOREIGN KEY (payroll_id) REFERENCES payroll(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS payroll_components (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                component_type TEXT NOT NULL DEFAULT 'Deduction',
+                calculation TEXT NOT NULL DEFAULT 'Fixed',
+                amount REAL NOT NULL DEFAULT 0,
+                employer_rate REAL NOT NULL DEFAULT 0,
+                basis TEXT NOT NULL DEFAULT 'Basic',
+                taxable INTEGER NOT NULL DEFAULT 1,
+                is_active INTEGER NOT NULL DEFAULT 1,
+                sort_order INTEGER NOT NULL DEFAULT 0
+            );
+
+            CREATE TABLE IF NOT EXISTS tax_brackets (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                lower_bound REAL NOT NULL DEFAULT 0,
+                upper_bound REAL,
+                rate REAL NOT NULL DEFAULT 0
+            );
+
+            CREATE TABLE IF NOT EXISTS payroll_lines (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                payroll_id INTEGER NOT NULL,
+                component_id INTEGER,
+                name TEXT NOT NULL,
+                line_type TEXT NOT NULL,
+                amount REAL NOT NULL DEFAULT 0,
+                employer_amount REAL NOT NULL DEFAULT 0,
+                loan_id INTEGER,
+                FOREIGN KEY (payroll_id) REFERENCES payroll(id) ON DELETE CASCADE,
+                FOREIGN KEY (loan_id) REFERENCES employee_loans(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS employee_loans (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                employee_id INTEGER NOT NULL,
+                loan_type TEXT NOT NULL DEFAULT 'Loan',
+                principal REAL NOT NULL,
+                instalment REAL NOT NULL,
+                start_date TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'Active',
+                notes TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
                 FOREIGN KEY (employee_id) REFERENCES employees(id)
             );
 
@@ -181,6 +579,7 @@ impl AppDb {
                 record_id TEXT,
                 description TEXT,
                 timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+                username TEXT, -- kept when the user is deleted
                 FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL
             );
 
@@ -214,19 +613,6 @@ impl AppDb {
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP
             );
 
-            -- Initial permissions
-            INSERT OR IGNORE INTO permissions (name, description) VALUES 
-                ('manage_users', 'Administer users and permissions'),
-                ('view_logs', 'View system audit logs'),
-                ('create_invoice', 'Create new invoices'),
-                ('delete_invoice', 'Delete existing invoices'),
-                ('manage_customers', 'Create, update or delete customers'),
-                ('manage_products', 'Create, update or delete products'),
-                ('manage_settings', 'Update business settings'),
-                ('manage_transactions', 'Manage income and expenses'),
-                ('manage_payroll', 'Manage employee payroll'),
-                ('view_reports', 'View financial reports');
-
             -- Default accounts and categories
             INSERT OR IGNORE INTO accounts (id, name, account_type, balance) VALUES (1, 'Cash', 'Cash', 0);
             INSERT OR IGNORE INTO accounts (id, name, account_type, balance) VALUES (2, 'Bank Account', 'Bank', 0);
@@ -239,10 +625,26 @@ impl AppDb {
                 ('Salary', 'Expense'),
                 ('Other', 'Expense');
 
+            INSERT OR IGNORE INTO stock_locations (id, name) VALUES (1, 'Main');
+
+            -- Common contribution schemes, left inactive until an admin enables them
+            INSERT OR IGNORE INTO payroll_components (name, component_type, calculation, amount, employer_rate, basis, is_active, sort_order) VALUES
+                ('EPF', 'Contribution', 'Percent', 8, 12, 'Basic', 0, 1),
+                ('ETF', 'Contribution', 'Percent', 0, 3, 'Basic', 0, 2);
+
+            INSERT OR IGNORE INTO leave_types (name, annual_days, accrual, is_paid) VALUES
+                ('Annual', 14, 'Annual', 1),
+                ('Casual', 7, 'Annual', 1),
+                ('Sick', 7, 'Monthly', 1),
+                ('No Pay', 0, 'Annual', 0);
+
             INSERT OR IGNORE INTO settings (id, business_name) VALUES (1, 'My Business');
+            INSERT OR IGNORE INTO password_policy (id) VALUES (1);
             ",
         )?;
 
+        Self::seed_permissions(&conn)?;
+
         // Migration: Add 'role' column to 'users' if it doesn't exist
         let has_role_col: bool = conn.query_row(
             "SELECT count(*) FROM pragma_table_info('users') WHERE name='role'",
@@ -256,6 +658,117 @@ impl AppDb {
             conn.execute("INSERT OR IGNORE INTO user_permissions (user_id, permission_id) SELECT u.id, p.id FROM users u CROSS JOIN permissions p WHERE u.role = 'Admin'", [])?;
         }
 
+        // Migration: forced password changes. An untouched default admin/admin
+        // account must be given a new password at its next login.
+        let has_must_change: bool = conn.query_row(
+            "SELECT count(*) FROM pragma_table_info('users') WHERE name='must_change_password'",
+            [],
+            |row| row.get(0),
+        ).unwrap_or(0) > 0;
+
+        if !has_must_change {
+            conn.execute("ALTER TABLE users ADD COLUMN must_change_password INTEGER NOT NULL DEFAULT 0", [])?;
+            conn.execute("ALTER TABLE users ADD COLUMN password_changed_at TEXT", [])?;
+            let default_admin: Option<(i64, String)> = conn.query_row(
+                "SELECT id, password_hash FROM users WHERE username = 'admin'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            ).ok();
+            if let Some((id, hash)) = default_admin {
+                if bcrypt::verify("admin", &hash).unwrap_or(false) {
+                    conn.execute("UPDATE users SET must_change_password = 1 WHERE id = ?1", params![id])?;
+                }
+            }
+        }
+
+        // Migration: login lockout
+        let has_lockout: bool = conn.query_row(
+            "SELECT count(*) FROM pragma_table_info('users') WHERE name='failed_login_attempts'",
+            [],
+            |row| row.get(0),
+        ).unwrap_or(0) > 0;
+
+        if !has_lockout {
+            conn.execute("ALTER TABLE users ADD COLUMN failed_login_attempts INTEGER NOT NULL DEFAULT 0", [])?;
+            conn.execute("ALTER TABLE users ADD COLUMN locked_until TEXT", [])?;
+        }
+
+        let has_lockout_policy: bool = conn.query_row(
+            "SELECT count(*) FROM pragma_table_info('password_policy') WHERE name='max_failed_logins'",
+            [],
+            |row| row.get(0),
+        ).unwrap_or(0) > 0;
+
+        if !has_lockout_policy {
+            conn.execute("ALTER TABLE password_policy ADD COLUMN max_failed_logins INTEGER NOT NULL DEFAULT 5", [])?;
+            conn.execute("ALTER TABLE password_policy ADD COLUMN lockout_minutes INTEGER NOT NULL DEFAULT 15", [])?;
+        }
+
+        // Migration: two-factor authentication
+        let has_totp: bool = conn.query_row(
+            "SELECT count(*) FROM pragma_table_info('users') WHERE name='totp_secret'",
+            [],
+            |row| row.get(0),
+        ).unwrap_or(0) > 0;
+
+        if !has_totp {
+            conn.execute("ALTER TABLE users ADD COLUMN totp_secret TEXT", [])?;
+            conn.execute("ALTER TABLE users ADD COLUMN totp_enabled INTEGER NOT NULL DEFAULT 0", [])?;
+        }
+
+        let has_totp_last_step: bool = conn.query_row(
+            "SELECT count(*) FROM pragma_table_info('users') WHERE name='totp_last_step'",
+            [],
+            |row| row.get(0),
+        ).unwrap_or(0) > 0;
+
+        if !has_totp_last_step {
+            conn.execute("ALTER TABLE users ADD COLUMN totp_last_step INTEGER", [])?;
+        }
+
+        let has_two_factor_policy: bool = conn.query_row(
+            "SELECT count(*) FROM pragma_table_info('password_policy') WHERE name='require_two_factor'",
+            [],
+            |row| row.get(0),
+        ).unwrap_or(0) > 0;
+
+        if !has_two_factor_policy {
+            conn.execute("ALTER TABLE password_policy ADD COLUMN require_two_factor INTEGER NOT NULL DEFAULT 0", [])?;
+        }
+
+        // Migration: user deactivation and deletion
+        let has_user_active: bool = conn.query_row(
+            "SELECT count(*) FROM pragma_table_info('users') WHERE name='is_active'",
+            [],
+            |row| row.get(0),
+        ).unwrap_or(0) > 0;
+
+        if !has_user_active {
+            conn.execute("ALTER TABLE users ADD COLUMN is_active INTEGER NOT NULL DEFAULT 1", [])?;
+        }
+
+        let has_log_username: bool = conn.query_row(
+            "SELECT count(*) FROM pragma_table_info('audit_logs') WHERE name='username'",
+            [],
+            |row| row.get(0),
+        ).unwrap_or(0) > 0;
+
+        if !has_log_username {
+            conn.execute("ALTER TABLE audit_logs ADD COLUMN username TEXT", [])?;
+        }
+
+        // Migration: session timeouts
+        let has_session_policy: bool = conn.query_row(
+            "SELECT count(*) FROM pragma_table_info('password_policy') WHERE name='idle_timeout_minutes'",
+            [],
+            |row| row.get(0),
+        ).unwrap_or(0) > 0;
+
+        if !has_session_policy {
+            conn.execute("ALTER TABLE password_policy ADD COLUMN idle_timeout_minutes INTEGER NOT NULL DEFAULT 15", [])?;
+            conn.execute("ALTER TABLE password_policy ADD COLUMN session_lifetime_hours INTEGER NOT NULL DEFAULT 12", [])?;
+        }
+
         // Migration for new settings and other tables
         let settings_cols: Vec<String> = conn.prepare("PRAGMA table_info('settings')")?
             .query_map([], |row| row.get(1))?
@@ -291,6 +804,28 @@ impl AppDb {
         if !settings_cols.contains(&"qr_code_path".to_string()) {
             conn.execute("ALTER TABLE settings ADD COLUMN qr_code_path TEXT", [])?;
         }
+        if !settings_cols.contains(&"inventory_valuation_method".to_string()) {
+            conn.execute("ALTER TABLE settings ADD COLUMN inventory_valuation_method TEXT DEFAULT 'FIFO'", [])?;
+        }
+        if !settings_cols.contains(&"default_location_id".to_string()) {
+            conn.execute("ALTER TABLE settings ADD COLUMN default_location_id INTEGER", [])?;
+        }
+
+        let user_cols: Vec<String> = conn.prepare("PRAGMA table_info('users')")?
+            .query_map([], |row| row.get(1))?
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        if !user_cols.contains(&"default_location_id".to_string()) {
+            conn.execute("ALTER TABLE users ADD COLUMN default_location_id INTEGER", [])?;
+        }
+
+        let customer_cols: Vec<String> = conn.prepare("PRAGMA table_info('customers')")?
+            .query_map([], |row| row.get(1))?
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        if !customer_cols.contains(&"price_list_id".to_string()) {
+            conn.execute("ALTER TABLE customers ADD COLUMN price_list_id INTEGER", [])?;
+        }
 
         // Migration for invoice advance and discount_percent columns
         let invoice_cols: Vec<String> = conn.prepare("PRAGMA table_info('invoices')")?
@@ -303,6 +838,79 @@ impl AppDb {
         if !invoice_cols.contains(&"advance".to_string()) {
             conn.execute("ALTER TABLE invoices ADD COLUMN advance REAL DEFAULT 0", [])?;
         }
+        if !invoice_cols.contains(&"location_id".to_string()) {
+            conn.execute("ALTER TABLE invoices ADD COLUMN location_id INTEGER", [])?;
+        }
+
+        // Migration for stock tracking columns
+        let product_cols: Vec<String> = conn.prepare("PRAGMA table_info('products')")?
+            .query_map([], |row| row.get(1))?
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        if !product_cols.contains(&"track_stock".to_string()) {
+            conn.execute("ALTER TABLE products ADD COLUMN track_stock INTEGER DEFAULT 0", [])?;
+            conn.execute("ALTER TABLE products ADD COLUMN stock_on_hand REAL DEFAULT 0", [])?;
+            conn.execute("ALTER TABLE products ADD COLUMN reorder_level REAL DEFAULT 0", [])?;
+        }
+        if !product_cols.contains(&"average_cost".to_string()) {
+            conn.execute("ALTER TABLE products ADD COLUMN average_cost REAL DEFAULT 0", [])?;
+        }
+        if !product_cols.contains(&"unit".to_string()) {
+            conn.execute("ALTER TABLE products ADD COLUMN unit TEXT DEFAULT 'pcs'", [])?;
+        }
+        if !product_cols.contains(&"sku".to_string()) {
+            conn.execute("ALTER TABLE products ADD COLUMN sku TEXT", [])?;
+            conn.execute("ALTER TABLE products ADD COLUMN barcode TEXT", [])?;
+            conn.execute("ALTER TABLE products ADD COLUMN category TEXT", [])?;
+            conn.execute("ALTER TABLE products ADD COLUMN is_active INTEGER DEFAULT 1", [])?;
+        }
+
+        let movement_cols: Vec<String> = conn.prepare("PRAGMA table_info('stock_movements')")?
+            .query_map([], |row| row.get(1))?
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        if !movement_cols.contains(&"unit_cost".to_string()) {
+            conn.execute("ALTER TABLE stock_movements ADD COLUMN unit_cost REAL DEFAULT 0", [])?;
+            conn.execute("ALTER TABLE stock_movements ADD COLUMN receipt_id INTEGER", [])?;
+        }
+        if !movement_cols.contains(&"location_id".to_string()) {
+            conn.execute("ALTER TABLE stock_movements ADD COLUMN location_id INTEGER NOT NULL DEFAULT 1", [])?;
+            conn.execute("ALTER TABLE stock_movements ADD COLUMN transfer_id INTEGER", [])?;
+            // Stock recorded before locations existed all sits in the default location
+            conn.execute(
+                "INSERT OR IGNORE INTO product_stock (product_id, location_id, quantity)
+                 SELECT id, 1, stock_on_hand FROM products WHERE track_stock = 1",
+                [],
+            )?;
+        }
+
+        let receipt_cols: Vec<String> = conn.prepare("PRAGMA table_info('purchase_receipts')")?
+            .query_map([], |row| row.get(1))?
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        if !receipt_cols.contains(&"location_id".to_string()) {
+            conn.execute("ALTER TABLE purchase_receipts ADD COLUMN location_id INTEGER NOT NULL DEFAULT 1", [])?;
+        }
+
+        let item_cols: Vec<String> = conn.prepare("PRAGMA table_info('invoice_items')")?
+            .query_map([], |row| row.get(1))?
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        if !item_cols.contains(&"product_id".to_string()) {
+            conn.execute("ALTER TABLE invoice_items ADD COLUMN product_id INTEGER", [])?;
+        }
+        if !item_cols.contains(&"cost_total".to_string()) {
+            conn.execute("ALTER TABLE invoice_items ADD COLUMN cost_total REAL DEFAULT 0", [])?;
+        }
+        // Older databases declare quantity as INTEGER; SQLite's column affinity still keeps
+        // fractional values as REAL there, so only the unit column needs adding.
+        if !item_cols.contains(&"unit".to_string()) {
+            conn.execute("ALTER TABLE invoice_items ADD COLUMN unit TEXT", [])?;
+        }
+        if !item_cols.contains(&"discount".to_string()) {
+            conn.execute("ALTER TABLE invoice_items ADD COLUMN discount REAL DEFAULT 0", [])?;
+            conn.execute("ALTER TABLE invoice_items ADD COLUMN discount_percent REAL DEFAULT 0", [])?;
+        }
 
         // Migration for payroll expanded columns
         let payroll_cols: Vec<String> = conn.prepare("PRAGMA table_info('payroll')")?
@@ -326,13 +934,83 @@ impl AppDb {
             // Migrate existing records: set base_salary=amount, gross_salary=amount, net_pay=amount
             conn.execute("UPDATE payroll SET base_salary = amount, gross_salary = amount, net_pay = amount WHERE base_salary = 0 AND amount > 0", [])?;
         }
-
-        // Migration for employee allowances field
-        let emp_has_allowances: bool = conn.prepare("SELECT allowances FROM employees LIMIT 1")
-            .is_ok();
-        if !emp_has_allowances {
+        if !payroll_cols.contains(&"employer_contributions".to_string()) {
+            conn.execute("ALTER TABLE payroll ADD COLUMN employer_contributions REAL DEFAULT 0", [])?;
+        }
+        if !payroll_cols.contains(&"account_id".to_string()) {
+            conn.execute("ALTER TABLE payroll ADD COLUMN account_id INTEGER", [])?;
+            conn.execute("ALTER TABLE payroll ADD COLUMN void_reason TEXT", [])?;
+            // Paid records used to be posted from Cash
+            conn.execute("UPDATE payroll SET account_id = 1 WHERE status = 'Paid'", [])?;
+        }
+        if !payroll_cols.contains(&"run_id".to_string()) {
+            conn.execute("ALTER TABLE payroll ADD COLUMN run_id INTEGER REFERENCES payroll_runs(id)", [])?;
+        }
+        let line_cols: Vec<String> = conn.prepare("PRAGMA table_info('payroll_lines')")?
+            .query_map([], |row| row.get(1))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        if !line_cols.contains(&"loan_id".to_string()) {
+            conn.execute("ALTER TABLE payroll_lines ADD COLUMN loan_id INTEGER REFERENCES employee_loans(id)", [])?;
+        }
+
+        // Migration for employee allowances field
+        let emp_has_allowances: bool = conn.prepare("SELECT allowances FROM employees LIMIT 1")
+            .is_ok();
+        if !emp_has_allowances {
             conn.execute("ALTER TABLE employees ADD COLUMN allowances REAL DEFAULT 0", [])?;
         }
+        let emp_cols: Vec<String> = conn.prepare("PRAGMA table_info('employees')")?
+            .query_map([], |row| row.get(1))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        if !emp_cols.contains(&"overtime_rate".to_string()) {
+            conn.execute("ALTER TABLE employees ADD COLUMN overtime_rate REAL DEFAULT 0", [])?;
+            conn.execute("ALTER TABLE employees ADD COLUMN standard_hours REAL DEFAULT 8", [])?;
+            conn.execute("ALTER TABLE employees ADD COLUMN shift_start TEXT", [])?;
+            conn.execute("ALTER TABLE employees ADD COLUMN late_penalty REAL DEFAULT 0", [])?;
+        }
+        if !emp_cols.contains(&"bank_account_no".to_string()) {
+            conn.execute("ALTER TABLE employees ADD COLUMN bank_name TEXT", [])?;
+            conn.execute("ALTER TABLE employees ADD COLUMN bank_branch TEXT", [])?;
+            conn.execute("ALTER TABLE employees ADD COLUMN bank_account_no TEXT", [])?;
+        }
+        if !emp_cols.contains(&"pay_frequency".to_string()) {
+            conn.execute("ALTER TABLE employees ADD COLUMN department TEXT", [])?;
+            conn.execute("ALTER TABLE employees ADD COLUMN status TEXT NOT NULL DEFAULT 'Active'", [])?;
+            conn.execute("ALTER TABLE employees ADD COLUMN join_date TEXT", [])?;
+            conn.execute("ALTER TABLE employees ADD COLUMN exit_date TEXT", [])?;
+            conn.execute("ALTER TABLE employees ADD COLUMN pay_frequency TEXT NOT NULL DEFAULT 'Monthly'", [])?;
+        }
+
+        // Starter roles are created once, with the roles table, so admins can
+        // freely rename, change or delete them afterwards.
+        if !has_roles_table {
+            let starter_roles: [(&str, &str, &[Permission]); 3] = [
+                ("Accountant", "Invoicing, income and expenses, and reports", &[
+                    Permission::ViewInvoices, Permission::CreateInvoices, Permission::EditInvoices, Permission::DeleteInvoices,
+                    Permission::ExportInvoices, Permission::ViewTransactions, Permission::CreateTransactions,
+                    Permission::EditTransactions, Permission::ViewCustomers, Permission::ViewProducts,
+                    Permission::ViewReports, Permission::ExportReports,
+                ]),
+                ("Cashier", "Point of sale invoicing and stock", &[
+                    Permission::ViewInvoices, Permission::CreateInvoices, Permission::ViewCustomers,
+                    Permission::CreateCustomers, Permission::ViewProducts, Permission::ManageInventory,
+                ]),
+                ("HR", "Employees, attendance and payroll", &[
+                    Permission::ViewEmployees, Permission::CreateEmployees, Permission::EditEmployees,
+                    Permission::DeleteEmployees, Permission::ViewPayroll, Permission::ManagePayroll,
+                ]),
+            ];
+            for (name, description, permissions) in starter_roles {
+                conn.execute("INSERT OR IGNORE INTO roles (name, description) VALUES (?1, ?2)", params![name, description])?;
+                for permission in permissions {
+                    conn.execute(
+                        "INSERT OR IGNORE INTO role_permissions (role_id, permission_id)
+                            SELECT r.id, p.id FROM roles r, permissions p WHERE r.name = ?1 AND p.name = ?2",
+                        params![name, permission.name()],
+                    )?;
+                }
+            }
+        }
 
         // Ensure newly added permissions are assigned to Admins
         conn.execute(
@@ -355,7 +1033,7 @@ impl AppDb {
                 .expect("Failed to hash default password");
             
             conn.execute(
-                "INSERT INTO users (username, password_hash, role) VALUES ('admin', ?1, 'Admin')",
+                "INSERT INTO users (username, password_hash, role, must_change_password) VALUES ('admin', ?1, 'Admin', 1)",
                 params![password_hash],
             )?;
 
@@ -371,12 +1049,59 @@ impl AppDb {
         Ok(())
     }
 
+    /// Inserts any registry permission missing from the table, carries grants of
+    /// renamed or split permissions over to their replacements, then drops names
+    /// that are no longer registered.
+    fn seed_permissions(conn: &Connection) -> SqlResult<()> {
+        let existing: Vec<String> = conn.prepare("SELECT name FROM permissions")?
+            .query_map([], |row| row.get(0))?
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        for p in Permission::ALL {
+            conn.execute(
+                "INSERT OR IGNORE INTO permissions (name, description) VALUES (?1, ?2)",
+                params![p.name(), p.description()],
+            )?;
+        }
+
+        for (old, replacements) in crate::permissions::UPGRADES {
+            if !existing.iter().any(|e| e == old) {
+                continue;
+            }
+            for new in replacements.iter().filter(|n| !existing.iter().any(|e| e == n.name())) {
+                for (table, owner) in [("user_permissions", "user_id"), ("role_permissions", "role_id")] {
+                    conn.execute(
+                        &format!(
+                            "INSERT OR IGNORE INTO {table} ({owner}, permission_id)
+                                SELECT g.{owner}, np.id FROM {table} g
+                                JOIN permissions op ON op.id = g.permission_id
+                                JOIN permissions np ON np.name = ?2
+                                WHERE op.name = ?1"
+                        ),
+                        params![old, new.name()],
+                    )?;
+                }
+            }
+        }
+
+        let registered: Vec<&str> = Permission::ALL.iter().map(|p| p.name()).collect();
+        for name in existing.iter().filter(|e| !registered.contains(&e.as_str())) {
+            conn.execute("DELETE FROM user_permissions WHERE permission_id = (SELECT id FROM permissions WHERE name = ?1)", params![name])?;
+            conn.execute("DELETE FROM role_permissions WHERE permission_id = (SELECT id FROM permissions WHERE name = ?1)", params![name])?;
+            conn.execute("DELETE FROM permissions WHERE name = ?1", params![name])?;
+        }
+        Ok(())
+    }
+
     // ── Customers ──────────────────────────────────────────
 
     pub fn get_customers(&self) -> SqlResult<Vec<Customer>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, name, company, phone, email, address, tax_id, created_at FROM customers ORDER BY name"
+            "SELECT c.id, c.name, c.company, c.phone, c.email, c.address, c.tax_id, c.price_list_id, pl.name, c.created_at
+             FROM customers c
+             LEFT JOIN price_lists pl ON c.price_list_id = pl.id
+             ORDER BY c.name"
         )?;
         let rows = stmt.query_map([], |row| {
             Ok(Customer {
@@ -387,7 +1112,9 @@ impl AppDb {
                 email: row.get(4)?,
                 address: row.get(5)?,
                 tax_id: row.get(6)?,
-                created_at: row.get(7)?,
+                price_list_id: row.get(7)?,
+                price_list_name: row.get(8)?,
+                created_at: row.get(9)?,
             })
         })?;
         rows.collect()
@@ -396,8 +1123,8 @@ impl AppDb {
     pub fn create_customer(&self, c: &Customer) -> SqlResult<i64> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO customers (name, company, phone, email, address, tax_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![c.name, c.company, c.phone, c.email, c.address, c.tax_id],
+            "INSERT INTO customers (name, company, phone, email, address, tax_id, price_list_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![c.name, c.company, c.phone, c.email, c.address, c.tax_id, c.price_list_id],
         )?;
         Ok(conn.last_insert_rowid())
     }
@@ -405,8 +1132,8 @@ impl AppDb {
     pub fn update_customer(&self, c: &Customer) -> SqlResult<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "UPDATE customers SET name=?1, company=?2, phone=?3, email=?4, address=?5, tax_id=?6 WHERE id=?7",
-            params![c.name, c.company, c.phone, c.email, c.address, c.tax_id, c.id],
+            "UPDATE customers SET name=?1, company=?2, phone=?3, email=?4, address=?5, tax_id=?6, price_list_id=?7 WHERE id=?8",
+            params![c.name, c.company, c.phone, c.email, c.address, c.tax_id, c.price_list_id, c.id],
         )?;
         Ok(())
     }
@@ -417,39 +1144,297 @@ impl AppDb {
         Ok(())
     }
 
-    // ── Products ───────────────────────────────────────────
+    // ── Price Lists ────────────────────────────────────────
 
-    pub fn get_products(&self) -> SqlResult<Vec<Product>> {
+    pub fn get_price_lists(&self) -> SqlResult<Vec<PriceList>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, name, description, unit_price, tax_percent FROM products ORDER BY name"
+            "SELECT id, name, description, discount_percent, is_active, created_at FROM price_lists ORDER BY name"
         )?;
         let rows = stmt.query_map([], |row| {
-            Ok(Product {
+            Ok(PriceList {
                 id: row.get(0)?,
                 name: row.get(1)?,
                 description: row.get(2)?,
-                unit_price: row.get(3)?,
-                tax_percent: row.get(4)?,
+                discount_percent: row.get(3)?,
+                is_active: row.get::<_, i32>(4)? != 0,
+                created_at: row.get(5)?,
+                items: None,
             })
         })?;
         rows.collect()
     }
 
-    pub fn create_product(&self, p: &Product) -> SqlResult<i64> {
+    pub fn get_price_list_detail(&self, id: i64) -> SqlResult<PriceList> {
         let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "INSERT INTO products (name, description, unit_price, tax_percent) VALUES (?1, ?2, ?3, ?4)",
-            params![p.name, p.description, p.unit_price, p.tax_percent],
+        let mut list = conn.query_row(
+            "SELECT id, name, description, discount_percent, is_active, created_at FROM price_lists WHERE id=?1",
+            params![id],
+            |row| {
+                Ok(PriceList {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    description: row.get(2)?,
+                    discount_percent: row.get(3)?,
+                    is_active: row.get::<_, i32>(4)? != 0,
+                    created_at: row.get(5)?,
+                    items: None,
+                })
+            },
         )?;
-        Ok(conn.last_insert_rowid())
+        let mut stmt = conn.prepare(
+            "SELECT i.id, i.price_list_id, i.product_id, p.name, i.min_quantity, i.unit_price, i.tax_percent
+             FROM price_list_items i
+             LEFT JOIN products p ON i.product_id = p.id
+             WHERE i.price_list_id = ?1
+             ORDER BY p.name, i.min_quantity"
+        )?;
+        let items = stmt.query_map(params![id], |row| {
+            Ok(PriceListItem {
+                id: row.get(0)?,
+                price_list_id: row.get(1)?,
+                product_id: row.get(2)?,
+                product_name: row.get(3)?,
+                min_quantity: row.get(4)?,
+                unit_price: row.get(5)?,
+                tax_percent: row.get(6)?,
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+        list.items = Some(items);
+        Ok(list)
+    }
+
+    fn replace_price_list_items_inner(conn: &Connection, price_list_id: i64, items: &[PriceListItem]) -> SqlResult<()> {
+        conn.execute("DELETE FROM price_list_items WHERE price_list_id=?1", params![price_list_id])?;
+        for item in items {
+            conn.execute(
+                "INSERT INTO price_list_items (price_list_id, product_id, min_quantity, unit_price, tax_percent)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![price_list_id, item.product_id, item.min_quantity, item.unit_price, item.tax_percent],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn create_price_list(&self, pl: &PriceList, items: &[PriceListItem]) -> SqlResult<i64> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        tx.execute(
+            "INSERT INTO price_lists (name, description, discount_percent, is_active) VALUES (?1, ?2, ?3, ?4)",
+            params![pl.name, pl.description, pl.discount_percent, pl.is_active as i32],
+        )?;
+        let id = tx.last_insert_rowid();
+        Self::replace_price_list_items_inner(&tx, id, items)?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Updates the list header and replaces all of its prices and tiers.
+    pub fn update_price_list(&self, pl: &PriceList, items: &[PriceListItem]) -> SqlResult<()> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        tx.execute(
+            "UPDATE price_lists SET name=?1, description=?2, discount_percent=?3, is_active=?4 WHERE id=?5",
+            params![pl.name, pl.description, pl.discount_percent, pl.is_active as i32, pl.id],
+        )?;
+        if let Some(id) = pl.id {
+            Self::replace_price_list_items_inner(&tx, id, items)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn delete_price_list(&self, id: i64) -> SqlResult<()> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        tx.execute("UPDATE customers SET price_list_id = NULL WHERE price_list_id=?1", params![id])?;
+        tx.execute("DELETE FROM price_list_items WHERE price_list_id=?1", params![id])?;
+        tx.execute("DELETE FROM price_lists WHERE id=?1", params![id])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Effective price and tax for a product sold to a customer in a given quantity.
+    /// The highest quantity tier of the customer's active price list that the quantity
+    /// reaches wins; products not on the list get the list's blanket discount, and
+    /// customers without a list pay the product's own price.
+    pub fn resolve_price(&self, product_id: i64, customer_id: Option<i64>, quantity: f64) -> SqlResult<ResolvedPrice> {
+        let conn = self.conn.lock().unwrap();
+        let (base_price, base_tax): (f64, f64) = conn.query_row(
+            "SELECT unit_price, tax_percent FROM products WHERE id=?1",
+            params![product_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let mut resolved = ResolvedPrice {
+            product_id,
+            unit_price: base_price,
+            tax_percent: base_tax,
+            source: "Product".to_string(),
+            price_list_id: None,
+            min_quantity: None,
+        };
+
+        let list = conn.query_row(
+            "SELECT pl.id, pl.discount_percent FROM customers c
+             JOIN price_lists pl ON c.price_list_id = pl.id
+             WHERE c.id = ?1 AND pl.is_active = 1",
+            params![customer_id],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?)),
+        );
+        let (price_list_id, discount_percent) = match list {
+            Ok(l) => l,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(resolved),
+            Err(e) => return Err(e),
+        };
+
+        let tier = conn.query_row(
+            "SELECT min_quantity, unit_price, tax_percent FROM price_list_items
+             WHERE price_list_id = ?1 AND product_id = ?2 AND min_quantity <= ?3
+             ORDER BY min_quantity DESC LIMIT 1",
+            params![price_list_id, product_id, quantity],
+            |row| Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?, row.get::<_, Option<f64>>(2)?)),
+        );
+        match tier {
+            Ok((min_quantity, unit_price, tax_percent)) => {
+                resolved.unit_price = unit_price;
+                resolved.tax_percent = tax_percent.unwrap_or(base_tax);
+                resolved.source = "PriceList".to_string();
+                resolved.price_list_id = Some(price_list_id);
+                resolved.min_quantity = Some(min_quantity);
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) if discount_percent > 0.0 => {
+                resolved.unit_price = base_price * (1.0 - discount_percent / 100.0);
+                resolved.source = "PriceListDiscount".to_string();
+                resolved.price_list_id = Some(price_list_id);
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => {}
+            Err(e) => return Err(e),
+        }
+        Ok(resolved)
+    }
+
+    // ── Products ───────────────────────────────────────────
+
+    const PRODUCT_COLUMNS: &'static str =
+        "id, name, description, unit_price, tax_percent, track_stock, stock_on_hand, reorder_level, average_cost, unit,
+         sku, barcode, category, is_active";
+
+    fn product_from_row(row: &rusqlite::Row) -> SqlResult<Product> {
+        Ok(Product {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            description: row.get(2)?,
+            unit_price: row.get(3)?,
+            tax_percent: row.get(4)?,
+            track_stock: row.get::<_, i32>(5)? != 0,
+            stock_on_hand: row.get(6)?,
+            reorder_level: row.get(7)?,
+            average_cost: row.get(8)?,
+            unit: row.get(9)?,
+            sku: row.get(10)?,
+            barcode: row.get(11)?,
+            category: row.get(12)?,
+            is_active: row.get::<_, Option<i32>>(13)?.unwrap_or(1) != 0,
+        })
+    }
+
+    pub fn get_products(&self) -> SqlResult<Vec<Product>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM products ORDER BY name", Self::PRODUCT_COLUMNS))?;
+        let rows = stmt.query_map([], Self::product_from_row)?;
+        rows.collect()
+    }
+
+    pub fn get_product(&self, id: i64) -> SqlResult<Product> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {} FROM products WHERE id=?1", Self::PRODUCT_COLUMNS),
+            params![id],
+            Self::product_from_row,
+        )
+    }
+
+    /// Looks up an active product by exact barcode or case-insensitive SKU, as typed
+    /// by a keyboard-wedge scanner.
+    pub fn find_product_by_code(&self, code: &str) -> SqlResult<Option<Product>> {
+        let conn = self.conn.lock().unwrap();
+        let result = conn.query_row(
+            &format!(
+                "SELECT {} FROM products
+                 WHERE COALESCE(is_active, 1) = 1 AND (barcode = ?1 OR sku = ?1 COLLATE NOCASE)
+                 ORDER BY CASE WHEN barcode = ?1 THEN 0 ELSE 1 END LIMIT 1",
+                Self::PRODUCT_COLUMNS
+            ),
+            params![code],
+            Self::product_from_row,
+        );
+        match result {
+            Ok(p) => Ok(Some(p)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// True when another product already uses `code` as its SKU or barcode.
+    pub fn product_code_in_use(&self, code: &str, exclude_id: Option<i64>) -> SqlResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM products
+             WHERE (sku = ?1 COLLATE NOCASE OR barcode = ?1) AND (?2 IS NULL OR id != ?2)",
+            params![code, exclude_id],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    pub fn create_product(&self, p: &Product, location_id: i64, user_id: Option<i64>) -> SqlResult<i64> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        tx.execute(
+            "INSERT INTO products (name, description, unit_price, tax_percent, track_stock, reorder_level, unit,
+                                   sku, barcode, category, is_active)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                p.name, p.description, p.unit_price, p.tax_percent, p.track_stock as i32, p.reorder_level, p.unit,
+                p.sku, p.barcode, p.category, p.is_active as i32
+            ],
+        )?;
+        let product_id = tx.last_insert_rowid();
+
+        // Opening balance goes through the ledger so on-hand always equals the movement sum
+        if p.track_stock && p.stock_on_hand != 0.0 {
+            Self::record_stock_movement_inner(&tx, &StockMovement {
+                id: None,
+                product_id,
+                product_name: None,
+                movement_type: "Opening".to_string(),
+                quantity: p.stock_on_hand,
+                unit_cost: p.average_cost,
+                location_id,
+                location_name: None,
+                invoice_id: None,
+                receipt_id: None,
+                transfer_id: None,
+                notes: None,
+                user_id,
+                created_at: None,
+            })?;
+        }
+
+        tx.commit()?;
+        Ok(product_id)
     }
 
     pub fn update_product(&self, p: &Product) -> SqlResult<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "UPDATE products SET name=?1, description=?2, unit_price=?3, tax_percent=?4 WHERE id=?5",
-            params![p.name, p.description, p.unit_price, p.tax_percent, p.id],
+            "UPDATE products SET name=?1, description=?2, unit_price=?3, tax_percent=?4, track_stock=?5, reorder_level=?6, unit=?7,
+                                 sku=?8, barcode=?9, category=?10, is_active=?11
+             WHERE id=?12",
+            params![
+                p.name, p.description, p.unit_price, p.tax_percent, p.track_stock as i32, p.reorder_level, p.unit,
+                p.sku, p.barcode, p.category, p.is_active as i32, p.id
+            ],
         )?;
         Ok(())
     }
@@ -467,7 +1452,7 @@ impl AppDb {
         let mut stmt = conn.prepare(
             "SELECT i.id, i.invoice_number, i.customer_id, c.name, c.phone, i.status,
                     i.issue_date, i.due_date, i.notes, i.subtotal, i.tax, i.discount,
-                    i.discount_percent, i.advance, i.total, i.created_at
+                    i.discount_percent, i.advance, i.total, i.location_id, i.created_at
              FROM invoices i
              LEFT JOIN customers c ON i.customer_id = c.id
              ORDER BY i.id DESC"
@@ -489,8 +1474,10 @@ impl AppDb {
                 discount_percent: row.get(12)?,
                 advance: row.get(13)?,
                 total: row.get(14)?,
-                created_at: row.get(15)?,
+                location_id: row.get(15)?,
+                created_at: row.get(16)?,
                 items: None,
+                charges: None,
             })
         })?;
         rows.collect()
@@ -501,7 +1488,7 @@ impl AppDb {
         let mut inv = conn.query_row(
             "SELECT i.id, i.invoice_number, i.customer_id, c.name, c.phone, i.status,
                     i.issue_date, i.due_date, i.notes, i.subtotal, i.tax, i.discount,
-                    i.discount_percent, i.advance, i.total, i.created_at
+                    i.discount_percent, i.advance, i.total, i.location_id, i.created_at
              FROM invoices i
              LEFT JOIN customers c ON i.customer_id = c.id
              WHERE i.id=?1",
@@ -523,14 +1510,17 @@ impl AppDb {
                     discount_percent: row.get(12)?,
                     advance: row.get(13)?,
                     total: row.get(14)?,
-                    created_at: row.get(15)?,
+                    location_id: row.get(15)?,
+                    created_at: row.get(16)?,
                     items: None,
+                    charges: None,
                 })
             },
         )?;
 
         let mut stmt = conn.prepare(
-            "SELECT id, invoice_id, product_name, description, quantity, unit_price, tax_percent, line_total
+            "SELECT id, invoice_id, product_id, product_name, description, quantity, unit, unit_price,
+                    COALESCE(discount, 0), COALESCE(discount_percent, 0), tax_percent, line_total
              FROM invoice_items WHERE invoice_id=?1"
         )?;
         let items: Vec<InvoiceItem> = stmt
@@ -538,43 +1528,83 @@ impl AppDb {
                 Ok(InvoiceItem {
                     id: row.get(0)?,
                     invoice_id: row.get(1)?,
-                    product_name: row.get(2)?,
-                    description: row.get(3)?,
-                    quantity: row.get(4)?,
-                    unit_price: row.get(5)?,
-                    tax_percent: row.get(6)?,
-                    line_total: row.get(7)?,
+                    product_id: row.get(2)?,
+                    product_name: row.get(3)?,
+                    description: row.get(4)?,
+                    quantity: row.get(5)?,
+                    unit: row.get(6)?,
+                    unit_price: row.get(7)?,
+                    discount: row.get(8)?,
+                    discount_percent: row.get(9)?,
+                    tax_percent: row.get(10)?,
+                    line_total: row.get(11)?,
+                })
+            })?
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, invoice_id, description, amount, tax_percent, line_total
+             FROM invoice_charges WHERE invoice_id=?1 ORDER BY id"
+        )?;
+        let charges: Vec<InvoiceCharge> = stmt
+            .query_map(params![id], |row| {
+                Ok(InvoiceCharge {
+                    id: row.get(0)?,
+                    invoice_id: row.get(1)?,
+                    description: row.get(2)?,
+                    amount: row.get(3)?,
+                    tax_percent: row.get(4)?,
+                    line_total: row.get(5)?,
                 })
             })?
             .collect::<SqlResult<Vec<_>>>()?;
 
         inv.items = Some(items);
+        inv.charges = Some(charges);
         Ok(inv)
     }
 
-    pub fn create_invoice(&self, inv: &Invoice, items: &[InvoiceItem]) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
+    /// Discount amount and taxable (discounted) amount of a line.
+    fn line_amounts(item: &InvoiceItem) -> (f64, f64) {
+        let base = item.unit_price * item.quantity;
+        let discount = if item.discount_percent > 0.0 {
+            base * item.discount_percent / 100.0
+        } else {
+            item.discount.min(base)
+        };
+        (discount, base - discount)
+    }
+
+    pub fn create_invoice(&self, inv: &Invoice, items: &[InvoiceItem], charges: &[InvoiceCharge], user_id: Option<i64>) -> SqlResult<i64> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let conn = conn_mu.transaction()?;
         let inv_num = self.next_invoice_number_inner(&conn)?;
 
-        // Calculate totals from items
+        // Calculate totals from items, net of line discounts
         let mut subtotal = 0.0_f64;
         let mut tax_total = 0.0_f64;
         for item in items {
-            let base = item.unit_price * item.quantity as f64;
-            subtotal += base;
-            tax_total += base * item.tax_percent / 100.0;
+            let (_, net) = Self::line_amounts(item);
+            subtotal += net;
+            tax_total += net * item.tax_percent / 100.0;
         }
-        // Apply discount percent then subtract advance
+        // Apply discount percent to the goods, then add charges and subtract advance
         let discount_amount = if inv.discount_percent > 0.0 {
             (subtotal + tax_total) * inv.discount_percent / 100.0
         } else {
             inv.discount
         };
-        let total = subtotal + tax_total - discount_amount - inv.advance;
+        // Charges are never discounted and carry their own tax rate
+        let mut charges_total = 0.0_f64;
+        for charge in charges {
+            charges_total += charge.amount;
+            tax_total += charge.amount * charge.tax_percent / 100.0;
+        }
+        let total = subtotal + charges_total + tax_total - discount_amount - inv.advance;
 
         conn.execute(
-            "INSERT INTO invoices (invoice_number, customer_id, status, issue_date, due_date, notes, subtotal, tax, discount, discount_percent, advance, total)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            "INSERT INTO invoices (invoice_number, customer_id, status, issue_date, due_date, notes, subtotal, tax, discount, discount_percent, advance, total, location_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
             params![
                 inv_num,
                 inv.customer_id,
@@ -588,29 +1618,69 @@ impl AppDb {
                 inv.discount_percent,
                 inv.advance,
                 total,
+                inv.location_id,
             ],
         )?;
         let invoice_id = conn.last_insert_rowid();
 
+        for charge in charges {
+            let line_total = charge.amount + charge.amount * charge.tax_percent / 100.0;
+            conn.execute(
+                "INSERT INTO invoice_charges (invoice_id, description, amount, tax_percent, line_total)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![invoice_id, charge.description, charge.amount, charge.tax_percent, line_total],
+            )?;
+        }
+
         for item in items {
-            let base = item.unit_price * item.quantity as f64;
-            let item_tax = base * item.tax_percent / 100.0;
-            let line_total = base + item_tax;
+            let (line_discount, net) = Self::line_amounts(item);
+            let item_tax = net * item.tax_percent / 100.0;
+            let line_total = net + item_tax;
             conn.execute(
-                "INSERT INTO invoice_items (invoice_id, product_name, description, quantity, unit_price, tax_percent, line_total)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                "INSERT INTO invoice_items (invoice_id, product_id, product_name, description, quantity, unit, unit_price,
+                                            discount, discount_percent, tax_percent, line_total)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
                 params![
                     invoice_id,
+                    item.product_id,
                     item.product_name,
                     item.description,
                     item.quantity,
+                    item.unit,
                     item.unit_price,
+                    line_discount,
+                    item.discount_percent,
                     item.tax_percent,
                     line_total,
                 ],
             )?;
+
+            if let Some(product_id) = item.product_id {
+                let item_id = conn.last_insert_rowid();
+                let cost_total = Self::record_stock_movement_inner(&conn, &StockMovement {
+                    id: None,
+                    product_id,
+                    product_name: None,
+                    movement_type: "Sale".to_string(),
+                    quantity: -item.quantity,
+                    unit_cost: 0.0,
+                    location_id: inv.location_id.unwrap_or(1),
+                    location_name: None,
+                    invoice_id: Some(invoice_id),
+                    receipt_id: None,
+                    transfer_id: None,
+                    notes: Some(inv_num.clone()),
+                    user_id,
+                    created_at: None,
+                })?;
+                conn.execute(
+                    "UPDATE invoice_items SET cost_total=?1 WHERE id=?2",
+                    params![cost_total, item_id],
+                )?;
+            }
         }
 
+        conn.commit()?;
         Ok(invoice_id)
     }
 
@@ -632,70 +1702,632 @@ impl AppDb {
         Ok(())
     }
 
-    pub fn delete_invoice(&self, id: i64) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM invoice_items WHERE invoice_id=?1", params![id])?;
-        conn.execute("DELETE FROM invoices WHERE id=?1", params![id])?;
+    pub fn delete_invoice(&self, id: i64, user_id: Option<i64>) -> SqlResult<()> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+
+        // Put back whatever this invoice still holds out of stock (sales net of returns)
+        let inv_num: Option<String> = tx.query_row(
+            "SELECT invoice_number FROM invoices WHERE id=?1",
+            params![id],
+            |row| row.get(0),
+        ).unwrap_or(None);
+        let outstanding: Vec<(i64, i64, f64)> = tx.prepare(
+            "SELECT product_id, location_id, -SUM(quantity) FROM stock_movements
+             WHERE invoice_id=?1 GROUP BY product_id, location_id HAVING SUM(quantity) < 0"
+        )?
+            .query_map(params![id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        let note = format!("Deleted invoice {}", inv_num.as_deref().unwrap_or("-"));
+        for (product_id, location_id, qty) in outstanding {
+            let unit_cost = Self::sale_unit_cost_inner(&tx, id, product_id)?;
+            Self::record_stock_movement_inner(&tx, &StockMovement {
+                id: None,
+                product_id,
+                product_name: None,
+                movement_type: "Reversal".to_string(),
+                quantity: qty,
+                unit_cost,
+                location_id,
+                location_name: None,
+                invoice_id: Some(id),
+                receipt_id: None,
+                transfer_id: None,
+                notes: Some(note.clone()),
+                user_id,
+                created_at: None,
+            })?;
+        }
+
+        tx.execute("DELETE FROM invoice_items WHERE invoice_id=?1", params![id])?;
+        tx.execute("DELETE FROM invoice_charges WHERE invoice_id=?1", params![id])?;
+        tx.execute("DELETE FROM invoices WHERE id=?1", params![id])?;
+        tx.commit()?;
         Ok(())
     }
 
-    // ── Categories ─────────────────────────────────────────
+    // ── Inventory ──────────────────────────────────────────
+
+    /// Appends a ledger entry, moves `stock_on_hand` (overall and at the movement's
+    /// location) and keeps the cost layers and running average in step. Incoming stock
+    /// is costed at `m.unit_cost`; outgoing stock is costed by the configured valuation
+    /// method. Transfers only move quantity between locations and carry no cost.
+    /// Returns the total cost of the movement. Untracked products are ignored so
+    /// callers can pass every invoice line.
+    fn record_stock_movement_inner(conn: &Connection, m: &StockMovement) -> SqlResult<f64> {
+        let product = conn.query_row(
+            "SELECT track_stock, stock_on_hand, average_cost FROM products WHERE id=?1",
+            params![m.product_id],
+            |row| Ok((row.get::<_, i32>(0)?, row.get::<_, f64>(1)?, row.get::<_, f64>(2)?)),
+        );
+        let (on_hand, average_cost) = match product {
+            Ok((tracked, on_hand, avg)) if tracked != 0 => (on_hand, avg),
+            Ok(_) | Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(0.0),
+            Err(e) => return Err(e),
+        };
+        let is_transfer = m.movement_type == "TransferOut" || m.movement_type == "TransferIn";
 
-    pub fn get_categories(&self) -> SqlResult<Vec<Category>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT id, name, category_type FROM categories ORDER BY name")?;
-        let rows = stmt.query_map([], |row| {
-            Ok(Category {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                category_type: row.get(2)?,
-            })
-        })?;
-        rows.collect()
-    }
+        let (unit_cost, total_cost) = if is_transfer {
+            (average_cost, 0.0)
+        } else if m.quantity >= 0.0 {
+            (m.unit_cost, m.quantity * m.unit_cost)
+        } else {
+            let method: String = conn.query_row(
+                "SELECT COALESCE(inventory_valuation_method, 'FIFO') FROM settings WHERE id=1",
+                [],
+                |row| row.get(0),
+            )?;
+
+            // Layers are consumed under both methods so switching method later stays consistent
+            let mut remaining = -m.quantity;
+            let mut fifo_cost = 0.0_f64;
+            let layers: Vec<(i64, f64, f64)> = conn.prepare(
+                "SELECT id, quantity_remaining, unit_cost FROM cost_layers
+                 WHERE product_id=?1 AND quantity_remaining > 0 ORDER BY id"
+            )?
+                .query_map(params![m.product_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect::<SqlResult<Vec<_>>>()?;
+            for (layer_id, layer_qty, layer_cost) in layers {
+                if remaining <= 0.0 { break; }
+                let take = layer_qty.min(remaining);
+                fifo_cost += take * layer_cost;
+                remaining -= take;
+                conn.execute(
+                    "UPDATE cost_layers SET quantity_remaining = quantity_remaining - ?1 WHERE id = ?2",
+                    params![take, layer_id],
+                )?;
+            }
+            // Anything sold beyond the recorded layers is costed at the running average
+            fifo_cost += remaining * average_cost;
+
+            let total = if method == "WeightedAverage" { -m.quantity * average_cost } else { fifo_cost };
+            (total / -m.quantity, total)
+        };
 
-    pub fn create_category(&self, c: &Category) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO categories (name, category_type) VALUES (?1, ?2)",
-            params![c.name, c.category_type],
+            "INSERT INTO stock_movements (product_id, movement_type, quantity, unit_cost, location_id, invoice_id, receipt_id, transfer_id, notes, user_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![m.product_id, m.movement_type, m.quantity, unit_cost, m.location_id, m.invoice_id, m.receipt_id, m.transfer_id, m.notes, m.user_id],
         )?;
-        Ok(conn.last_insert_rowid())
+        let movement_id = conn.last_insert_rowid();
+
+        conn.execute(
+            "INSERT INTO product_stock (product_id, location_id, quantity) VALUES (?1, ?2, ?3)
+             ON CONFLICT(product_id, location_id) DO UPDATE SET quantity = quantity + excluded.quantity",
+            params![m.product_id, m.location_id, m.quantity],
+        )?;
+        if is_transfer {
+            return Ok(total_cost);
+        }
+
+        if m.quantity > 0.0 {
+            // Stock sold beyond the layers was already costed at the average, so a
+            // receipt first makes up that shortfall and only the rest opens a layer.
+            let layered: f64 = conn.query_row(
+                "SELECT COALESCE(SUM(quantity_remaining), 0) FROM cost_layers WHERE product_id=?1",
+                params![m.product_id],
+                |row| row.get(0),
+            )?;
+            let layer_qty = m.quantity.min(on_hand + m.quantity - layered);
+            if layer_qty > 0.0 {
+                conn.execute(
+                    "INSERT INTO cost_layers (product_id, movement_id, quantity_remaining, unit_cost) VALUES (?1, ?2, ?3, ?4)",
+                    params![m.product_id, movement_id, layer_qty, unit_cost],
+                )?;
+            }
+            let held = on_hand.max(0.0);
+            let new_average = (held * average_cost + m.quantity * unit_cost) / (held + m.quantity);
+            conn.execute(
+                "UPDATE products SET average_cost = ?1 WHERE id = ?2",
+                params![new_average, m.product_id],
+            )?;
+        }
+        conn.execute(
+            "UPDATE products SET stock_on_hand = stock_on_hand + ?1 WHERE id = ?2",
+            params![m.quantity, m.product_id],
+        )?;
+        Ok(total_cost)
     }
 
-    pub fn delete_category(&self, id: i64) -> SqlResult<()> {
+    /// Average cost per unit at which a product left stock on an invoice, used to
+    /// bring returns and reversals back in at the value they went out at.
+    fn sale_unit_cost_inner(conn: &Connection, invoice_id: i64, product_id: i64) -> SqlResult<f64> {
+        conn.query_row(
+            "SELECT COALESCE(SUM(quantity * unit_cost) / NULLIF(SUM(quantity), 0), 0) FROM stock_movements
+             WHERE invoice_id=?1 AND product_id=?2 AND movement_type = 'Sale'",
+            params![invoice_id, product_id],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn adjust_stock(&self, product_id: i64, location_id: i64, quantity: f64, notes: Option<&str>, user_id: Option<i64>) -> SqlResult<()> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        let average_cost: f64 = tx.query_row(
+            "SELECT average_cost FROM products WHERE id=?1",
+            params![product_id],
+            |row| row.get(0),
+        )?;
+        Self::record_stock_movement_inner(&tx, &StockMovement {
+            id: None,
+            product_id,
+            product_name: None,
+            movement_type: "Adjustment".to_string(),
+            quantity,
+            unit_cost: average_cost,
+            location_id,
+            location_name: None,
+            invoice_id: None,
+            receipt_id: None,
+            transfer_id: None,
+            notes: notes.map(|n| n.to_string()),
+            user_id,
+            created_at: None,
+        })?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Quantity of a product sold on an invoice that has not been returned yet.
+    pub fn returnable_quantity(&self, invoice_id: i64, product_id: i64) -> SqlResult<f64> {
         let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM categories WHERE id=?1", params![id])?;
+        conn.query_row(
+            "SELECT COALESCE(-SUM(quantity), 0) FROM stock_movements
+             WHERE invoice_id=?1 AND product_id=?2 AND movement_type IN ('Sale', 'Return')",
+            params![invoice_id, product_id],
+            |row| row.get(0),
+        )
+    }
+
+    /// Returned goods go back to the location the invoice was fulfilled from.
+    pub fn record_stock_return(&self, invoice_id: i64, product_id: i64, quantity: f64, notes: Option<&str>, user_id: Option<i64>) -> SqlResult<()> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        let unit_cost = Self::sale_unit_cost_inner(&tx, invoice_id, product_id)?;
+        let location_id: i64 = tx.query_row(
+            "SELECT COALESCE(location_id, 1) FROM invoices WHERE id=?1",
+            params![invoice_id],
+            |row| row.get(0),
+        )?;
+        Self::record_stock_movement_inner(&tx, &StockMovement {
+            id: None,
+            product_id,
+            product_name: None,
+            movement_type: "Return".to_string(),
+            quantity,
+            unit_cost,
+            location_id,
+            location_name: None,
+            invoice_id: Some(invoice_id),
+            receipt_id: None,
+            transfer_id: None,
+            notes: notes.map(|n| n.to_string()),
+            user_id,
+            created_at: None,
+        })?;
+        tx.commit()?;
         Ok(())
     }
 
-    // ── Accounts ───────────────────────────────────────────
+    pub fn create_purchase_receipt(&self, r: &PurchaseReceipt, user_id: Option<i64>) -> SqlResult<i64> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        tx.execute(
+            "INSERT INTO purchase_receipts (product_id, location_id, quantity, unit_cost, supplier, reference, received_date, notes, user_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![r.product_id, r.location_id, r.quantity, r.unit_cost, r.supplier, r.reference, r.received_date, r.notes, user_id],
+        )?;
+        let receipt_id = tx.last_insert_rowid();
+
+        Self::record_stock_movement_inner(&tx, &StockMovement {
+            id: None,
+            product_id: r.product_id,
+            product_name: None,
+            movement_type: "Purchase".to_string(),
+            quantity: r.quantity,
+            unit_cost: r.unit_cost,
+            location_id: r.location_id,
+            location_name: None,
+            invoice_id: None,
+            receipt_id: Some(receipt_id),
+            transfer_id: None,
+            notes: r.reference.clone(),
+            user_id,
+            created_at: None,
+        })?;
 
-    pub fn get_accounts(&self) -> SqlResult<Vec<Account>> {
+        tx.commit()?;
+        Ok(receipt_id)
+    }
+
+    pub fn get_purchase_receipts(&self, product_id: Option<i64>) -> SqlResult<Vec<PurchaseReceipt>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT id, name, account_type, balance, currency FROM accounts")?;
-        let rows = stmt.query_map([], |row| {
-            Ok(Account {
+        let mut stmt = conn.prepare(
+            "SELECT r.id, r.product_id, p.name, r.location_id, r.quantity, r.unit_cost, r.supplier, r.reference, r.received_date, r.notes, r.created_at
+             FROM purchase_receipts r
+             LEFT JOIN products p ON r.product_id = p.id
+             WHERE ?1 IS NULL OR r.product_id = ?1
+             ORDER BY r.received_date DESC, r.id DESC"
+        )?;
+        let rows = stmt.query_map(params![product_id], |row| {
+            Ok(PurchaseReceipt {
                 id: row.get(0)?,
-                name: row.get(1)?,
-                account_type: row.get(2)?,
-                balance: row.get(3)?,
-                currency: row.get(4)?,
+                product_id: row.get(1)?,
+                product_name: row.get(2)?,
+                location_id: row.get(3)?,
+                quantity: row.get(4)?,
+                unit_cost: row.get(5)?,
+                supplier: row.get(6)?,
+                reference: row.get(7)?,
+                received_date: row.get(8)?,
+                notes: row.get(9)?,
+                created_at: row.get(10)?,
             })
         })?;
         rows.collect()
     }
 
-    pub fn create_account(&self, a: &Account) -> SqlResult<i64> {
+    pub fn get_stock_movements(&self, product_id: Option<i64>, location_id: Option<i64>, limit: i64) -> SqlResult<Vec<StockMovement>> {
         let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "INSERT INTO accounts (name, account_type, balance, currency) VALUES (?1, ?2, ?3, ?4)",
-            params![a.name, a.account_type, a.balance, a.currency],
+        let mut stmt = conn.prepare(
+            "SELECT m.id, m.product_id, p.name, m.movement_type, m.quantity, m.unit_cost, m.location_id, l.name,
+                    m.invoice_id, m.receipt_id, m.transfer_id, m.notes, m.user_id, m.created_at
+             FROM stock_movements m
+             LEFT JOIN products p ON m.product_id = p.id
+             LEFT JOIN stock_locations l ON m.location_id = l.id
+             WHERE (?1 IS NULL OR m.product_id = ?1) AND (?2 IS NULL OR m.location_id = ?2)
+             ORDER BY m.id DESC LIMIT ?3"
         )?;
-        Ok(conn.last_insert_rowid())
-    }
-
-    // ── Transactions ───────────────────────────────────────
+        let rows = stmt.query_map(params![product_id, location_id, limit], |row| {
+            Ok(StockMovement {
+                id: row.get(0)?,
+                product_id: row.get(1)?,
+                product_name: row.get(2)?,
+                movement_type: row.get(3)?,
+                quantity: row.get(4)?,
+                unit_cost: row.get(5)?,
+                location_id: row.get(6)?,
+                location_name: row.get(7)?,
+                invoice_id: row.get(8)?,
+                receipt_id: row.get(9)?,
+                transfer_id: row.get(10)?,
+                notes: row.get(11)?,
+                user_id: row.get(12)?,
+                created_at: row.get(13)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    pub fn get_stock_report(&self) -> SqlResult<StockReport> {
+        let conn = self.conn.lock().unwrap();
+        let valuation_method: String = conn.query_row(
+            "SELECT COALESCE(inventory_valuation_method, 'FIFO') FROM settings WHERE id=1",
+            [],
+            |row| row.get(0),
+        )?;
+        let mut stmt = conn.prepare(
+            "SELECT p.id, p.name, p.stock_on_hand, p.reorder_level, p.average_cost,
+                    (SELECT COALESCE(SUM(l.quantity_remaining * l.unit_cost), 0) FROM cost_layers l WHERE l.product_id = p.id)
+             FROM products p
+             WHERE p.track_stock = 1 ORDER BY p.name"
+        )?;
+        let items: Vec<StockLevel> = stmt.query_map([], |row| {
+            let stock_on_hand: f64 = row.get(2)?;
+            let reorder_level: f64 = row.get(3)?;
+            let average_cost: f64 = row.get(4)?;
+            let fifo_value: f64 = row.get(5)?;
+            let stock_value = if valuation_method == "WeightedAverage" {
+                stock_on_hand.max(0.0) * average_cost
+            } else {
+                fifo_value
+            };
+            Ok(StockLevel {
+                product_id: row.get(0)?,
+                product_name: row.get(1)?,
+                stock_on_hand,
+                reorder_level,
+                unit_cost: if stock_on_hand > 0.0 { stock_value / stock_on_hand } else { average_cost },
+                stock_value,
+                is_low: stock_on_hand <= reorder_level,
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+
+        let total_value = items.iter().map(|i| i.stock_value).sum();
+        let low_stock = items.iter().filter(|i| i.is_low).cloned().collect();
+        Ok(StockReport { valuation_method, total_value, items, low_stock })
+    }
+
+    // ── Stock Locations ────────────────────────────────────
+
+    pub fn get_stock_locations(&self) -> SqlResult<Vec<StockLocation>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, name, address, is_active FROM stock_locations ORDER BY id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(StockLocation {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                address: row.get(2)?,
+                is_active: row.get::<_, i32>(3)? != 0,
+            })
+        })?;
+        rows.collect()
+    }
+
+    pub fn create_stock_location(&self, l: &StockLocation) -> SqlResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO stock_locations (name, address, is_active) VALUES (?1, ?2, ?3)",
+            params![l.name, l.address, l.is_active as i32],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn update_stock_location(&self, l: &StockLocation) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE stock_locations SET name=?1, address=?2, is_active=?3 WHERE id=?4",
+            params![l.name, l.address, l.is_active as i32, l.id],
+        )?;
+        Ok(())
+    }
+
+    /// Returns `Some(is_active)` for an existing location, `None` if it does not exist.
+    pub fn stock_location_status(&self, location_id: i64) -> SqlResult<Option<bool>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT is_active FROM stock_locations WHERE id=?1",
+            params![location_id],
+            |row| row.get::<_, i32>(0),
+        ) {
+            Ok(active) => Ok(Some(active != 0)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Per-location quantities of tracked products, optionally for a single location.
+    pub fn get_location_stock(&self, location_id: Option<i64>) -> SqlResult<Vec<LocationStock>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT l.id, l.name, p.id, p.name, s.quantity
+             FROM product_stock s
+             JOIN stock_locations l ON s.location_id = l.id
+             JOIN products p ON s.product_id = p.id
+             WHERE p.track_stock = 1 AND (?1 IS NULL OR s.location_id = ?1)
+             ORDER BY l.name, p.name"
+        )?;
+        let rows = stmt.query_map(params![location_id], |row| {
+            Ok(LocationStock {
+                location_id: row.get(0)?,
+                location_name: row.get(1)?,
+                product_id: row.get(2)?,
+                product_name: row.get(3)?,
+                quantity: row.get(4)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Location stock is drawn from when none is given: the user's own default,
+    /// then the company default from Settings, then the seeded "Main" location.
+    pub fn default_location_for_user(&self, user_id: Option<i64>) -> SqlResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT COALESCE(
+                (SELECT default_location_id FROM users WHERE id = ?1),
+                (SELECT default_location_id FROM settings WHERE id = 1),
+                1)",
+            params![user_id],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn set_user_default_location(&self, user_id: i64, location_id: Option<i64>) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE users SET default_location_id=?1 WHERE id=?2",
+            params![location_id, user_id],
+        )?;
+        Ok(())
+    }
+
+    /// Moves stock between locations. Availability at the source is checked in
+    /// the same transaction as the moves, so concurrent transfers cannot both
+    /// draw on the same stock.
+    pub fn create_stock_transfer(&self, t: &StockTransfer, items: &[StockTransferItem], user_id: Option<i64>) -> Result<i64, String> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction().map_err(|e| e.to_string())?;
+        for item in items {
+            let requested: f64 = items.iter()
+                .filter(|i| i.product_id == item.product_id)
+                .map(|i| i.quantity)
+                .sum();
+            let (name, available): (String, f64) = tx.query_row(
+                "SELECT p.name, COALESCE((SELECT SUM(quantity) FROM product_stock WHERE product_id = p.id AND location_id = ?2), 0)
+                 FROM products p WHERE p.id = ?1",
+                params![item.product_id, t.from_location_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            ).map_err(|e| e.to_string())?;
+            if requested > available {
+                return Err(format!("Only {} unit(s) of {} available at the source location", available, name));
+            }
+        }
+        let transfer_id = Self::insert_stock_transfer_inner(&tx, t, items, user_id).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(transfer_id)
+    }
+
+    fn insert_stock_transfer_inner(conn: &Connection, t: &StockTransfer, items: &[StockTransferItem], user_id: Option<i64>) -> SqlResult<i64> {
+        let next: i64 = conn.query_row("SELECT COALESCE(MAX(id), 0) + 1 FROM stock_transfers", [], |row| row.get(0))?;
+        let transfer_number = format!("TRF-{:05}", next);
+        conn.execute(
+            "INSERT INTO stock_transfers (transfer_number, from_location_id, to_location_id, transfer_date, notes, user_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![transfer_number, t.from_location_id, t.to_location_id, t.transfer_date, t.notes, user_id],
+        )?;
+        let transfer_id = conn.last_insert_rowid();
+
+        for item in items {
+            conn.execute(
+                "INSERT INTO stock_transfer_items (transfer_id, product_id, quantity) VALUES (?1, ?2, ?3)",
+                params![transfer_id, item.product_id, item.quantity],
+            )?;
+            for (movement_type, location_id, quantity) in [
+                ("TransferOut", t.from_location_id, -item.quantity),
+                ("TransferIn", t.to_location_id, item.quantity),
+            ] {
+                Self::record_stock_movement_inner(conn, &StockMovement {
+                    id: None,
+                    product_id: item.product_id,
+                    product_name: None,
+                    movement_type: movement_type.to_string(),
+                    quantity,
+                    unit_cost: 0.0,
+                    location_id,
+                    location_name: None,
+                    invoice_id: None,
+                    receipt_id: None,
+                    transfer_id: Some(transfer_id),
+                    notes: Some(transfer_number.clone()),
+                    user_id,
+                    created_at: None,
+                })?;
+            }
+        }
+        Ok(transfer_id)
+    }
+
+    pub fn get_stock_transfers(&self) -> SqlResult<Vec<StockTransfer>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT t.id, t.transfer_number, t.from_location_id, f.name, t.to_location_id, d.name,
+                    t.transfer_date, t.notes, t.user_id, u.username, t.created_at
+             FROM stock_transfers t
+             LEFT JOIN stock_locations f ON t.from_location_id = f.id
+             LEFT JOIN stock_locations d ON t.to_location_id = d.id
+             LEFT JOIN users u ON t.user_id = u.id
+             ORDER BY t.id DESC"
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(StockTransfer {
+                id: row.get(0)?,
+                transfer_number: row.get(1)?,
+                from_location_id: row.get(2)?,
+                from_location_name: row.get(3)?,
+                to_location_id: row.get(4)?,
+                to_location_name: row.get(5)?,
+                transfer_date: row.get(6)?,
+                notes: row.get(7)?,
+                user_id: row.get(8)?,
+                username: row.get(9)?,
+                created_at: row.get(10)?,
+                items: None,
+            })
+        })?;
+        rows.collect()
+    }
+
+    pub fn get_stock_transfer_detail(&self, id: i64) -> SqlResult<StockTransfer> {
+        let mut transfer = self.get_stock_transfers()?
+            .into_iter()
+            .find(|t| t.id == Some(id))
+            .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT i.product_id, p.name, i.quantity
+             FROM stock_transfer_items i
+             LEFT JOIN products p ON i.product_id = p.id
+             WHERE i.transfer_id = ?1 ORDER BY i.id"
+        )?;
+        let items = stmt.query_map(params![id], |row| {
+            Ok(StockTransferItem {
+                product_id: row.get(0)?,
+                product_name: row.get(1)?,
+                quantity: row.get(2)?,
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+        transfer.items = Some(items);
+        Ok(transfer)
+    }
+
+    // ── Categories ─────────────────────────────────────────
+
+    pub fn get_categories(&self) -> SqlResult<Vec<Category>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, name, category_type FROM categories ORDER BY name")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Category {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                category_type: row.get(2)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    pub fn create_category(&self, c: &Category) -> SqlResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO categories (name, category_type) VALUES (?1, ?2)",
+            params![c.name, c.category_type],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn delete_category(&self, id: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM categories WHERE id=?1", params![id])?;
+        Ok(())
+    }
+
+    // ── Accounts ───────────────────────────────────────────
+
+    pub fn get_accounts(&self) -> SqlResult<Vec<Account>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, name, account_type, balance, currency FROM accounts")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Account {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                account_type: row.get(2)?,
+                balance: row.get(3)?,
+                currency: row.get(4)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    pub fn create_account(&self, a: &Account) -> SqlResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO accounts (name, account_type, balance, currency) VALUES (?1, ?2, ?3, ?4)",
+            params![a.name, a.account_type, a.balance, a.currency],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    // ── Transactions ───────────────────────────────────────
 
     pub fn get_transactions(&self, limit: i64) -> SqlResult<Vec<Transaction>> {
         let conn = self.conn.lock().unwrap();
@@ -719,6 +2351,30 @@ impl AppDb {
         rows.collect()
     }
 
+    pub fn get_transaction(&self, id: i64) -> SqlResult<Option<Transaction>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            "SELECT id, account_id, category_id, amount, transaction_type, description, date, reference_id, created_at
+             FROM transactions WHERE id = ?1",
+            params![id],
+            |row| Ok(Transaction {
+                id: row.get(0)?,
+                account_id: row.get(1)?,
+                category_id: row.get(2)?,
+                amount: row.get(3)?,
+                transaction_type: row.get(4)?,
+                description: row.get(5)?,
+                date: row.get(6)?,
+                reference_id: row.get(7)?,
+                created_at: row.get(8)?,
+            }),
+        ) {
+            Ok(t) => Ok(Some(t)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     pub fn create_transaction(&self, t: &Transaction) -> SqlResult<i64> {
         let mut conn_mu = self.conn.lock().unwrap();
         let tx = conn_mu.transaction()?;
@@ -742,144 +2398,903 @@ impl AppDb {
         Ok(tx_id)
     }
 
-    // ── Employees & Payroll ────────────────────────────────
+    // ── Employees & Payroll ────────────────────────────────
+
+    const EMPLOYEE_COLUMNS: &'static str =
+        "id, name, role, email, phone, salary, allowances, overtime_rate, standard_hours, shift_start, late_penalty, bank_name, bank_branch, bank_account_no,
+         department, status, join_date, exit_date, pay_frequency, created_at";
+
+    fn employee_from_row(row: &rusqlite::Row) -> SqlResult<Employee> {
+        Ok(Employee {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            role: row.get(2)?,
+            email: row.get(3)?,
+            phone: row.get(4)?,
+            salary: row.get(5)?,
+            allowances: row.get::<_, Option<f64>>(6)?.unwrap_or(0.0),
+            overtime_rate: row.get::<_, Option<f64>>(7)?.unwrap_or(0.0),
+            standard_hours: row.get::<_, Option<f64>>(8)?.unwrap_or(8.0),
+            shift_start: row.get(9)?,
+            late_penalty: row.get::<_, Option<f64>>(10)?.unwrap_or(0.0),
+            bank_name: row.get(11)?,
+            bank_branch: row.get(12)?,
+            bank_account_no: row.get(13)?,
+            department: row.get(14)?,
+            status: row.get::<_, Option<String>>(15)?.unwrap_or_else(|| "Active".to_string()),
+            join_date: row.get(16)?,
+            exit_date: row.get(17)?,
+            pay_frequency: row.get::<_, Option<String>>(18)?.unwrap_or_else(|| "Monthly".to_string()),
+            created_at: row.get(19)?,
+        })
+    }
+
+    pub fn get_employees(&self) -> SqlResult<Vec<Employee>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM employees ORDER BY name", Self::EMPLOYEE_COLUMNS))?;
+        let rows = stmt.query_map([], Self::employee_from_row)?;
+        rows.collect()
+    }
+
+    pub fn get_employee(&self, id: i64) -> SqlResult<Option<Employee>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(
+            &format!("SELECT {} FROM employees WHERE id=?1", Self::EMPLOYEE_COLUMNS),
+            params![id],
+            Self::employee_from_row,
+        ) {
+            Ok(e) => Ok(Some(e)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn create_employee(&self, e: &Employee) -> SqlResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO employees (name, role, email, phone, salary, allowances, overtime_rate, standard_hours, shift_start, late_penalty, bank_name, bank_branch, bank_account_no,
+                                    department, status, join_date, exit_date, pay_frequency)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
+            params![e.name, e.role, e.email, e.phone, e.salary, e.allowances, e.overtime_rate, e.standard_hours, e.shift_start, e.late_penalty, e.bank_name, e.bank_branch, e.bank_account_no,
+                    e.department, e.status, e.join_date, e.exit_date, e.pay_frequency],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn update_employee(&self, e: &Employee) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE employees SET name = ?1, role = ?2, email = ?3, phone = ?4, salary = ?5, allowances = ?6,
+             overtime_rate = ?7, standard_hours = ?8, shift_start = ?9, late_penalty = ?10,
+             bank_name = ?11, bank_branch = ?12, bank_account_no = ?13,
+             department = ?14, status = ?15, join_date = ?16, exit_date = ?17, pay_frequency = ?18 WHERE id = ?19",
+            params![e.name, e.role, e.email, e.phone, e.salary, e.allowances, e.overtime_rate, e.standard_hours, e.shift_start, e.late_penalty,
+                    e.bank_name, e.bank_branch, e.bank_account_no, e.department, e.status, e.join_date, e.exit_date, e.pay_frequency, e.id],
+        )?;
+        Ok(())
+    }
+
+    fn insert_payroll_lines(conn: &Connection, payroll_id: i64, lines: &[PayrollLine]) -> SqlResult<()> {
+        for line in lines {
+            conn.execute(
+                "INSERT INTO payroll_lines (payroll_id, component_id, name, line_type, amount, employer_amount, loan_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![payroll_id, line.component_id, line.name, line.line_type, line.amount, line.employer_amount, line.loan_id],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn insert_payroll_inner(conn: &Connection, p: &PayrollRecord) -> SqlResult<i64> {
+        conn.execute(
+            "INSERT INTO payroll (employee_id, base_salary, overtime_pay, bonuses, allowances, gross_salary, tax, late_penalties, absences, other_deductions, total_deductions, net_pay, pay_period_start, pay_period_end, payment_date, status, notes, employer_contributions, run_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
+            params![p.employee_id, p.base_salary, p.overtime_pay, p.bonuses, p.allowances, p.gross_salary, p.tax, p.late_penalties, p.absences, p.other_deductions, p.total_deductions, p.net_pay, p.pay_period_start, p.pay_period_end, p.payment_date, p.status, p.notes, p.employer_contributions, p.run_id],
+        )?;
+        let payroll_id = conn.last_insert_rowid();
+        Self::insert_payroll_lines(conn, payroll_id, p.lines.as_deref().unwrap_or(&[]))?;
+        Ok(payroll_id)
+    }
+
+    /// Employees who already have payroll, in a run or on its own and not void,
+    /// for a period overlapping `start`..`end`.
+    pub fn employees_paid_in_period(&self, start: &str, end: &str) -> SqlResult<Vec<i64>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT employee_id FROM payroll
+             WHERE status != 'Void' AND pay_period_start <= ?2 AND pay_period_end >= ?1"
+        )?;
+        let rows = stmt.query_map(params![start, end], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    pub fn create_payroll(&self, p: &PayrollRecord) -> SqlResult<i64> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+
+        let payroll_id = Self::insert_payroll_inner(&tx, p)?;
+
+        if p.status == "Paid" {
+            Self::post_payroll_inner(&tx, payroll_id, p.account_id.unwrap_or(1), &p.payment_date)?;
+        }
+
+        tx.commit()?;
+        Ok(payroll_id)
+    }
+
+    /// Pays a Pending record that is not part of a run.
+    pub fn pay_payroll(&self, id: i64, account_id: i64, date: &str) -> SqlResult<()> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        tx.query_row(
+            "SELECT id FROM payroll WHERE id=?1 AND status='Pending' AND run_id IS NULL",
+            params![id],
+            |row| row.get::<_, i64>(0),
+        )?;
+        Self::post_payroll_inner(&tx, id, account_id, date)?;
+        tx.commit()
+    }
+
+    fn salary_category_id(conn: &Connection) -> SqlResult<i64> {
+        match conn.query_row(
+            "SELECT id FROM categories WHERE name='Salary' AND category_type='Expense' ORDER BY id LIMIT 1",
+            [],
+            |row| row.get(0),
+        ) {
+            Ok(id) => Ok(id),
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
+                conn.execute("INSERT INTO categories (name, category_type) VALUES ('Salary', 'Expense')", [])?;
+                Ok(conn.last_insert_rowid())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Records a Salary expense and moves the account balance in one step.
+    fn post_salary_expense(conn: &Connection, account_id: i64, amount: f64, description: &str, date: &str, reference: &str) -> SqlResult<i64> {
+        let category_id = Self::salary_category_id(conn)?;
+        conn.execute(
+            "INSERT INTO transactions (account_id, category_id, amount, transaction_type, description, date, reference_id)
+             VALUES (?1, ?2, ?3, 'Expense', ?4, ?5, ?6)",
+            params![account_id, category_id, amount, description, date, reference],
+        )?;
+        let id = conn.last_insert_rowid();
+        conn.execute("UPDATE accounts SET balance = balance - ?1 WHERE id = ?2", params![amount, account_id])?;
+        Ok(id)
+    }
+
+    /// Posts a paid payroll record: net pay is expensed to Salary from `account_id`,
+    /// and tax withheld, contributions (both shares) and other deduction
+    /// components become outstanding liabilities, expensed when remitted. Loan
+    /// instalments are not liabilities; they repay money the business already lent.
+    fn post_payroll_inner(conn: &Connection, payroll_id: i64, account_id: i64, date: &str) -> SqlResult<()> {
+        let (employee_name, net_pay): (String, f64) = conn.query_row(
+            "SELECT e.name, p.net_pay FROM payroll p JOIN employees e ON p.employee_id = e.id WHERE p.id = ?1",
+            params![payroll_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        conn.execute(
+            "UPDATE payroll SET status='Paid', account_id=?1, payment_date=?2 WHERE id=?3",
+            params![account_id, date, payroll_id],
+        )?;
+        Self::post_salary_expense(
+            conn, account_id, net_pay,
+            &format!("Salary: {}", employee_name), date, &format!("PAY-{}", payroll_id),
+        )?;
+        conn.execute(
+            "INSERT INTO payroll_liabilities (payroll_id, name, employee_amount, employer_amount, amount)
+             SELECT payroll_id, name, amount, employer_amount, amount + employer_amount
+             FROM payroll_lines
+             WHERE payroll_id=?1 AND amount + employer_amount > 0
+               AND (line_type IN ('Tax', 'Contribution') OR (line_type = 'Deduction' AND loan_id IS NULL))",
+            params![payroll_id],
+        )?;
+        Ok(())
+    }
+
+    /// Voids a payroll record. The net pay posting is reversed with a negative
+    /// Salary expense (keeping the original for the audit trail) and outstanding
+    /// liabilities are cancelled. Amounts already remitted stay posted, since that
+    /// money has left the business and must be recovered separately.
+    pub fn void_payroll(&self, id: i64, reason: Option<&str>) -> SqlResult<()> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        Self::void_payroll_inner(&tx, id, reason)?;
+        tx.commit()
+    }
+
+    fn void_payroll_inner(tx: &Connection, id: i64, reason: Option<&str>) -> SqlResult<()> {
+        let postings: Vec<(i64, f64, String, String)> = tx.prepare(
+            "SELECT account_id, amount, COALESCE(description, ''), reference_id FROM transactions
+             WHERE transaction_type='Expense' AND reference_id = 'PAY-' || ?1"
+        )?
+            .query_map(params![id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        for (account_id, amount, description, reference) in postings {
+            Self::post_salary_expense(
+                tx, account_id, -amount,
+                &format!("Reversal of {}", description), &today, &format!("{}-VOID", reference),
+            )?;
+        }
+
+        tx.execute(
+            "UPDATE payroll_liabilities SET status='Cancelled' WHERE payroll_id=?1 AND status='Outstanding'",
+            params![id],
+        )?;
+        tx.execute(
+            "UPDATE payroll SET status='Void', void_reason=?1 WHERE id=?2",
+            params![reason, id],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_payroll_liabilities(&self, status: Option<&str>) -> SqlResult<Vec<PayrollLiability>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT l.id, l.payroll_id, e.name, p.pay_period_end, l.name, l.employee_amount, l.employer_amount, l.amount,
+                    l.status, l.remitted_date, l.account_id, l.reference
+             FROM payroll_liabilities l
+             JOIN payroll p ON l.payroll_id = p.id
+             LEFT JOIN employees e ON p.employee_id = e.id
+             WHERE (?1 IS NULL OR l.status = ?1)
+             ORDER BY p.pay_period_end DESC, l.name, e.name"
+        )?;
+        let rows = stmt.query_map(params![status], |row| {
+            Ok(PayrollLiability {
+                id: row.get(0)?,
+                payroll_id: row.get(1)?,
+                employee_name: row.get(2)?,
+                pay_period_end: row.get(3)?,
+                name: row.get(4)?,
+                employee_amount: row.get(5)?,
+                employer_amount: row.get(6)?,
+                amount: row.get(7)?,
+                status: row.get(8)?,
+                remitted_date: row.get(9)?,
+                account_id: row.get(10)?,
+                reference: row.get(11)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Pays outstanding liabilities from `account_id`, expensing each to Salary.
+    /// Returns the total remitted.
+    pub fn remit_payroll_liabilities(&self, ids: &[i64], account_id: i64, date: &str, reference: Option<&str>) -> SqlResult<f64> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        let mut total = 0.0;
+        for id in ids {
+            let (name, employee_name, amount): (String, String, f64) = tx.query_row(
+                "SELECT l.name, COALESCE(e.name, ''), l.amount FROM payroll_liabilities l
+                 JOIN payroll p ON l.payroll_id = p.id
+                 LEFT JOIN employees e ON p.employee_id = e.id
+                 WHERE l.id=?1 AND l.status='Outstanding'",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )?;
+            let description = match reference {
+                Some(r) => format!("{} remittance: {} ({})", name, employee_name, r),
+                None => format!("{} remittance: {}", name, employee_name),
+            };
+            let tx_id = Self::post_salary_expense(&tx, account_id, amount, &description, date, &format!("PAYL-{}", id))?;
+            tx.execute(
+                "UPDATE payroll_liabilities SET status='Remitted', remitted_date=?1, account_id=?2, transaction_id=?3, reference=?4
+                 WHERE id=?5",
+                params![date, account_id, tx_id, reference, id],
+            )?;
+            total += amount;
+        }
+        tx.commit()?;
+        Ok(total)
+    }
+
+    const PAYROLL_SELECT: &'static str =
+        "SELECT p.id, p.employee_id, e.name, e.role, p.base_salary, p.overtime_pay, p.bonuses, p.allowances, p.gross_salary, p.tax, p.late_penalties, p.absences, p.other_deductions, p.total_deductions, p.net_pay, p.pay_period_start, p.pay_period_end, p.payment_date, p.status, p.notes, p.employer_contributions, p.account_id, p.run_id
+         FROM payroll p LEFT JOIN employees e ON p.employee_id = e.id";
+
+    fn payroll_from_row(row: &rusqlite::Row) -> SqlResult<PayrollRecord> {
+        Ok(PayrollRecord {
+            id: row.get(0)?,
+            employee_id: row.get(1)?,
+            employee_name: row.get(2)?,
+            employee_role: row.get(3)?,
+            base_salary: row.get::<_, f64>(4).unwrap_or(0.0),
+            overtime_pay: row.get::<_, f64>(5).unwrap_or(0.0),
+            bonuses: row.get::<_, f64>(6).unwrap_or(0.0),
+            allowances: row.get::<_, f64>(7).unwrap_or(0.0),
+            gross_salary: row.get::<_, f64>(8).unwrap_or(0.0),
+            tax: row.get::<_, f64>(9).unwrap_or(0.0),
+            late_penalties: row.get::<_, f64>(10).unwrap_or(0.0),
+            absences: row.get::<_, f64>(11).unwrap_or(0.0),
+            other_deductions: row.get::<_, f64>(12).unwrap_or(0.0),
+            total_deductions: row.get::<_, f64>(13).unwrap_or(0.0),
+            net_pay: row.get::<_, f64>(14).unwrap_or(0.0),
+            pay_period_start: row.get::<_, String>(15).unwrap_or_default(),
+            pay_period_end: row.get::<_, String>(16).unwrap_or_default(),
+            payment_date: row.get(17)?,
+            status: row.get(18)?,
+            notes: row.get(19)?,
+            employer_contributions: row.get::<_, f64>(20).unwrap_or(0.0),
+            lines: None,
+            account_id: row.get(21)?,
+            run_id: row.get(22)?,
+        })
+    }
+
+    /// Lines of a payroll record. Loan instalments carry the loan balance left
+    /// after this record and any earlier pay periods.
+    fn payroll_lines_inner(conn: &Connection, payroll_id: i64) -> SqlResult<Vec<PayrollLine>> {
+        let mut stmt = conn.prepare(
+            "SELECT pl.id, pl.payroll_id, pl.component_id, pl.name, pl.line_type, pl.amount, pl.employer_amount, pl.loan_id,
+                    CASE WHEN pl.loan_id IS NULL THEN NULL ELSE
+                        l.principal - COALESCE((SELECT SUM(x.amount) FROM payroll_lines x JOIN payroll px ON x.payroll_id = px.id
+                                                WHERE x.loan_id = pl.loan_id AND px.status != 'Void'
+                                                  AND (px.pay_period_end < p.pay_period_end OR px.id = p.id)), 0)
+                    END
+             FROM payroll_lines pl
+             JOIN payroll p ON pl.payroll_id = p.id
+             LEFT JOIN employee_loans l ON pl.loan_id = l.id
+             WHERE pl.payroll_id=?1 ORDER BY pl.id"
+        )?;
+        let lines = stmt.query_map(params![payroll_id], |row| {
+            Ok(PayrollLine {
+                id: row.get(0)?,
+                payroll_id: row.get(1)?,
+                component_id: row.get(2)?,
+                name: row.get(3)?,
+                line_type: row.get(4)?,
+                amount: row.get(5)?,
+                employer_amount: row.get(6)?,
+                loan_id: row.get(7)?,
+                loan_balance: row.get::<_, Option<f64>>(8)?.map(|b| (b * 100.0).round() / 100.0),
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+        Ok(lines)
+    }
+
+    pub fn get_payroll_summary(&self) -> SqlResult<Vec<PayrollRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("{} ORDER BY p.payment_date DESC", Self::PAYROLL_SELECT))?;
+        let rows = stmt.query_map([], Self::payroll_from_row)?;
+        rows.collect()
+    }
+
+    /// Payroll records (with their lines) falling within a pay period, by employee
+    /// name. Void records are left out.
+    pub fn get_payroll_for_period(&self, start: &str, end: &str) -> SqlResult<Vec<PayrollRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE p.pay_period_start >= ?1 AND p.pay_period_end <= ?2 AND p.status != 'Void' ORDER BY e.name, p.pay_period_start, p.id",
+            Self::PAYROLL_SELECT
+        ))?;
+        let mut records = stmt.query_map(params![start, end], Self::payroll_from_row)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        for r in records.iter_mut() {
+            if let Some(id) = r.id {
+                r.lines = Some(Self::payroll_lines_inner(&conn, id)?);
+            }
+        }
+        Ok(records)
+    }
+
+    /// Paid payroll records (with their lines) by payment date, for year-end reporting.
+    pub fn get_paid_payroll_between(&self, start: &str, end: &str) -> SqlResult<Vec<PayrollRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE p.payment_date >= ?1 AND p.payment_date <= ?2 AND p.status = 'Paid' ORDER BY e.name, p.payment_date, p.id",
+            Self::PAYROLL_SELECT
+        ))?;
+        let mut records = stmt.query_map(params![start, end], Self::payroll_from_row)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        for r in records.iter_mut() {
+            if let Some(id) = r.id {
+                r.lines = Some(Self::payroll_lines_inner(&conn, id)?);
+            }
+        }
+        Ok(records)
+    }
+
+    /// Net pay transfers for payroll within a pay period, with each employee's
+    /// bank details. Void records and records in runs not yet approved are left out.
+    pub fn get_payroll_bank_payments(&self, start: &str, end: &str) -> SqlResult<Vec<BankPayment>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT p.id, COALESCE(e.name, ''), e.bank_name, e.bank_branch, e.bank_account_no, p.net_pay, p.pay_period_start, p.pay_period_end
+             FROM payroll p
+             LEFT JOIN employees e ON p.employee_id = e.id
+             LEFT JOIN payroll_runs r ON p.run_id = r.id
+             WHERE p.pay_period_start >= ?1 AND p.pay_period_end <= ?2 AND p.status != 'Void' AND p.net_pay > 0
+               AND (p.run_id IS NULL OR r.status IN ('Approved', 'Paid'))
+             ORDER BY e.name, p.id"
+        )?;
+        let rows = stmt.query_map(params![start, end], |row| {
+            let id: i64 = row.get(0)?;
+            let (from, to): (String, String) = (row.get(6)?, row.get(7)?);
+            Ok(BankPayment {
+                payee_name: row.get(1)?,
+                bank_name: row.get(2)?,
+                bank_branch: row.get(3)?,
+                account_no: row.get(4)?,
+                amount: row.get::<_, f64>(5).unwrap_or(0.0),
+                reference: format!("PAY-{}", id),
+                remittance_info: Some(format!("Salary {} to {}", from, to)),
+                transaction_id: None,
+            })
+        })?;
+        rows.collect()
+    }
+
+    pub fn get_payroll_detail(&self, id: i64) -> SqlResult<PayrollRecord> {
+        let conn = self.conn.lock().unwrap();
+        let mut record = conn.query_row(
+            &format!("{} WHERE p.id = ?1", Self::PAYROLL_SELECT),
+            params![id],
+            Self::payroll_from_row,
+        )?;
+        record.lines = Some(Self::payroll_lines_inner(&conn, id)?);
+        Ok(record)
+    }
+
+    // ── Payroll Runs ───────────────────────────────────────
+
+    const PAYROLL_RUN_SELECT: &'static str =
+        "SELECT r.id, r.run_number, r.pay_period_start, r.pay_period_end, r.payment_date, r.account_id, r.status, r.notes,
+                r.created_by, cu.username, r.approved_by, au.username, r.approved_at, r.paid_at, r.void_reason, r.created_at,
+                (SELECT COUNT(*) FROM payroll p WHERE p.run_id = r.id),
+                (SELECT COALESCE(SUM(gross_salary), 0) FROM payroll p WHERE p.run_id = r.id),
+                (SELECT COALESCE(SUM(net_pay), 0) FROM payroll p WHERE p.run_id = r.id)
+         FROM payroll_runs r
+         LEFT JOIN users cu ON r.created_by = cu.id
+         LEFT JOIN users au ON r.approved_by = au.id";
+
+    fn payroll_run_from_row(row: &rusqlite::Row) -> SqlResult<PayrollRun> {
+        Ok(PayrollRun {
+            id: row.get(0)?,
+            run_number: row.get(1)?,
+            pay_period_start: row.get(2)?,
+            pay_period_end: row.get(3)?,
+            payment_date: row.get(4)?,
+            account_id: row.get(5)?,
+            status: row.get(6)?,
+            notes: row.get(7)?,
+            created_by: row.get(8)?,
+            created_by_name: row.get(9)?,
+            approved_by: row.get(10)?,
+            approved_by_name: row.get(11)?,
+            approved_at: row.get(12)?,
+            paid_at: row.get(13)?,
+            void_reason: row.get(14)?,
+            created_at: row.get(15)?,
+            employee_count: row.get(16)?,
+            total_gross: row.get(17)?,
+            total_net: row.get(18)?,
+            records: None,
+        })
+    }
+
+    pub fn get_payroll_runs(&self) -> SqlResult<Vec<PayrollRun>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("{} ORDER BY r.id DESC", Self::PAYROLL_RUN_SELECT))?;
+        let rows = stmt.query_map([], Self::payroll_run_from_row)?;
+        rows.collect()
+    }
+
+    pub fn get_payroll_run_detail(&self, id: i64) -> SqlResult<Option<PayrollRun>> {
+        let conn = self.conn.lock().unwrap();
+        let mut run = match conn.query_row(
+            &format!("{} WHERE r.id = ?1", Self::PAYROLL_RUN_SELECT),
+            params![id],
+            Self::payroll_run_from_row,
+        ) {
+            Ok(r) => r,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut stmt = conn.prepare(&format!("{} WHERE p.run_id = ?1 ORDER BY e.name", Self::PAYROLL_SELECT))?;
+        let mut records = stmt.query_map(params![id], Self::payroll_from_row)?.collect::<SqlResult<Vec<_>>>()?;
+        for r in records.iter_mut() {
+            r.lines = Some(Self::payroll_lines_inner(&conn, r.id.unwrap_or(0))?);
+        }
+        run.records = Some(records);
+        Ok(Some(run))
+    }
+
+    /// Saves a draft run with its (unposted) records.
+    pub fn create_payroll_run(&self, run: &PayrollRun, records: &[PayrollRecord]) -> SqlResult<i64> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+
+        let count: i64 = tx.query_row("SELECT COUNT(*) FROM payroll_runs", [], |row| row.get(0))?;
+        tx.execute(
+            "INSERT INTO payroll_runs (run_number, pay_period_start, pay_period_end, payment_date, account_id, status, notes, created_by)
+             VALUES (?1, ?2, ?3, ?4, ?5, 'Draft', ?6, ?7)",
+            params![format!("RUN-{:05}", count + 1), run.pay_period_start, run.pay_period_end, run.payment_date, run.account_id, run.notes, run.created_by],
+        )?;
+        let run_id = tx.last_insert_rowid();
+        for r in records {
+            Self::insert_payroll_inner(&tx, &PayrollRecord { run_id: Some(run_id), status: "Pending".to_string(), ..r.clone() })?;
+        }
+
+        tx.commit()?;
+        Ok(run_id)
+    }
+
+    /// Replaces the figures of a record in a draft run after an adjustment.
+    pub fn replace_payroll_record(&self, id: i64, p: &PayrollRecord) -> SqlResult<()> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        tx.execute(
+            "UPDATE payroll SET base_salary=?1, overtime_pay=?2, bonuses=?3, allowances=?4, gross_salary=?5, tax=?6, late_penalties=?7,
+                    absences=?8, other_deductions=?9, total_deductions=?10, net_pay=?11, notes=?12, employer_contributions=?13
+             WHERE id=?14",
+            params![p.base_salary, p.overtime_pay, p.bonuses, p.allowances, p.gross_salary, p.tax, p.late_penalties, p.absences, p.other_deductions, p.total_deductions, p.net_pay, p.notes, p.employer_contributions, id],
+        )?;
+        tx.execute("DELETE FROM payroll_lines WHERE payroll_id=?1", params![id])?;
+        Self::insert_payroll_lines(&tx, id, p.lines.as_deref().unwrap_or(&[]))?;
+        tx.commit()
+    }
+
+    /// Drops an employee from a draft run.
+    pub fn delete_payroll_record(&self, id: i64) -> SqlResult<()> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        tx.execute("DELETE FROM payroll_lines WHERE payroll_id=?1", params![id])?;
+        tx.execute("DELETE FROM payroll WHERE id=?1", params![id])?;
+        tx.commit()
+    }
+
+    pub fn approve_payroll_run(&self, id: i64, user_id: Option<i64>) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE payroll_runs SET status='Approved', approved_by=?1, approved_at=CURRENT_TIMESTAMP WHERE id=?2 AND status='Draft'",
+            params![user_id, id],
+        )?;
+        Ok(())
+    }
+
+    /// Pays every record in an approved run from the run's account, posting each
+    /// as `create_payroll` does for a paid record.
+    pub fn pay_payroll_run(&self, id: i64) -> SqlResult<()> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        let (account_id, payment_date): (i64, String) = tx.query_row(
+            "SELECT account_id, payment_date FROM payroll_runs WHERE id=?1 AND status='Approved'",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let record_ids: Vec<i64> = tx.prepare("SELECT id FROM payroll WHERE run_id=?1 AND status='Pending'")?
+            .query_map(params![id], |row| row.get(0))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        for record_id in record_ids {
+            Self::post_payroll_inner(&tx, record_id, account_id, &payment_date)?;
+        }
+        tx.execute("UPDATE payroll_runs SET status='Paid', paid_at=CURRENT_TIMESTAMP WHERE id=?1", params![id])?;
+        tx.commit()
+    }
+
+    /// Voids a run and every record in it, reversing postings for paid records.
+    pub fn void_payroll_run(&self, id: i64, reason: Option<&str>) -> SqlResult<()> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        let record_ids: Vec<i64> = tx.prepare("SELECT id FROM payroll WHERE run_id=?1 AND status != 'Void'")?
+            .query_map(params![id], |row| row.get(0))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        for record_id in record_ids {
+            Self::void_payroll_inner(&tx, record_id, reason)?;
+        }
+        tx.execute(
+            "UPDATE payroll_runs SET status='Void', void_reason=?1 WHERE id=?2",
+            params![reason, id],
+        )?;
+        tx.commit()
+    }
+
+    // ── Employee Loans ─────────────────────────────────────
+
+    const LOAN_SELECT: &'static str =
+        "SELECT l.id, l.employee_id, e.name, l.loan_type, l.principal, l.instalment, l.start_date, l.status, l.notes, l.created_at,
+                COALESCE((SELECT SUM(pl.amount) FROM payroll_lines pl JOIN payroll p ON pl.payroll_id = p.id
+                          WHERE pl.loan_id = l.id AND p.status != 'Void'), 0)
+         FROM employee_loans l LEFT JOIN employees e ON l.employee_id = e.id";
+
+    fn loan_from_row(row: &rusqlite::Row) -> SqlResult<EmployeeLoan> {
+        let principal: f64 = row.get(4)?;
+        let recovered: f64 = row.get(10)?;
+        let balance = ((principal - recovered) * 100.0).round() / 100.0;
+        let status: String = row.get(7)?;
+        Ok(EmployeeLoan {
+            id: row.get(0)?,
+            employee_id: row.get(1)?,
+            employee_name: row.get(2)?,
+            loan_type: row.get(3)?,
+            principal,
+            instalment: row.get(5)?,
+            start_date: row.get(6)?,
+            status: if status == "Active" && balance <= 0.0 { "Settled".to_string() } else { status },
+            recovered: (recovered * 100.0).round() / 100.0,
+            balance: balance.max(0.0),
+            notes: row.get(8)?,
+            created_at: row.get(9)?,
+        })
+    }
+
+    pub fn get_employee_loans(&self, employee_id: Option<i64>) -> SqlResult<Vec<EmployeeLoan>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE (?1 IS NULL OR l.employee_id = ?1) ORDER BY l.start_date DESC, l.id DESC",
+            Self::LOAN_SELECT
+        ))?;
+        let rows = stmt.query_map(params![employee_id], Self::loan_from_row)?;
+        rows.collect()
+    }
+
+    pub fn get_employee_loan(&self, id: i64) -> SqlResult<Option<EmployeeLoan>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(&format!("{} WHERE l.id = ?1", Self::LOAN_SELECT), params![id], Self::loan_from_row) {
+            Ok(l) => Ok(Some(l)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn create_employee_loan(&self, l: &EmployeeLoan) -> SqlResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO employee_loans (employee_id, loan_type, principal, instalment, start_date, notes)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![l.employee_id, l.loan_type, l.principal, l.instalment, l.start_date, l.notes],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn update_employee_loan(&self, l: &EmployeeLoan) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE employee_loans SET loan_type=?1, principal=?2, instalment=?3, start_date=?4, status=?5, notes=?6 WHERE id=?7",
+            params![l.loan_type, l.principal, l.instalment, l.start_date, l.status, l.notes, l.id],
+        )?;
+        Ok(())
+    }
+
+    /// Removes a loan that has had nothing recovered through payroll.
+    pub fn delete_employee_loan(&self, id: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM employee_loans WHERE id=?1", params![id])?;
+        Ok(())
+    }
+
+    /// Instalments due from an employee for a pay period: one per active loan that
+    /// has started by the period end and still has a balance, capped at that
+    /// balance. Loans already deducted in an overlapping period are skipped.
+    /// `replacing` is a payroll record being recalculated, whose own instalments
+    /// are ignored.
+    pub fn get_loan_instalments(
+        &self,
+        employee_id: i64,
+        start: &str,
+        end: &str,
+        replacing: Option<i64>,
+    ) -> SqlResult<Vec<crate::payroll::LoanInstalment>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT l.id, l.loan_type, l.instalment,
+                    l.principal - COALESCE((SELECT SUM(pl.amount) FROM payroll_lines pl JOIN payroll p ON pl.payroll_id = p.id
+                                            WHERE pl.loan_id = l.id AND p.status != 'Void' AND p.id IS NOT ?4), 0)
+             FROM employee_loans l
+             WHERE l.employee_id = ?1 AND l.status = 'Active' AND l.start_date <= ?3
+               AND NOT EXISTS (SELECT 1 FROM payroll_lines pl JOIN payroll p ON pl.payroll_id = p.id
+                               WHERE pl.loan_id = l.id AND p.status != 'Void' AND p.id IS NOT ?4
+                                 AND p.pay_period_start <= ?3 AND p.pay_period_end >= ?2)
+             ORDER BY l.start_date, l.id"
+        )?;
+        let rows = stmt.query_map(params![employee_id, start, end, replacing], |row| {
+            let loan_type: String = row.get(1)?;
+            let instalment: f64 = row.get(2)?;
+            let balance: f64 = row.get(3)?;
+            Ok(crate::payroll::LoanInstalment {
+                loan_id: row.get(0)?,
+                name: if loan_type == "Advance" { "Salary Advance Recovery".to_string() } else { "Loan Repayment".to_string() },
+                amount: ((instalment.min(balance)) * 100.0).round() / 100.0,
+            })
+        })?;
+        Ok(rows.collect::<SqlResult<Vec<_>>>()?.into_iter().filter(|l| l.amount > 0.0).collect())
+    }
+
+    // ── Payroll Rules ──────────────────────────────────────
+
+    pub fn get_payroll_components(&self, active_only: bool) -> SqlResult<Vec<PayrollComponent>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, component_type, calculation, amount, employer_rate, basis, taxable, is_active, sort_order
+             FROM payroll_components WHERE (?1 = 0 OR is_active = 1) ORDER BY sort_order, id"
+        )?;
+        let rows = stmt.query_map(params![active_only], |row| {
+            Ok(PayrollComponent {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                component_type: row.get(2)?,
+                calculation: row.get(3)?,
+                amount: row.get(4)?,
+                employer_rate: row.get(5)?,
+                basis: row.get(6)?,
+                taxable: row.get(7)?,
+                is_active: row.get(8)?,
+                sort_order: row.get(9)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    pub fn create_payroll_component(&self, c: &PayrollComponent) -> SqlResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO payroll_components (name, component_type, calculation, amount, employer_rate, basis, taxable, is_active, sort_order)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![c.name, c.component_type, c.calculation, c.amount, c.employer_rate, c.basis, c.taxable, c.is_active, c.sort_order],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn update_payroll_component(&self, c: &PayrollComponent) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE payroll_components SET name=?1, component_type=?2, calculation=?3, amount=?4, employer_rate=?5, basis=?6, taxable=?7, is_active=?8, sort_order=?9
+             WHERE id=?10",
+            params![c.name, c.component_type, c.calculation, c.amount, c.employer_rate, c.basis, c.taxable, c.is_active, c.sort_order, c.id],
+        )?;
+        Ok(())
+    }
+
+    /// Payroll lines keep the component name, so past payslips are unaffected.
+    pub fn delete_payroll_component(&self, id: i64) -> SqlResult<()> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        tx.execute("UPDATE payroll_lines SET component_id = NULL WHERE component_id=?1", params![id])?;
+        tx.execute("DELETE FROM payroll_components WHERE id=?1", params![id])?;
+        tx.commit()
+    }
+
+    pub fn get_tax_brackets(&self) -> SqlResult<Vec<TaxBracket>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, lower_bound, upper_bound, rate FROM tax_brackets ORDER BY lower_bound")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(TaxBracket {
+                id: row.get(0)?,
+                lower_bound: row.get(1)?,
+                upper_bound: row.get(2)?,
+                rate: row.get(3)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Replaces the whole tax table.
+    pub fn save_tax_brackets(&self, brackets: &[TaxBracket]) -> SqlResult<()> {
+        let mut conn_mu = self.conn.lock().unwrap();
+        let tx = conn_mu.transaction()?;
+        tx.execute("DELETE FROM tax_brackets", [])?;
+        for b in brackets {
+            tx.execute(
+                "INSERT INTO tax_brackets (lower_bound, upper_bound, rate) VALUES (?1, ?2, ?3)",
+                params![b.lower_bound, b.upper_bound, b.rate],
+            )?;
+        }
+        tx.commit()
+    }
+
+    // ── Attendance & Leave ─────────────────────────────────
+
+    pub fn get_attendance(&self, employee_id: Option<i64>, start: &str, end: &str) -> SqlResult<Vec<AttendanceRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT a.id, a.employee_id, e.name, a.date, a.status, a.check_in, a.check_out, a.leave_type_id, lt.name, a.notes
+             FROM attendance a
+             JOIN employees e ON a.employee_id = e.id
+             LEFT JOIN leave_types lt ON a.leave_type_id = lt.id
+             WHERE (?1 IS NULL OR a.employee_id = ?1) AND a.date BETWEEN ?2 AND ?3
+             ORDER BY a.date, e.name"
+        )?;
+        let rows = stmt.query_map(params![employee_id, start, end], |row| {
+            Ok(AttendanceRecord {
+                id: row.get(0)?,
+                employee_id: row.get(1)?,
+                employee_name: row.get(2)?,
+                date: row.get(3)?,
+                status: row.get(4)?,
+                check_in: row.get(5)?,
+                check_out: row.get(6)?,
+                leave_type_id: row.get(7)?,
+                leave_type_name: row.get(8)?,
+                notes: row.get(9)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Records (or replaces) an employee's attendance for a day.
+    pub fn record_attendance(&self, a: &AttendanceRecord) -> SqlResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "INSERT INTO attendance (employee_id, date, status, check_in, check_out, leave_type_id, notes)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(employee_id, date) DO UPDATE SET
+                status=excluded.status, check_in=excluded.check_in, check_out=excluded.check_out,
+                leave_type_id=excluded.leave_type_id, notes=excluded.notes
+             RETURNING id",
+            params![a.employee_id, a.date, a.status, a.check_in, a.check_out, a.leave_type_id, a.notes],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn delete_attendance(&self, id: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM attendance WHERE id=?1", params![id])?;
+        Ok(())
+    }
 
-    pub fn get_employees(&self) -> SqlResult<Vec<Employee>> {
+    pub fn get_leave_types(&self) -> SqlResult<Vec<LeaveType>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT id, name, role, email, phone, salary, allowances, created_at FROM employees ORDER BY name")?;
+        let mut stmt = conn.prepare("SELECT id, name, annual_days, accrual, is_paid, is_active FROM leave_types ORDER BY name")?;
         let rows = stmt.query_map([], |row| {
-            Ok(Employee {
+            Ok(LeaveType {
                 id: row.get(0)?,
                 name: row.get(1)?,
-                role: row.get(2)?,
-                email: row.get(3)?,
-                phone: row.get(4)?,
-                salary: row.get(5)?,
-                allowances: row.get(6)?,
-                created_at: row.get(7)?,
+                annual_days: row.get(2)?,
+                accrual: row.get(3)?,
+                is_paid: row.get(4)?,
+                is_active: row.get(5)?,
             })
         })?;
         rows.collect()
     }
 
-    pub fn create_employee(&self, e: &Employee) -> SqlResult<i64> {
+    pub fn create_leave_type(&self, t: &LeaveType) -> SqlResult<i64> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO employees (name, role, email, phone, salary, allowances) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![e.name, e.role, e.email, e.phone, e.salary, e.allowances],
+            "INSERT INTO leave_types (name, annual_days, accrual, is_paid, is_active) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![t.name, t.annual_days, t.accrual, t.is_paid, t.is_active],
         )?;
         Ok(conn.last_insert_rowid())
     }
 
-    pub fn update_employee(&self, e: &Employee) -> SqlResult<()> {
+    pub fn update_leave_type(&self, t: &LeaveType) -> SqlResult<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "UPDATE employees SET name = ?1, role = ?2, email = ?3, phone = ?4, salary = ?5, allowances = ?6 WHERE id = ?7",
-            params![e.name, e.role, e.email, e.phone, e.salary, e.allowances, e.id],
+            "UPDATE leave_types SET name=?1, annual_days=?2, accrual=?3, is_paid=?4, is_active=?5 WHERE id=?6",
+            params![t.name, t.annual_days, t.accrual, t.is_paid, t.is_active, t.id],
         )?;
         Ok(())
     }
 
-    pub fn create_payroll(&self, p: &PayrollRecord) -> SqlResult<i64> {
-        let mut conn_mu = self.conn.lock().unwrap();
-        let tx = conn_mu.transaction()?;
-
-        tx.execute(
-            "INSERT INTO payroll (employee_id, base_salary, overtime_pay, bonuses, allowances, gross_salary, tax, late_penalties, absences, other_deductions, total_deductions, net_pay, pay_period_start, pay_period_end, payment_date, status, notes) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
-            params![p.employee_id, p.base_salary, p.overtime_pay, p.bonuses, p.allowances, p.gross_salary, p.tax, p.late_penalties, p.absences, p.other_deductions, p.total_deductions, p.net_pay, p.pay_period_start, p.pay_period_end, p.payment_date, p.status, p.notes],
-        )?;
-        let payroll_id = tx.last_insert_rowid();
-
-        // If status is Paid, automatically record an expense from Cash account (Account 1)
-        if p.status == "Paid" {
-            let employee_name: String = tx.query_row(
-                "SELECT name FROM employees WHERE id = ?1",
-                params![p.employee_id],
-                |row| row.get(0),
-            )?;
-
-            tx.execute(
-                "INSERT INTO transactions (account_id, amount, transaction_type, description, date, reference_id)
-                 VALUES (1, ?1, 'Expense', ?2, ?3, ?4)",
-                params![p.net_pay, format!("Salary: {}", employee_name), p.payment_date, format!("PAY-{}", payroll_id)],
-            )?;
-
-            tx.execute(
-                "UPDATE accounts SET balance = balance - ?1 WHERE id = 1",
-                params![p.net_pay],
-            )?;
-        }
-
-        tx.commit()?;
-        Ok(payroll_id)
-    }
-
-    pub fn get_payroll_summary(&self) -> SqlResult<Vec<PayrollRecord>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT p.id, p.employee_id, e.name, e.role, p.base_salary, p.overtime_pay, p.bonuses, p.allowances, p.gross_salary, p.tax, p.late_penalties, p.absences, p.other_deductions, p.total_deductions, p.net_pay, p.pay_period_start, p.pay_period_end, p.payment_date, p.status, p.notes
-             FROM payroll p LEFT JOIN employees e ON p.employee_id = e.id
-             ORDER BY p.payment_date DESC"
-        )?;
-        let rows = stmt.query_map([], |row| {
-            Ok(PayrollRecord {
-                id: row.get(0)?,
-                employee_id: row.get(1)?,
-                employee_name: row.get(2)?,
-                employee_role: row.get(3)?,
-                base_salary: row.get::<_, f64>(4).unwrap_or(0.0),
-                overtime_pay: row.get::<_, f64>(5).unwrap_or(0.0),
-                bonuses: row.get::<_, f64>(6).unwrap_or(0.0),
-                allowances: row.get::<_, f64>(7).unwrap_or(0.0),
-                gross_salary: row.get::<_, f64>(8).unwrap_or(0.0),
-                tax: row.get::<_, f64>(9).unwrap_or(0.0),
-                late_penalties: row.get::<_, f64>(10).unwrap_or(0.0),
-                absences: row.get::<_, f64>(11).unwrap_or(0.0),
-                other_deductions: row.get::<_, f64>(12).unwrap_or(0.0),
-                total_deductions: row.get::<_, f64>(13).unwrap_or(0.0),
-                net_pay: row.get::<_, f64>(14).unwrap_or(0.0),
-                pay_period_start: row.get::<_, String>(15).unwrap_or_default(),
-                pay_period_end: row.get::<_, String>(16).unwrap_or_default(),
-                payment_date: row.get(17)?,
-                status: row.get(18)?,
-                notes: row.get(19)?,
-            })
-        })?;
-        rows.collect()
-    }
-
-    pub fn get_payroll_detail(&self, id: i64) -> SqlResult<PayrollRecord> {
+    /// Leave accrued and taken per leave type in `year`, counting accrual up to
+    /// `through_month` (1-12) for monthly-accruing types.
+    pub fn get_leave_balances(&self, employee_id: i64, year: i32, through_month: u32) -> SqlResult<Vec<LeaveBalance>> {
+        let types = self.get_leave_types()?;
         let conn = self.conn.lock().unwrap();
-        conn.query_row(
-            "SELECT p.id, p.employee_id, e.name, e.role, p.base_salary, p.overtime_pay, p.bonuses, p.allowances, p.gross_salary, p.tax, p.late_penalties, p.absences, p.other_deductions, p.total_deductions, p.net_pay, p.pay_period_start, p.pay_period_end, p.payment_date, p.status, p.notes
-             FROM payroll p LEFT JOIN employees e ON p.employee_id = e.id
-             WHERE p.id = ?1",
-            params![id],
-            |row| {
-                Ok(PayrollRecord {
-                    id: row.get(0)?,
-                    employee_id: row.get(1)?,
-                    employee_name: row.get(2)?,
-                    employee_role: row.get(3)?,
-                    base_salary: row.get::<_, f64>(4).unwrap_or(0.0),
-                    overtime_pay: row.get::<_, f64>(5).unwrap_or(0.0),
-                    bonuses: row.get::<_, f64>(6).unwrap_or(0.0),
-                    allowances: row.get::<_, f64>(7).unwrap_or(0.0),
-                    gross_salary: row.get::<_, f64>(8).unwrap_or(0.0),
-                    tax: row.get::<_, f64>(9).unwrap_or(0.0),
-                    late_penalties: row.get::<_, f64>(10).unwrap_or(0.0),
-                    absences: row.get::<_, f64>(11).unwrap_or(0.0),
-                    other_deductions: row.get::<_, f64>(12).unwrap_or(0.0),
-                    total_deductions: row.get::<_, f64>(13).unwrap_or(0.0),
-                    net_pay: row.get::<_, f64>(14).unwrap_or(0.0),
-                    pay_period_start: row.get::<_, String>(15).unwrap_or_default(),
-                    pay_period_end: row.get::<_, String>(16).unwrap_or_default(),
-                    payment_date: row.get(17)?,
-                    status: row.get(18)?,
-                    notes: row.get(19)?,
-                })
-            },
-        )
+        let mut balances = Vec::new();
+        for t in &types {
+            let taken: f64 = conn.query_row(
+                "SELECT COUNT(*) FROM attendance
+                 WHERE employee_id=?1 AND leave_type_id=?2 AND status='Leave' AND substr(date, 1, 4)=?3",
+                params![employee_id, t.id, year.to_string()],
+                |row| row.get::<_, i64>(0),
+            )? as f64;
+            let accrued = crate::payroll::accrued_leave(t, through_month);
+            if !t.is_active && taken == 0.0 {
+                continue;
+            }
+            balances.push(LeaveBalance {
+                leave_type_id: t.id.unwrap_or(0),
+                leave_type_name: t.name.clone(),
+                year,
+                accrued,
+                taken,
+                // Unpaid leave has no entitlement to run down
+                balance: if t.is_paid { accrued - taken } else { 0.0 },
+            });
+        }
+        Ok(balances)
     }
 
     // ── Dashboard (Updated) ────────────────────────────────
@@ -917,7 +3332,7 @@ impl AppDb {
         let mut stmt = conn.prepare(
             "SELECT i.id, i.invoice_number, i.customer_id, c.name, c.phone, i.status,
                     i.issue_date, i.due_date, i.notes, i.subtotal, i.tax, i.discount,
-                    i.discount_percent, i.advance, i.total, i.created_at
+                    i.discount_percent, i.advance, i.total, i.location_id, i.created_at
              FROM invoices i
              LEFT JOIN customers c ON i.customer_id = c.id
              ORDER BY i.id DESC LIMIT 5"
@@ -939,8 +3354,10 @@ impl AppDb {
                 discount_percent: row.get(12)?,
                 advance: row.get(13)?,
                 total: row.get(14)?,
-                created_at: row.get(15)?,
+                location_id: row.get(15)?,
+                created_at: row.get(16)?,
                 items: None,
+                charges: None,
             })
         })?.collect::<SqlResult<Vec<_>>>()?;
 
@@ -964,7 +3381,8 @@ impl AppDb {
             "SELECT business_name, business_address, business_phone, business_email, 
                     currency_symbol, tax_label, logo_path, default_footer, template_type,
                     signature_path, bank_name, bank_account_name, bank_account_no,
-                    bank_branch, business_tagline, qr_code_path
+                    bank_branch, business_tagline, qr_code_path,
+                    COALESCE(inventory_valuation_method, 'FIFO'), default_location_id
              FROM settings WHERE id=1",
             [],
             |row| {
@@ -985,6 +3403,8 @@ impl AppDb {
                     bank_branch: row.get(13)?,
                     business_tagline: row.get(14)?,
                     qr_code_path: row.get(15)?,
+                    inventory_valuation_method: row.get(16)?,
+                    default_location_id: row.get(17)?,
                 })
             },
         )
@@ -998,7 +3418,8 @@ impl AppDb {
                                  logo_path=?7, default_footer=?8, template_type=?9,
                                  signature_path=?10, bank_name=?11, bank_account_name=?12,
                                  bank_account_no=?13, bank_branch=?14, business_tagline=?15,
-                                 qr_code_path=?16
+                                 qr_code_path=?16, inventory_valuation_method=?17,
+                                 default_location_id=?18
              WHERE id=1",
             params![
                 s.business_name, s.business_address, s.business_phone, 
@@ -1006,7 +3427,7 @@ impl AppDb {
                 s.logo_path, s.default_footer, s.template_type,
                 s.signature_path, s.bank_name, s.bank_account_name,
                 s.bank_account_no, s.bank_branch, s.business_tagline,
-                s.qr_code_path
+                s.qr_code_path, s.inventory_valuation_method, s.default_location_id
             ],
         )?;
         Ok(())
@@ -1014,17 +3435,42 @@ impl AppDb {
 
     // ── Authentication ──────────────────────────────────────
 
+    const USER_SELECT: &'static str =
+        "SELECT u.id, u.username, u.password_hash, u.role,
+                (SELECT GROUP_CONCAT(r.name, char(10)) FROM user_roles ur JOIN roles r ON ur.role_id = r.id WHERE ur.user_id = u.id),
+                u.must_change_password, u.password_changed_at, u.failed_login_attempts, u.locked_until,
+                u.totp_enabled, u.is_active
+         FROM users u";
+
+    fn user_from_row(row: &rusqlite::Row) -> SqlResult<User> {
+        let roles: Option<String> = row.get(4)?;
+        Ok(User {
+            id: Some(row.get(0)?),
+            username: row.get(1)?,
+            password_hash: row.get(2)?,
+            role: row.get(3)?,
+            roles: roles.map(|r| r.split('\n').map(String::from).collect()).unwrap_or_default(),
+            must_change_password: row.get(5)?,
+            password_changed_at: row.get(6)?,
+            failed_login_attempts: row.get(7)?,
+            locked_until: row.get(8)?,
+            totp_enabled: row.get(9)?,
+            is_active: row.get(10)?,
+        })
+    }
+
     pub fn has_any_user(&self) -> SqlResult<bool> {
         let conn = self.conn.lock().unwrap();
         let count: i64 = conn.query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))?;
         Ok(count > 0)
     }
 
-    pub fn create_user(&self, username: &str, password_hash: &str, role: &str) -> SqlResult<i64> {
+    pub fn create_user(&self, username: &str, password_hash: &str, role: &str, must_change_password: bool) -> SqlResult<i64> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO users (username, password_hash, role) VALUES (?1, ?2, ?3)",
-            params![username, password_hash, role],
+            "INSERT INTO users (username, password_hash, role, must_change_password, password_changed_at)
+             VALUES (?1, ?2, ?3, ?4, datetime('now'))",
+            params![username, password_hash, role, must_change_password],
         )?;
         let user_id = conn.last_insert_rowid();
 
@@ -1042,15 +3488,8 @@ impl AppDb {
 
     pub fn get_user_by_username(&self, username: &str) -> SqlResult<Option<User>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT id, username, password_hash, role FROM users WHERE username = ?1")?;
-        let user = stmt.query_row(params![username], |row| {
-            Ok(User {
-                id: Some(row.get(0)?),
-                username: row.get(1)?,
-                password_hash: row.get(2)?,
-                role: row.get(3)?,
-            })
-        });
+        let mut stmt = conn.prepare(&format!("{} WHERE u.username = ?1", Self::USER_SELECT))?;
+        let user = stmt.query_row(params![username], Self::user_from_row);
 
         match user {
             Ok(u) => Ok(Some(u)),
@@ -1059,6 +3498,221 @@ impl AppDb {
         }
     }
 
+    /// Permissions granted directly to the user, not counting their roles.
+    pub fn get_user(&self, id: i64) -> SqlResult<Option<User>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(&format!("{} WHERE u.id = ?1", Self::USER_SELECT), params![id], Self::user_from_row) {
+            Ok(u) => Ok(Some(u)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The user's current password hash followed by earlier ones, newest first.
+    pub fn get_password_hashes(&self, user_id: i64, limit: i64) -> SqlResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT password_hash FROM (
+                SELECT password_hash, 1 AS current, 0 AS id FROM users WHERE id = ?1
+                UNION ALL
+                SELECT password_hash, 0, id FROM password_history WHERE user_id = ?1
+             ) ORDER BY current DESC, id DESC LIMIT ?2"
+        )?;
+        let rows = stmt.query_map(params![user_id, limit], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Replaces a user's password, keeping the old hash in their history.
+    pub fn set_password(&self, user_id: i64, password_hash: &str, must_change_password: bool) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO password_history (user_id, password_hash) SELECT id, password_hash FROM users WHERE id = ?1",
+            params![user_id],
+        )?;
+        tx.execute(
+            "UPDATE users SET password_hash = ?1, must_change_password = ?2, password_changed_at = datetime('now') WHERE id = ?3",
+            params![password_hash, must_change_password, user_id],
+        )?;
+        // Only as much history as the longest allowed policy needs
+        tx.execute(
+            "DELETE FROM password_history WHERE user_id = ?1 AND id NOT IN
+                (SELECT id FROM password_history WHERE user_id = ?1 ORDER BY id DESC LIMIT 24)",
+            params![user_id],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Counts a wrong password. Once `max_attempts` is reached (and it is not 0)
+    /// the account is locked until `lock_until` and the count starts over.
+    /// Returns the attempt count and whether this attempt locked the account.
+    pub fn record_failed_login(&self, user_id: i64, max_attempts: i64, lock_until: &str) -> SqlResult<(i64, bool)> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE users SET failed_login_attempts = failed_login_attempts + 1 WHERE id = ?1",
+            params![user_id],
+        )?;
+        let attempts: i64 = tx.query_row(
+            "SELECT failed_login_attempts FROM users WHERE id = ?1",
+            params![user_id],
+            |row| row.get(0),
+        )?;
+        let locked = max_attempts > 0 && attempts >= max_attempts;
+        if locked {
+            tx.execute(
+                "UPDATE users SET failed_login_attempts = 0, locked_until = ?1 WHERE id = ?2",
+                params![lock_until, user_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok((attempts, locked))
+    }
+
+    /// Clears failed attempts and any lock, after a good login or an admin unlock.
+    pub fn clear_login_failures(&self, user_id: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE users SET failed_login_attempts = 0, locked_until = NULL WHERE id = ?1",
+            params![user_id],
+        )?;
+        Ok(())
+    }
+
+    /// The user's TOTP secret, whether enrollment has been confirmed or not.
+    pub fn get_totp_secret(&self, user_id: i64) -> SqlResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT totp_secret FROM users WHERE id = ?1",
+            params![user_id],
+            |row| row.get(0),
+        )
+    }
+
+    /// The time step of the last TOTP code accepted for the user.
+    pub fn get_totp_last_step(&self, user_id: i64) -> SqlResult<Option<i64>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT totp_last_step FROM users WHERE id = ?1",
+            params![user_id],
+            |row| row.get(0),
+        )
+    }
+
+    /// Records an accepted code's time step. Returns false if the same or a
+    /// later step was recorded meanwhile, i.e. the code was already used.
+    pub fn record_totp_step(&self, user_id: i64, step: i64) -> SqlResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let changed = conn.execute(
+            "UPDATE users SET totp_last_step = ?1 WHERE id = ?2 AND (totp_last_step IS NULL OR totp_last_step < ?1)",
+            params![step, user_id],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// Stores a secret awaiting confirmation; two-factor stays off until
+    /// `enable_totp` is called.
+    pub fn set_pending_totp_secret(&self, user_id: i64, secret: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE users SET totp_secret = ?1, totp_enabled = 0 WHERE id = ?2",
+            params![secret, user_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn enable_totp(&self, user_id: i64, recovery_code_hashes: &[String]) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("UPDATE users SET totp_enabled = 1 WHERE id = ?1", params![user_id])?;
+        Self::write_recovery_codes_inner(&tx, user_id, recovery_code_hashes)?;
+        tx.commit()
+    }
+
+    /// Turns two-factor off and forgets the secret and recovery codes.
+    pub fn disable_totp(&self, user_id: i64) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("UPDATE users SET totp_secret = NULL, totp_enabled = 0 WHERE id = ?1", params![user_id])?;
+        tx.execute("DELETE FROM recovery_codes WHERE user_id = ?1", params![user_id])?;
+        tx.commit()
+    }
+
+    pub fn replace_recovery_codes(&self, user_id: i64, code_hashes: &[String]) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        Self::write_recovery_codes_inner(&tx, user_id, code_hashes)?;
+        tx.commit()
+    }
+
+    fn write_recovery_codes_inner(conn: &Connection, user_id: i64, code_hashes: &[String]) -> SqlResult<()> {
+        conn.execute("DELETE FROM recovery_codes WHERE user_id = ?1", params![user_id])?;
+        for hash in code_hashes {
+            conn.execute(
+                "INSERT INTO recovery_codes (user_id, code_hash) VALUES (?1, ?2)",
+                params![user_id, hash],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// (id, hash) of each recovery code not used yet.
+    pub fn get_unused_recovery_codes(&self, user_id: i64) -> SqlResult<Vec<(i64, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, code_hash FROM recovery_codes WHERE user_id = ?1 AND used_at IS NULL")?;
+        let rows = stmt.query_map(params![user_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    pub fn use_recovery_code(&self, code_id: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE recovery_codes SET used_at = datetime('now') WHERE id = ?1",
+            params![code_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_password_policy(&self) -> SqlResult<PasswordPolicy> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT min_length, require_uppercase, require_lowercase, require_digit, require_symbol, history_count,
+                    max_failed_logins, lockout_minutes, require_two_factor, idle_timeout_minutes, session_lifetime_hours
+             FROM password_policy WHERE id = 1",
+            [],
+            |row| Ok(PasswordPolicy {
+                min_length: row.get(0)?,
+                require_uppercase: row.get(1)?,
+                require_lowercase: row.get(2)?,
+                require_digit: row.get(3)?,
+                require_symbol: row.get(4)?,
+                history_count: row.get(5)?,
+                max_failed_logins: row.get(6)?,
+                lockout_minutes: row.get(7)?,
+                require_two_factor: row.get(8)?,
+                idle_timeout_minutes: row.get(9)?,
+                session_lifetime_hours: row.get(10)?,
+            }),
+        )
+    }
+
+    pub fn update_password_policy(&self, p: &PasswordPolicy) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE password_policy SET min_length = ?1, require_uppercase = ?2, require_lowercase = ?3,
+                    require_digit = ?4, require_symbol = ?5, history_count = ?6,
+                    max_failed_logins = ?7, lockout_minutes = ?8, require_two_factor = ?9,
+                    idle_timeout_minutes = ?10, session_lifetime_hours = ?11
+             WHERE id = 1",
+            params![
+                p.min_length, p.require_uppercase, p.require_lowercase, p.require_digit, p.require_symbol, p.history_count,
+                p.max_failed_logins, p.lockout_minutes, p.require_two_factor, p.idle_timeout_minutes, p.session_lifetime_hours
+            ],
+        )?;
+        Ok(())
+    }
+
     pub fn get_user_permissions(&self, user_id: i64) -> SqlResult<Vec<String>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
@@ -1070,6 +3724,25 @@ impl AppDb {
         rows.collect()
     }
 
+    /// Everything the user may do: the union of their direct grants and the
+    /// permissions of every role assigned to them.
+    pub fn get_effective_permissions(&self, user_id: i64) -> SqlResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT p.name FROM permissions p
+             JOIN user_permissions up ON p.id = up.permission_id
+             WHERE up.user_id = ?1
+             UNION
+             SELECT p.name FROM permissions p
+             JOIN role_permissions rp ON p.id = rp.permission_id
+             JOIN user_roles ur ON ur.role_id = rp.role_id
+             WHERE ur.user_id = ?1
+             ORDER BY 1"
+        )?;
+        let rows = stmt.query_map(params![user_id], |row| row.get(0))?;
+        rows.collect()
+    }
+
     pub fn log_activity(&self, user_id: Option<i64>, action: &str, module: &str, record_id: Option<&str>, description: &str) -> SqlResult<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
@@ -1090,7 +3763,7 @@ impl AppDb {
     ) -> SqlResult<Vec<AuditLog>> {
         let conn = self.conn.lock().unwrap();
         let mut query = String::from(
-            "SELECT l.id, l.user_id, u.username, l.action, l.module, l.record_id, l.description, l.timestamp
+            "SELECT l.id, l.user_id, COALESCE(u.username, l.username), l.action, l.module, l.record_id, l.description, l.timestamp
              FROM audit_logs l
              LEFT JOIN users u ON l.user_id = u.id"
         );
@@ -1134,18 +3807,51 @@ impl AppDb {
 
     pub fn get_users(&self) -> SqlResult<Vec<User>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT id, username, password_hash, role FROM users ORDER BY username")?;
-        let rows = stmt.query_map([], |row| {
-            Ok(User {
-                id: Some(row.get(0)?),
-                username: row.get(1)?,
-                password_hash: row.get(2)?,
-                role: row.get(3)?,
-            })
-        })?;
+        let mut stmt = conn.prepare(&format!("{} ORDER BY u.username", Self::USER_SELECT))?;
+        let rows = stmt.query_map([], Self::user_from_row)?;
         rows.collect()
     }
 
+    pub fn count_active_admins(&self) -> SqlResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT COUNT(*) FROM users WHERE role = 'Admin' AND is_active = 1",
+            [],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn set_user_active(&self, user_id: i64, active: bool) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE users SET is_active = ?1 WHERE id = ?2", params![active, user_id])?;
+        Ok(())
+    }
+
+    pub fn set_user_role(&self, user_id: i64, role: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE users SET role = ?1 WHERE id = ?2", params![role, user_id])?;
+        Ok(())
+    }
+
+    pub fn rename_user(&self, user_id: i64, username: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE users SET username = ?1 WHERE id = ?2", params![username, user_id])?;
+        Ok(())
+    }
+
+    /// Deletes the user. Their audit log entries stay, keeping the username
+    /// they were recorded under; grants, roles and password history go with them.
+    pub fn delete_user(&self, user_id: i64) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE audit_logs SET username = (SELECT username FROM users WHERE id = ?1) WHERE user_id = ?1",
+            params![user_id],
+        )?;
+        tx.execute("DELETE FROM users WHERE id = ?1", params![user_id])?;
+        tx.commit()
+    }
+
     pub fn update_user_permissions(&self, user_id: i64, permission_names: Vec<String>) -> SqlResult<()> {
         let mut conn = self.conn.lock().unwrap();
         let tx = conn.transaction()?;
@@ -1164,6 +3870,101 @@ impl AppDb {
         Ok(())
     }
 
+    // ── Roles ──────────────────────────────────────────────
+
+    const ROLE_SELECT: &'static str =
+        "SELECT r.id, r.name, r.description, r.created_at,
+                (SELECT GROUP_CONCAT(p.name, char(10)) FROM role_permissions rp JOIN permissions p ON rp.permission_id = p.id WHERE rp.role_id = r.id),
+                (SELECT COUNT(*) FROM user_roles ur WHERE ur.role_id = r.id)
+         FROM roles r";
+
+    fn role_from_row(row: &rusqlite::Row) -> SqlResult<Role> {
+        let permissions: Option<String> = row.get(4)?;
+        Ok(Role {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            description: row.get(2)?,
+            created_at: row.get(3)?,
+            permissions: permissions.map(|p| p.split('\n').map(String::from).collect()).unwrap_or_default(),
+            user_count: row.get(5)?,
+        })
+    }
+
+    pub fn get_roles(&self) -> SqlResult<Vec<Role>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("{} ORDER BY r.name", Self::ROLE_SELECT))?;
+        let rows = stmt.query_map([], Self::role_from_row)?;
+        rows.collect()
+    }
+
+    pub fn get_role(&self, id: i64) -> SqlResult<Option<Role>> {
+        let conn = self.conn.lock().unwrap();
+        match conn.query_row(&format!("{} WHERE r.id = ?1", Self::ROLE_SELECT), params![id], Self::role_from_row) {
+            Ok(r) => Ok(Some(r)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Inserts or updates a role and replaces its permission set.
+    pub fn save_role(&self, role: &Role) -> SqlResult<i64> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let id = match role.id {
+            Some(id) => {
+                tx.execute(
+                    "UPDATE roles SET name = ?1, description = ?2 WHERE id = ?3",
+                    params![role.name, role.description, id],
+                )?;
+                tx.execute("DELETE FROM role_permissions WHERE role_id = ?1", params![id])?;
+                id
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO roles (name, description) VALUES (?1, ?2)",
+                    params![role.name, role.description],
+                )?;
+                tx.last_insert_rowid()
+            }
+        };
+        for name in &role.permissions {
+            tx.execute(
+                "INSERT OR IGNORE INTO role_permissions (role_id, permission_id)
+                 SELECT ?1, id FROM permissions WHERE name = ?2",
+                params![id, name],
+            )?;
+        }
+        tx.commit()?;
+        Ok(id)
+    }
+
+    pub fn delete_role(&self, id: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM roles WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    pub fn get_user_role_ids(&self, user_id: i64) -> SqlResult<Vec<i64>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT role_id FROM user_roles WHERE user_id = ?1 ORDER BY role_id")?;
+        let rows = stmt.query_map(params![user_id], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    pub fn update_user_roles(&self, user_id: i64, role_ids: &[i64]) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM user_roles WHERE user_id = ?1", params![user_id])?;
+        for role_id in role_ids {
+            tx.execute(
+                "INSERT OR IGNORE INTO user_roles (user_id, role_id) VALUES (?1, ?2)",
+                params![user_id, role_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
     pub fn get_cash_flow_report(&self) -> SqlResult<Vec<CashFlowEntry>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
@@ -1210,6 +4011,65 @@ impl AppDb {
         rows.collect()
     }
 
+    /// Revenue and cost per product, net of returns. Revenue excludes tax and has
+    /// each invoice's overall discount spread across its lines by their share of
+    /// the discounted goods total; a return gives back its share of the revenue
+    /// and the cost it came back in at.
+    pub fn get_gross_margin_report(&self) -> SqlResult<Vec<GrossMarginEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "WITH goods AS (
+                SELECT invoice_id, SUM(line_total) AS total FROM invoice_items GROUP BY invoice_id
+             ),
+             sold AS (
+                SELECT ii.invoice_id, ii.product_id, MAX(ii.product_name) AS product_name,
+                       SUM(ii.quantity) AS quantity,
+                       SUM((ii.quantity * ii.unit_price - COALESCE(ii.discount, 0))
+                           * CASE WHEN g.total > 0 THEN MAX(0, 1 - COALESCE(i.discount, 0) / g.total) ELSE 1 END) AS revenue,
+                       SUM(ii.cost_total) AS cost
+                FROM invoice_items ii
+                JOIN invoices i ON ii.invoice_id = i.id
+                JOIN goods g ON g.invoice_id = ii.invoice_id
+                WHERE ii.product_id IS NOT NULL
+                GROUP BY ii.invoice_id, ii.product_id
+             ),
+             returned AS (
+                SELECT invoice_id, product_id, SUM(quantity) AS quantity, SUM(quantity * unit_cost) AS cost
+                FROM stock_movements
+                WHERE movement_type = 'Return'
+                GROUP BY invoice_id, product_id
+             ),
+             net AS (
+                SELECT s.product_id, s.product_name,
+                       s.quantity - COALESCE(r.quantity, 0) AS quantity,
+                       CASE WHEN s.quantity > 0 THEN s.revenue * (1 - COALESCE(r.quantity, 0) / s.quantity) ELSE s.revenue END AS revenue,
+                       s.cost - COALESCE(r.cost, 0) AS cost
+                FROM sold s
+                LEFT JOIN returned r ON r.invoice_id = s.invoice_id AND r.product_id = s.product_id
+             )
+             SELECT n.product_id, COALESCE(p.name, MAX(n.product_name)), SUM(n.quantity), SUM(n.revenue), SUM(n.cost)
+             FROM net n
+             LEFT JOIN products p ON n.product_id = p.id
+             GROUP BY n.product_id
+             ORDER BY SUM(n.revenue) - SUM(n.cost) DESC"
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let revenue: f64 = row.get(3)?;
+            let cost_of_goods_sold: f64 = row.get(4)?;
+            let gross_margin = revenue - cost_of_goods_sold;
+            Ok(GrossMarginEntry {
+                product_id: row.get(0)?,
+                product_name: row.get(1)?,
+                quantity_sold: row.get(2)?,
+                revenue,
+                cost_of_goods_sold,
+                gross_margin,
+                margin_percent: if revenue != 0.0 { gross_margin / revenue * 100.0 } else { 0.0 },
+            })
+        })?;
+        rows.collect()
+    }
+
     // ── Activation Methods ──────────────────────────────────────
 
     pub fn is_activated(&self) -> SqlResult<bool> {
@@ -0,0 +1,106 @@
+//! Encryption at rest with SQLCipher.
+//!
+//! An encrypted database is unlocked either with an admin passphrase typed in
+//! at startup or with the contents of a keyfile, which can live anywhere (a USB
+//! stick, a network share). SQLCipher derives the page key from either. Which
+//! one is in use is recorded in `encryption.json` beside the database.
+
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Every plaintext SQLite file starts with this; SQLCipher files do not.
+const PLAINTEXT_HEADER: &[u8] = b"SQLite format 3\0";
+const MIN_PASSPHRASE_LEN: usize = 12;
+const MIN_KEYFILE_LEN: usize = 32;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "key_source", rename_all = "lowercase")]
+pub enum KeySource {
+    Passphrase,
+    Keyfile { path: String },
+}
+
+/// The database file and its encryption settings. Managed from startup, since
+/// the database itself cannot be opened until it is unlocked.
+pub struct DbFiles {
+    pub db_path: PathBuf,
+    config_path: PathBuf,
+}
+
+impl DbFiles {
+    pub fn new(app_dir: &Path) -> Self {
+        DbFiles {
+            db_path: app_dir.join("antigravity.db"),
+            config_path: app_dir.join("encryption.json"),
+        }
+    }
+
+    pub fn db_path_str(&self) -> &str {
+        self.db_path.to_str().unwrap()
+    }
+
+    /// A missing or empty file is a new, plaintext database.
+    pub fn is_encrypted(&self) -> bool {
+        let mut header = [0u8; 16];
+        match std::fs::File::open(&self.db_path).and_then(|mut f| f.read_exact(&mut header)) {
+            Ok(()) => header != PLAINTEXT_HEADER,
+            Err(_) => false,
+        }
+    }
+
+    /// How the encrypted database is unlocked. A missing or unreadable
+    /// setting falls back to asking for the passphrase.
+    pub fn key_source(&self) -> KeySource {
+        std::fs::read_to_string(&self.config_path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or(KeySource::Passphrase)
+    }
+
+    pub fn save_key_source(&self, source: &KeySource) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(source).map_err(|e| e.to_string())?;
+        std::fs::write(&self.config_path, json).map_err(|e| format!("Could not save encryption settings: {}", e))
+    }
+}
+
+pub fn check_passphrase(passphrase: &str) -> Result<(), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!("Passphrase must be at least {} characters", MIN_PASSPHRASE_LEN));
+    }
+    Ok(())
+}
+
+/// The key material stored in a keyfile.
+pub fn read_keyfile(path: &str) -> Result<String, String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("Could not read keyfile {}: {}", path, e))?;
+    let key = contents.trim().to_string();
+    if key.len() < MIN_KEYFILE_LEN {
+        return Err(format!("Keyfile {} does not contain a valid key", path));
+    }
+    Ok(key)
+}
+
+/// Writes a new random keyfile, refusing to overwrite an existing file.
+pub fn create_keyfile(path: &str) -> Result<String, String> {
+    if Path::new(path).exists() {
+        return Err(format!("{} already exists", path));
+    }
+    let key = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+    std::fs::write(path, &key).map_err(|e| format!("Could not write keyfile {}: {}", path, e))?;
+    Ok(key)
+}
+
+/// The key for `source`: the passphrase itself, or the keyfile's contents
+/// (an existing keyfile is reused, otherwise a new one is created).
+pub fn resolve_new_key(source: &KeySource, passphrase: Option<&str>) -> Result<String, String> {
+    match source {
+        KeySource::Passphrase => {
+            let passphrase = passphrase.ok_or("Passphrase is required")?;
+            check_passphrase(passphrase)?;
+            Ok(passphrase.to_string())
+        }
+        KeySource::Keyfile { path } if Path::new(path).exists() => read_keyfile(path),
+        KeySource::Keyfile { path } => create_keyfile(path),
+    }
+}
@@ -1,12 +1,22 @@
+mod bank_file;
+mod barcode;
 mod commands;
 mod db;
+mod encryption;
 mod models;
+mod payroll;
+mod password;
 mod pdf;
+mod permissions;
+mod session;
+mod two_factor;
 
 use tauri::Manager;
 use db::AppDb;
+use encryption::{DbFiles, KeySource};
 use std::path::PathBuf;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use serde::{Serialize, Deserialize};
 
 #[derive(Default, Serialize, Deserialize, Clone)]
@@ -15,10 +25,47 @@ pub struct SessionUser {
     pub username: String,
     pub role: String,
     pub permissions: Vec<String>,
+    /// Set until the user replaces a default or admin-assigned password;
+    /// every command except `change_password` is refused meanwhile.
+    pub must_change_password: bool,
+    /// Set when the security policy requires two-factor authentication for
+    /// this user and they have not enrolled yet; handled like a password change.
+    pub must_enroll_two_factor: bool,
+    /// Random per-login token the window that logged in sends with every call.
+    pub token: String,
+    /// Locked by the user or after the idle timeout; only `unlock_session`
+    /// and `logout` are accepted until the password is entered again.
+    pub locked: bool,
+}
+
+/// The signed-in session. The app keeps one at a time, belonging to the
+/// window holding its token; `session::guard` expires and locks it.
+pub struct Session {
+    pub user: SessionUser,
+    pub started: Instant,
+    pub last_active: Instant,
+    /// Zero means the session is never locked for inactivity.
+    pub idle_timeout: Duration,
+    pub lifetime: Duration,
+}
+
+/// A login whose password was accepted but which still needs a TOTP or
+/// recovery code before a session is started.
+pub struct PendingLogin {
+    pub user_id: i64,
+    pub started: Instant,
+}
+
+/// What `login` returns: either a session, or a request for the second step.
+#[derive(Serialize, Clone)]
+pub struct LoginResult {
+    pub session: Option<SessionUser>,
+    pub two_factor_required: bool,
 }
 
 pub struct AuthState {
-    pub user: Mutex<Option<SessionUser>>,
+    pub session: Mutex<Option<Session>>,
+    pub pending_login: Mutex<Option<PendingLogin>>,
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -27,7 +74,8 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .manage(AuthState {
-            user: Mutex::new(None),
+            session: Mutex::new(None),
+            pending_login: Mutex::new(None),
         })
         .setup(|app| {
             // Store DB in app data dir
@@ -36,23 +84,56 @@ pub fn run() {
                 .app_data_dir()
                 .expect("Failed to get app data dir");
             std::fs::create_dir_all(&app_dir).ok();
-            let db_path = app_dir.join("antigravity.db");
+            let files = DbFiles::new(&app_dir);
 
-            let database = AppDb::new(db_path.to_str().unwrap())
-                .expect("Failed to initialize database");
+            // An encrypted database is opened here only when its keyfile is
+            // at hand; otherwise it stays unmanaged until `unlock_database`.
+            if !files.is_encrypted() {
+                let database = AppDb::new(files.db_path_str(), None)
+                    .expect("Failed to initialize database");
+                app.manage(database);
+            } else if let KeySource::Keyfile { path } = files.key_source() {
+                let opened = encryption::read_keyfile(&path)
+                    .and_then(|key| AppDb::new(files.db_path_str(), Some(&key)).map_err(|e| e.to_string()));
+                if let Ok(database) = opened {
+                    app.manage(database);
+                }
+            }
 
-            app.manage(database);
+            app.manage(files);
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![
+        .invoke_handler(session::guard(tauri::generate_handler![
             commands::get_customers,
             commands::create_customer,
             commands::update_customer,
             commands::delete_customer,
+            commands::get_price_lists,
+            commands::get_price_list_detail,
+            commands::create_price_list,
+            commands::update_price_list,
+            commands::delete_price_list,
+            commands::resolve_price,
             commands::get_products,
             commands::create_product,
             commands::update_product,
             commands::delete_product,
+            commands::find_product_by_code,
+            commands::export_barcode_labels_pdf,
+            commands::adjust_stock,
+            commands::record_stock_return,
+            commands::create_purchase_receipt,
+            commands::get_purchase_receipts,
+            commands::get_stock_movements,
+            commands::get_stock_report,
+            commands::get_stock_locations,
+            commands::create_stock_location,
+            commands::update_stock_location,
+            commands::get_location_stock,
+            commands::set_user_default_location,
+            commands::create_stock_transfer,
+            commands::get_stock_transfers,
+            commands::get_stock_transfer_detail,
             commands::get_invoices,
             commands::get_invoice_detail,
             commands::create_invoice,
@@ -67,9 +148,38 @@ pub fn run() {
             commands::login,
             commands::logout,
             commands::get_current_session,
+            commands::change_password,
+            commands::admin_reset_password,
+            commands::unlock_user,
+            commands::set_user_active,
+            commands::update_user_role,
+            commands::rename_user,
+            commands::delete_user,
+            commands::lock_session,
+            commands::unlock_session,
+            commands::get_database_status,
+            commands::unlock_database,
+            commands::encrypt_database,
+            commands::change_database_key,
+            commands::verify_two_factor,
+            commands::get_two_factor_status,
+            commands::begin_two_factor_setup,
+            commands::confirm_two_factor_setup,
+            commands::regenerate_recovery_codes,
+            commands::disable_two_factor,
+            commands::reset_two_factor,
+            commands::get_password_policy,
+            commands::update_password_policy,
             commands::get_users,
             commands::get_user_permissions,
             commands::update_user_permissions,
+            commands::get_permissions,
+            commands::get_roles,
+            commands::create_role,
+            commands::update_role,
+            commands::delete_role,
+            commands::get_user_roles,
+            commands::update_user_roles,
             commands::get_audit_logs,
             commands::upload_logo,
             commands::upload_signature,
@@ -85,12 +195,50 @@ pub fn run() {
             commands::create_employee,
             commands::update_employee,
             commands::create_payroll,
+            commands::get_payroll_runs,
+            commands::get_payroll_run_detail,
+            commands::create_payroll_run,
             commands::create_bulk_payroll,
+            commands::adjust_payroll_run_record,
+            commands::remove_payroll_run_record,
+            commands::approve_payroll_run,
+            commands::pay_payroll_run,
+            commands::void_payroll_run,
             commands::get_payroll_summary,
             commands::get_payroll_detail,
+            commands::preview_payroll,
+            commands::pay_payroll,
+            commands::void_payroll,
+            commands::get_payroll_liabilities,
+            commands::remit_payroll_liabilities,
+            commands::export_payroll_payment_file,
+            commands::export_supplier_payment_file,
+            commands::export_payslips,
+            commands::export_payroll_register,
+            commands::get_year_end_summary,
+            commands::export_earnings_certificates,
+            commands::export_year_end_xlsx,
+            commands::get_employee_loans,
+            commands::create_employee_loan,
+            commands::update_employee_loan,
+            commands::delete_employee_loan,
+            commands::get_attendance,
+            commands::record_attendance,
+            commands::delete_attendance,
+            commands::get_leave_types,
+            commands::create_leave_type,
+            commands::update_leave_type,
+            commands::get_leave_balances,
+            commands::get_payroll_components,
+            commands::create_payroll_component,
+            commands::update_payroll_component,
+            commands::delete_payroll_component,
+            commands::get_tax_brackets,
+            commands::save_tax_brackets,
             commands::export_payslip_pdf,
             commands::get_cash_flow_report,
             commands::get_category_report,
+            commands::get_gross_margin_report,
             commands::export_data_csv,
             commands::export_data_xlsx,
             commands::check_activation_status,
@@ -101,7 +249,7 @@ pub fn run() {
             commands::create_custom_template,
             commands::update_custom_template,
             commands::delete_custom_template,
-        ])
+        ]))
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
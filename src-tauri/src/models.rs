@@ -9,6 +9,8 @@ pub struct Customer {
     pub email: Option<String>,
     pub address: Option<String>,
     pub tax_id: Option<String>,
+    pub price_list_id: Option<i64>,
+    pub price_list_name: Option<String>, // Join for UI
     pub created_at: Option<String>,
 }
 
@@ -19,16 +21,174 @@ pub struct Product {
     pub description: Option<String>,
     pub unit_price: f64,
     pub tax_percent: f64,
+    pub track_stock: bool,
+    pub stock_on_hand: f64,
+    pub reorder_level: f64,
+    pub average_cost: f64,
+    pub unit: Option<String>, // Unit of measure, e.g. "pcs", "hrs", "kg", "m"
+    pub sku: Option<String>,
+    pub barcode: Option<String>, // EAN-13 or Code 128 content
+    pub category: Option<String>,
+    pub is_active: bool,         // Archived products stay on old invoices but can't be sold
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StockMovement {
+    pub id: Option<i64>,
+    pub product_id: i64,
+    pub product_name: Option<String>, // Join for UI
+    pub movement_type: String, // "Opening", "Purchase", "Sale", "Return", "Adjustment", "Reversal", "TransferOut", "TransferIn"
+    pub quantity: f64,         // Positive adds stock, negative removes it
+    pub unit_cost: f64,
+    pub location_id: i64,
+    pub location_name: Option<String>, // Join for UI
+    pub invoice_id: Option<i64>,
+    pub receipt_id: Option<i64>,
+    pub transfer_id: Option<i64>,
+    pub notes: Option<String>,
+    pub user_id: Option<i64>,
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StockLevel {
+    pub product_id: i64,
+    pub product_name: String,
+    pub stock_on_hand: f64,
+    pub reorder_level: f64,
+    pub unit_cost: f64,
+    pub stock_value: f64,
+    pub is_low: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StockReport {
+    pub valuation_method: String,
+    pub total_value: f64,
+    pub items: Vec<StockLevel>,
+    pub low_stock: Vec<StockLevel>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StockLocation {
+    pub id: Option<i64>,
+    pub name: String,
+    pub address: Option<String>,
+    pub is_active: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LocationStock {
+    pub location_id: i64,
+    pub location_name: String,
+    pub product_id: i64,
+    pub product_name: String,
+    pub quantity: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StockTransferItem {
+    pub product_id: i64,
+    pub product_name: Option<String>, // Join for UI
+    pub quantity: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StockTransfer {
+    pub id: Option<i64>,
+    pub transfer_number: Option<String>,
+    pub from_location_id: i64,
+    pub from_location_name: Option<String>, // Join for UI
+    pub to_location_id: i64,
+    pub to_location_name: Option<String>,   // Join for UI
+    pub transfer_date: String,
+    pub notes: Option<String>,
+    pub user_id: Option<i64>,
+    pub username: Option<String>,           // Join for UI
+    pub created_at: Option<String>,
+    pub items: Option<Vec<StockTransferItem>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PurchaseReceipt {
+    pub id: Option<i64>,
+    pub product_id: i64,
+    pub product_name: Option<String>, // Join for UI
+    pub location_id: i64,
+    pub quantity: f64,
+    pub unit_cost: f64,
+    pub supplier: Option<String>,
+    pub reference: Option<String>,
+    pub received_date: String,
+    pub notes: Option<String>,
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PriceList {
+    pub id: Option<i64>,
+    pub name: String,
+    pub description: Option<String>,
+    pub discount_percent: f64, // Applied to products the list has no explicit price for
+    pub is_active: bool,
+    pub created_at: Option<String>,
+    pub items: Option<Vec<PriceListItem>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PriceListItem {
+    pub id: Option<i64>,
+    pub price_list_id: Option<i64>,
+    pub product_id: i64,
+    pub product_name: Option<String>, // Join for UI
+    pub min_quantity: f64,            // Tier applies from this quantity upwards
+    pub unit_price: f64,
+    pub tax_percent: Option<f64>,     // Overrides the product's tax rate when set
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResolvedPrice {
+    pub product_id: i64,
+    pub unit_price: f64,
+    pub tax_percent: f64,
+    pub source: String, // "Product", "PriceList" or "PriceListDiscount"
+    pub price_list_id: Option<i64>,
+    pub min_quantity: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GrossMarginEntry {
+    pub product_id: i64,
+    pub product_name: String,
+    pub quantity_sold: f64,
+    pub revenue: f64,
+    pub cost_of_goods_sold: f64,
+    pub gross_margin: f64,
+    pub margin_percent: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct InvoiceItem {
     pub id: Option<i64>,
     pub invoice_id: Option<i64>,
+    pub product_id: Option<i64>,
     pub product_name: String,
     pub description: Option<String>,
-    pub quantity: i64,
+    pub quantity: f64,
+    pub unit: Option<String>,
     pub unit_price: f64,
+    pub discount: f64,         // Line discount amount; recalculated when a percent is given
+    pub discount_percent: f64, // Takes precedence over `discount` when above zero
+    pub tax_percent: f64,      // Charged on the discounted line amount
+    pub line_total: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InvoiceCharge {
+    pub id: Option<i64>,
+    pub invoice_id: Option<i64>,
+    pub description: String, // e.g. "Shipping", "Service charge"
+    pub amount: f64,
     pub tax_percent: f64,
     pub line_total: f64,
 }
@@ -50,8 +210,10 @@ pub struct Invoice {
     pub discount_percent: f64,
     pub advance: f64,
     pub total: f64,
+    pub location_id: Option<i64>, // Stock location goods are drawn from
     pub created_at: Option<String>,
     pub items: Option<Vec<InvoiceItem>>,
+    pub charges: Option<Vec<InvoiceCharge>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -83,6 +245,19 @@ pub struct Transaction {
     pub created_at: Option<String>,
 }
 
+/// One credit transfer in a bulk bank payment file.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BankPayment {
+    pub payee_name: String,
+    pub bank_name: Option<String>,
+    pub bank_branch: Option<String>,
+    pub account_no: Option<String>,
+    pub amount: f64,
+    pub reference: String, // end-to-end id, e.g. "PAY-12"
+    pub remittance_info: Option<String>,
+    pub transaction_id: Option<i64>, // supplier payments: the expense being paid
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Employee {
     pub id: Option<i64>,
@@ -92,9 +267,80 @@ pub struct Employee {
     pub phone: Option<String>,
     pub salary: f64,
     pub allowances: f64,
+    pub overtime_rate: f64,      // paid per overtime hour
+    pub standard_hours: f64,     // working hours per day, beyond which time is overtime
+    pub shift_start: Option<String>, // "HH:MM"; checking in later counts as late
+    pub late_penalty: f64,       // deducted per late arrival
+    pub bank_name: Option<String>,
+    pub bank_branch: Option<String>,
+    pub bank_account_no: Option<String>,
+    pub department: Option<String>,
+    pub status: String,        // "Active", "Suspended", "Terminated"
+    pub join_date: Option<String>,
+    pub exit_date: Option<String>, // last day employed
+    pub pay_frequency: String, // "Monthly", "BiWeekly", "Weekly"; salary is per pay period
     pub created_at: Option<String>,
 }
 
+/// One day of attendance for an employee.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AttendanceRecord {
+    pub id: Option<i64>,
+    pub employee_id: i64,
+    pub employee_name: Option<String>,
+    pub date: String,
+    pub status: String, // "Present", "Absent", "HalfDay", "Leave", "Holiday"
+    pub check_in: Option<String>,  // "HH:MM"
+    pub check_out: Option<String>, // "HH:MM"
+    pub leave_type_id: Option<i64>,
+    pub leave_type_name: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LeaveType {
+    pub id: Option<i64>,
+    pub name: String,
+    pub annual_days: f64,
+    pub accrual: String, // "Annual" (granted on 1 January) or "Monthly"
+    pub is_paid: bool,
+    pub is_active: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LeaveBalance {
+    pub leave_type_id: i64,
+    pub leave_type_name: String,
+    pub year: i32,
+    pub accrued: f64,
+    pub taken: f64,
+    pub balance: f64,
+}
+
+/// Attendance totals for a pay period, and the pay adjustments derived from them.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AttendanceSummary {
+    pub working_days: f64,
+    pub days_present: f64,
+    pub days_absent: f64,
+    pub half_days: f64,
+    pub paid_leave_days: f64,
+    pub unpaid_leave_days: f64,
+    pub late_days: f64,
+    pub overtime_hours: f64,
+    pub overtime_pay: f64,
+    pub late_penalties: f64,
+    pub absences: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PayrollPreview {
+    pub record: PayrollRecord,
+    pub attendance: AttendanceSummary,
+    /// Share of the period's working days the employee was employed (1 = full period).
+    pub proration: f64,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PayrollRecord {
     pub id: Option<i64>,
@@ -115,8 +361,144 @@ pub struct PayrollRecord {
     pub pay_period_start: String,
     pub pay_period_end: String,
     pub payment_date: String,
-    pub status: String, // "Paid", "Pending"
+    pub status: String, // "Paid", "Pending", "Void"
+    pub notes: Option<String>,
+    pub employer_contributions: f64,
+    pub lines: Option<Vec<PayrollLine>>,
+    pub account_id: Option<i64>, // account net pay was paid from
+    pub run_id: Option<i64>,
+}
+
+/// A batch of payroll records for one pay period, reviewed and approved before
+/// it is paid.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PayrollRun {
+    pub id: Option<i64>,
+    pub run_number: Option<String>,
+    pub pay_period_start: String,
+    pub pay_period_end: String,
+    pub payment_date: String,
+    pub account_id: i64,
+    pub status: String, // "Draft", "Approved", "Paid", "Void"
+    pub notes: Option<String>,
+    pub created_by: Option<i64>,
+    pub created_by_name: Option<String>,
+    pub approved_by: Option<i64>,
+    pub approved_by_name: Option<String>,
+    pub approved_at: Option<String>,
+    pub paid_at: Option<String>,
+    pub void_reason: Option<String>,
+    pub created_at: Option<String>,
+    pub employee_count: i64,
+    pub total_gross: f64,
+    pub total_net: f64,
+    pub records: Option<Vec<PayrollRecord>>,
+}
+
+/// One employee's paid payroll totalled over a tax year, for earnings certificates.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct YearEndSummary {
+    pub employee_id: i64,
+    pub employee_name: Option<String>,
+    pub employee_role: Option<String>,
+    pub tax_year_start: String,
+    pub tax_year_end: String,
+    pub payslips: i64,
+    pub base_salary: f64,
+    pub allowances: f64,
+    pub overtime_pay: f64,
+    pub bonuses: f64,
+    pub gross_salary: f64,
+    pub tax: f64,
+    pub late_penalties: f64,
+    pub absences: f64,
+    pub other_deductions: f64,
+    pub total_deductions: f64,
+    pub net_pay: f64,
+    pub employer_contributions: f64,
+    pub components: Vec<YearEndComponent>,
+}
+
+/// A payroll line (earning, tax, deduction or contribution) totalled over the year.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct YearEndComponent {
+    pub name: String,
+    pub line_type: String,
+    pub amount: f64,
+    pub employer_amount: f64,
+}
+
+/// Tax withheld or a contribution owed to an authority or fund by a paid payroll
+/// record, tracked until it is remitted.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PayrollLiability {
+    pub id: Option<i64>,
+    pub payroll_id: i64,
+    pub employee_name: Option<String>,
+    pub pay_period_end: Option<String>,
+    pub name: String,
+    pub employee_amount: f64,
+    pub employer_amount: f64,
+    pub amount: f64,
+    pub status: String, // "Outstanding", "Remitted", "Cancelled"
+    pub remitted_date: Option<String>,
+    pub account_id: Option<i64>,
+    pub reference: Option<String>,
+}
+
+/// A payroll rule applied to every payslip while active.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PayrollComponent {
+    pub id: Option<i64>,
+    pub name: String,
+    pub component_type: String, // "Earning", "Deduction", "Contribution"
+    pub calculation: String,    // "Fixed" or "Percent"
+    pub amount: f64,            // fixed amount, or the employee rate for "Percent"
+    pub employer_rate: f64,     // employer share of a "Contribution", same calculation
+    pub basis: String,          // "Basic" or "Gross", for "Percent" rules
+    pub taxable: bool,          // earnings only: counts towards taxable pay
+    pub is_active: bool,
+    pub sort_order: i64,
+}
+
+/// One band of the progressive income tax applied to taxable pay per pay period.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaxBracket {
+    pub id: Option<i64>,
+    pub lower_bound: f64,
+    pub upper_bound: Option<f64>, // None = no upper limit
+    pub rate: f64,
+}
+
+/// A computed component amount on a payroll record.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PayrollLine {
+    pub id: Option<i64>,
+    pub payroll_id: Option<i64>,
+    pub component_id: Option<i64>,
+    pub name: String,
+    pub line_type: String, // "Earning", "Deduction", "Contribution", "Tax"
+    pub amount: f64,          // paid to (earning) or withheld from (others) the employee
+    pub employer_amount: f64, // employer-paid share, not deducted from net pay
+    pub loan_id: Option<i64>, // set on loan and advance instalments
+    pub loan_balance: Option<f64>, // loan balance after this payslip, when read back
+}
+
+/// A loan or salary advance recovered from pay in fixed instalments.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmployeeLoan {
+    pub id: Option<i64>,
+    pub employee_id: i64,
+    pub employee_name: Option<String>,
+    pub loan_type: String, // "Loan" or "Advance"
+    pub principal: f64,
+    pub instalment: f64,
+    pub start_date: String, // instalments begin with the first pay period ending on or after this date
+    pub status: String,     // "Active", "Settled", "Cancelled"
+    pub recovered: f64,     // instalments on payroll that is not void
+    pub balance: f64,
     pub notes: Option<String>,
+    pub created_at: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -149,6 +531,8 @@ pub struct Settings {
     pub bank_branch: Option<String>,
     pub business_tagline: Option<String>,
     pub qr_code_path: Option<String>,
+    pub inventory_valuation_method: String, // "FIFO" or "WeightedAverage"
+    pub default_location_id: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -157,6 +541,81 @@ pub struct User {
     pub username: String,
     pub password_hash: String,
     pub role: String, // "Admin" or "User"
+    pub roles: Vec<String>, // names of assigned roles
+    pub must_change_password: bool,
+    pub password_changed_at: Option<String>,
+    pub failed_login_attempts: i64,
+    pub locked_until: Option<String>, // UTC, "YYYY-MM-DD HH:MM:SS"
+    pub totp_enabled: bool,
+    /// Deactivated users cannot log in but keep their history.
+    pub is_active: bool,
+}
+
+/// Rules every new password must meet. `history_count` is how many of the
+/// user's recent passwords (including the current one) cannot be reused.
+/// After `max_failed_logins` wrong passwords in a row (0 turns lockout off) the
+/// account is locked for `lockout_minutes`. With `require_two_factor`, anyone
+/// who can manage payroll or users must enroll in two-factor authentication.
+/// Sessions lock after `idle_timeout_minutes` without activity (0 = never) and
+/// end `session_lifetime_hours` after login.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: i64,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    pub history_count: i64,
+    pub max_failed_logins: i64,
+    pub lockout_minutes: i64,
+    pub require_two_factor: bool,
+    pub idle_timeout_minutes: i64,
+    pub session_lifetime_hours: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    /// Whether the security policy requires this user to use it.
+    pub required: bool,
+    pub recovery_codes_remaining: i64,
+}
+
+/// Shown once while enrolling: the secret to type in by hand, the otpauth URI
+/// and a base64 PNG QR code of it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TwoFactorSetup {
+    pub secret: String,
+    pub otpauth_uri: String,
+    pub qr_code: String,
+}
+
+/// Whether the database is encrypted and, before login, whether it still
+/// has to be unlocked. `key_source` is "passphrase" or "keyfile".
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DatabaseStatus {
+    pub encrypted: bool,
+    pub unlocked: bool,
+    pub key_source: Option<String>,
+    pub keyfile_path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PermissionInfo {
+    pub name: String,
+    pub group: String,
+    pub description: String,
+}
+
+/// A named bundle of permissions that can be assigned to many users.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Role {
+    pub id: Option<i64>,
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<String>,
+    pub user_count: i64,
+    pub created_at: Option<String>,
 }
 
 
@@ -0,0 +1,104 @@
+//! Password policy checks and hashing.
+
+use crate::models::PasswordPolicy;
+
+/// Checks a new password against the policy and lists every rule it breaks.
+pub fn validate(policy: &PasswordPolicy, password: &str) -> Result<(), String> {
+    let mut problems = Vec::new();
+    if (password.chars().count() as i64) < policy.min_length {
+        problems.push(format!("at least {} characters", policy.min_length));
+    }
+    if policy.require_uppercase && !password.chars().any(|c| c.is_uppercase()) {
+        problems.push("an uppercase letter".to_string());
+    }
+    if policy.require_lowercase && !password.chars().any(|c| c.is_lowercase()) {
+        problems.push("a lowercase letter".to_string());
+    }
+    if policy.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+        problems.push("a digit".to_string());
+    }
+    if policy.require_symbol && !password.chars().any(|c| !c.is_alphanumeric() && !c.is_whitespace()) {
+        problems.push("a symbol".to_string());
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Password must contain {}", problems.join(", ")))
+    }
+}
+
+/// Rejects a password matching any of `previous_hashes` (the current password
+/// first, then older ones), as far back as the policy's history count reaches.
+pub fn check_history(policy: &PasswordPolicy, password: &str, previous_hashes: &[String]) -> Result<(), String> {
+    let reused = previous_hashes.iter()
+        .take(policy.history_count.max(0) as usize)
+        .any(|h| bcrypt::verify(password, h).unwrap_or(false));
+    if reused {
+        Err(format!("Password must differ from your last {} password(s)", policy.history_count))
+    } else {
+        Ok(())
+    }
+}
+
+pub fn hash(password: &str) -> Result<String, String> {
+    bcrypt::hash(password, bcrypt::DEFAULT_COST).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 10,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_digit: true,
+            require_symbol: true,
+            history_count: 2,
+            max_failed_logins: 5,
+            lockout_minutes: 15,
+            require_two_factor: false,
+            idle_timeout_minutes: 15,
+            session_lifetime_hours: 12,
+        }
+    }
+
+    #[test]
+    fn validate_lists_every_broken_rule() {
+        assert_eq!(validate(&policy(), "Correct#Horse9"), Ok(()));
+        assert_eq!(
+            validate(&policy(), "short"),
+            Err("Password must contain at least 10 characters, an uppercase letter, a digit, a symbol".to_string())
+        );
+        // Spaces do not count as symbols, and length is counted in characters.
+        assert!(validate(&policy(), "Correct Horse9").unwrap_err().contains("a symbol"));
+        assert_eq!(validate(&policy(), "Ünïcödé#Pw9"), Ok(()));
+    }
+
+    #[test]
+    fn validate_skips_rules_the_policy_turns_off() {
+        let relaxed = PasswordPolicy {
+            require_uppercase: false,
+            require_lowercase: false,
+            require_digit: false,
+            require_symbol: false,
+            ..policy()
+        };
+        assert_eq!(validate(&relaxed, "aaaaaaaaaa"), Ok(()));
+        assert!(validate(&relaxed, "aaaaaaaaa").is_err());
+    }
+
+    #[test]
+    fn check_history_only_looks_back_as_far_as_the_policy() {
+        let hashes: Vec<String> = ["current#Pw1", "older#Pw2", "oldest#Pw3"].iter()
+            .map(|p| bcrypt::hash(p, 4).unwrap())
+            .collect();
+        assert!(check_history(&policy(), "current#Pw1", &hashes).is_err());
+        assert!(check_history(&policy(), "older#Pw2", &hashes).is_err());
+        assert_eq!(check_history(&policy(), "oldest#Pw3", &hashes), Ok(()));
+        assert_eq!(check_history(&policy(), "brand#New4", &hashes), Ok(()));
+        let no_history = PasswordPolicy { history_count: 0, ..policy() };
+        assert_eq!(check_history(&no_history, "current#Pw1", &hashes), Ok(()));
+    }
+}
@@ -0,0 +1,539 @@
+//! Payroll rules engine.
+//!
+//! Turns an employee's basic pay plus the configured components and tax brackets
+//! into gross pay, deductions and net pay. Earnings are applied first (percentages
+//! of basic pay only), then tax on taxable gross, then deductions and contribution
+//! schemes such as EPF/ETF, whose percentages may be taken from basic or gross pay.
+//! Overtime, late penalties and absence deductions come from attendance; see
+//! [`summarize_attendance`]. Loan and advance instalments are deducted last.
+//! Deductions are capped at what is left of gross pay, in that order, so net pay
+//! never goes below zero.
+
+use chrono::{Datelike, NaiveDate, NaiveTime, Weekday};
+use crate::models::{
+    AttendanceRecord, AttendanceSummary, Employee, LeaveType, PayrollComponent, PayrollLine, PayrollRecord, TaxBracket,
+    YearEndComponent, YearEndSummary,
+};
+
+/// Pay amounts entered for the period, before any rules are applied.
+#[derive(Debug, Clone, Default)]
+pub struct PayInput {
+    pub base_salary: f64,
+    pub allowances: f64,
+    pub overtime_pay: f64,
+    pub bonuses: f64,
+    pub late_penalties: f64,
+    pub absences: f64,
+    pub loan_instalments: Vec<LoanInstalment>,
+}
+
+/// An instalment due on an employee loan or advance this period.
+#[derive(Debug, Clone)]
+pub struct LoanInstalment {
+    pub loan_id: i64,
+    pub name: String,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PayBreakdown {
+    /// Fixed allowances plus earning components.
+    pub allowances: f64,
+    pub gross_salary: f64,
+    pub taxable_pay: f64,
+    pub tax: f64,
+    /// Late penalties and absences as deducted, after capping.
+    pub late_penalties: f64,
+    pub absences: f64,
+    /// Deduction components, the employee share of contributions and loan instalments.
+    pub other_deductions: f64,
+    pub employer_contributions: f64,
+    pub total_deductions: f64,
+    pub net_pay: f64,
+    pub lines: Vec<PayrollLine>,
+}
+
+fn round2(v: f64) -> f64 {
+    (v * 100.0).round() / 100.0
+}
+
+fn line(c: &PayrollComponent, line_type: &str, amount: f64, employer_amount: f64) -> PayrollLine {
+    PayrollLine {
+        id: None,
+        payroll_id: None,
+        component_id: c.id,
+        name: c.name.clone(),
+        line_type: line_type.to_string(),
+        amount,
+        employer_amount,
+        loan_id: None,
+        loan_balance: None,
+    }
+}
+
+/// Applies `rate` the way the component is configured: a fixed amount, or a
+/// percentage of basic or gross pay.
+fn rule_amount(c: &PayrollComponent, rate: f64, basic: f64, gross: f64) -> f64 {
+    if c.calculation == "Percent" {
+        let basis = if c.basis == "Gross" { gross } else { basic };
+        round2(basis * rate / 100.0)
+    } else {
+        round2(rate)
+    }
+}
+
+/// Progressive tax: each bracket's rate applies only to the slice of pay inside it.
+pub fn progressive_tax(taxable: f64, brackets: &[TaxBracket]) -> f64 {
+    let tax: f64 = brackets.iter()
+        .map(|b| {
+            let top = b.upper_bound.map_or(taxable, |u| taxable.min(u));
+            (top - b.lower_bound).max(0.0) * b.rate / 100.0
+        })
+        .sum();
+    round2(tax)
+}
+
+/// Computes a payslip from active `components` (in the given order) and `brackets`.
+pub fn compute(input: &PayInput, components: &[PayrollComponent], brackets: &[TaxBracket]) -> PayBreakdown {
+    let mut out = PayBreakdown { allowances: input.allowances, ..Default::default() };
+    let basic = input.base_salary;
+    let active = || components.iter().filter(|c| c.is_active);
+
+    let mut non_taxable = 0.0;
+    for c in active().filter(|c| c.component_type == "Earning") {
+        let amount = rule_amount(c, c.amount, basic, basic);
+        if amount <= 0.0 {
+            continue;
+        }
+        out.allowances += amount;
+        if !c.taxable {
+            non_taxable += amount;
+        }
+        out.lines.push(line(c, "Earning", amount, 0.0));
+    }
+
+    out.gross_salary = round2(basic + out.allowances + input.overtime_pay + input.bonuses);
+    out.taxable_pay = round2((out.gross_salary - non_taxable).max(0.0));
+    out.tax = progressive_tax(out.taxable_pay, brackets);
+    if out.tax > 0.0 {
+        out.lines.push(PayrollLine {
+            id: None,
+            payroll_id: None,
+            component_id: None,
+            name: "Income Tax".to_string(),
+            line_type: "Tax".to_string(),
+            amount: out.tax,
+            employer_amount: 0.0,
+            loan_id: None,
+            loan_balance: None,
+        });
+    }
+
+    // What is left of gross pay; each deduction after tax takes at most this much.
+    let mut available = out.gross_salary - out.tax;
+    let mut take = |amount: f64| {
+        let taken = round2(amount.min(available.max(0.0)));
+        available -= taken;
+        taken
+    };
+    out.late_penalties = take(input.late_penalties);
+    out.absences = take(input.absences);
+
+    for c in active().filter(|c| c.component_type != "Earning") {
+        let employee = take(rule_amount(c, c.amount, basic, out.gross_salary));
+        let employer = if c.component_type == "Contribution" {
+            rule_amount(c, c.employer_rate, basic, out.gross_salary)
+        } else {
+            0.0
+        };
+        if employee <= 0.0 && employer <= 0.0 {
+            continue;
+        }
+        out.other_deductions += employee;
+        out.employer_contributions += employer;
+        out.lines.push(line(c, &c.component_type, employee, employer));
+    }
+
+    for l in &input.loan_instalments {
+        let amount = take(l.amount);
+        if amount <= 0.0 {
+            continue;
+        }
+        out.other_deductions += amount;
+        out.lines.push(PayrollLine {
+            id: None,
+            payroll_id: None,
+            component_id: None,
+            name: l.name.clone(),
+            line_type: "Deduction".to_string(),
+            amount,
+            employer_amount: 0.0,
+            loan_id: Some(l.loan_id),
+            loan_balance: None,
+        });
+    }
+
+    out.allowances = round2(out.allowances);
+    out.other_deductions = round2(out.other_deductions);
+    out.employer_contributions = round2(out.employer_contributions);
+    out.total_deductions = round2(out.tax + out.other_deductions + out.late_penalties + out.absences);
+    out.net_pay = round2(out.gross_salary - out.total_deductions);
+    out
+}
+
+/// Monday to Friday between `start` and `end` inclusive.
+pub fn working_days(start: NaiveDate, end: NaiveDate) -> f64 {
+    start.iter_days()
+        .take_while(|d| *d <= end)
+        .filter(|d| !matches!(d.weekday(), Weekday::Sat | Weekday::Sun))
+        .count() as f64
+}
+
+fn parse_time(t: Option<&str>) -> Option<NaiveTime> {
+    t.and_then(|t| NaiveTime::parse_from_str(t, "%H:%M").ok())
+}
+
+/// Hours between check-in and check-out, or `None` if either is missing.
+fn hours_worked(r: &AttendanceRecord) -> Option<f64> {
+    let (start, end) = (parse_time(r.check_in.as_deref())?, parse_time(r.check_out.as_deref())?);
+    Some(((end - start).num_minutes().max(0)) as f64 / 60.0)
+}
+
+/// Totals `records` for one employee over the pay period and prices them.
+///
+/// Overtime is time worked beyond `standard_hours` on a working day, or all time
+/// worked on a holiday. Absences, unpaid leave and the missing half of half days
+/// are deducted at the daily rate (basic pay / weekdays in the period). Days
+/// without a record are not treated as absences.
+pub fn summarize_attendance(
+    emp: &Employee,
+    base_salary: f64,
+    start: NaiveDate,
+    end: NaiveDate,
+    records: &[AttendanceRecord],
+    leave_types: &[LeaveType],
+) -> AttendanceSummary {
+    let mut s = AttendanceSummary { working_days: working_days(start, end), ..Default::default() };
+    let shift_start = parse_time(emp.shift_start.as_deref());
+
+    for r in records {
+        match r.status.as_str() {
+            "Present" | "HalfDay" => {
+                if r.status == "Present" {
+                    s.days_present += 1.0;
+                } else {
+                    s.half_days += 1.0;
+                }
+                if let (Some(shift), Some(check_in)) = (shift_start, parse_time(r.check_in.as_deref())) {
+                    if check_in > shift {
+                        s.late_days += 1.0;
+                    }
+                }
+                if r.status == "Present" {
+                    if let Some(hours) = hours_worked(r) {
+                        s.overtime_hours += (hours - emp.standard_hours).max(0.0);
+                    }
+                }
+            }
+            "Holiday" => {
+                s.overtime_hours += hours_worked(r).unwrap_or(0.0);
+            }
+            "Leave" => {
+                let paid = leave_types.iter()
+                    .find(|t| t.id == r.leave_type_id)
+                    .is_some_and(|t| t.is_paid);
+                if paid {
+                    s.paid_leave_days += 1.0;
+                } else {
+                    s.unpaid_leave_days += 1.0;
+                }
+            }
+            "Absent" => s.days_absent += 1.0,
+            _ => {}
+        }
+    }
+
+    let daily_rate = if s.working_days > 0.0 { base_salary / s.working_days } else { 0.0 };
+    s.overtime_hours = round2(s.overtime_hours);
+    s.overtime_pay = round2(s.overtime_hours * emp.overtime_rate);
+    s.late_penalties = round2(s.late_days * emp.late_penalty);
+    s.absences = round2(daily_rate * (s.days_absent + s.unpaid_leave_days + s.half_days * 0.5));
+    s
+}
+
+/// Share of the working days in a pay period that fall within employment, for
+/// prorating pay of employees who join or leave mid-period. `exit` is the last
+/// day employed.
+pub fn employed_fraction(start: NaiveDate, end: NaiveDate, join: Option<NaiveDate>, exit: Option<NaiveDate>) -> f64 {
+    let from = join.map_or(start, |j| j.max(start));
+    let to = exit.map_or(end, |x| x.min(end));
+    if to < from {
+        return 0.0;
+    }
+    let period = working_days(start, end);
+    if period == 0.0 {
+        return 1.0;
+    }
+    (working_days(from, to) / period).min(1.0)
+}
+
+/// Leave accrued by the end of `month` (1-12) of a year, for a leave type.
+pub fn accrued_leave(t: &LeaveType, month: u32) -> f64 {
+    if t.accrual == "Monthly" {
+        round2(t.annual_days * month.min(12) as f64 / 12.0)
+    } else {
+        t.annual_days
+    }
+}
+
+/// A labelled amount read from a report row.
+pub type AmountColumn<T> = (&'static str, fn(&T) -> f64);
+
+/// Amount columns of the payroll register, in display order.
+pub const REGISTER_COLUMNS: [AmountColumn<PayrollRecord>; 12] = [
+    ("Basic", |r| r.base_salary),
+    ("Allowances", |r| r.allowances),
+    ("Overtime", |r| r.overtime_pay),
+    ("Bonuses", |r| r.bonuses),
+    ("Gross Pay", |r| r.gross_salary),
+    ("Tax", |r| r.tax),
+    ("Other Deductions", |r| r.other_deductions),
+    ("Late Penalties", |r| r.late_penalties),
+    ("Absences", |r| r.absences),
+    ("Total Deductions", |r| r.total_deductions),
+    ("Net Pay", |r| r.net_pay),
+    ("Employer Contributions", |r| r.employer_contributions),
+];
+
+/// One employee's line in the payroll register, with amounts in [`REGISTER_COLUMNS`] order.
+#[derive(Debug, Clone)]
+pub struct RegisterRow {
+    pub employee_name: String,
+    pub employee_role: Option<String>,
+    pub amounts: Vec<f64>,
+}
+
+/// Groups payroll `records` into one row per employee (summing any repeat records
+/// in the period) and returns the rows with the column totals.
+pub fn payroll_register(records: &[PayrollRecord]) -> (Vec<RegisterRow>, Vec<f64>) {
+    let mut rows: Vec<(i64, RegisterRow)> = Vec::new();
+    for r in records {
+        let idx = match rows.iter().position(|(id, _)| *id == r.employee_id) {
+            Some(idx) => idx,
+            None => {
+                rows.push((r.employee_id, RegisterRow {
+                    employee_name: r.employee_name.clone().unwrap_or_default(),
+                    employee_role: r.employee_role.clone(),
+                    amounts: vec![0.0; REGISTER_COLUMNS.len()],
+                }));
+                rows.len() - 1
+            }
+        };
+        for (amount, (_, value)) in rows[idx].1.amounts.iter_mut().zip(REGISTER_COLUMNS.iter()) {
+            *amount = round2(*amount + value(r));
+        }
+    }
+    let rows: Vec<RegisterRow> = rows.into_iter().map(|(_, row)| row).collect();
+    let totals = (0..REGISTER_COLUMNS.len())
+        .map(|i| round2(rows.iter().map(|row| row.amounts[i]).sum()))
+        .collect();
+    (rows, totals)
+}
+
+/// First and last day of the tax year beginning in `start_month` (1-12) of `year`.
+pub fn tax_year_bounds(year: i32, start_month: u32) -> Option<(NaiveDate, NaiveDate)> {
+    let start = NaiveDate::from_ymd_opt(year, start_month, 1)?;
+    let next = NaiveDate::from_ymd_opt(year + 1, start_month, 1)?;
+    Some((start, next.pred_opt()?))
+}
+
+fn line_type_order(line_type: &str) -> u8 {
+    match line_type {
+        "Earning" => 0,
+        "Tax" => 1,
+        "Deduction" => 2,
+        _ => 3,
+    }
+}
+
+/// Totals paid payroll `records` (with lines) per employee for a tax year.
+/// Components are keyed by line type and name and listed earnings first, then
+/// tax, deductions and contributions.
+pub fn year_end_summaries(records: &[PayrollRecord], tax_year_start: &str, tax_year_end: &str) -> Vec<YearEndSummary> {
+    let mut out: Vec<YearEndSummary> = Vec::new();
+    for r in records {
+        let idx = match out.iter().position(|s| s.employee_id == r.employee_id) {
+            Some(idx) => idx,
+            None => {
+                out.push(YearEndSummary {
+                    employee_id: r.employee_id,
+                    employee_name: r.employee_name.clone(),
+                    employee_role: r.employee_role.clone(),
+                    tax_year_start: tax_year_start.to_string(),
+                    tax_year_end: tax_year_end.to_string(),
+                    payslips: 0,
+                    base_salary: 0.0,
+                    allowances: 0.0,
+                    overtime_pay: 0.0,
+                    bonuses: 0.0,
+                    gross_salary: 0.0,
+                    tax: 0.0,
+                    late_penalties: 0.0,
+                    absences: 0.0,
+                    other_deductions: 0.0,
+                    total_deductions: 0.0,
+                    net_pay: 0.0,
+                    employer_contributions: 0.0,
+                    components: Vec::new(),
+                });
+                out.len() - 1
+            }
+        };
+        let s = &mut out[idx];
+        s.payslips += 1;
+        s.base_salary = round2(s.base_salary + r.base_salary);
+        s.allowances = round2(s.allowances + r.allowances);
+        s.overtime_pay = round2(s.overtime_pay + r.overtime_pay);
+        s.bonuses = round2(s.bonuses + r.bonuses);
+        s.gross_salary = round2(s.gross_salary + r.gross_salary);
+        s.tax = round2(s.tax + r.tax);
+        s.late_penalties = round2(s.late_penalties + r.late_penalties);
+        s.absences = round2(s.absences + r.absences);
+        s.other_deductions = round2(s.other_deductions + r.other_deductions);
+        s.total_deductions = round2(s.total_deductions + r.total_deductions);
+        s.net_pay = round2(s.net_pay + r.net_pay);
+        s.employer_contributions = round2(s.employer_contributions + r.employer_contributions);
+        for l in r.lines.as_deref().unwrap_or(&[]) {
+            match s.components.iter_mut().find(|c| c.line_type == l.line_type && c.name == l.name) {
+                Some(c) => {
+                    c.amount = round2(c.amount + l.amount);
+                    c.employer_amount = round2(c.employer_amount + l.employer_amount);
+                }
+                None => s.components.push(YearEndComponent {
+                    name: l.name.clone(),
+                    line_type: l.line_type.clone(),
+                    amount: l.amount,
+                    employer_amount: l.employer_amount,
+                }),
+            }
+        }
+    }
+    for s in out.iter_mut() {
+        s.components.sort_by_key(|c| line_type_order(&c.line_type));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn component(component_type: &str, calculation: &str, amount: f64, basis: &str) -> PayrollComponent {
+        PayrollComponent {
+            id: None,
+            name: format!("{} {}", component_type, amount),
+            component_type: component_type.to_string(),
+            calculation: calculation.to_string(),
+            amount,
+            employer_rate: 0.0,
+            basis: basis.to_string(),
+            taxable: true,
+            is_active: true,
+            sort_order: 0,
+        }
+    }
+
+    fn bracket(lower_bound: f64, upper_bound: Option<f64>, rate: f64) -> TaxBracket {
+        TaxBracket { id: None, lower_bound, upper_bound, rate }
+    }
+
+    fn brackets() -> Vec<TaxBracket> {
+        vec![
+            bracket(0.0, Some(100_000.0), 0.0),
+            bracket(100_000.0, Some(141_667.0), 6.0),
+            bracket(141_667.0, None, 12.0),
+        ]
+    }
+
+    #[test]
+    fn progressive_tax_applies_each_rate_to_its_slice() {
+        let b = brackets();
+        assert_eq!(progressive_tax(0.0, &b), 0.0);
+        assert_eq!(progressive_tax(100_000.0, &b), 0.0);
+        assert_eq!(progressive_tax(100_001.0, &b), 0.06);
+        // Exactly on a boundary the upper bracket contributes nothing.
+        assert_eq!(progressive_tax(141_667.0, &b), 2_500.02);
+        assert_eq!(progressive_tax(200_000.0, &b), round2(2_500.02 + 58_333.0 * 0.12));
+        assert_eq!(progressive_tax(50_000.0, &[]), 0.0);
+    }
+
+    #[test]
+    fn rule_amount_handles_fixed_and_percent() {
+        let fixed = component("Deduction", "Fixed", 1_234.567, "Basic");
+        assert_eq!(rule_amount(&fixed, fixed.amount, 50_000.0, 80_000.0), 1_234.57);
+        let of_basic = component("Contribution", "Percent", 8.0, "Basic");
+        assert_eq!(rule_amount(&of_basic, of_basic.amount, 50_000.0, 80_000.0), 4_000.0);
+        let of_gross = component("Contribution", "Percent", 8.0, "Gross");
+        assert_eq!(rule_amount(&of_gross, of_gross.amount, 50_000.0, 80_000.0), 6_400.0);
+        assert_eq!(rule_amount(&of_gross, 3.0, 50_000.0, 80_000.0), 2_400.0);
+    }
+
+    #[test]
+    fn compute_builds_gross_tax_and_net() {
+        let mut transport = component("Earning", "Fixed", 5_000.0, "Basic");
+        transport.taxable = false;
+        let mut epf = component("Contribution", "Percent", 8.0, "Basic");
+        epf.employer_rate = 12.0;
+        let mut inactive = component("Deduction", "Fixed", 999.0, "Basic");
+        inactive.is_active = false;
+        let input = PayInput {
+            base_salary: 150_000.0,
+            allowances: 2_000.0,
+            overtime_pay: 1_000.0,
+            bonuses: 0.0,
+            late_penalties: 500.0,
+            absences: 0.0,
+            loan_instalments: vec![LoanInstalment { loan_id: 1, name: "Loan".into(), amount: 3_000.0 }],
+        };
+
+        let pay = compute(&input, &[transport, epf, inactive], &brackets());
+        assert_eq!(pay.allowances, 7_000.0);
+        assert_eq!(pay.gross_salary, 158_000.0);
+        assert_eq!(pay.taxable_pay, 153_000.0);
+        assert_eq!(pay.tax, progressive_tax(153_000.0, &brackets()));
+        assert_eq!(pay.other_deductions, 12_000.0 + 3_000.0);
+        assert_eq!(pay.employer_contributions, 18_000.0);
+        assert_eq!(pay.total_deductions, round2(pay.tax + 15_000.0 + 500.0));
+        assert_eq!(pay.net_pay, round2(158_000.0 - pay.total_deductions));
+        assert_eq!(pay.lines.len(), 4);
+    }
+
+    #[test]
+    fn compute_caps_deductions_at_gross_pay() {
+        let union_dues = component("Deduction", "Fixed", 700.0, "Basic");
+        let mut epf = component("Contribution", "Percent", 8.0, "Basic");
+        epf.employer_rate = 12.0;
+        let input = PayInput {
+            base_salary: 1_000.0,
+            late_penalties: 200.0,
+            absences: 500.0,
+            loan_instalments: vec![LoanInstalment { loan_id: 1, name: "Advance".into(), amount: 400.0 }],
+            ..Default::default()
+        };
+
+        let pay = compute(&input, &[union_dues, epf], &[]);
+        assert_eq!(pay.late_penalties, 200.0);
+        assert_eq!(pay.absences, 500.0);
+        // 300 left: dues take it all, EPF and the loan get nothing from the employee.
+        assert_eq!(pay.other_deductions, 300.0);
+        assert_eq!(pay.employer_contributions, 120.0);
+        assert_eq!(pay.total_deductions, 1_000.0);
+        assert_eq!(pay.net_pay, 0.0);
+        assert!(pay.lines.iter().all(|l| l.loan_id.is_none()));
+
+        let over = PayInput { base_salary: 1_000.0, absences: 1_500.0, ..Default::default() };
+        let pay = compute(&over, &[], &[]);
+        assert_eq!(pay.absences, 1_000.0);
+        assert_eq!(pay.net_pay, 0.0);
+    }
+}
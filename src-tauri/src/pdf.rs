@@ -3,7 +3,7 @@ use std::fs::File;
 use std::io::BufWriter;
 use image as image_crate;
 
-use crate::models::{Invoice, Settings, CustomTemplate};
+use crate::models::{Invoice, InvoiceItem, Settings, CustomTemplate};
 
 /// Minimum y position (mm) before content overflows into footer area.
 const MIN_CONTENT_Y: f32 = 35.0;
@@ -91,6 +91,52 @@ fn right_x(text: &str, font_size: f32, right_edge_mm: f32) -> f32 {
     (right_edge_mm - text_width_mm(text, font_size)).max(0.0)
 }
 
+/// Quantity as printed in the Qty column: up to three decimals without trailing
+/// zeros, followed by the unit of measure when the line has one (e.g. "2.5 hrs").
+fn format_quantity(quantity: f64, unit: Option<&str>) -> String {
+    let qty = format!("{:.3}", quantity);
+    let qty = qty.trim_end_matches('0').trim_end_matches('.');
+    match unit.map(str::trim).filter(|u| !u.is_empty()) {
+        Some(u) => format!("{} {}", qty, u),
+        None => qty.to_string(),
+    }
+}
+
+/// Sub-line printed under an item that carries a line discount.
+fn line_discount_text(item: &InvoiceItem, currency: &str) -> Option<String> {
+    if item.discount <= 0.0 {
+        return None;
+    }
+    Some(if item.discount_percent > 0.0 {
+        format!("Less {}% discount: -{}{:.2}", format_quantity(item.discount_percent, None), currency, item.discount)
+    } else {
+        format!("Less discount: -{}{:.2}", currency, item.discount)
+    })
+}
+
+/// Label/value rows printed between the items table and the grand total: subtotal,
+/// additional charges, tax, advance and invoice discount. Labels carry no colon so
+/// each template can punctuate them in its own style.
+fn summary_rows(invoice: &Invoice, settings: &Settings, currency: &str) -> Vec<(String, String)> {
+    let mut rows = vec![("Subtotal".to_string(), format!("{}{:.2}", currency, invoice.subtotal))];
+    if let Some(ref charges) = invoice.charges {
+        for c in charges {
+            rows.push((truncate_text(&c.description, 9.0, 30.0), format!("{}{:.2}", currency, c.amount)));
+        }
+    }
+    rows.push((settings.tax_label.clone(), format!("{}{:.2}", currency, invoice.tax)));
+    if invoice.advance > 0.0 {
+        rows.push(("Advance".to_string(), format!("-{}{:.2}", currency, invoice.advance)));
+    }
+    // `discount` holds the amount actually applied, whichever way it was entered
+    if invoice.discount_percent > 0.0 {
+        rows.push((format!("Discount ({:.0}%)", invoice.discount_percent), format!("-{}{:.2}", currency, invoice.discount)));
+    } else if invoice.discount > 0.0 {
+        rows.push(("Discount".to_string(), format!("-{}{:.2}", currency, invoice.discount)));
+    }
+    rows
+}
+
 pub fn generate_invoice_pdf(
     invoice: &Invoice,
     settings: &Settings,
@@ -322,7 +368,8 @@ fn render_professional_pdf(
             layer.use_text(&format!("{}", i + 1), 9.0, Mm(23.0), Mm(y), font);
             let name = truncate_text(&item.product_name, 9.0, 55.0);
             layer.use_text(&name, 9.0, Mm(33.0), Mm(y), font);
-            layer.use_text(&item.quantity.to_string(), 9.0, Mm(98.0), Mm(y), font);
+            let qty = truncate_text(&format_quantity(item.quantity, item.unit.as_deref()), 9.0, 16.0);
+            layer.use_text(&qty, 9.0, Mm(98.0), Mm(y), font);
             let up = format!("{}{:.2}", currency, item.unit_price);
             layer.use_text(&up, 9.0, Mm(115.0), Mm(y), font);
             layer.use_text(&format!("{:.0}%", item.tax_percent), 9.0, Mm(142.0), Mm(y), font);
@@ -339,6 +386,12 @@ fn render_professional_pdf(
                     layer.set_fill_color(black.clone());
                 }
             }
+            if let Some(disc) = line_discount_text(item, currency) {
+                y -= 4.0;
+                layer.set_fill_color(gray.clone());
+                layer.use_text(&disc, 7.0, Mm(33.0), Mm(y), font);
+                layer.set_fill_color(black.clone());
+            }
 
             // Row separator
             y -= 1.5;
@@ -358,49 +411,18 @@ fn render_professional_pdf(
     // Totals with blue accent (right-aligned box)
     y -= 4.0;
     // Calculate totals box height dynamically
-    let mut totals_h: f32 = 22.0; // base: subtotal + tax + separator + total
-    if invoice.advance > 0.0 { totals_h += 5.0; }
-    if invoice.discount_percent > 0.0 || invoice.discount > 0.0 { totals_h += 5.0; }
+    let rows = summary_rows(invoice, settings, currency);
+    let totals_h: f32 = 12.0 + 5.0 * rows.len() as f32; // rows + separator + total
     draw_rect(layer, 120.0, y - (totals_h - 5.0), 70.0, totals_h, light_bg.clone());
     // Blue left border
     draw_rect(layer, 120.0, y - (totals_h - 5.0), 1.5, totals_h, mid_blue.clone());
 
-    layer.set_fill_color(gray.clone());
-    layer.use_text("Subtotal:", 9.0, Mm(125.0), Mm(y), font);
-    layer.set_fill_color(black.clone());
-    let v = format!("{}{:.2}", currency, invoice.subtotal);
-    layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font_bold);
-    y -= 5.0;
-
-    layer.set_fill_color(gray.clone());
-    layer.use_text(&format!("{}:", &settings.tax_label), 9.0, Mm(125.0), Mm(y), font);
-    layer.set_fill_color(black.clone());
-    let v = format!("{}{:.2}", currency, invoice.tax);
-    layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font_bold);
-    y -= 5.0;
-
-    if invoice.advance > 0.0 {
-        layer.set_fill_color(gray.clone());
-        layer.use_text("Advance:", 9.0, Mm(125.0), Mm(y), font);
-        layer.set_fill_color(black.clone());
-        let v = format!("-{}{:.2}", currency, invoice.advance);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
-        y -= 5.0;
-    }
-    if invoice.discount_percent > 0.0 {
-        let disc_amt = (invoice.subtotal + invoice.tax) * invoice.discount_percent / 100.0;
-        layer.set_fill_color(gray.clone());
-        layer.use_text(&format!("Discount ({:.0}%):", invoice.discount_percent), 9.0, Mm(125.0), Mm(y), font);
-        layer.set_fill_color(black.clone());
-        let v = format!("-{}{:.2}", currency, disc_amt);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
-        y -= 5.0;
-    } else if invoice.discount > 0.0 {
+    for (label, value) in &rows {
         layer.set_fill_color(gray.clone());
-        layer.use_text("Discount:", 9.0, Mm(125.0), Mm(y), font);
+        layer.use_text(&format!("{}:", label), 9.0, Mm(125.0), Mm(y), font);
         layer.set_fill_color(black.clone());
-        let v = format!("-{}{:.2}", currency, invoice.discount);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
+        let value_font = if value.starts_with('-') { font } else { font_bold };
+        layer.use_text(value, 9.0, Mm(right_x(value, 9.0, 188.0)), Mm(y), value_font);
         y -= 5.0;
     }
 
@@ -553,7 +575,8 @@ fn render_modern_pdf(
             layer.use_text(&format!("{}", i + 1), 9.0, Mm(20.0), Mm(y), font);
             let name = truncate_text(&item.product_name, 9.0, 58.0);
             layer.use_text(&name, 9.0, Mm(30.0), Mm(y), font);
-            layer.use_text(&item.quantity.to_string(), 9.0, Mm(98.0), Mm(y), font);
+            let qty = truncate_text(&format_quantity(item.quantity, item.unit.as_deref()), 9.0, 16.0);
+            layer.use_text(&qty, 9.0, Mm(98.0), Mm(y), font);
             let up = format!("{}{:.2}", currency, item.unit_price);
             layer.use_text(&up, 9.0, Mm(115.0), Mm(y), font);
             layer.use_text(&format!("{:.0}%", item.tax_percent), 9.0, Mm(140.0), Mm(y), font);
@@ -570,48 +593,23 @@ fn render_modern_pdf(
                     layer.set_fill_color(black.clone());
                 }
             }
+            if let Some(disc) = line_discount_text(item, currency) {
+                y -= 4.0;
+                layer.set_fill_color(gray.clone());
+                layer.use_text(&disc, 7.0, Mm(30.0), Mm(y), font);
+                layer.set_fill_color(black.clone());
+            }
             y -= 7.0;
         }
     }
 
     // Totals (right-aligned, minimal)
     y -= 6.0;
-    layer.set_fill_color(gray.clone());
-    layer.use_text("Subtotal", 9.0, Mm(130.0), Mm(y), font);
-    layer.set_fill_color(black.clone());
-    let v = format!("{}{:.2}", currency, invoice.subtotal);
-    layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 190.0)), Mm(y), font);
-    y -= 5.0;
-
-    layer.set_fill_color(gray.clone());
-    layer.use_text(&settings.tax_label, 9.0, Mm(130.0), Mm(y), font);
-    layer.set_fill_color(black.clone());
-    let v = format!("{}{:.2}", currency, invoice.tax);
-    layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 190.0)), Mm(y), font);
-    y -= 5.0;
-
-    if invoice.advance > 0.0 {
-        layer.set_fill_color(gray.clone());
-        layer.use_text("Advance", 9.0, Mm(130.0), Mm(y), font);
-        layer.set_fill_color(black.clone());
-        let v = format!("-{}{:.2}", currency, invoice.advance);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 190.0)), Mm(y), font);
-        y -= 5.0;
-    }
-    if invoice.discount_percent > 0.0 {
-        let da = (invoice.subtotal + invoice.tax) * invoice.discount_percent / 100.0;
-        layer.set_fill_color(gray.clone());
-        layer.use_text(&format!("Discount ({:.0}%)", invoice.discount_percent), 9.0, Mm(130.0), Mm(y), font);
-        layer.set_fill_color(black.clone());
-        let v = format!("-{}{:.2}", currency, da);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 190.0)), Mm(y), font);
-        y -= 5.0;
-    } else if invoice.discount > 0.0 {
+    for (label, value) in summary_rows(invoice, settings, currency) {
         layer.set_fill_color(gray.clone());
-        layer.use_text("Discount", 9.0, Mm(130.0), Mm(y), font);
+        layer.use_text(&label, 9.0, Mm(130.0), Mm(y), font);
         layer.set_fill_color(black.clone());
-        let v = format!("-{}{:.2}", currency, invoice.discount);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 190.0)), Mm(y), font);
+        layer.use_text(&value, 9.0, Mm(right_x(&value, 9.0, 190.0)), Mm(y), font);
         y -= 5.0;
     }
 
@@ -774,7 +772,8 @@ fn render_ClearStyle_pdf(
             layer.use_text(&format!("{}", i + 1), 9.0, Mm(23.0), Mm(y), font);
             let name = truncate_text(&item.product_name, 9.0, 55.0);
             layer.use_text(&name, 9.0, Mm(35.0), Mm(y), font);
-            layer.use_text(&item.quantity.to_string(), 9.0, Mm(100.0), Mm(y), font);
+            let qty = truncate_text(&format_quantity(item.quantity, item.unit.as_deref()), 9.0, 16.0);
+            layer.use_text(&qty, 9.0, Mm(100.0), Mm(y), font);
             let up = format!("{}{:.2}", currency, item.unit_price);
             layer.use_text(&up, 9.0, Mm(115.0), Mm(y), font);
             layer.use_text(&format!("{:.1}%", item.tax_percent), 9.0, Mm(145.0), Mm(y), font);
@@ -791,6 +790,12 @@ fn render_ClearStyle_pdf(
                     layer.set_fill_color(black.clone());
                 }
             }
+            if let Some(disc) = line_discount_text(item, currency) {
+                y -= 4.0;
+                layer.set_fill_color(gray.clone());
+                layer.use_text(&disc, 7.0, Mm(35.0), Mm(y), font);
+                layer.set_fill_color(black.clone());
+            }
 
             y -= 7.0;
         }
@@ -811,32 +816,9 @@ fn render_ClearStyle_pdf(
     y -= 5.0;
 
     layer.set_fill_color(black.clone());
-    layer.use_text("Subtotal:", 9.0, Mm(130.0), Mm(y), font);
-    let v = format!("{}{:.2}", currency, invoice.subtotal);
-    layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
-    y -= 5.0;
-
-    layer.use_text(&format!("{}:", &settings.tax_label), 9.0, Mm(130.0), Mm(y), font);
-    let v = format!("{}{:.2}", currency, invoice.tax);
-    layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
-    y -= 5.0;
-
-    if invoice.advance > 0.0 {
-        layer.use_text("Advance:", 9.0, Mm(130.0), Mm(y), font);
-        let v = format!("-{}{:.2}", currency, invoice.advance);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
-        y -= 5.0;
-    }
-    if invoice.discount_percent > 0.0 {
-        let da = (invoice.subtotal + invoice.tax) * invoice.discount_percent / 100.0;
-        layer.use_text(&format!("Discount ({:.0}%):", invoice.discount_percent), 9.0, Mm(130.0), Mm(y), font);
-        let v = format!("-{}{:.2}", currency, da);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
-        y -= 5.0;
-    } else if invoice.discount > 0.0 {
-        layer.use_text("Discount:", 9.0, Mm(130.0), Mm(y), font);
-        let v = format!("-{}{:.2}", currency, invoice.discount);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
+    for (label, value) in summary_rows(invoice, settings, currency) {
+        layer.use_text(&format!("{}:", label), 9.0, Mm(130.0), Mm(y), font);
+        layer.use_text(&value, 9.0, Mm(right_x(&value, 9.0, 188.0)), Mm(y), font);
         y -= 5.0;
     }
 
@@ -983,7 +965,8 @@ fn render_items_table(
             layer.use_text(&format!("{}", i + 1), 9.0, Mm(23.0), Mm(y), font);
             let name = truncate_text(&item.product_name, 9.0, 55.0);
             layer.use_text(&name, 9.0, Mm(33.0), Mm(y), font);
-            layer.use_text(&item.quantity.to_string(), 9.0, Mm(98.0), Mm(y), font);
+            let qty = truncate_text(&format_quantity(item.quantity, item.unit.as_deref()), 9.0, 16.0);
+            layer.use_text(&qty, 9.0, Mm(98.0), Mm(y), font);
             let up = format!("{}{:.2}", currency, item.unit_price);
             layer.use_text(&up, 9.0, Mm(112.0), Mm(y), font);
             layer.use_text(&format!("{:.1}%", item.tax_percent), 9.0, Mm(142.0), Mm(y), font);
@@ -1000,6 +983,12 @@ fn render_items_table(
                     layer.set_fill_color(black.clone());
                 }
             }
+            if let Some(disc) = line_discount_text(item, currency) {
+                y -= 4.0;
+                layer.set_fill_color(gray.clone());
+                layer.use_text(&disc, 7.0, Mm(33.0), Mm(y), font);
+                layer.set_fill_color(black.clone());
+            }
             y -= 7.0;
         }
     }
@@ -1045,32 +1034,9 @@ fn render_totals(
     y -= 5.0;
 
     layer.set_fill_color(black.clone());
-    layer.use_text("Subtotal:", 9.0, Mm(130.0), Mm(y), font);
-    let v = format!("{}{:.2}", currency, invoice.subtotal);
-    layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 190.0)), Mm(y), font);
-    y -= 5.0;
-
-    layer.use_text(&format!("{}:", &settings.tax_label), 9.0, Mm(130.0), Mm(y), font);
-    let v = format!("{}{:.2}", currency, invoice.tax);
-    layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 190.0)), Mm(y), font);
-    y -= 5.0;
-
-    if invoice.advance > 0.0 {
-        layer.use_text("Advance:", 9.0, Mm(130.0), Mm(y), font);
-        let v = format!("-{}{:.2}", currency, invoice.advance);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 190.0)), Mm(y), font);
-        y -= 5.0;
-    }
-    if invoice.discount_percent > 0.0 {
-        let da = (invoice.subtotal + invoice.tax) * invoice.discount_percent / 100.0;
-        layer.use_text(&format!("Discount ({:.0}%):", invoice.discount_percent), 9.0, Mm(130.0), Mm(y), font);
-        let v = format!("-{}{:.2}", currency, da);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 190.0)), Mm(y), font);
-        y -= 5.0;
-    } else if invoice.discount > 0.0 {
-        layer.use_text("Discount:", 9.0, Mm(130.0), Mm(y), font);
-        let v = format!("-{}{:.2}", currency, invoice.discount);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 190.0)), Mm(y), font);
+    for (label, value) in summary_rows(invoice, settings, currency) {
+        layer.use_text(&format!("{}:", label), 9.0, Mm(130.0), Mm(y), font);
+        layer.use_text(&value, 9.0, Mm(right_x(&value, 9.0, 190.0)), Mm(y), font);
         y -= 5.0;
     }
 
@@ -1173,17 +1139,48 @@ pub fn generate_payslip_pdf(
     settings: &crate::models::Settings,
     file_path: &str,
 ) -> Result<String, String> {
-    let (doc, page1, layer1) = PdfDocument::new(
-        &format!("Payslip {}", payroll.employee_name.as_deref().unwrap_or("")),
-        Mm(210.0),
-        Mm(297.0),
-        "Layer 1",
-    );
+    generate_payslips_pdf(std::slice::from_ref(payroll), settings, file_path)
+}
 
-    let layer = doc.get_page(page1).get_layer(layer1);
+/// Renders each payroll record as its own page of a single document.
+pub fn generate_payslips_pdf(
+    payrolls: &[crate::models::PayrollRecord],
+    settings: &crate::models::Settings,
+    file_path: &str,
+) -> Result<String, String> {
+    let first = payrolls.first().ok_or("No payslips to export")?;
+    let title = if payrolls.len() == 1 {
+        format!("Payslip {}", first.employee_name.as_deref().unwrap_or(""))
+    } else {
+        format!("Payslips {} - {}", first.pay_period_start, first.pay_period_end)
+    };
+    let (doc, page1, layer1) = PdfDocument::new(&title, Mm(210.0), Mm(297.0), "Layer 1");
     let font = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| e.to_string())?;
     let font_bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(|e| e.to_string())?;
 
+    for (i, payroll) in payrolls.iter().enumerate() {
+        let layer = if i == 0 {
+            doc.get_page(page1).get_layer(layer1)
+        } else {
+            let (page, layer_idx) = doc.add_page(Mm(210.0), Mm(297.0), "Layer 1");
+            doc.get_page(page).get_layer(layer_idx)
+        };
+        render_payslip(&layer, &font, &font_bold, payroll, settings);
+    }
+
+    let file = File::create(file_path).map_err(|e| e.to_string())?;
+    doc.save(&mut BufWriter::new(file)).map_err(|e| e.to_string())?;
+
+    Ok(file_path.to_string())
+}
+
+fn render_payslip(
+    layer: &printpdf::PdfLayerReference,
+    font: &printpdf::IndirectFontRef,
+    font_bold: &printpdf::IndirectFontRef,
+    payroll: &crate::models::PayrollRecord,
+    settings: &crate::models::Settings,
+) {
     let currency = &settings.currency_symbol;
     let black = Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None));
     let light_bg = Color::Rgb(Rgb::new(0.94, 0.94, 0.94, None));
@@ -1192,12 +1189,12 @@ pub fn generate_payslip_pdf(
 
     // ── Header ──
     layer.set_fill_color(black.clone());
-    layer.use_text(&settings.business_name, 16.0, Mm(20.0), Mm(y), &font_bold);
+    layer.use_text(&settings.business_name, 16.0, Mm(20.0), Mm(y), font_bold);
     y -= 10.0;
-    layer.use_text("PAYSLIP", 12.0, Mm(20.0), Mm(y), &font_bold);
+    layer.use_text("PAYSLIP", 12.0, Mm(20.0), Mm(y), font_bold);
 
     // Pay period on right
-    layer.use_text("Pay Period", 8.0, Mm(140.0), Mm(280.0), &font);
+    layer.use_text("Pay Period", 8.0, Mm(140.0), Mm(280.0), font);
     layer.use_text(
         &format!("{} - {}", &payroll.pay_period_start, &payroll.pay_period_end),
         9.0, Mm(140.0), Mm(274.0), &font_bold
@@ -1217,64 +1214,75 @@ pub fn generate_payslip_pdf(
 
     // ── Employee Details ──
     layer.set_fill_color(black.clone());
-    layer.use_text("EMPLOYEE NAME", 7.0, Mm(20.0), Mm(y), &font_bold);
-    layer.use_text("POSITION", 7.0, Mm(110.0), Mm(y), &font_bold);
+    layer.use_text("EMPLOYEE NAME", 7.0, Mm(20.0), Mm(y), font_bold);
+    layer.use_text("POSITION", 7.0, Mm(110.0), Mm(y), font_bold);
     y -= 5.0;
-    layer.use_text(payroll.employee_name.as_deref().unwrap_or("—"), 10.0, Mm(20.0), Mm(y), &font_bold);
-    layer.use_text(payroll.employee_role.as_deref().unwrap_or("—"), 10.0, Mm(110.0), Mm(y), &font_bold);
+    layer.use_text(payroll.employee_name.as_deref().unwrap_or("—"), 10.0, Mm(20.0), Mm(y), font_bold);
+    layer.use_text(payroll.employee_role.as_deref().unwrap_or("—"), 10.0, Mm(110.0), Mm(y), font_bold);
     y -= 8.0;
 
-    layer.use_text("PAYMENT DATE", 7.0, Mm(20.0), Mm(y), &font_bold);
-    layer.use_text("STATUS", 7.0, Mm(110.0), Mm(y), &font_bold);
+    layer.use_text("PAYMENT DATE", 7.0, Mm(20.0), Mm(y), font_bold);
+    layer.use_text("STATUS", 7.0, Mm(110.0), Mm(y), font_bold);
     y -= 5.0;
-    layer.use_text(&payroll.payment_date, 10.0, Mm(20.0), Mm(y), &font);
-    layer.use_text(&payroll.status, 10.0, Mm(110.0), Mm(y), &font);
+    layer.use_text(&payroll.payment_date, 10.0, Mm(20.0), Mm(y), font);
+    layer.use_text(&payroll.status, 10.0, Mm(110.0), Mm(y), font);
     y -= 12.0;
 
     // ── Earnings Table Header ──
-    draw_rect(&layer, 20.0, y - 2.0, 170.0, 8.0, black.clone());
+    draw_rect(layer, 20.0, y - 2.0, 170.0, 8.0, black.clone());
     layer.set_fill_color(Color::Rgb(Rgb::new(1.0, 1.0, 1.0, None)));
-    layer.use_text("Description", 8.0, Mm(25.0), Mm(y), &font_bold);
+    layer.use_text("Description", 8.0, Mm(25.0), Mm(y), font_bold);
     let th = "Amount";
-    layer.use_text(th, 8.0, Mm(right_x(th, 8.0, 188.0)), Mm(y), &font_bold);
+    layer.use_text(th, 8.0, Mm(right_x(th, 8.0, 188.0)), Mm(y), font_bold);
     y -= 10.0;
 
     // ── Earnings rows ──
     layer.set_fill_color(black.clone());
 
     // Base Salary
-    draw_rect(&layer, 20.0, y - 2.0, 170.0, 7.0, light_bg.clone());
+    draw_rect(layer, 20.0, y - 2.0, 170.0, 7.0, light_bg.clone());
     layer.set_fill_color(black.clone());
-    layer.use_text("Base Salary", 9.0, Mm(25.0), Mm(y), &font);
+    layer.use_text("Base Salary", 9.0, Mm(25.0), Mm(y), font);
     let v = format!("{}{:.2}", currency, payroll.base_salary);
-    layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), &font);
+    layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
     y -= 7.0;
 
     // Overtime Pay
     if payroll.overtime_pay > 0.0 {
         layer.set_fill_color(black.clone());
-        layer.use_text("Overtime Pay", 9.0, Mm(25.0), Mm(y), &font);
+        layer.use_text("Overtime Pay", 9.0, Mm(25.0), Mm(y), font);
         let v = format!("{}{:.2}", currency, payroll.overtime_pay);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), &font);
+        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
         y -= 7.0;
     }
 
     // Bonuses
     if payroll.bonuses > 0.0 {
-        draw_rect(&layer, 20.0, y - 2.0, 170.0, 7.0, light_bg.clone());
+        draw_rect(layer, 20.0, y - 2.0, 170.0, 7.0, light_bg.clone());
         layer.set_fill_color(black.clone());
-        layer.use_text("Bonuses", 9.0, Mm(25.0), Mm(y), &font);
+        layer.use_text("Bonuses", 9.0, Mm(25.0), Mm(y), font);
         let v = format!("{}{:.2}", currency, payroll.bonuses);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), &font);
+        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
         y -= 7.0;
     }
 
-    // Allowances
-    if payroll.allowances > 0.0 {
+    // Allowances, itemised by earning component where payroll rules produced them
+    let lines = payroll.lines.as_deref().unwrap_or(&[]);
+    let earnings: Vec<_> = lines.iter().filter(|l| l.line_type == "Earning").collect();
+    let component_earnings: f64 = earnings.iter().map(|l| l.amount).sum();
+    let fixed_allowances = payroll.allowances - component_earnings;
+    if fixed_allowances > 0.005 {
+        layer.set_fill_color(black.clone());
+        layer.use_text("Allowances", 9.0, Mm(25.0), Mm(y), font);
+        let v = format!("{}{:.2}", currency, fixed_allowances);
+        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
+        y -= 7.0;
+    }
+    for l in &earnings {
         layer.set_fill_color(black.clone());
-        layer.use_text("Allowances", 9.0, Mm(25.0), Mm(y), &font);
-        let v = format!("{}{:.2}", currency, payroll.allowances);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), &font);
+        layer.use_text(&l.name, 9.0, Mm(25.0), Mm(y), font);
+        let v = format!("{}{:.2}", currency, l.amount);
+        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
         y -= 7.0;
     }
 
@@ -1291,9 +1299,9 @@ pub fn generate_payslip_pdf(
     layer.add_line(sep);
     y -= 6.0;
     layer.set_fill_color(black.clone());
-    layer.use_text("Gross Salary", 9.0, Mm(25.0), Mm(y), &font_bold);
+    layer.use_text("Gross Salary", 9.0, Mm(25.0), Mm(y), font_bold);
     let v = format!("{}{:.2}", currency, payroll.gross_salary);
-    layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), &font_bold);
+    layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font_bold);
     y -= 12.0;
 
     // ── Deductions Table ──
@@ -1301,43 +1309,52 @@ pub fn generate_payslip_pdf(
         || payroll.absences > 0.0 || payroll.other_deductions > 0.0;
 
     if has_deductions {
-        draw_rect(&layer, 20.0, y - 2.0, 170.0, 8.0, black.clone());
+        draw_rect(layer, 20.0, y - 2.0, 170.0, 8.0, black.clone());
         layer.set_fill_color(Color::Rgb(Rgb::new(1.0, 1.0, 1.0, None)));
-        layer.use_text("Deductions", 8.0, Mm(25.0), Mm(y), &font_bold);
+        layer.use_text("Deductions", 8.0, Mm(25.0), Mm(y), font_bold);
         let th = "Amount";
-        layer.use_text(th, 8.0, Mm(right_x(th, 8.0, 188.0)), Mm(y), &font_bold);
+        layer.use_text(th, 8.0, Mm(right_x(th, 8.0, 188.0)), Mm(y), font_bold);
         y -= 10.0;
 
         layer.set_fill_color(black.clone());
 
         if payroll.tax > 0.0 {
-            draw_rect(&layer, 20.0, y - 2.0, 170.0, 7.0, light_bg.clone());
+            draw_rect(layer, 20.0, y - 2.0, 170.0, 7.0, light_bg.clone());
             layer.set_fill_color(black.clone());
-            layer.use_text("Tax", 9.0, Mm(25.0), Mm(y), &font);
+            layer.use_text("Tax", 9.0, Mm(25.0), Mm(y), font);
             let v = format!("-{}{:.2}", currency, payroll.tax);
-            layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), &font);
+            layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
             y -= 7.0;
         }
         if payroll.late_penalties > 0.0 {
             layer.set_fill_color(black.clone());
-            layer.use_text("Late Penalties", 9.0, Mm(25.0), Mm(y), &font);
+            layer.use_text("Late Penalties", 9.0, Mm(25.0), Mm(y), font);
             let v = format!("-{}{:.2}", currency, payroll.late_penalties);
-            layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), &font);
+            layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
             y -= 7.0;
         }
         if payroll.absences > 0.0 {
-            draw_rect(&layer, 20.0, y - 2.0, 170.0, 7.0, light_bg.clone());
+            draw_rect(layer, 20.0, y - 2.0, 170.0, 7.0, light_bg.clone());
             layer.set_fill_color(black.clone());
-            layer.use_text("Absences", 9.0, Mm(25.0), Mm(y), &font);
+            layer.use_text("Absences", 9.0, Mm(25.0), Mm(y), font);
             let v = format!("-{}{:.2}", currency, payroll.absences);
-            layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), &font);
+            layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
             y -= 7.0;
         }
-        if payroll.other_deductions > 0.0 {
+        let mut itemised = 0.0;
+        for l in lines.iter().filter(|l| (l.line_type == "Deduction" || l.line_type == "Contribution") && l.amount > 0.0) {
+            itemised += l.amount;
             layer.set_fill_color(black.clone());
-            layer.use_text("Other Deductions", 9.0, Mm(25.0), Mm(y), &font);
-            let v = format!("-{}{:.2}", currency, payroll.other_deductions);
-            layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), &font);
+            layer.use_text(&l.name, 9.0, Mm(25.0), Mm(y), font);
+            let v = format!("-{}{:.2}", currency, l.amount);
+            layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
+            y -= 7.0;
+        }
+        if payroll.other_deductions - itemised > 0.005 {
+            layer.set_fill_color(black.clone());
+            layer.use_text("Other Deductions", 9.0, Mm(25.0), Mm(y), font);
+            let v = format!("-{}{:.2}", currency, payroll.other_deductions - itemised);
+            layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font);
             y -= 7.0;
         }
 
@@ -1353,9 +1370,9 @@ pub fn generate_payslip_pdf(
         layer.add_line(sep);
         y -= 6.0;
         layer.set_fill_color(black.clone());
-        layer.use_text("Total Deductions", 9.0, Mm(25.0), Mm(y), &font_bold);
+        layer.use_text("Total Deductions", 9.0, Mm(25.0), Mm(y), font_bold);
         let v = format!("-{}{:.2}", currency, payroll.total_deductions);
-        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), &font_bold);
+        layer.use_text(&v, 9.0, Mm(right_x(&v, 9.0, 188.0)), Mm(y), font_bold);
         y -= 12.0;
     }
 
@@ -1379,27 +1396,448 @@ pub fn generate_payslip_pdf(
     };
     layer.add_line(thick_bot);
     layer.set_fill_color(black.clone());
-    layer.use_text("NET PAY", 12.0, Mm(25.0), Mm(y), &font_bold);
+    layer.use_text("NET PAY", 12.0, Mm(25.0), Mm(y), font_bold);
     let np = format!("{}{:.2}", currency, payroll.net_pay);
-    layer.use_text(&np, 12.0, Mm(right_x(&np, 12.0, 188.0)), Mm(y), &font_bold);
+    layer.use_text(&np, 12.0, Mm(right_x(&np, 12.0, 188.0)), Mm(y), font_bold);
     y -= 18.0;
 
+    // ── Employer contributions (paid on top of net pay) ──
+    if payroll.employer_contributions > 0.0 {
+        layer.use_text("Employer Contributions", 9.0, Mm(20.0), Mm(y), font_bold);
+        y -= 6.0;
+        for l in lines.iter().filter(|l| l.employer_amount > 0.0) {
+            layer.use_text(&l.name, 8.0, Mm(25.0), Mm(y), font);
+            let v = format!("{}{:.2}", currency, l.employer_amount);
+            layer.use_text(&v, 8.0, Mm(right_x(&v, 8.0, 188.0)), Mm(y), font);
+            y -= 5.0;
+        }
+        y -= 5.0;
+    }
+
+    // ── Loans and advances still outstanding after this payslip ──
+    let loans: Vec<_> = lines.iter().filter(|l| l.loan_id.is_some()).collect();
+    if !loans.is_empty() {
+        layer.use_text("Loan Balances", 9.0, Mm(20.0), Mm(y), font_bold);
+        y -= 6.0;
+        for l in &loans {
+            layer.use_text(&l.name, 8.0, Mm(25.0), Mm(y), font);
+            let v = format!("{}{:.2}", currency, l.loan_balance.unwrap_or(0.0).max(0.0));
+            layer.use_text(&v, 8.0, Mm(right_x(&v, 8.0, 188.0)), Mm(y), font);
+            y -= 5.0;
+        }
+        y -= 5.0;
+    }
+
     // ── Notes ──
     if let Some(ref notes) = payroll.notes {
         if !notes.is_empty() {
-            layer.use_text("Notes:", 9.0, Mm(20.0), Mm(y), &font_bold);
+            layer.use_text("Notes:", 9.0, Mm(20.0), Mm(y), font_bold);
             y -= 5.0;
-            layer.use_text(notes, 8.0, Mm(20.0), Mm(y), &font);
+            layer.use_text(notes, 8.0, Mm(20.0), Mm(y), font);
             y -= 8.0;
         }
     }
 
     // ── Footer ──
-    layer.use_text(&settings.business_name, 7.0, Mm(20.0), Mm(25.0), &font);
+    layer.use_text(&settings.business_name, 7.0, Mm(20.0), Mm(25.0), font);
+}
+// ══════════════════════════════════════════════════════════
+//  YEAR-END EARNINGS CERTIFICATE
+// ══════════════════════════════════════════════════════════
+
+/// One description/amount row of a certificate table; `shade` draws the light band.
+fn certificate_row(
+    layer: &printpdf::PdfLayerReference,
+    font: &printpdf::IndirectFontRef,
+    label: &str,
+    value: &str,
+    y: f32,
+    shade: bool,
+) {
+    if shade {
+        draw_rect(layer, 20.0, y - 2.0, 170.0, 7.0, Color::Rgb(Rgb::new(0.94, 0.94, 0.94, None)));
+    }
+    layer.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+    layer.use_text(label, 9.0, Mm(25.0), Mm(y), font);
+    layer.use_text(value, 9.0, Mm(right_x(value, 9.0, 188.0)), Mm(y), font);
+}
+
+fn certificate_heading(layer: &printpdf::PdfLayerReference, font_bold: &printpdf::IndirectFontRef, title: &str, y: f32) {
+    draw_rect(layer, 20.0, y - 2.0, 170.0, 8.0, Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+    layer.set_fill_color(Color::Rgb(Rgb::new(1.0, 1.0, 1.0, None)));
+    layer.use_text(title, 8.0, Mm(25.0), Mm(y), font_bold);
+    layer.use_text("Amount", 8.0, Mm(right_x("Amount", 8.0, 188.0)), Mm(y), font_bold);
+}
+
+fn certificate_rule(layer: &printpdf::PdfLayerReference, y: f32) {
+    layer.set_outline_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+    layer.add_line(Line {
+        points: vec![
+            (Point::new(Mm(20.0), Mm(y)), false),
+            (Point::new(Mm(190.0), Mm(y)), false),
+        ],
+        is_closed: false,
+    });
+}
+
+/// Renders one certificate of earnings and tax deducted per employee, each on its
+/// own page of a single document.
+pub fn generate_earnings_certificates_pdf(
+    summaries: &[crate::models::YearEndSummary],
+    settings: &Settings,
+    file_path: &str,
+) -> Result<String, String> {
+    let first = summaries.first().ok_or("No earnings to report")?;
+    let (doc, page1, layer1) = PdfDocument::new(
+        &format!("Earnings Certificates {} - {}", first.tax_year_start, first.tax_year_end),
+        Mm(210.0),
+        Mm(297.0),
+        "Layer 1",
+    );
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| e.to_string())?;
+    let font_bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(|e| e.to_string())?;
+
+    for (i, summary) in summaries.iter().enumerate() {
+        let layer = if i == 0 {
+            doc.get_page(page1).get_layer(layer1)
+        } else {
+            let (page, layer_idx) = doc.add_page(Mm(210.0), Mm(297.0), "Layer 1");
+            doc.get_page(page).get_layer(layer_idx)
+        };
+        render_earnings_certificate(&layer, &font, &font_bold, summary, settings);
+    }
+
+    let file = File::create(file_path).map_err(|e| e.to_string())?;
+    doc.save(&mut BufWriter::new(file)).map_err(|e| e.to_string())?;
+
+    Ok(file_path.to_string())
+}
+
+fn render_earnings_certificate(
+    layer: &printpdf::PdfLayerReference,
+    font: &printpdf::IndirectFontRef,
+    font_bold: &printpdf::IndirectFontRef,
+    s: &crate::models::YearEndSummary,
+    settings: &Settings,
+) {
+    let currency = &settings.currency_symbol;
+    let black = Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None));
+    let money = |v: f64| format!("{}{:.2}", currency, v);
+    let mut y: f32 = 280.0;
+
+    // ── Header ──
+    layer.set_fill_color(black.clone());
+    layer.use_text(&settings.business_name, 16.0, Mm(20.0), Mm(y), font_bold);
+    if let Some(ref address) = settings.business_address {
+        layer.use_text(address, 8.0, Mm(20.0), Mm(y - 6.0), font);
+    }
+    layer.use_text("Tax Year", 8.0, Mm(140.0), Mm(280.0), font);
+    layer.use_text(&format!("{} - {}", s.tax_year_start, s.tax_year_end), 9.0, Mm(140.0), Mm(274.0), font_bold);
+    y -= 16.0;
+    layer.use_text("CERTIFICATE OF EARNINGS AND TAX DEDUCTED", 12.0, Mm(20.0), Mm(y), font_bold);
+    y -= 4.0;
+    certificate_rule(layer, y);
+    y -= 8.0;
+
+    // ── Employee ──
+    layer.set_fill_color(black.clone());
+    layer.use_text("EMPLOYEE NAME", 7.0, Mm(20.0), Mm(y), font_bold);
+    layer.use_text("POSITION", 7.0, Mm(110.0), Mm(y), font_bold);
+    y -= 5.0;
+    layer.use_text(s.employee_name.as_deref().unwrap_or("—"), 10.0, Mm(20.0), Mm(y), font_bold);
+    layer.use_text(s.employee_role.as_deref().unwrap_or("—"), 10.0, Mm(110.0), Mm(y), font_bold);
+    y -= 8.0;
+    layer.use_text("PAYSLIPS", 7.0, Mm(20.0), Mm(y), font_bold);
+    layer.use_text("ISSUED", 7.0, Mm(110.0), Mm(y), font_bold);
+    y -= 5.0;
+    layer.use_text(&s.payslips.to_string(), 10.0, Mm(20.0), Mm(y), font);
+    layer.use_text(&chrono::Local::now().format("%Y-%m-%d").to_string(), 10.0, Mm(110.0), Mm(y), font);
+    y -= 12.0;
+
+    // ── Earnings ──
+    certificate_heading(layer, font_bold, "Earnings", y);
+    y -= 10.0;
+    let earnings: Vec<_> = s.components.iter().filter(|c| c.line_type == "Earning").collect();
+    let fixed_allowances = s.allowances - earnings.iter().map(|c| c.amount).sum::<f64>();
+    let mut rows: Vec<(String, f64)> = vec![("Basic Salary".to_string(), s.base_salary)];
+    if fixed_allowances > 0.005 {
+        rows.push(("Allowances".to_string(), fixed_allowances));
+    }
+    rows.extend(earnings.iter().map(|c| (c.name.clone(), c.amount)));
+    if s.overtime_pay > 0.0 {
+        rows.push(("Overtime Pay".to_string(), s.overtime_pay));
+    }
+    if s.bonuses > 0.0 {
+        rows.push(("Bonuses".to_string(), s.bonuses));
+    }
+    for (n, (label, amount)) in rows.iter().enumerate() {
+        certificate_row(layer, font, label, &money(*amount), y, n % 2 == 0);
+        y -= 7.0;
+    }
+    y -= 2.0;
+    certificate_row(layer, font_bold, "Gross Earnings", &money(s.gross_salary), y - 6.0, false);
+    certificate_rule(layer, y);
+    y -= 18.0;
+
+    // ── Deductions ──
+    certificate_heading(layer, font_bold, "Deductions", y);
+    y -= 10.0;
+    let mut rows: Vec<(String, f64)> = vec![("Income Tax Deducted".to_string(), s.tax)];
+    let mut itemised = 0.0;
+    for c in s.components.iter().filter(|c| (c.line_type == "Deduction" || c.line_type == "Contribution") && c.amount > 0.0) {
+        itemised += c.amount;
+        rows.push((c.name.clone(), c.amount));
+    }
+    if s.other_deductions - itemised > 0.005 {
+        rows.push(("Other Deductions".to_string(), s.other_deductions - itemised));
+    }
+    if s.late_penalties > 0.0 {
+        rows.push(("Late Penalties".to_string(), s.late_penalties));
+    }
+    if s.absences > 0.0 {
+        rows.push(("Absences".to_string(), s.absences));
+    }
+    for (n, (label, amount)) in rows.iter().enumerate() {
+        certificate_row(layer, font, label, &money(*amount), y, n % 2 == 0);
+        y -= 7.0;
+    }
+    y -= 2.0;
+    certificate_row(layer, font_bold, "Total Deductions", &money(s.total_deductions), y - 6.0, false);
+    certificate_rule(layer, y);
+    y -= 18.0;
+
+    // ── Net Pay ──
+    certificate_rule(layer, y + 6.0);
+    certificate_rule(layer, y - 8.0);
+    layer.set_fill_color(black.clone());
+    layer.use_text("NET PAY", 12.0, Mm(25.0), Mm(y), font_bold);
+    let np = money(s.net_pay);
+    layer.use_text(&np, 12.0, Mm(right_x(&np, 12.0, 188.0)), Mm(y), font_bold);
+    y -= 18.0;
+
+    // ── Employer contributions ──
+    if s.employer_contributions > 0.0 {
+        layer.use_text("Employer Contributions", 9.0, Mm(20.0), Mm(y), font_bold);
+        y -= 6.0;
+        for c in s.components.iter().filter(|c| c.employer_amount > 0.0) {
+            layer.use_text(&c.name, 8.0, Mm(25.0), Mm(y), font);
+            let v = money(c.employer_amount);
+            layer.use_text(&v, 8.0, Mm(right_x(&v, 8.0, 188.0)), Mm(y), font);
+            y -= 5.0;
+        }
+    }
+
+    // ── Certification ──
+    layer.use_text(
+        "Certified that the above particulars are correct according to the payroll records.",
+        8.0, Mm(20.0), Mm(50.0), font,
+    );
+    if let Some(ref sig_path) = settings.signature_path {
+        add_image(layer, sig_path, 150.0, 38.0, 15.0);
+    }
+    certificate_rule_at(layer, 145.0, 190.0, 36.0);
+    layer.use_text("Authorised Signatory", 8.0, Mm(153.0), Mm(32.0), font);
+    layer.use_text(&settings.business_name, 7.0, Mm(20.0), Mm(25.0), font);
+}
+
+fn certificate_rule_at(layer: &printpdf::PdfLayerReference, x1: f32, x2: f32, y: f32) {
+    layer.set_outline_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+    layer.add_line(Line {
+        points: vec![
+            (Point::new(Mm(x1), Mm(y)), false),
+            (Point::new(Mm(x2), Mm(y)), false),
+        ],
+        is_closed: false,
+    });
+}
+
+// ══════════════════════════════════════════════════════════
+//  PAYROLL REGISTER
+// ══════════════════════════════════════════════════════════
+
+const REGISTER_NAME_W: f32 = 52.0;
+const REGISTER_LEFT: f32 = 12.0;
+const REGISTER_RIGHT: f32 = 285.0;
+
+fn register_header(
+    layer: &printpdf::PdfLayerReference,
+    font_bold: &printpdf::IndirectFontRef,
+    col_w: f32,
+    y: f32,
+) {
+    draw_rect(layer, REGISTER_LEFT, y - 5.0, REGISTER_RIGHT - REGISTER_LEFT, 10.0, Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+    layer.set_fill_color(Color::Rgb(Rgb::new(1.0, 1.0, 1.0, None)));
+    layer.use_text("Employee", 7.0, Mm(REGISTER_LEFT + 2.0), Mm(y - 1.5), font_bold);
+    for (i, (label, _)) in crate::payroll::REGISTER_COLUMNS.iter().enumerate() {
+        let edge = REGISTER_LEFT + REGISTER_NAME_W + col_w * (i + 1) as f32 - 1.5;
+        // Two-word headers wrap onto a second line to fit the narrow columns
+        let (top, bottom) = label.split_once(' ').unwrap_or(("", label));
+        if !top.is_empty() {
+            layer.use_text(top, 6.5, Mm(right_x(top, 6.5, edge)), Mm(y + 1.0), font_bold);
+        }
+        layer.use_text(bottom, 6.5, Mm(right_x(bottom, 6.5, edge)), Mm(y - 2.5), font_bold);
+    }
+}
+
+/// Landscape register of a pay period: one row per employee, a column per
+/// earnings and deduction amount, and a totals row.
+pub fn generate_payroll_register_pdf(
+    rows: &[crate::payroll::RegisterRow],
+    totals: &[f64],
+    pay_period_start: &str,
+    pay_period_end: &str,
+    settings: &Settings,
+    file_path: &str,
+) -> Result<String, String> {
+    let (doc, page1, layer1) = PdfDocument::new("Payroll Register", Mm(297.0), Mm(210.0), "Layer 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| e.to_string())?;
+    let font_bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(|e| e.to_string())?;
+    let black = Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None));
+    let light_bg = Color::Rgb(Rgb::new(0.94, 0.94, 0.94, None));
+    let col_w = (REGISTER_RIGHT - REGISTER_LEFT - REGISTER_NAME_W) / crate::payroll::REGISTER_COLUMNS.len() as f32;
+    let amount_x = |i: usize| REGISTER_LEFT + REGISTER_NAME_W + col_w * (i + 1) as f32 - 1.5;
+
+    let mut layer = doc.get_page(page1).get_layer(layer1);
+    layer.set_fill_color(black.clone());
+    layer.use_text(&settings.business_name, 14.0, Mm(REGISTER_LEFT), Mm(198.0), &font_bold);
+    layer.use_text("PAYROLL REGISTER", 10.0, Mm(REGISTER_LEFT), Mm(191.0), &font_bold);
+    let period = format!("Pay Period {} - {}", pay_period_start, pay_period_end);
+    layer.use_text(&period, 9.0, Mm(right_x(&period, 9.0, REGISTER_RIGHT)), Mm(191.0), &font);
+    let currency_note = format!("Amounts in {}", settings.currency_symbol);
+    layer.use_text(&currency_note, 7.0, Mm(right_x(&currency_note, 7.0, REGISTER_RIGHT)), Mm(186.0), &font);
+
+    let mut y: f32 = 178.0;
+    register_header(&layer, &font_bold, col_w, y);
+    y -= 11.0;
+
+    for (n, row) in rows.iter().enumerate() {
+        if y < 22.0 {
+            let (page, layer_idx) = doc.add_page(Mm(297.0), Mm(210.0), "Layer 1");
+            layer = doc.get_page(page).get_layer(layer_idx);
+            y = 195.0;
+            register_header(&layer, &font_bold, col_w, y);
+            y -= 11.0;
+        }
+        if n % 2 == 1 {
+            draw_rect(&layer, REGISTER_LEFT, y - 2.0, REGISTER_RIGHT - REGISTER_LEFT, 6.0, light_bg.clone());
+        }
+        layer.set_fill_color(black.clone());
+        let name = match &row.employee_role {
+            Some(role) if !role.is_empty() => format!("{} ({})", row.employee_name, role),
+            _ => row.employee_name.clone(),
+        };
+        layer.use_text(&truncate_text(&name, 7.0, REGISTER_NAME_W - 3.0), 7.0, Mm(REGISTER_LEFT + 2.0), Mm(y), &font);
+        for (i, amount) in row.amounts.iter().enumerate() {
+            let v = format!("{:.2}", amount);
+            layer.use_text(&v, 7.0, Mm(right_x(&v, 7.0, amount_x(i))), Mm(y), &font);
+        }
+        y -= 6.0;
+    }
+
+    // ── Totals ──
+    layer.set_outline_color(black.clone());
+    layer.add_line(Line {
+        points: vec![
+            (Point::new(Mm(REGISTER_LEFT), Mm(y + 3.5)), false),
+            (Point::new(Mm(REGISTER_RIGHT), Mm(y + 3.5)), false),
+        ],
+        is_closed: false,
+    });
+    layer.set_fill_color(black.clone());
+    let label = format!("Total ({} employee{})", rows.len(), if rows.len() == 1 { "" } else { "s" });
+    layer.use_text(&label, 7.0, Mm(REGISTER_LEFT + 2.0), Mm(y - 1.0), &font_bold);
+    for (i, amount) in totals.iter().enumerate() {
+        let v = format!("{:.2}", amount);
+        layer.use_text(&v, 7.0, Mm(right_x(&v, 7.0, amount_x(i))), Mm(y - 1.0), &font_bold);
+    }
+
+    let file = File::create(file_path).map_err(|e| e.to_string())?;
+    doc.save(&mut BufWriter::new(file)).map_err(|e| e.to_string())?;
+
+    Ok(file_path.to_string())
+}
+
+// ══════════════════════════════════════════════════════════
+//  BARCODE LABEL SHEET
+// ══════════════════════════════════════════════════════════
+
+/// A4 sheet of 3 x 7 labels (63.5 x 38.1 mm, the common L7160 layout).
+const LABEL_COLS: usize = 3;
+const LABEL_ROWS: usize = 7;
+const LABEL_W: f32 = 63.5;
+const LABEL_H: f32 = 38.1;
+const LABEL_LEFT: f32 = 7.2;
+const LABEL_TOP: f32 = 15.1;
+const LABEL_GAP_X: f32 = 2.5;
+/// Narrowest bar typical handheld scanners read reliably (about 7.5 mil).
+const LABEL_MIN_MODULE_W: f32 = 0.19;
+
+/// Renders one label per entry in `products`, each with its name, barcode, the
+/// human-readable code and unit price. Callers repeat products for multiple copies.
+/// The barcode falls back to the SKU (as Code 128) when a product has no barcode.
+pub fn generate_barcode_labels_pdf(
+    products: &[crate::models::Product],
+    settings: &Settings,
+    file_path: &str,
+) -> Result<String, String> {
+    let (doc, page1, layer1) = PdfDocument::new("Barcode Labels", Mm(210.0), Mm(297.0), "Layer 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| e.to_string())?;
+    let font_bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(|e| e.to_string())?;
+    let black = Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None));
+    let per_page = LABEL_COLS * LABEL_ROWS;
+
+    let mut layer = doc.get_page(page1).get_layer(layer1);
+    for (i, product) in products.iter().enumerate() {
+        if i > 0 && i % per_page == 0 {
+            let (page, layer_idx) = doc.add_page(Mm(210.0), Mm(297.0), "Layer 1");
+            layer = doc.get_page(page).get_layer(layer_idx);
+        }
+        let slot = i % per_page;
+        let x = LABEL_LEFT + (slot % LABEL_COLS) as f32 * (LABEL_W + LABEL_GAP_X);
+        let top = 297.0 - LABEL_TOP - (slot / LABEL_COLS) as f32 * LABEL_H;
+
+        let code = product.barcode.as_deref()
+            .or(product.sku.as_deref())
+            .ok_or_else(|| format!("{} has no barcode or SKU", product.name))?;
+        let (_, modules) = crate::barcode::encode(code)?;
+
+        layer.set_fill_color(black.clone());
+        let name = truncate_text(&product.name, 8.0, LABEL_W - 24.0);
+        layer.use_text(&name, 8.0, Mm(x + 3.0), Mm(top - 6.0), &font_bold);
+
+        // Ten-module quiet zone either side, capped so short codes don't get too wide
+        let module_w = ((LABEL_W - 6.0) / (modules.len() + 20) as f32).min(0.4);
+        if module_w < LABEL_MIN_MODULE_W {
+            return Err(format!(
+                "The code {} of {} is too long to scan reliably on a label; use a shorter barcode or SKU",
+                code, product.name
+            ));
+        }
+        let bars_w = module_w * modules.len() as f32;
+        let mut bx = x + (LABEL_W - bars_w) / 2.0;
+        let bar_h = 16.0;
+        let bar_y = top - 26.0;
+        let mut run_start: Option<f32> = None;
+        for bar in modules.iter().chain(std::iter::once(&false)) {
+            match (bar, run_start) {
+                (true, None) => run_start = Some(bx),
+                (false, Some(start)) => {
+                    draw_rect(&layer, start, bar_y, bx - start, bar_h, black.clone());
+                    run_start = None;
+                }
+                _ => {}
+            }
+            bx += module_w;
+        }
+
+        layer.set_fill_color(black.clone());
+        layer.use_text(code, 7.0, Mm(x + (LABEL_W - text_width_mm(code, 7.0)) / 2.0), Mm(bar_y - 4.0), &font);
+        let price = format!("{}{:.2}", settings.currency_symbol, product.unit_price);
+        layer.use_text(&price, 9.0, Mm(right_x(&price, 9.0, x + LABEL_W - 3.0)), Mm(top - 6.0), &font_bold);
+    }
 
-    // Save
     let file = File::create(file_path).map_err(|e| e.to_string())?;
     doc.save(&mut BufWriter::new(file)).map_err(|e| e.to_string())?;
 
     Ok(file_path.to_string())
-}
\ No newline at end of file
+}
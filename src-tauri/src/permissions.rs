@@ -0,0 +1,135 @@
+//! The permission registry.
+//!
+//! Every permission a command can check is listed here once. The registry seeds
+//! the `permissions` table on startup, is what `check_permission` takes, and is
+//! what user and role grants are validated against.
+
+/// A single grantable action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ViewInvoices,
+    CreateInvoices,
+    EditInvoices,
+    DeleteInvoices,
+    ExportInvoices,
+    ViewTransactions,
+    CreateTransactions,
+    EditTransactions,
+    DeleteTransactions,
+    ViewReports,
+    ExportReports,
+    ViewCustomers,
+    CreateCustomers,
+    EditCustomers,
+    DeleteCustomers,
+    ViewEmployees,
+    CreateEmployees,
+    EditEmployees,
+    DeleteEmployees,
+    ViewPayroll,
+    ManagePayroll,
+    ViewProducts,
+    CreateProducts,
+    EditProducts,
+    DeleteProducts,
+    ManageInventory,
+    ManageTemplates,
+    ManageSettings,
+    ManageUsers,
+    ViewActivityLogs,
+}
+
+use Permission::*;
+
+impl Permission {
+    /// Every registered permission, in the order they are listed to admins.
+    pub const ALL: [Permission; 30] = [
+        ViewInvoices, CreateInvoices, EditInvoices, DeleteInvoices, ExportInvoices,
+        ViewTransactions, CreateTransactions, EditTransactions, DeleteTransactions,
+        ViewReports, ExportReports,
+        ViewCustomers, CreateCustomers, EditCustomers, DeleteCustomers,
+        ViewEmployees, CreateEmployees, EditEmployees, DeleteEmployees,
+        ViewPayroll, ManagePayroll,
+        ViewProducts, CreateProducts, EditProducts, DeleteProducts,
+        ManageInventory, ManageTemplates, ManageSettings,
+        ManageUsers, ViewActivityLogs,
+    ];
+
+    /// (name, group, description)
+    fn info(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            ViewInvoices => ("view_invoices", "Financial Management", "View invoice list and details"),
+            CreateInvoices => ("create_invoices", "Financial Management", "Create new invoices"),
+            EditInvoices => ("edit_invoices", "Financial Management", "Edit existing invoices and change status"),
+            DeleteInvoices => ("delete_invoices", "Financial Management", "Delete invoices"),
+            ExportInvoices => ("export_invoices", "Financial Management", "Export invoices to PDF/Excel"),
+            ViewTransactions => ("view_transactions", "Financial Management", "View transaction history and records"),
+            CreateTransactions => ("create_transactions", "Financial Management", "Record new income/expense transactions"),
+            EditTransactions => ("edit_transactions", "Financial Management", "Edit existing transactions and pay expenses"),
+            DeleteTransactions => ("delete_transactions", "Financial Management", "Delete transaction records"),
+            ViewReports => ("view_reports", "Financial Management", "Access business reports and dashboards"),
+            ExportReports => ("export_reports", "Financial Management", "Export report data to CSV/Excel"),
+            ViewCustomers => ("view_customers", "Team & Operations", "View customer list and details"),
+            CreateCustomers => ("create_customers", "Team & Operations", "Add new customers"),
+            EditCustomers => ("edit_customers", "Team & Operations", "Edit customer information"),
+            DeleteCustomers => ("delete_customers", "Team & Operations", "Remove customers"),
+            ViewEmployees => ("view_employees", "Team & Operations", "View employee roster"),
+            CreateEmployees => ("create_employees", "Team & Operations", "Add new employees"),
+            EditEmployees => ("edit_employees", "Team & Operations", "Edit employee details"),
+            DeleteEmployees => ("delete_employees", "Team & Operations", "Remove employees"),
+            ViewPayroll => ("view_payroll", "Team & Operations", "View payroll information and summaries"),
+            ManagePayroll => ("manage_payroll", "Team & Operations", "Create and manage payroll records"),
+            ViewProducts => ("view_products", "Business Setup", "View product/service catalog"),
+            CreateProducts => ("create_products", "Business Setup", "Add new products/services"),
+            EditProducts => ("edit_products", "Business Setup", "Edit product details and pricing"),
+            DeleteProducts => ("delete_products", "Business Setup", "Remove products/services"),
+            ManageInventory => ("manage_inventory", "Business Setup", "Adjust stock levels and record returns"),
+            ManageTemplates => ("manage_templates", "Business Setup", "Create and customize invoice templates"),
+            ManageSettings => ("manage_settings", "Business Setup", "Edit business information, currency, tax settings"),
+            ManageUsers => ("manage_users", "Administration", "Create/edit/delete user accounts and roles"),
+            ViewActivityLogs => ("view_activity_logs", "Administration", "View system audit logs and activity history"),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        self.info().0
+    }
+
+    pub fn group(self) -> &'static str {
+        self.info().1
+    }
+
+    pub fn description(self) -> &'static str {
+        self.info().2
+    }
+
+    pub fn from_name(name: &str) -> Option<Permission> {
+        Self::ALL.iter().copied().find(|p| p.name() == name)
+    }
+}
+
+/// Older permissions that were renamed or split. When a database is upgraded,
+/// anyone holding the old name is granted the new ones the first time they are
+/// registered; names no longer in the registry are then dropped.
+pub const UPGRADES: &[(&str, &[Permission])] = &[
+    ("create_invoice", &[ViewInvoices, CreateInvoices]),
+    ("delete_invoice", &[ViewInvoices, DeleteInvoices]),
+    ("manage_customers", &[ViewCustomers, CreateCustomers, EditCustomers, DeleteCustomers]),
+    ("manage_products", &[ViewProducts, CreateProducts, EditProducts, DeleteProducts]),
+    ("manage_transactions", &[ViewTransactions, CreateTransactions, EditTransactions, DeleteTransactions]),
+    ("manage_payroll", &[ViewPayroll, ViewEmployees, CreateEmployees, EditEmployees, DeleteEmployees]),
+    ("view_logs", &[ViewActivityLogs]),
+];
+
+/// Checks a list of permission names from the UI, naming every unknown one.
+pub fn validate_names(names: &[String]) -> Result<(), String> {
+    let unknown: Vec<&str> = names.iter()
+        .map(String::as_str)
+        .filter(|n| Permission::from_name(n).is_none())
+        .collect();
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Unknown permission: {}", unknown.join(", ")))
+    }
+}
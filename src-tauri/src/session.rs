@@ -0,0 +1,193 @@
+//! Session tokens, idle locking and expiry.
+//!
+//! Every command passes through `guard` before it runs. The window that logged
+//! in sends the session token in the `X-Session-Token` header; calls without it
+//! are treated as logged out, so another window cannot use the session.
+
+use std::time::Instant;
+use tauri::ipc::Invoke;
+use tauri::{Manager, Runtime};
+
+use crate::db::AppDb;
+use crate::AuthState;
+
+pub const SESSION_EXPIRED: &str = "Your session has expired. Please log in again.";
+pub const SESSION_LOCKED: &str = "Session locked. Enter your password to continue.";
+
+const TOKEN_HEADER: &str = "x-session-token";
+
+/// Commands used before anyone has logged in.
+const PUBLIC_COMMANDS: &[&str] = &[
+    "check_auth_initialized",
+    "login",
+    "verify_two_factor",
+    "check_activation_status",
+    "activate_with_key",
+    "verify_offline_activation",
+    "get_database_status",
+    "unlock_database",
+];
+
+/// Commands accepted before an encrypted database has been unlocked.
+const DATABASE_LOCKED_COMMANDS: &[&str] = &["get_database_status", "unlock_database"];
+
+pub const DATABASE_LOCKED: &str = "The database is locked. Unlock it to continue.";
+
+/// Commands still accepted while the session is locked.
+const LOCKED_COMMANDS: &[&str] = &["get_current_session", "unlock_session", "logout"];
+
+/// Commands that do not count as activity, so polling the session state does
+/// not keep it from locking.
+const PASSIVE_COMMANDS: &[&str] = &["get_current_session"];
+
+/// Wraps the command handler so each call is checked against the session first.
+pub fn guard<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke: Invoke<R>| {
+        let result = {
+            let message = &invoke.message;
+            let token = message.headers().get(TOKEN_HEADER).and_then(|v| v.to_str().ok());
+            let webview = message.webview_ref();
+            // An encrypted database is not managed until it has been unlocked.
+            match webview.try_state::<AppDb>() {
+                Some(db) => authorize(&webview.state::<AuthState>(), &db, message.command(), token),
+                None if DATABASE_LOCKED_COMMANDS.contains(&message.command()) => Ok(()),
+                None => Err(DATABASE_LOCKED.to_string()),
+            }
+        };
+        match result {
+            Ok(()) => handler(invoke),
+            Err(e) => {
+                invoke.resolver.reject(e);
+                true
+            }
+        }
+    }
+}
+
+fn authorize(auth: &AuthState, db: &AppDb, command: &str, token: Option<&str>) -> Result<(), String> {
+    if PUBLIC_COMMANDS.contains(&command) {
+        return Ok(());
+    }
+    let mut current = auth.session.lock().unwrap();
+    // Without a session the commands themselves answer "Not logged in", which
+    // also leaves the first-user `register` open.
+    let Some(session) = current.as_mut() else { return Ok(()) };
+    if token != Some(session.user.token.as_str()) {
+        return Err("Not logged in".to_string());
+    }
+
+    let now = Instant::now();
+    if now.duration_since(session.started) > session.lifetime {
+        db.log_activity(
+            Some(session.user.id),
+            "SESSION_EXPIRED",
+            "Session",
+            None,
+            &format!("Session for {} expired after {} hour(s)", session.user.username, session.lifetime.as_secs() / 3600)
+        ).ok();
+        *current = None;
+        return Err(SESSION_EXPIRED.to_string());
+    }
+
+    if !session.user.locked && !session.idle_timeout.is_zero() && now.duration_since(session.last_active) > session.idle_timeout {
+        session.user.locked = true;
+        db.log_activity(
+            Some(session.user.id),
+            "SESSION_LOCK",
+            "Session",
+            None,
+            &format!("Session for {} locked after {} minute(s) idle", session.user.username, session.idle_timeout.as_secs() / 60)
+        ).ok();
+    }
+    if session.user.locked && !LOCKED_COMMANDS.contains(&command) {
+        return Err(SESSION_LOCKED.to_string());
+    }
+
+    if !PASSIVE_COMMANDS.contains(&command) {
+        session.last_active = now;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Session, SessionUser};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    fn logged_in(idle_timeout: Duration, lifetime: Duration) -> AuthState {
+        let now = Instant::now();
+        AuthState {
+            session: Mutex::new(Some(Session {
+                user: SessionUser {
+                    id: 1,
+                    username: "admin".to_string(),
+                    role: "Admin".to_string(),
+                    permissions: Vec::new(),
+                    must_change_password: false,
+                    must_enroll_two_factor: false,
+                    token: "secret-token".to_string(),
+                    locked: false,
+                },
+                started: now,
+                last_active: now,
+                idle_timeout,
+                lifetime,
+            })),
+            pending_login: Mutex::new(None),
+        }
+    }
+
+    fn db() -> AppDb {
+        AppDb::new(":memory:", None).unwrap()
+    }
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn authorize_requires_the_session_token() {
+        let (auth, db) = (logged_in(HOUR, HOUR), db());
+        assert_eq!(authorize(&auth, &db, "get_invoices", Some("secret-token")), Ok(()));
+        assert_eq!(authorize(&auth, &db, "get_invoices", Some("other-token")), Err("Not logged in".to_string()));
+        assert_eq!(authorize(&auth, &db, "get_invoices", None), Err("Not logged in".to_string()));
+        assert_eq!(authorize(&auth, &db, "login", None), Ok(()));
+    }
+
+    #[test]
+    fn authorize_locks_an_idle_session() {
+        let (auth, db) = (logged_in(Duration::from_millis(5), HOUR), db());
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(authorize(&auth, &db, "get_invoices", Some("secret-token")), Err(SESSION_LOCKED.to_string()));
+        assert!(auth.session.lock().unwrap().as_ref().unwrap().user.locked);
+        // Only the lock screen's commands get through until it is unlocked.
+        assert_eq!(authorize(&auth, &db, "unlock_session", Some("secret-token")), Ok(()));
+        assert_eq!(authorize(&auth, &db, "get_invoices", Some("secret-token")), Err(SESSION_LOCKED.to_string()));
+    }
+
+    #[test]
+    fn authorize_never_locks_with_a_zero_idle_timeout() {
+        let (auth, db) = (logged_in(Duration::ZERO, HOUR), db());
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(authorize(&auth, &db, "get_invoices", Some("secret-token")), Ok(()));
+    }
+
+    #[test]
+    fn authorize_ends_a_session_past_its_lifetime() {
+        let (auth, db) = (logged_in(HOUR, Duration::from_millis(5)), db());
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(authorize(&auth, &db, "get_current_session", Some("secret-token")), Err(SESSION_EXPIRED.to_string()));
+        assert!(auth.session.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn polling_the_session_does_not_count_as_activity() {
+        let (auth, db) = (logged_in(Duration::from_millis(30), HOUR), db());
+        std::thread::sleep(Duration::from_millis(20));
+        authorize(&auth, &db, "get_current_session", Some("secret-token")).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(authorize(&auth, &db, "get_invoices", Some("secret-token")), Err(SESSION_LOCKED.to_string()));
+    }
+}
@@ -0,0 +1,131 @@
+//! RFC 6238 time-based one-time passwords and recovery codes.
+
+use totp_rs::{Algorithm, Secret, TOTP};
+
+const ISSUER: &str = "NyxoWealth";
+const STEP_SECONDS: u64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Recovery codes are long random strings, so a cheap bcrypt cost is enough
+/// and keeps checking all of a user's codes fast.
+const RECOVERY_CODE_COST: u32 = 6;
+
+/// A fresh base32-encoded shared secret.
+pub fn new_secret() -> String {
+    Secret::generate_secret().to_encoded().to_string()
+}
+
+fn totp(secret: &str, username: &str) -> Result<TOTP, String> {
+    let bytes = Secret::Encoded(secret.to_string()).to_bytes().map_err(|e| format!("{:?}", e))?;
+    // The otpauth label is "issuer:account", so the account may not contain ':'.
+    // Skew is 0 because `verify_code` walks the neighbouring steps itself.
+    TOTP::new(Algorithm::SHA1, 6, 0, STEP_SECONDS, bytes, Some(ISSUER.to_string()), username.replace(':', "_"))
+        .map_err(|e| e.to_string())
+}
+
+/// The otpauth URI and a base64 PNG QR code of it for authenticator apps.
+pub fn provisioning(secret: &str, username: &str) -> Result<(String, String), String> {
+    let totp = totp(secret, username)?;
+    Ok((totp.get_url(), totp.get_qr_base64()?))
+}
+
+/// Accepts the current code or the one either side of it, ignoring spaces,
+/// and returns the time step it belongs to. Steps at or before `last_step`,
+/// the last one accepted for this user, are refused so a code cannot be
+/// replayed (RFC 6238 §5.2).
+pub fn verify_code(secret: &str, username: &str, code: &str, last_step: Option<i64>) -> Result<Option<i64>, String> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.len() != 6 || !code.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+    let totp = totp(secret, username)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs();
+    let current = (now / STEP_SECONDS) as i64;
+    Ok((current - 1..=current + 1)
+        .filter(|step| last_step.is_none_or(|last| *step > last))
+        .find(|step| totp.check(&code, *step as u64 * STEP_SECONDS)))
+}
+
+/// New one-time recovery codes, formatted `xxxxx-xxxxx`.
+pub fn generate_recovery_codes() -> Vec<String> {
+    const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            // Bytes 6 and 8 of a v4 UUID carry the version and variant bits.
+            let random = uuid::Uuid::new_v4();
+            let chars: String = random.as_bytes().iter()
+                .enumerate()
+                .filter(|(i, _)| *i != 6 && *i != 8)
+                .take(10)
+                .map(|(_, b)| ALPHABET[*b as usize % ALPHABET.len()] as char)
+                .collect();
+            format!("{}-{}", &chars[..5], &chars[5..])
+        })
+        .collect()
+}
+
+/// Codes are compared without case, spaces or dashes.
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+pub fn hash_recovery_code(code: &str) -> Result<String, String> {
+    bcrypt::hash(normalize_recovery_code(code), RECOVERY_CODE_COST).map_err(|e| e.to_string())
+}
+
+/// Returns the id of the unused code matching `code`, if any.
+pub fn match_recovery_code(code: &str, unused: &[(i64, String)]) -> Option<i64> {
+    let code = normalize_recovery_code(code);
+    if code.len() != 10 {
+        return None;
+    }
+    unused.iter()
+        .find(|(_, hash)| bcrypt::verify(&code, hash).unwrap_or(false))
+        .map(|(id, _)| *id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_code_refuses_a_replayed_step() {
+        let secret = new_secret();
+        let code = totp(&secret, "alice").unwrap().generate_current().unwrap();
+        let step = verify_code(&secret, "alice", &code, None).unwrap().expect("current code is accepted");
+        assert_eq!(verify_code(&secret, "alice", &code, Some(step)).unwrap(), None);
+        assert_eq!(verify_code(&secret, "alice", &code, Some(step - 1)).unwrap(), Some(step));
+    }
+
+    #[test]
+    fn verify_code_ignores_spaces_and_rejects_malformed_codes() {
+        let secret = new_secret();
+        let code = totp(&secret, "bob").unwrap().generate_current().unwrap();
+        let spaced = format!("{} {}", &code[..3], &code[3..]);
+        assert!(verify_code(&secret, "bob", &spaced, None).unwrap().is_some());
+        assert_eq!(verify_code(&secret, "bob", "12345", None).unwrap(), None);
+        assert_eq!(verify_code(&secret, "bob", "12345a", None).unwrap(), None);
+    }
+
+    #[test]
+    fn recovery_codes_are_unique_and_match_once_normalized() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert!(codes.iter().all(|c| c.len() == 11 && c.as_bytes()[5] == b'-'));
+        let unique: std::collections::HashSet<_> = codes.iter().collect();
+        assert_eq!(unique.len(), codes.len());
+
+        let unused: Vec<(i64, String)> = codes.iter().take(3).enumerate()
+            .map(|(i, c)| (i as i64 + 1, hash_recovery_code(c).unwrap()))
+            .collect();
+        assert_eq!(match_recovery_code(&codes[1], &unused), Some(2));
+        assert_eq!(match_recovery_code(&format!(" {} ", codes[2].to_uppercase().replace('-', "")), &unused), Some(3));
+        assert_eq!(match_recovery_code(&codes[5], &unused), None);
+        assert_eq!(match_recovery_code("abc", &unused), None);
+    }
+}
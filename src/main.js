@@ -3,7 +3,26 @@
    Hash-based SPA with Tauri IPC
    ══════════════════════════════════════════════════════════ */
 
-const { invoke } = (window.__TAURI__ && window.__TAURI__.core) ? window.__TAURI__.core : { invoke: () => Promise.reject("Tauri not ready") };
+const { invoke: tauriInvoke } = (window.__TAURI__ && window.__TAURI__.core) ? window.__TAURI__.core : { invoke: () => Promise.reject("Tauri not ready") };
+
+// ── Session token ───────────────────────────────────────
+// Kept in sessionStorage so it belongs to this window only; the backend
+// rejects calls that don't carry it.
+const SESSION_TOKEN_KEY = 'nyxo_session_token';
+const SESSION_EXPIRED = 'Your session has expired';
+const SESSION_LOCKED = 'Session locked';
+
+function invoke(cmd, args, options = {}) {
+  const token = sessionStorage.getItem(SESSION_TOKEN_KEY);
+  const headers = token ? { ...options.headers, 'X-Session-Token': token } : options.headers;
+  return tauriInvoke(cmd, args, { ...options, headers }).catch(err => {
+    if (typeof err === 'string' && currentUser) {
+      if (err.startsWith(SESSION_EXPIRED)) endSession(err);
+      else if (err.startsWith(SESSION_LOCKED)) renderLockScreen();
+    }
+    throw err;
+  });
+}
 
 // ── Error resilience ────────────────────────────────────
 window.onerror = function(msg, url, line, col, error) {
@@ -108,6 +127,8 @@ function statusBadge(status) {
     'Paid': 'badge-paid',
     'Overdue': 'badge-overdue',
     'Cancelled': 'badge-cancelled',
+    'Void': 'badge-cancelled',
+    'Approved': 'badge-sent',
   }[status] || 'badge-draft';
   return `<span class="badge ${cls}">${status}</span>`;
 }
@@ -273,6 +294,11 @@ function hasPermission(perm) {
   return currentUser.role === 'Admin' || (currentUser.permissions && currentUser.permissions.includes(perm));
 }
 
+// Salaries, bank details and payroll records
+function hasPayrollAccess() {
+  return hasPermission('view_payroll') || hasPermission('manage_payroll');
+}
+
 function updateUiPermissions() {
   if (!currentUser) return;
 
@@ -367,10 +393,10 @@ function navigate() {
   const main = $('#main-content');
   main.innerHTML = '<div style="text-align:center;padding:80px;color:var(--text-muted)">Loading...</div>';
 
-  if (renderer) {
-    renderer(main, params);
-  }
-  updateUiPermissions();
+  // Page content is rendered asynchronously; apply data-perm once it is in place.
+  Promise.resolve(renderer ? renderer(main, params) : null)
+    .catch(err => toast(err, 'error'))
+    .finally(updateUiPermissions);
 }
 
 window.addEventListener('hashchange', navigate);
@@ -525,16 +551,285 @@ function renderLoginForm() {
     }
 
     try {
-      const session = await invoke('login', {
+      const result = await invoke('login', {
         username: username,
         password: fd.get('password'),
       });
-      currentUser = session;
-      toast('Logged in successfully');
-      showApp();
+      if (result.two_factor_required) renderTwoFactorPrompt();
+      else startSession(result.session);
+    } catch (err) { toast(err, 'error'); }
+  });
+}
+
+// Routes a new session through any forced password change or 2FA enrollment.
+function startSession(session) {
+  currentUser = session;
+  sessionStorage.setItem(SESSION_TOKEN_KEY, session.token);
+  if (session.must_change_password) renderForcedPasswordChange();
+  else if (session.must_enroll_two_factor) renderForcedTwoFactorSetup();
+  else {
+    toast('Logged in successfully');
+    showApp();
+  }
+}
+
+function renderTwoFactorPrompt() {
+  const container = $('#auth-container');
+  container.innerHTML = `
+    <div class="auth-card">
+      <div class="auth-header">
+        <h2 class="auth-title">Two-Factor Authentication</h2>
+        <p class="auth-subtitle">Enter the 6-digit code from your authenticator app, or one of your recovery codes.</p>
+      </div>
+      <form class="auth-form" id="two-factor-form">
+        <div class="form-group">
+          <label class="form-label">Code</label>
+          <input class="form-input" name="code" required autofocus autocomplete="one-time-code" />
+        </div>
+        <button type="submit" class="btn btn-primary" style="width:100%;justify-content:center;margin-top:8px;">Verify</button>
+        <button type="button" class="btn btn-secondary" id="btn-two-factor-back" style="width:100%;justify-content:center;">Back to Sign In</button>
+      </form>
+    </div>
+  `;
+
+  $('#two-factor-form').addEventListener('submit', async (e) => {
+    e.preventDefault();
+    try {
+      const session = await invoke('verify_two_factor', { code: new FormData(e.target).get('code') });
+      startSession(session);
+    } catch (err) {
+      toast(err, 'error');
+      e.target.code.value = '';
+    }
+  });
+  $('#btn-two-factor-back').onclick = () => renderLoginForm();
+}
+
+// Enrollment steps shared by the forced setup screen and the settings modal:
+// scan the QR code, confirm a code, then show the recovery codes once.
+async function renderTwoFactorSetup(target, onDone) {
+  let setup;
+  try { setup = await invoke('begin_two_factor_setup'); } catch (e) { toast(e, 'error'); return; }
+
+  target.innerHTML = `
+    <form id="two-factor-setup-form">
+      <p>Scan this QR code with an authenticator app (Google Authenticator, Microsoft Authenticator, 1Password, ...), then enter the code it shows.</p>
+      <div style="text-align: center; margin: 12px 0">
+        <img src="data:image/png;base64,${setup.qr_code}" alt="Two-factor QR code" style="width: 200px; height: 200px; background: #fff; padding: 8px; border-radius: 8px" />
+      </div>
+      <p style="color: var(--text-muted); font-size: var(--font-size-xs)">Can't scan it? Enter this key manually: <code style="user-select: all">${escHtml(setup.secret)}</code></p>
+      <div class="form-group">
+        <label class="form-label">Code</label>
+        <input class="form-input" name="code" required inputmode="numeric" autocomplete="one-time-code" />
+      </div>
+      <div class="form-actions">
+        <button type="submit" class="btn btn-primary">Turn On</button>
+      </div>
+    </form>
+  `;
+
+  target.querySelector('#two-factor-setup-form').onsubmit = async (e) => {
+    e.preventDefault();
+    try {
+      const codes = await invoke('confirm_two_factor_setup', { code: new FormData(e.target).get('code') });
+      toast('Two-factor authentication is on');
+      showRecoveryCodes(target, codes, onDone);
+    } catch (err) { toast(err, 'error'); }
+  };
+}
+
+function showRecoveryCodes(target, codes, onDone) {
+  target.innerHTML = `
+    <p>Save these recovery codes somewhere safe. Each one can be used once to sign in if you lose your authenticator. They will not be shown again.</p>
+    <pre style="user-select: all; margin: 12px 0; padding: 12px; border-radius: 8px; background: var(--bg-secondary); columns: 2">${codes.map(escHtml).join('\n')}</pre>
+    <div class="form-actions">
+      <button type="button" class="btn btn-secondary" id="btn-copy-recovery-codes">Copy</button>
+      <button type="button" class="btn btn-primary" id="btn-recovery-codes-done">I've Saved Them</button>
+    </div>
+  `;
+  target.querySelector('#btn-copy-recovery-codes').onclick = () => {
+    navigator.clipboard.writeText(codes.join('\n')).then(() => toast('Recovery codes copied'));
+  };
+  target.querySelector('#btn-recovery-codes-done').onclick = onDone;
+}
+
+function renderForcedTwoFactorSetup() {
+  const container = $('#auth-container');
+  container.innerHTML = `
+    <div class="auth-card">
+      <div class="auth-header">
+        <h2 class="auth-title">Set Up Two-Factor Authentication</h2>
+        <p class="auth-subtitle">Your account can manage payroll or users, so a second sign-in step is required.</p>
+      </div>
+      <div class="auth-form" id="forced-two-factor"></div>
+      <button type="button" class="btn btn-secondary" id="btn-forced-two-factor-logout" style="width:100%;justify-content:center;">Log Out</button>
+    </div>
+  `;
+  renderTwoFactorSetup($('#forced-two-factor'), () => {
+    currentUser.must_enroll_two_factor = false;
+    showApp();
+  });
+  $('#btn-forced-two-factor-logout').onclick = async () => {
+    await invoke('logout').catch(() => {});
+    endSession();
+  };
+}
+
+async function openTwoFactorModal() {
+  let status;
+  try { status = await invoke('get_two_factor_status'); } catch (e) { toast(e, 'error'); return; }
+
+  if (!status.enabled) {
+    openModal('Two-Factor Authentication', '<div id="two-factor-modal-body"></div>');
+    renderTwoFactorSetup($('#two-factor-modal-body'), closeModal);
+    return;
+  }
+
+  openModal('Two-Factor Authentication', `
+    <div id="two-factor-modal-body">
+      <p>Two-factor authentication is <strong>on</strong>. ${status.recovery_codes_remaining} recovery code(s) left.</p>
+      ${status.required ? '<p style="color: var(--text-muted); font-size: var(--font-size-xs)">Required for your account by the security policy.</p>' : ''}
+      <form id="two-factor-manage-form">
+        <div class="form-group">
+          <label class="form-label">Current Password</label>
+          <input class="form-input" name="password" type="password" required autocomplete="current-password" />
+        </div>
+        <div class="form-actions">
+          <button type="button" class="btn btn-secondary" onclick="closeModal()">Close</button>
+          ${status.required ? '' : '<button type="button" class="btn btn-danger" id="btn-disable-two-factor">Turn Off</button>'}
+          <button type="submit" class="btn btn-primary">New Recovery Codes</button>
+        </div>
+      </form>
+    </div>
+  `);
+
+  const form = $('#two-factor-manage-form');
+  form.onsubmit = async (e) => {
+    e.preventDefault();
+    try {
+      const codes = await invoke('regenerate_recovery_codes', { password: form.password.value });
+      showRecoveryCodes($('#two-factor-modal-body'), codes, closeModal);
+    } catch (err) { toast(err, 'error'); }
+  };
+  const disable = $('#btn-disable-two-factor');
+  if (disable) disable.onclick = async () => {
+    if (!form.reportValidity()) return;
+    if (!confirm('Turn off two-factor authentication?')) return;
+    try {
+      await invoke('disable_two_factor', { password: form.password.value });
+      toast('Two-factor authentication is off');
+      closeModal();
+    } catch (err) { toast(err, 'error'); }
+  };
+}
+window.openTwoFactorModal = openTwoFactorModal;
+
+function describePasswordPolicy(policy, withHistory = true) {
+  const rules = [`at least ${policy.min_length} characters`];
+  if (policy.require_uppercase) rules.push('an uppercase letter');
+  if (policy.require_lowercase) rules.push('a lowercase letter');
+  if (policy.require_digit) rules.push('a digit');
+  if (policy.require_symbol) rules.push('a symbol');
+  let text = `Use ${rules.join(', ')}.`;
+  if (withHistory && policy.history_count > 0) text += ` It must differ from your last ${policy.history_count} password${policy.history_count !== 1 ? 's' : ''}.`;
+  return text;
+}
+
+function changePasswordFields(policy) {
+  return `
+    <div class="form-group">
+      <label class="form-label">Current Password</label>
+      <input type="password" class="form-input" name="current_password" required autocomplete="current-password" />
+    </div>
+    <div class="form-group">
+      <label class="form-label">New Password</label>
+      <input type="password" class="form-input" name="new_password" required autocomplete="new-password" />
+    </div>
+    <div class="form-group">
+      <label class="form-label">Confirm New Password</label>
+      <input type="password" class="form-input" name="confirm_password" required autocomplete="new-password" />
+    </div>
+    ${policy ? `<p style="color: var(--text-muted); font-size: var(--font-size-xs)">${describePasswordPolicy(policy)}</p>` : ''}
+  `;
+}
+
+async function submitPasswordChange(form) {
+  const fd = new FormData(form);
+  if (fd.get('new_password') !== fd.get('confirm_password')) {
+    throw 'New passwords do not match';
+  }
+  try {
+    await invoke('change_password', {
+      currentPassword: fd.get('current_password'),
+      newPassword: fd.get('new_password'),
+    });
+  } catch (err) {
+    // Too many wrong current passwords lock the account and end the session.
+    if (String(err).startsWith('Account locked')) endSession();
+    throw err;
+  }
+}
+
+// Shown instead of the app until a default or admin-assigned password is replaced.
+async function renderForcedPasswordChange() {
+  let policy = null;
+  try { policy = await invoke('get_password_policy'); } catch (e) {}
+
+  const container = $('#auth-container');
+  container.innerHTML = `
+    <div class="auth-card">
+      <div class="auth-header">
+        <h2 class="auth-title">Choose a New Password</h2>
+        <p class="auth-subtitle">Your password was set by an administrator or is the default. Replace it to continue.</p>
+      </div>
+      <form class="auth-form" id="forced-password-form">
+        ${changePasswordFields(policy)}
+        <button type="submit" class="btn btn-primary" style="width:100%;justify-content:center;margin-top:8px;">Change Password</button>
+        <button type="button" class="btn btn-secondary" id="btn-forced-password-logout" style="width:100%;justify-content:center;">Log Out</button>
+      </form>
+    </div>
+  `;
+
+  $('#forced-password-form').addEventListener('submit', async (e) => {
+    e.preventDefault();
+    try {
+      await submitPasswordChange(e.target);
+      currentUser.must_change_password = false;
+      toast('Password changed');
+      if (currentUser.must_enroll_two_factor) renderForcedTwoFactorSetup();
+      else showApp();
     } catch (err) { toast(err, 'error'); }
   });
+  $('#btn-forced-password-logout').onclick = async () => {
+    await invoke('logout').catch(() => {});
+    endSession();
+  };
+}
+
+async function openChangePasswordModal() {
+  let policy = null;
+  try { policy = await invoke('get_password_policy'); } catch (e) {}
+
+  openModal('Change Password', `
+    <form id="change-password-form">
+      ${changePasswordFields(policy)}
+      <div class="form-actions">
+        <button type="button" class="btn btn-secondary" onclick="closeModal()">Cancel</button>
+        <button type="submit" class="btn btn-primary">Change Password</button>
+      </div>
+    </form>
+  `);
+
+  $('#change-password-form').onsubmit = async (e) => {
+    e.preventDefault();
+    try {
+      await submitPasswordChange(e.target);
+      toast('Password changed');
+      closeModal();
+    } catch (err) { toast(err, 'error'); }
+  };
 }
+window.openChangePasswordModal = openChangePasswordModal;
 
 function showApp() {
   $('#auth-container').style.display = 'none';
@@ -855,17 +1150,102 @@ function addReplayButton() {
 
 async function handleLogout() {
   if (confirm('Are you sure you want to logout?')) {
-    try {
-      await invoke('logout');
-      currentUser = null;
-      $('#app-layout').style.display = 'none';
-      $('#auth-container').style.display = 'flex'; // Ensure flex layout is restored
-      location.hash = 'dashboard';
-      renderLoginForm(document.getElementById('auth-container')); // Re-render login
-    } catch (e) { toast(e, 'error'); }
+    // An expired or locked-out session is already gone on the backend.
+    await invoke('logout').catch(() => {});
+    location.hash = 'dashboard';
+    endSession();
   }
 }
 
+// Back to the login screen, e.g. after logout or when the session expired.
+function endSession(message) {
+  sessionStorage.removeItem(SESSION_TOKEN_KEY);
+  currentUser = null;
+  closeModal();
+  $('#modal-overlay').style.visibility = '';
+  $('#app-layout').style.display = 'none';
+  $('#auth-container').style.display = 'flex'; // Ensure flex layout is restored
+  renderLoginForm();
+  if (message) toast(message, 'error');
+}
+
+async function lockSession() {
+  try {
+    await invoke('lock_session');
+    renderLockScreen();
+  } catch (e) { toast(e, 'error'); }
+}
+window.lockSession = lockSession;
+
+// Covers the app without discarding it, so unlocking returns to the same page
+// and any open form.
+function renderLockScreen() {
+  if (!currentUser || $('#lock-screen-form')) return;
+  currentUser.locked = true;
+  const appWasShown = $('#app-layout').style.display === 'flex';
+  $('#app-layout').style.display = 'none';
+  $('#modal-overlay').style.visibility = 'hidden';
+
+  const container = $('#auth-container');
+  container.style.display = 'flex';
+  container.innerHTML = `
+    <div class="auth-card">
+      <div class="auth-header">
+        <h2 class="auth-title">Session Locked</h2>
+        <p class="auth-subtitle">Signed in as <strong>${escHtml(currentUser.username)}</strong>. Enter your password to continue.</p>
+      </div>
+      <form class="auth-form" id="lock-screen-form">
+        <div class="form-group">
+          <label class="form-label">Password</label>
+          <input class="form-input" name="password" type="password" required autofocus autocomplete="current-password" />
+        </div>
+        <button type="submit" class="btn btn-primary" style="width:100%;justify-content:center;margin-top:8px;">Unlock</button>
+        <button type="button" class="btn btn-secondary" id="btn-lock-screen-logout" style="width:100%;justify-content:center;">Log Out</button>
+      </form>
+    </div>
+  `;
+
+  $('#lock-screen-form').addEventListener('submit', async (e) => {
+    e.preventDefault();
+    try {
+      currentUser = await invoke('unlock_session', { password: e.target.password.value });
+      container.style.display = 'none';
+      container.innerHTML = '';
+      $('#modal-overlay').style.visibility = '';
+      if (appWasShown) {
+        $('#app-layout').style.display = 'flex';
+        updateUiPermissions();
+      } else {
+        showApp();
+      }
+    } catch (err) {
+      // Too many wrong passwords lock the account and end the session.
+      if (err.startsWith('Account locked') || err === 'Not logged in') endSession(err);
+      else {
+        toast(err, 'error');
+        e.target.password.value = '';
+      }
+    }
+  });
+  $('#btn-lock-screen-logout').onclick = async () => {
+    await invoke('logout').catch(() => {});
+    endSession();
+  };
+}
+
+// Checks the session without counting as activity, so the lock screen shows up
+// once the idle timeout passes even if nothing is clicked.
+setInterval(async () => {
+  if (!currentUser || currentUser.locked) return;
+  try {
+    const session = await invoke('get_current_session').catch(() => null);
+    if (!session) endSession('You have been signed out.');
+    else if (session.locked) renderLockScreen();
+  } catch (e) {
+    if (e === 'Not logged in') endSession('You have been signed out.');
+  }
+}, 60 * 1000);
+
 // Initial check
 window.addEventListener('DOMContentLoaded', async () => {
   console.log('DOMContentLoaded fired');
@@ -893,7 +1273,72 @@ window.addEventListener('DOMContentLoaded', async () => {
   }
 });
 
+// An encrypted database opened by passphrase, or whose keyfile was not found
+// at startup, must be unlocked before anything else can load.
+function renderDatabaseUnlock(status) {
+  const usesKeyfile = status.key_source === 'keyfile';
+  const container = $('#auth-container');
+  container.style.display = 'flex';
+  container.innerHTML = `
+    <div class="auth-card">
+      <div class="auth-header">
+        <h2 class="auth-title">Database Locked</h2>
+        <p class="auth-subtitle">${usesKeyfile
+          ? 'The keyfile for the encrypted database was not found. Select it to continue.'
+          : 'The database is encrypted. Enter the database passphrase to continue.'}</p>
+      </div>
+      <form class="auth-form" id="database-unlock-form">
+        ${usesKeyfile ? `
+        <div class="form-group">
+          <label class="form-label">Keyfile</label>
+          <div style="display:flex;gap:8px">
+            <input class="form-input" name="keyfile_path" required value="${escHtml(status.keyfile_path || '')}" />
+            <button type="button" class="btn btn-secondary" id="btn-select-keyfile">Browse</button>
+          </div>
+        </div>` : `
+        <div class="form-group">
+          <label class="form-label">Passphrase</label>
+          <input class="form-input" name="passphrase" type="password" required autofocus />
+        </div>`}
+        <button type="submit" class="btn btn-primary" style="width:100%;justify-content:center;margin-top:8px;">Unlock Database</button>
+      </form>
+    </div>
+  `;
+
+  if (usesKeyfile) {
+    $('#btn-select-keyfile').onclick = async () => {
+      const { open } = window.__TAURI__.dialog;
+      const selected = await open({ multiple: false });
+      if (selected) $('#database-unlock-form').keyfile_path.value = selected;
+    };
+  }
+  $('#database-unlock-form').addEventListener('submit', async (e) => {
+    e.preventDefault();
+    const fd = new FormData(e.target);
+    try {
+      await invoke('unlock_database', usesKeyfile
+        ? { keyfilePath: fd.get('keyfile_path') }
+        : { passphrase: fd.get('passphrase') });
+      container.innerHTML = '';
+      checkActivationAndAuth();
+    } catch (err) {
+      toast(err, 'error');
+      if (!usesKeyfile) e.target.passphrase.value = '';
+    }
+  });
+}
+
 async function checkActivationAndAuth() {
+  try {
+    const dbStatus = await invoke('get_database_status');
+    if (!dbStatus.unlocked) {
+      renderDatabaseUnlock(dbStatus);
+      return;
+    }
+  } catch (e) {
+    console.error('Database status check failed', e);
+  }
+
   try {
     // Step 1: Check activation status
     console.log('Checking activation status...');
@@ -912,7 +1357,10 @@ async function checkActivationAndAuth() {
     console.log('Session response:', session);
     if (session) {
       currentUser = session;
-      showApp();
+      if (session.locked) renderLockScreen();
+      else if (session.must_change_password) renderForcedPasswordChange();
+      else if (session.must_enroll_two_factor) renderForcedTwoFactorSetup();
+      else showApp();
     } else {
       console.log('No session, calling initAuth');
       initAuth();
@@ -1117,6 +1565,20 @@ function renderActivationScreen(errorMessage = null) {
 // ══════════════════════════════════════════════════════════
 
 async function renderDashboard(container) {
+  if (!hasPermission('view_reports')) {
+    container.innerHTML = `
+      <div class="page-enter">
+        <div class="page-header">
+          <div>
+            <h1 class="page-title">Welcome, ${escHtml(currentUser.username)}</h1>
+            <p class="page-subtitle">Use the menu to get to your work. The business overview needs the view reports permission.</p>
+          </div>
+        </div>
+      </div>
+    `;
+    return;
+  }
+
   let stats;
   try {
     stats = await invoke('get_dashboard_stats');
@@ -1354,6 +1816,12 @@ function openCustomerModal(data = null) {
           <label class="form-label">Tax ID</label>
           <input class="form-input" name="tax_id" value="${escHtml(data?.tax_id || '')}" />
         </div>
+        <div class="form-group">
+          <label class="form-label">Price List</label>
+          <select class="form-select" name="price_list_id" id="customer-price-list">
+            <option value="">Standard prices</option>
+          </select>
+        </div>
       </div>
       <div class="form-actions">
         <button type="button" class="btn btn-secondary" onclick="closeModal()">Cancel</button>
@@ -1362,6 +1830,13 @@ function openCustomerModal(data = null) {
     </form>
   `);
 
+  invoke('get_price_lists').then(lists => {
+    $('#customer-price-list').innerHTML += lists
+      .filter(l => l.is_active || l.id === data?.price_list_id)
+      .map(l => `<option value="${l.id}" ${l.id === data?.price_list_id ? 'selected' : ''}>${escHtml(l.name)}</option>`)
+      .join('');
+  }).catch(() => {});
+
   $('#customer-form').addEventListener('submit', async (e) => {
     e.preventDefault();
     const fd = new FormData(e.target);
@@ -1372,6 +1847,7 @@ function openCustomerModal(data = null) {
       email: fd.get('email') || null,
       address: fd.get('address') || null,
       tax_id: fd.get('tax_id') || null,
+      priceListId: Number(fd.get('price_list_id')) || null,
     };
     try {
       if (isEdit) {
@@ -1411,6 +1887,7 @@ async function renderProducts(container) {
           <div class="search-bar">
             <input class="form-input" id="product-search" placeholder="Search products..." />
           </div>
+          <button class="btn btn-secondary" id="btn-print-labels">Print Labels</button>
           <button class="btn btn-primary" id="btn-add-product" data-perm="create_products"><svg xmlns="http://www.w3.org/2000/svg" width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" style="vertical-align:middle;margin-right:4px"><circle cx="12" cy="12" r="10"/><line x1="12" y1="8" x2="12" y2="16"/><line x1="8" y1="12" x2="16" y2="12"/></svg> Add Product</button>
         </div>
       </div>
@@ -1419,7 +1896,8 @@ async function renderProducts(container) {
           <thead>
             <tr>
               <th>Name</th>
-              <th>Description</th>
+              <th>SKU</th>
+              <th>Category</th>
               <th>Unit Price</th>
               <th>Tax %</th>
               <th class="col-actions">Actions</th>
@@ -1427,7 +1905,7 @@ async function renderProducts(container) {
           </thead>
           <tbody id="products-tbody">
             ${products.length === 0
-              ? '<tr><td colspan="5" class="table-empty">No products yet</td></tr>'
+              ? '<tr><td colspan="6" class="table-empty">No products yet</td></tr>'
               : products.map(p => productRow(p)).join('')
             }
           </tbody>
@@ -1438,14 +1916,34 @@ async function renderProducts(container) {
 
   $('#btn-add-product').addEventListener('click', () => openProductModal());
 
+  $('#btn-print-labels').addEventListener('click', async () => {
+    const labelled = products.filter(p => p.is_active && (p.barcode || p.sku));
+    if (labelled.length === 0) { toast('No active products have a barcode or SKU', 'error'); return; }
+    const copies = Number(prompt(`Labels per product (${labelled.length} product${labelled.length !== 1 ? 's' : ''})`, '1'));
+    if (!copies || copies < 1) return;
+    try {
+      const { save } = window.__TAURI__.dialog;
+      const filePath = await save({
+        defaultPath: 'barcode-labels.pdf',
+        filters: [{ name: 'PDF', extensions: ['pdf'] }],
+      });
+      if (!filePath) return;
+      await invoke('export_barcode_labels_pdf', { productIds: labelled.map(p => p.id), copies, filePath });
+      toast('Labels exported successfully!');
+    } catch (err) { toast(String(err), 'error'); }
+  });
+
   $('#product-search').addEventListener('input', (e) => {
     const q = e.target.value.toLowerCase();
     const filtered = products.filter(p =>
       (p.name||'').toLowerCase().includes(q) ||
-      (p.description||'').toLowerCase().includes(q)
+      (p.description||'').toLowerCase().includes(q) ||
+      (p.sku||'').toLowerCase().includes(q) ||
+      (p.barcode||'').toLowerCase().includes(q) ||
+      (p.category||'').toLowerCase().includes(q)
     );
     $('#products-tbody').innerHTML = filtered.length === 0
-      ? '<tr><td colspan="5" class="table-empty">No matches</td></tr>'
+      ? '<tr><td colspan="6" class="table-empty">No matches</td></tr>'
       : filtered.map(p => productRow(p)).join('');
     bindProductActions();
   });
@@ -1456,8 +1954,9 @@ async function renderProducts(container) {
 function productRow(p) {
   return `
     <tr>
-      <td>${escHtml(p.name)}</td>
-      <td>${escHtml(p.description) || '—'}</td>
+      <td>${escHtml(p.name)}${p.is_active ? '' : ' <span class="badge badge-cancelled">Archived</span>'}</td>
+      <td>${escHtml(p.sku) || '—'}</td>
+      <td>${escHtml(p.category) || '—'}</td>
       <td>${currency(p.unit_price)}</td>
       <td>${p.tax_percent}%</td>
       <td>
@@ -1511,6 +2010,31 @@ function openProductModal(data = null) {
           <label class="form-label">Tax %</label>
           <input class="form-input" name="tax_percent" type="number" step="0.1" min="0" value="${data?.tax_percent ?? 0}" />
         </div>
+        <div class="form-group">
+          <label class="form-label">Unit</label>
+          <input class="form-input" name="unit" list="unit-options" placeholder="pcs" value="${escHtml(data?.unit || '')}" />
+          <datalist id="unit-options"><option value="pcs"><option value="hrs"><option value="kg"><option value="m"><option value="m²"></datalist>
+        </div>
+        <div class="form-group">
+          <label class="form-label">SKU</label>
+          <input class="form-input" name="sku" value="${escHtml(data?.sku || '')}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Barcode (EAN-13 or Code 128)</label>
+          <input class="form-input" name="barcode" value="${escHtml(data?.barcode || '')}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Category</label>
+          <input class="form-input" name="category" value="${escHtml(data?.category || '')}" />
+        </div>
+        ${isEdit ? `
+        <div class="form-group">
+          <label class="form-label">Status</label>
+          <select class="form-select" name="is_active">
+            <option value="true" ${data.is_active ? 'selected' : ''}>Active</option>
+            <option value="false" ${!data.is_active ? 'selected' : ''}>Archived</option>
+          </select>
+        </div>` : ''}
       </div>
       <div class="form-actions">
         <button type="button" class="btn btn-secondary" onclick="closeModal()">Cancel</button>
@@ -1527,7 +2051,12 @@ function openProductModal(data = null) {
       description: fd.get('description') || null,
       unitPrice: Number(fd.get('unit_price')),
       taxPercent: Number(fd.get('tax_percent') || 0),
+      unit: fd.get('unit') ?? null,
+      sku: fd.get('sku') ?? null,
+      barcode: fd.get('barcode') ?? null,
+      category: fd.get('category') ?? null,
     };
+    if (isEdit) args.isActive = fd.get('is_active') === 'true';
     try {
       if (isEdit) {
         await invoke('update_product', { id: data.id, ...args });
@@ -1608,6 +2137,7 @@ async function renderCreateInvoice(container) {
               <th class="col-product">Product / Description</th>
               <th class="col-qty">Qty</th>
               <th class="col-price">Unit Price</th>
+              <th class="col-discount">Discount</th>
               <th class="col-tax">Tax %</th>
               <th class="col-total">Line Total</th>
               <th class="col-remove"></th>
@@ -1618,10 +2148,28 @@ async function renderCreateInvoice(container) {
         </table>
 
         <button type="button" class="btn btn-secondary btn-sm" id="btn-add-item"><svg xmlns="http://www.w3.org/2000/svg" width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" style="vertical-align:middle;margin-right:4px"><circle cx="12" cy="12" r="10"/><line x1="12" y1="8" x2="12" y2="16"/><line x1="8" y1="12" x2="16" y2="12"/></svg> Add Item</button>
+        <input class="form-input" id="inv-scan" placeholder="Scan barcode or type SKU and press Enter" style="display:inline-block;width:280px;margin-left:8px" />
+
+        <h3 style="margin:24px 0 8px; font-size:var(--font-size-md); font-weight:600;">Additional Charges</h3>
+        <table class="invoice-items-table">
+          <thead>
+            <tr>
+              <th class="col-product">Description</th>
+              <th class="col-price">Amount</th>
+              <th class="col-tax">Tax %</th>
+              <th class="col-total">Total</th>
+              <th class="col-remove"></th>
+            </tr>
+          </thead>
+          <tbody id="invoice-charges-body">
+          </tbody>
+        </table>
+        <button type="button" class="btn btn-secondary btn-sm" id="btn-add-charge">Add Charge</button>
 
         <div class="invoice-totals">
           <div class="invoice-totals-inner">
             <div class="totals-row"><span>Subtotal</span><span id="inv-subtotal">$0.00</span></div>
+            <div class="totals-row"><span>Charges</span><span id="inv-charges">$0.00</span></div>
             <div class="totals-row"><span>Tax</span><span id="inv-tax">$0.00</span></div>
             <div class="totals-row">
               <span>Advance</span>
@@ -1645,6 +2193,20 @@ async function renderCreateInvoice(container) {
 
   let itemIndex = 0;
 
+  // Catalogue lines follow the customer's price list; only users who may edit invoices can change it
+  async function applyPrice(tr) {
+    const productId = Number(tr.querySelector('.item-product')?.value);
+    if (!productId) return;
+    const customerId = Number($('#invoice-form [name="customer_id"]').value) || null;
+    const quantity = Number(tr.querySelector('.item-qty')?.value || 0);
+    try {
+      const price = await invoke('resolve_price', { productId, customerId, quantity });
+      tr.querySelector('.item-price').value = price.unit_price;
+      tr.querySelector('.item-tax').value = price.tax_percent;
+      recalcTotals();
+    } catch (e) { toast(String(e), 'error'); }
+  }
+
   function addItemRow() {
     const tbody = $('#invoice-items-body');
     const tr = document.createElement('tr');
@@ -1653,12 +2215,18 @@ async function renderCreateInvoice(container) {
       <td>
         <select class="form-select item-product" data-idx="${itemIndex}" style="width:100%">
           <option value="">Custom item...</option>
-          ${products.map(p => `<option value="${p.id}" data-price="${p.unit_price}" data-tax="${p.tax_percent}">${escHtml(p.name)}</option>`).join('')}
+          ${products.filter(p => p.is_active).map(p => `<option value="${p.id}" data-price="${p.unit_price}" data-tax="${p.tax_percent}" data-unit="${escHtml(p.unit || '')}">${escHtml(p.name)}</option>`).join('')}
         </select>
         <input class="form-input item-name" data-idx="${itemIndex}" placeholder="Item name" style="margin-top:4px;width:100%" />
       </td>
-      <td><input class="form-input item-qty" data-idx="${itemIndex}" type="number" min="1" value="1" style="width:100%" /></td>
+      <td><input class="form-input item-qty" data-idx="${itemIndex}" type="number" step="any" min="0" value="1" style="width:100%" />
+        <input class="form-input item-unit" data-idx="${itemIndex}" placeholder="Unit" style="margin-top:4px;width:100%" /></td>
       <td><input class="form-input item-price" data-idx="${itemIndex}" type="number" step="0.01" min="0" value="0" style="width:100%" /></td>
+      <td><input class="form-input item-discount" data-idx="${itemIndex}" type="number" step="0.01" min="0" value="0" style="width:100%" />
+        <select class="form-select item-discount-type" data-idx="${itemIndex}" style="margin-top:4px;width:100%">
+          <option value="amount">Amount</option>
+          <option value="percent">%</option>
+        </select></td>
       <td><input class="form-input item-tax" data-idx="${itemIndex}" type="number" step="0.1" min="0" value="0" style="width:100%" /></td>
       <td class="item-total" data-idx="${itemIndex}">$0.00</td>
       <td><button type="button" class="btn btn-danger btn-sm btn-icon remove-item" data-idx="${itemIndex}"><svg xmlns="http://www.w3.org/2000/svg" width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><line x1="18" y1="6" x2="6" y2="18"/><line x1="6" y1="6" x2="18" y2="18"/></svg></button></td>
@@ -1668,16 +2236,22 @@ async function renderCreateInvoice(container) {
     // When product selected, fill in price & tax
     tr.querySelector('.item-product').addEventListener('change', (e) => {
       const opt = e.target.selectedOptions[0];
+      const fixedPrice = !!opt.value && !hasPermission('edit_invoices');
+      tr.querySelector('.item-price').readOnly = fixedPrice;
+      tr.querySelector('.item-tax').readOnly = fixedPrice;
       if (opt.value) {
         tr.querySelector('.item-name').value = opt.textContent;
         tr.querySelector('.item-price').value = opt.dataset.price;
         tr.querySelector('.item-tax').value = opt.dataset.tax;
+        tr.querySelector('.item-unit').value = opt.dataset.unit;
+        applyPrice(tr);
       }
       recalcTotals();
     });
+    tr.querySelector('.item-qty').addEventListener('change', () => applyPrice(tr));
 
     // Recalc on any numeric change
-    tr.querySelectorAll('.item-qty, .item-price, .item-tax').forEach(inp => {
+    tr.querySelectorAll('.item-qty, .item-price, .item-tax, .item-discount, .item-discount-type').forEach(inp => {
       inp.addEventListener('input', recalcTotals);
     });
 
@@ -1690,6 +2264,29 @@ async function renderCreateInvoice(container) {
     recalcTotals();
   }
 
+  function lineDiscount(tr) {
+    const value = Number(tr.querySelector('.item-discount')?.value || 0);
+    const isPercent = tr.querySelector('.item-discount-type')?.value === 'percent';
+    return { discount: isPercent ? 0 : value, discount_percent: isPercent ? value : 0 };
+  }
+
+  function addChargeRow() {
+    const tr = document.createElement('tr');
+    tr.innerHTML = `
+      <td><input class="form-input charge-desc" placeholder="e.g. Shipping" style="width:100%" /></td>
+      <td><input class="form-input charge-amount" type="number" step="0.01" min="0" value="0" style="width:100%" /></td>
+      <td><input class="form-input charge-tax" type="number" step="0.1" min="0" value="0" style="width:100%" /></td>
+      <td class="charge-total">$0.00</td>
+      <td><button type="button" class="btn btn-danger btn-sm btn-icon remove-charge">&times;</button></td>
+    `;
+    $('#invoice-charges-body').appendChild(tr);
+    tr.querySelectorAll('.charge-amount, .charge-tax').forEach(inp => inp.addEventListener('input', recalcTotals));
+    tr.querySelector('.remove-charge').addEventListener('click', () => { tr.remove(); recalcTotals(); });
+    recalcTotals();
+  }
+
+  // Mirrors the server: line discounts reduce the taxable amount, the invoice
+  // discount applies to goods only, and charges carry their own tax
   function recalcTotals() {
     let subtotal = 0;
     let tax = 0;
@@ -1700,10 +2297,12 @@ async function renderCreateInvoice(container) {
       const taxPct = Number(tr.querySelector('.item-tax')?.value || 0);
 
       const base = qty * price;
-      const itemTax = base * taxPct / 100;
-      const lineTotal = base + itemTax;
+      const { discount, discount_percent } = lineDiscount(tr);
+      const net = base - (discount_percent > 0 ? base * discount_percent / 100 : Math.min(discount, base));
+      const itemTax = net * taxPct / 100;
+      const lineTotal = net + itemTax;
 
-      subtotal += base;
+      subtotal += net;
       tax += itemTax;
 
       const totalCell = tr.querySelector('.item-total');
@@ -1713,14 +2312,54 @@ async function renderCreateInvoice(container) {
     const advance = Number($('#inv-advance')?.value || 0);
     const discountPct = Number($('#inv-discount-percent')?.value || 0);
     const discountAmount = (subtotal + tax) * discountPct / 100;
-    const total = subtotal + tax - advance - discountAmount;
 
-    $('#inv-subtotal').textContent = currency(subtotal);
+    let charges = 0;
+    $$('#invoice-charges-body tr').forEach(tr => {
+      const amount = Number(tr.querySelector('.charge-amount')?.value || 0);
+      const chargeTax = amount * Number(tr.querySelector('.charge-tax')?.value || 0) / 100;
+      charges += amount;
+      tax += chargeTax;
+      tr.querySelector('.charge-total').textContent = currency(amount + chargeTax);
+    });
+
+    const total = subtotal + charges + tax - advance - discountAmount;
+
+    $('#inv-subtotal').textContent = currency(subtotal);
+    $('#inv-charges').textContent = currency(charges);
     $('#inv-tax').textContent = currency(tax);
     $('#inv-total').textContent = currency(total);
   }
 
   $('#btn-add-item').addEventListener('click', addItemRow);
+  $('#btn-add-charge').addEventListener('click', addChargeRow);
+  $('#invoice-form [name="customer_id"]').addEventListener('change', () => {
+    $$('#invoice-items-body tr').forEach(tr => applyPrice(tr));
+  });
+
+  // Keyboard-wedge scanners type the code followed by Enter
+  $('#inv-scan').addEventListener('keydown', async (e) => {
+    if (e.key !== 'Enter') return;
+    e.preventDefault();
+    const code = e.target.value.trim();
+    e.target.value = '';
+    if (!code) return;
+    try {
+      const product = await invoke('find_product_by_code', { code });
+      if (!product) { toast(`No product found for ${code}`, 'error'); return; }
+      const existing = [...$$('#invoice-items-body tr')].find(tr => tr.querySelector('.item-product')?.value === String(product.id));
+      if (existing) {
+        const qty = existing.querySelector('.item-qty');
+        qty.value = Number(qty.value || 0) + 1;
+      } else {
+        let tr = [...$$('#invoice-items-body tr')].find(tr => !tr.querySelector('.item-name')?.value);
+        if (!tr) { addItemRow(); tr = [...$$('#invoice-items-body tr')].pop(); }
+        const select = tr.querySelector('.item-product');
+        select.value = String(product.id);
+        select.dispatchEvent(new Event('change'));
+      }
+      recalcTotals();
+    } catch (err) { toast(err, 'error'); }
+  });
   $('#inv-advance').addEventListener('input', recalcTotals);
   $('#inv-discount-percent').addEventListener('input', recalcTotals);
 
@@ -1739,10 +2378,13 @@ async function renderCreateInvoice(container) {
       items.push({
         id: null,
         invoice_id: null,
+        product_id: Number(tr.querySelector('.item-product')?.value) || null,
         product_name: name,
         description: null,
         quantity: Number(tr.querySelector('.item-qty')?.value || 1),
+        unit: tr.querySelector('.item-unit')?.value || null,
         unit_price: Number(tr.querySelector('.item-price')?.value || 0),
+        ...lineDiscount(tr),
         tax_percent: Number(tr.querySelector('.item-tax')?.value || 0),
         line_total: 0,
       });
@@ -1764,6 +2406,16 @@ async function renderCreateInvoice(container) {
         discountPercent: Number(fd.get('discount_percent') || 0),
         advance: Number(fd.get('advance') || 0),
         items: items,
+        charges: [...$$('#invoice-charges-body tr')]
+          .filter(tr => tr.querySelector('.charge-desc').value.trim())
+          .map(tr => ({
+            id: null,
+            invoice_id: null,
+            description: tr.querySelector('.charge-desc').value.trim(),
+            amount: Number(tr.querySelector('.charge-amount').value || 0),
+            tax_percent: Number(tr.querySelector('.charge-tax').value || 0),
+            line_total: 0,
+          })),
       });
       toast('Invoice created!');
       location.hash = `invoice-detail/${id}`;
@@ -1794,7 +2446,7 @@ async function renderInvoices(container) {
           <h1 class="page-title">Invoice History</h1>
           <p class="page-subtitle" id="invoice-count">${invoices.length} invoice${invoices.length !== 1 ? 's' : ''}</p>
         </div>
-        <a href="#create-invoice" class="btn btn-primary" data-perm="create_invoice"><svg xmlns="http://www.w3.org/2000/svg" width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" style="vertical-align:middle;margin-right:4px"><circle cx="12" cy="12" r="10"/><line x1="12" y1="8" x2="12" y2="16"/><line x1="8" y1="12" x2="16" y2="12"/></svg> New Invoice</a>
+        <a href="#create-invoice" class="btn btn-primary" data-perm="create_invoices"><svg xmlns="http://www.w3.org/2000/svg" width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" style="vertical-align:middle;margin-right:4px"><circle cx="12" cy="12" r="10"/><line x1="12" y1="8" x2="12" y2="16"/><line x1="8" y1="12" x2="16" y2="12"/></svg> New Invoice</a>
       </div>
       <div class="filter-bar">
         <div class="filter-group">
@@ -1972,8 +2624,8 @@ async function renderInvoiceDetail(container, params) {
             ${(inv.items || []).map(item => `
               <tr>
                 <td>${escHtml(item.product_name)}</td>
-                <td>${item.quantity}</td>
-                <td>${currency(item.unit_price)}</td>
+                <td>${item.quantity}${item.unit ? ' ' + escHtml(item.unit) : ''}</td>
+                <td>${currency(item.unit_price)}${item.discount > 0 ? `<div class="text-muted" style="font-size:12px">Less ${item.discount_percent > 0 ? item.discount_percent + '%' : currency(item.discount)}</div>` : ''}</td>
                 <td>${item.tax_percent}%</td>
                 <td class="text-right">${currency(item.line_total)}</td>
               </tr>
@@ -1985,9 +2637,10 @@ async function renderInvoiceDetail(container, params) {
       <div class="invoice-totals" style="margin-top:20px;">
         <div class="invoice-totals-inner">
           <div class="totals-row"><span>Subtotal</span><span>${currency(inv.subtotal)}</span></div>
+          ${(inv.charges || []).map(c => `<div class="totals-row"><span>${escHtml(c.description)}</span><span>${currency(c.amount)}</span></div>`).join('')}
           <div class="totals-row"><span>Tax</span><span>${currency(inv.tax)}</span></div>
           ${inv.advance > 0 ? `<div class="totals-row"><span>Advance</span><span>-${currency(inv.advance)}</span></div>` : ''}
-          ${inv.discount_percent > 0 ? `<div class="totals-row"><span>Discount (${inv.discount_percent}%)</span><span>-${currency((inv.subtotal + inv.tax) * inv.discount_percent / 100)}</span></div>` : (inv.discount > 0 ? `<div class="totals-row"><span>Discount</span><span>-${currency(inv.discount)}</span></div>` : '')}
+          ${inv.discount_percent > 0 ? `<div class="totals-row"><span>Discount (${inv.discount_percent}%)</span><span>-${currency(inv.discount)}</span></div>` : (inv.discount > 0 ? `<div class="totals-row"><span>Discount</span><span>-${currency(inv.discount)}</span></div>` : '')}
           <div class="totals-row grand-total"><span>Total Due</span><span>${currency(inv.total)}</span></div>
         </div>
       </div>
@@ -3403,7 +4056,12 @@ async function renderUsers(container) {
           <h1 class="page-title">User Management</h1>
           <p class="page-subtitle">Manage admin and staff accounts</p>
         </div>
-        <button class="btn btn-primary" id="btn-add-user"><svg xmlns="http://www.w3.org/2000/svg" width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" style="vertical-align:middle;margin-right:4px"><circle cx="12" cy="12" r="10"/><line x1="12" y1="8" x2="12" y2="16"/><line x1="8" y1="12" x2="16" y2="12"/></svg> New User</button>
+        <div class="btn-group">
+          ${currentUser.role === 'Admin' ? '<button class="btn btn-secondary" id="btn-database-encryption">Database Encryption</button>' : ''}
+          <button class="btn btn-secondary" id="btn-password-policy">Password Policy</button>
+          <button class="btn btn-secondary" id="btn-manage-roles">Manage Roles</button>
+          <button class="btn btn-primary" id="btn-add-user"><svg xmlns="http://www.w3.org/2000/svg" width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" style="vertical-align:middle;margin-right:4px"><circle cx="12" cy="12" r="10"/><line x1="12" y1="8" x2="12" y2="16"/><line x1="8" y1="12" x2="16" y2="12"/></svg> New User</button>
+        </div>
       </div>
 
       <div class="card">
@@ -3412,16 +4070,35 @@ async function renderUsers(container) {
             <tr>
               <th>Username</th>
               <th>Role</th>
+              <th>Assigned Roles</th>
               <th>Actions</th>
             </tr>
           </thead>
           <tbody>
             ${users.map(u => `
               <tr>
-                <td><strong>${escHtml(u.username)}</strong></td>
-                <td><span class="badge ${u.role === 'Admin' ? 'badge-paid' : 'badge-draft'}">${u.role}</span></td>
                 <td>
+                  <strong>${escHtml(u.username)}</strong>
+                  ${u.must_change_password ? '<div style="color: var(--text-muted); font-size: var(--font-size-xs)">Must change password</div>' : ''}
+                  ${isLocked(u) ? `<div style="color: var(--danger); font-size: var(--font-size-xs)">Locked until ${lockedUntil(u).toLocaleString()}</div>` : ''}
+                  ${u.totp_enabled ? '<div style="color: var(--text-muted); font-size: var(--font-size-xs)">Two-factor on</div>' : ''}
+                </td>
+                <td>
+                  <span class="badge ${u.role === 'Admin' ? 'badge-paid' : 'badge-draft'}">${u.role}</span>
+                  ${u.is_active ? '' : '<span class="badge badge-cancelled">Inactive</span>'}
+                </td>
+                <td>${u.roles.length ? u.roles.map(r => `<span class="badge badge-sent">${escHtml(r)}</span>`).join(' ') : '—'}</td>
+                <td>
+                  <button class="btn btn-secondary btn-sm btn-user-roles" data-id="${u.id}" data-username="${escHtml(u.username)}">Roles</button>
                   <button class="btn btn-secondary btn-sm" onclick="editPermissions(${u.id}, '${u.username}')">Permissions</button>
+                  ${u.id === currentUser.id ? '' : `<button class="btn btn-secondary btn-sm btn-reset-password" data-id="${u.id}" data-username="${escHtml(u.username)}">Reset Password</button>`}
+                  ${u.totp_enabled ? `<button class="btn btn-secondary btn-sm btn-reset-two-factor" data-id="${u.id}" data-username="${escHtml(u.username)}">Reset 2FA</button>` : ''}
+                  ${isLocked(u) ? `<button class="btn btn-secondary btn-sm btn-unlock-user" data-id="${u.id}" data-username="${escHtml(u.username)}">Unlock</button>` : ''}
+                  <button class="btn btn-secondary btn-sm btn-edit-user" data-id="${u.id}">Edit</button>
+                  ${u.id === currentUser.id ? '' : `
+                    <button class="btn btn-secondary btn-sm btn-toggle-user-active" data-id="${u.id}" data-username="${escHtml(u.username)}" data-active="${u.is_active}">${u.is_active ? 'Deactivate' : 'Activate'}</button>
+                    <button class="btn btn-danger btn-sm btn-delete-user" data-id="${u.id}" data-username="${escHtml(u.username)}">Delete</button>
+                  `}
                 </td>
               </tr>
             `).join('')}
@@ -3431,6 +4108,59 @@ async function renderUsers(container) {
     </div>
   `;
 
+  $('#btn-manage-roles').onclick = () => openRolesModal(() => renderUsers(container));
+  $('#btn-password-policy').onclick = () => openPasswordPolicyModal();
+  if ($('#btn-database-encryption')) $('#btn-database-encryption').onclick = () => openDatabaseEncryptionModal();
+  $$('.btn-reset-password').forEach(btn => {
+    btn.onclick = () => openResetPasswordModal(Number(btn.dataset.id), btn.dataset.username, () => renderUsers(container));
+  });
+  $$('.btn-reset-two-factor').forEach(btn => {
+    btn.onclick = async () => {
+      if (!confirm(`Remove two-factor authentication for ${btn.dataset.username}? Their recovery codes stop working too.`)) return;
+      try {
+        await invoke('reset_two_factor', { userId: Number(btn.dataset.id) });
+        toast(`Two-factor authentication reset for ${btn.dataset.username}`);
+        renderUsers(container);
+      } catch (err) { toast(err, 'error'); }
+    };
+  });
+  $$('.btn-unlock-user').forEach(btn => {
+    btn.onclick = async () => {
+      try {
+        await invoke('unlock_user', { userId: Number(btn.dataset.id) });
+        toast(`${btn.dataset.username} unlocked`);
+        renderUsers(container);
+      } catch (err) { toast(err, 'error'); }
+    };
+  });
+  $$('.btn-edit-user').forEach(btn => {
+    btn.onclick = () => openEditUserModal(users.find(u => u.id === Number(btn.dataset.id)), () => renderUsers(container));
+  });
+  $$('.btn-toggle-user-active').forEach(btn => {
+    btn.onclick = async () => {
+      const active = btn.dataset.active !== 'true';
+      if (!active && !confirm(`Deactivate ${btn.dataset.username}? They will no longer be able to log in.`)) return;
+      try {
+        await invoke('set_user_active', { userId: Number(btn.dataset.id), active });
+        toast(`${btn.dataset.username} ${active ? 'reactivated' : 'deactivated'}`);
+        renderUsers(container);
+      } catch (err) { toast(err, 'error'); }
+    };
+  });
+  $$('.btn-delete-user').forEach(btn => {
+    btn.onclick = async () => {
+      if (!confirm(`Delete ${btn.dataset.username} permanently? Their audit history is kept. To only block their login, deactivate them instead.`)) return;
+      try {
+        await invoke('delete_user', { userId: Number(btn.dataset.id) });
+        toast(`${btn.dataset.username} deleted`);
+        renderUsers(container);
+      } catch (err) { toast(err, 'error'); }
+    };
+  });
+  $$('.btn-user-roles').forEach(btn => {
+    btn.onclick = () => editUserRoles(Number(btn.dataset.id), btn.dataset.username, () => renderUsers(container));
+  });
+
   $('#btn-add-user').addEventListener('click', () => {
     openModal('Create New User', `
       <form id="new-user-form" class="modal-form">
@@ -3442,6 +4172,7 @@ async function renderUsers(container) {
           <label class="form-label">Password</label>
           <input type="password" class="form-input" name="password" required />
         </div>
+        <p style="color: var(--text-muted); font-size: var(--font-size-xs)">The user will be asked to choose their own password when they first log in.</p>
         <div class="form-actions">
           <button type="submit" class="btn btn-primary">Create User</button>
         </div>
@@ -3464,87 +4195,41 @@ async function renderUsers(container) {
   });
 }
 
+function permissionCheckboxes(registry) {
+  const groups = [];
+  registry.forEach(p => {
+    let group = groups.find(g => g.title === p.group);
+    if (!group) groups.push(group = { title: p.group, perms: [] });
+    group.perms.push(p);
+  });
+  return `
+    <div class="perms-grid">
+      ${groups.map(g => `
+        <div>
+          <h4 class="perms-section-title">${escHtml(g.title)}</h4>
+          <div class="perms-list">
+            ${g.perms.map(p => `
+              <div class="perm-item" title="${escHtml(p.description)}">
+                <input type="checkbox" name="perms" value="${p.name}" id="perm-${p.name}">
+                <label for="perm-${p.name}">${p.name.replace(/_/g, ' ')}</label>
+              </div>
+            `).join('')}
+          </div>
+        </div>
+      `).join('')}
+    </div>
+  `;
+}
+
 async function editPermissions(userId, username) {
-  const permsList = [
-    // Financial Management - Invoices
-    'view_invoices', 'create_invoices', 'edit_invoices', 'delete_invoices', 'export_invoices',
-    // Financial Management - Transactions
-    'view_transactions', 'create_transactions', 'edit_transactions', 'delete_transactions',
-    // Financial Management - Reports
-    'view_reports', 'export_reports',
-    // Team & Operations - Customers
-    'view_customers', 'create_customers', 'edit_customers', 'delete_customers',
-    // Team & Operations - Employees
-    'view_employees', 'create_employees', 'edit_employees', 'delete_employees',
-    // Team & Operations - Payroll
-    'view_payroll', 'manage_payroll',
-    // Business Setup - Products
-    'view_products', 'create_products', 'edit_products', 'delete_products',
-    // Business Setup - Templates
-    'manage_templates',
-    // Business Setup - Settings
-    'manage_settings',
-    // Admin
-    'manage_users', 'view_activity_logs'
-  ];
+  let registry = [];
+  try { registry = await invoke('get_permissions'); } catch (e) { toast(e, 'error'); return; }
 
   openModal(`Permissions: ${username}`, `
     <div style="padding: 10px 0">
-      <p style="margin-bottom: 20px; color: var(--text-muted)">Grant specific access to this user.</p>
+      <p style="margin-bottom: 20px; color: var(--text-muted)">Grant specific access to this user. Permissions from the user's roles are added to these.</p>
       <form id="perms-form">
-        <div class="perms-grid">
-          <!-- Financial Management -->
-          <div>
-            <h4 class="perms-section-title">Financial Management</h4>
-            <div class="perms-list">
-              ${['view_invoices', 'create_invoices', 'edit_invoices', 'delete_invoices', 'export_invoices', 'view_transactions', 'create_transactions', 'edit_transactions', 'delete_transactions', 'view_reports', 'export_reports'].map(p => `
-                <div class="perm-item">
-                  <input type="checkbox" name="perms" value="${p}" id="perm-${p}">
-                  <label for="perm-${p}">${p.replace(/_/g, ' ')}</label>
-                </div>
-              `).join('')}
-            </div>
-          </div>
-
-          <!-- Team & Operations -->
-          <div>
-            <h4 class="perms-section-title">Team & Operations</h4>
-            <div class="perms-list">
-              ${['view_customers', 'create_customers', 'edit_customers', 'delete_customers', 'view_employees', 'create_employees', 'edit_employees', 'delete_employees', 'view_payroll', 'manage_payroll'].map(p => `
-                <div class="perm-item">
-                  <input type="checkbox" name="perms" value="${p}" id="perm-${p}">
-                  <label for="perm-${p}">${p.replace(/_/g, ' ')}</label>
-                </div>
-              `).join('')}
-            </div>
-          </div>
-
-          <!-- Business Setup -->
-          <div>
-            <h4 class="perms-section-title">Business Setup</h4>
-            <div class="perms-list">
-              ${['view_products', 'create_products', 'edit_products', 'delete_products', 'manage_templates', 'manage_settings'].map(p => `
-                <div class="perm-item">
-                  <input type="checkbox" name="perms" value="${p}" id="perm-${p}">
-                  <label for="perm-${p}">${p.replace(/_/g, ' ')}</label>
-                </div>
-              `).join('')}
-            </div>
-          </div>
-
-          <!-- Administration -->
-          <div>
-            <h4 class="perms-section-title">Administration</h4>
-            <div class="perms-list">
-              ${['manage_users', 'view_activity_logs'].map(p => `
-                <div class="perm-item">
-                  <input type="checkbox" name="perms" value="${p}" id="perm-${p}">
-                  <label for="perm-${p}">${p.replace(/_/g, ' ')}</label>
-                </div>
-              `).join('')}
-            </div>
-          </div>
-        </div>
+        ${permissionCheckboxes(registry)}
         <hr style="margin: 20px 0; border: none; border-top: 1px solid var(--border-color);"/>
         <div class="form-actions" style="margin-top: 20px;">
           <button type="submit" class="btn btn-primary">Update Permissions</button>
@@ -3580,8 +4265,392 @@ async function editPermissions(userId, username) {
 }
 window.editPermissions = editPermissions;
 
+function openEditUserModal(user, onSaved) {
+  const isSelf = user.id === currentUser.id;
+  openModal(`Edit User: ${escHtml(user.username)}`, `
+    <form id="edit-user-form">
+      <div class="form-group">
+        <label class="form-label">Username</label>
+        <input class="form-input" name="username" required value="${escHtml(user.username)}" />
+      </div>
+      <div class="form-group">
+        <label class="form-label">Role</label>
+        <select class="form-select" name="role" ${isSelf ? 'disabled' : ''}>
+          <option value="User" ${user.role === 'User' ? 'selected' : ''}>User</option>
+          <option value="Admin" ${user.role === 'Admin' ? 'selected' : ''}>Admin</option>
+        </select>
+        ${isSelf ? '<p style="margin-top: 4px; color: var(--text-muted); font-size: var(--font-size-xs)">You cannot change your own role.</p>' : ''}
+      </div>
+      <div class="form-actions">
+        <button type="button" class="btn btn-secondary" onclick="closeModal()">Cancel</button>
+        <button type="submit" class="btn btn-primary">Save</button>
+      </div>
+    </form>
+  `);
+
+  $('#edit-user-form').onsubmit = async (e) => {
+    e.preventDefault();
+    const fd = new FormData(e.target);
+    try {
+      const username = fd.get('username').trim();
+      if (username !== user.username) {
+        await invoke('rename_user', { userId: user.id, username });
+        if (isSelf) currentUser.username = username;
+      }
+      if (!isSelf && fd.get('role') !== user.role) {
+        await invoke('update_user_role', { userId: user.id, role: fd.get('role') });
+      }
+      toast('User updated');
+      closeModal();
+      if (onSaved) onSaved();
+    } catch (err) { toast(err, 'error'); }
+  };
+}
+
+async function openResetPasswordModal(userId, username, onSaved) {
+  let policy = null;
+  try { policy = await invoke('get_password_policy'); } catch (e) {}
+
+  openModal(`Reset Password: ${escHtml(username)}`, `
+    <form id="reset-password-form">
+      <div class="form-group">
+        <label class="form-label">New Password</label>
+        <input type="password" class="form-input" name="new_password" required autocomplete="new-password" />
+      </div>
+      ${policy ? `<p style="color: var(--text-muted); font-size: var(--font-size-xs)">${describePasswordPolicy(policy, false)}</p>` : ''}
+      <div class="perm-item" style="margin-top: 12px">
+        <input type="checkbox" name="require_change" id="reset-require-change" checked>
+        <label for="reset-require-change">Require a new password at next login</label>
+      </div>
+      <div class="form-actions">
+        <button type="button" class="btn btn-secondary" onclick="closeModal()">Cancel</button>
+        <button type="submit" class="btn btn-primary">Reset Password</button>
+      </div>
+    </form>
+  `);
+
+  $('#reset-password-form').onsubmit = async (e) => {
+    e.preventDefault();
+    const fd = new FormData(e.target);
+    try {
+      await invoke('admin_reset_password', {
+        userId,
+        newPassword: fd.get('new_password'),
+        requireChange: fd.get('require_change') === 'on',
+      });
+      toast('Password reset');
+      closeModal();
+      if (onSaved) onSaved();
+    } catch (err) { toast(err, 'error'); }
+  };
+}
+
+// locked_until is stored in UTC as "YYYY-MM-DD HH:MM:SS".
+function lockedUntil(user) {
+  return user.locked_until ? new Date(user.locked_until.replace(' ', 'T') + 'Z') : null;
+}
+
+function isLocked(user) {
+  const until = lockedUntil(user);
+  return until !== null && until > new Date();
+}
+
+async function openPasswordPolicyModal() {
+  let policy;
+  try { policy = await invoke('get_password_policy'); } catch (e) { toast(e, 'error'); return; }
+
+  const flag = (name, label) => `
+    <div class="perm-item">
+      <input type="checkbox" name="${name}" id="policy-${name}" ${policy[name] ? 'checked' : ''}>
+      <label for="policy-${name}">${label}</label>
+    </div>
+  `;
+  openModal('Password Policy', `
+    <form id="password-policy-form">
+      <div class="form-grid">
+        <div class="form-group">
+          <label class="form-label">Minimum Length</label>
+          <input class="form-input" name="min_length" type="number" min="4" max="128" required value="${policy.min_length}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Passwords Remembered</label>
+          <input class="form-input" name="history_count" type="number" min="0" max="24" required value="${policy.history_count}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Lock After Failed Logins</label>
+          <input class="form-input" name="max_failed_logins" type="number" min="0" max="100" required value="${policy.max_failed_logins}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Lockout Duration (minutes)</label>
+          <input class="form-input" name="lockout_minutes" type="number" min="1" max="1440" required value="${policy.lockout_minutes}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Lock After Idle (minutes)</label>
+          <input class="form-input" name="idle_timeout_minutes" type="number" min="0" max="480" required value="${policy.idle_timeout_minutes}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Session Lifetime (hours)</label>
+          <input class="form-input" name="session_lifetime_hours" type="number" min="1" max="168" required value="${policy.session_lifetime_hours}" />
+        </div>
+      </div>
+      <div class="perms-list" style="margin-top: 12px">
+        ${flag('require_uppercase', 'Require an uppercase letter')}
+        ${flag('require_lowercase', 'Require a lowercase letter')}
+        ${flag('require_digit', 'Require a digit')}
+        ${flag('require_symbol', 'Require a symbol')}
+        ${flag('require_two_factor', 'Require two-factor authentication for users who can manage payroll or users')}
+      </div>
+      <p style="margin-top: 12px; color: var(--text-muted); font-size: var(--font-size-xs)">Applies to new and changed passwords. Existing passwords are not affected. Set failed logins to 0 to turn lockout off, or idle minutes to 0 to never lock idle sessions. Session settings apply from the next login.</p>
+      <div class="form-actions">
+        <button type="button" class="btn btn-secondary" onclick="closeModal()">Cancel</button>
+        <button type="submit" class="btn btn-primary">Save Policy</button>
+      </div>
+    </form>
+  `);
+
+  $('#password-policy-form').onsubmit = async (e) => {
+    e.preventDefault();
+    const fd = new FormData(e.target);
+    try {
+      await invoke('update_password_policy', {
+        policy: {
+          min_length: Number(fd.get('min_length')),
+          history_count: Number(fd.get('history_count')),
+          max_failed_logins: Number(fd.get('max_failed_logins')),
+          lockout_minutes: Number(fd.get('lockout_minutes')),
+          idle_timeout_minutes: Number(fd.get('idle_timeout_minutes')),
+          session_lifetime_hours: Number(fd.get('session_lifetime_hours')),
+          require_uppercase: fd.get('require_uppercase') === 'on',
+          require_lowercase: fd.get('require_lowercase') === 'on',
+          require_digit: fd.get('require_digit') === 'on',
+          require_symbol: fd.get('require_symbol') === 'on',
+          require_two_factor: fd.get('require_two_factor') === 'on',
+        },
+      });
+      toast('Password policy saved');
+      closeModal();
+    } catch (err) { toast(err, 'error'); }
+  };
+}
+
+async function openDatabaseEncryptionModal() {
+  let status;
+  try { status = await invoke('get_database_status'); } catch (e) { toast(e, 'error'); return; }
+
+  const current = !status.encrypted
+    ? 'The database is <strong>not encrypted</strong>. Anyone with access to this computer can read it.'
+    : status.key_source === 'keyfile'
+      ? `The database is encrypted with the keyfile <strong>${escHtml(status.keyfile_path || '')}</strong>.`
+      : 'The database is encrypted with a passphrase, asked for each time the app starts.';
+  openModal('Database Encryption', `
+    <form id="database-encryption-form">
+      <p style="margin-bottom: 12px">${current}</p>
+      ${status.encrypted && status.key_source === 'passphrase' ? `
+      <div class="form-group">
+        <label class="form-label">Current Passphrase</label>
+        <input class="form-input" name="current_passphrase" type="password" required />
+      </div>` : ''}
+      <div class="form-group">
+        <label class="form-label">${status.encrypted ? 'New Key' : 'Encrypt With'}</label>
+        <select class="form-select" name="key_source" id="encryption-key-source">
+          <option value="passphrase">Passphrase</option>
+          <option value="keyfile">Keyfile</option>
+        </select>
+      </div>
+      <div id="encryption-passphrase-fields" class="form-grid">
+        <div class="form-group">
+          <label class="form-label">Passphrase</label>
+          <input class="form-input" name="passphrase" type="password" minlength="12" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Confirm Passphrase</label>
+          <input class="form-input" name="confirm_passphrase" type="password" minlength="12" />
+        </div>
+      </div>
+      <div id="encryption-keyfile-fields" class="form-group" style="display:none">
+        <label class="form-label">Keyfile</label>
+        <div style="display:flex;gap:8px">
+          <input class="form-input" name="keyfile_path" />
+          <button type="button" class="btn btn-secondary" id="btn-choose-keyfile">Browse</button>
+        </div>
+      </div>
+      <p style="margin-top: 12px; color: var(--text-muted); font-size: var(--font-size-xs)">A passphrase must be at least 12 characters and is asked for at every start. A keyfile that does not exist yet is created with a random key; keep a copy somewhere safe. The database cannot be recovered if the passphrase or keyfile is lost.</p>
+      <div class="form-actions">
+        <button type="button" class="btn btn-secondary" onclick="closeModal()">Cancel</button>
+        <button type="submit" class="btn btn-primary">${status.encrypted ? 'Change Key' : 'Encrypt Database'}</button>
+      </div>
+    </form>
+  `);
+
+  const form = $('#database-encryption-form');
+  $('#encryption-key-source').onchange = (e) => {
+    const keyfile = e.target.value === 'keyfile';
+    $('#encryption-passphrase-fields').style.display = keyfile ? 'none' : '';
+    $('#encryption-keyfile-fields').style.display = keyfile ? '' : 'none';
+  };
+  $('#btn-choose-keyfile').onclick = async () => {
+    const { save } = window.__TAURI__.dialog;
+    const selected = await save({ defaultPath: 'nyxowealth.key' });
+    if (selected) form.keyfile_path.value = selected;
+  };
+
+  form.onsubmit = async (e) => {
+    e.preventDefault();
+    const fd = new FormData(e.target);
+    const keySource = fd.get('key_source');
+    if (keySource === 'passphrase' && fd.get('passphrase') !== fd.get('confirm_passphrase')) {
+      toast('Passphrases do not match', 'error');
+      return;
+    }
+    const args = {
+      keySource,
+      passphrase: keySource === 'passphrase' ? fd.get('passphrase') : null,
+      keyfilePath: keySource === 'keyfile' ? fd.get('keyfile_path') : null,
+    };
+    try {
+      if (status.encrypted) {
+        await invoke('change_database_key', { ...args, currentPassphrase: fd.get('current_passphrase') });
+        toast('Database key changed');
+      } else {
+        await invoke('encrypt_database', args);
+        toast('Database encrypted');
+      }
+      closeModal();
+    } catch (err) { toast(err, 'error'); }
+  };
+}
+
+async function editUserRoles(userId, username, onSaved) {
+  let roles = [];
+  let assigned = [];
+  try {
+    [roles, assigned] = await Promise.all([invoke('get_roles'), invoke('get_user_roles', { userId })]);
+  } catch (e) { toast(e, 'error'); return; }
+
+  openModal(`Roles: ${username}`, `
+    <form id="user-roles-form">
+      ${roles.length === 0 ? `<p style="color: var(--text-muted)">No roles defined yet. Create one from Manage Roles.</p>` : `
+      <div class="perms-list">
+        ${roles.map(r => `
+          <div class="perm-item">
+            <input type="checkbox" name="roles" value="${r.id}" id="role-${r.id}" ${assigned.includes(r.id) ? 'checked' : ''}>
+            <label for="role-${r.id}"><strong>${escHtml(r.name)}</strong>${r.description ? ` — ${escHtml(r.description)}` : ''}</label>
+          </div>
+        `).join('')}
+      </div>`}
+      <p style="margin-top: 12px; color: var(--text-muted); font-size: var(--font-size-xs)">A logged-in user picks up the changes as soon as they next use a protected feature.</p>
+      <div class="form-actions">
+        <button type="button" class="btn btn-secondary" onclick="closeModal()">Cancel</button>
+        <button type="submit" class="btn btn-primary">Save Roles</button>
+      </div>
+    </form>
+  `);
+
+  $('#user-roles-form').onsubmit = async (e) => {
+    e.preventDefault();
+    const roleIds = new FormData(e.target).getAll('roles').map(Number);
+    try {
+      await invoke('update_user_roles', { userId, roleIds });
+      toast('Roles updated');
+      closeModal();
+      if (onSaved) onSaved();
+    } catch (err) { toast(err, 'error'); }
+  };
+}
+
+async function openRolesModal(onChanged) {
+  let roles = [];
+  try { roles = await invoke('get_roles'); } catch (e) { toast(e, 'error'); return; }
+
+  openModal('Roles', `
+    <div class="table-wrap">
+      <table>
+        <thead><tr><th>Role</th><th>Permissions</th><th>Users</th><th></th></tr></thead>
+        <tbody>
+          ${roles.length === 0 ? `<tr><td colspan="4" style="color: var(--text-muted)">No roles yet.</td></tr>` : roles.map(r => `
+            <tr>
+              <td><strong>${escHtml(r.name)}</strong>${r.description ? `<div style="color: var(--text-muted); font-size: var(--font-size-xs)">${escHtml(r.description)}</div>` : ''}</td>
+              <td style="font-size: var(--font-size-xs)">${r.permissions.map(p => p.replace(/_/g, ' ')).join(', ') || '—'}</td>
+              <td>${r.user_count}</td>
+              <td>
+                <button class="btn btn-secondary btn-sm btn-edit-role" data-id="${r.id}">Edit</button>
+                <button class="btn btn-danger btn-sm btn-delete-role" data-id="${r.id}">Delete</button>
+              </td>
+            </tr>
+          `).join('')}
+        </tbody>
+      </table>
+    </div>
+    <div class="form-actions">
+      <button type="button" class="btn btn-primary" id="btn-new-role">New Role</button>
+    </div>
+  `);
+  document.querySelector('.modal').style.maxWidth = '800px';
+
+  const reopen = () => { openRolesModal(onChanged); if (onChanged) onChanged(); };
+  $('#btn-new-role').onclick = () => openRoleForm(null, reopen);
+  $$('.btn-edit-role').forEach(btn => {
+    btn.onclick = () => openRoleForm(roles.find(r => r.id === Number(btn.dataset.id)), reopen);
+  });
+  $$('.btn-delete-role').forEach(btn => {
+    btn.onclick = async () => {
+      const role = roles.find(r => r.id === Number(btn.dataset.id));
+      const users = role.user_count > 0 ? ` It is assigned to ${role.user_count} user${role.user_count !== 1 ? 's' : ''}, who will lose its permissions.` : '';
+      if (!confirm(`Delete role "${role.name}"?${users}`)) return;
+      try {
+        await invoke('delete_role', { id: role.id });
+        toast('Role deleted');
+        reopen();
+      } catch (err) { toast(err, 'error'); }
+    };
+  });
+}
+
+async function openRoleForm(role, onSaved) {
+  let registry = [];
+  try { registry = await invoke('get_permissions'); } catch (e) { toast(e, 'error'); return; }
+
+  openModal(role ? `Edit Role: ${role.name}` : 'New Role', `
+    <form id="role-form">
+      <div class="form-grid">
+        <div class="form-group">
+          <label class="form-label">Name *</label>
+          <input class="form-input" name="name" required value="${escHtml(role?.name || '')}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Description</label>
+          <input class="form-input" name="description" value="${escHtml(role?.description || '')}" />
+        </div>
+      </div>
+      ${permissionCheckboxes(registry)}
+      <div class="form-actions">
+        <button type="button" class="btn btn-secondary" onclick="closeModal()">Cancel</button>
+        <button type="submit" class="btn btn-primary">${role ? 'Save Role' : 'Create Role'}</button>
+      </div>
+    </form>
+  `);
+  document.querySelector('.modal').style.maxWidth = '700px';
+
+  (role?.permissions || []).forEach(p => {
+    const cb = $(`#role-form input[value="${p}"]`);
+    if (cb) cb.checked = true;
+  });
+
+  $('#role-form').onsubmit = async (e) => {
+    e.preventDefault();
+    const fd = new FormData(e.target);
+    const args = { name: fd.get('name'), description: fd.get('description'), permissions: fd.getAll('perms') };
+    try {
+      if (role) await invoke('update_role', { id: role.id, ...args });
+      else await invoke('create_role', args);
+      toast(role ? 'Role updated' : 'Role created');
+      onSaved();
+    } catch (err) { toast(err, 'error'); }
+  };
+}
+
 async function renderLogs(container) {
-  if (!hasPermission('view_logs')) {
+  if (!hasPermission('view_activity_logs')) {
     container.innerHTML = '<h2>Permission Denied</h2>';
     return;
   }
@@ -3716,7 +4785,10 @@ async function renderTransactions(container) {
           <h1 class="page-title">Transactions</h1>
           <p class="page-subtitle">Income and Expense history</p>
         </div>
-        <button class="btn btn-primary" id="btn-add-tx"><svg xmlns="http://www.w3.org/2000/svg" width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" style="vertical-align:middle;margin-right:4px"><circle cx="12" cy="12" r="10"/><line x1="12" y1="8" x2="12" y2="16"/><line x1="8" y1="12" x2="16" y2="12"/></svg> Record Transaction</button>
+        <div class="btn-group">
+          <button class="btn btn-secondary" id="btn-supplier-bank-file">Supplier Payment File</button>
+          <button class="btn btn-primary" id="btn-add-tx"><svg xmlns="http://www.w3.org/2000/svg" width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" style="vertical-align:middle;margin-right:4px"><circle cx="12" cy="12" r="10"/><line x1="12" y1="8" x2="12" y2="16"/><line x1="8" y1="12" x2="16" y2="12"/></svg> Record Transaction</button>
+        </div>
       </div>
 
       <div class="table-wrap">
@@ -3754,6 +4826,7 @@ async function renderTransactions(container) {
   `;
 
   $('#btn-add-tx').onclick = () => openTransactionModal(accounts, categories);
+  $('#btn-supplier-bank-file').onclick = () => openSupplierBankFileModal(txs.filter(t => t.transaction_type === 'Expense'));
 }
 
 async function openTransactionModal(accounts, categories) {
@@ -3848,9 +4921,11 @@ async function openTransactionModal(accounts, categories) {
 // ── Payroll ───────────────────────────────────────────
 
 async function renderPayroll(container) {
-  let [payrollRecords, employees] = await Promise.all([
+  let [payrollRecords, employees, accounts, runs] = await Promise.all([
     invoke('get_payroll_summary'),
-    invoke('get_employees')
+    invoke('get_employees'),
+    invoke('get_accounts'),
+    invoke('get_payroll_runs'),
   ]);
 
   container.innerHTML = `
@@ -3859,19 +4934,50 @@ async function renderPayroll(container) {
         </div>
         <div class="btn-group">
           <button class="btn btn-primary" id="btn-add-payroll"><svg xmlns="http://www.w3.org/2000/svg" width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" style="vertical-align:middle;margin-right:4px"><circle cx="12" cy="12" r="10"/><line x1="12" y1="8" x2="12" y2="16"/><line x1="8" y1="12" x2="16" y2="12"/></svg> New Payroll</button>
-          <button class="btn btn-secondary" id="btn-bulk-payroll">Bulk Run</button>
+          <button class="btn btn-secondary" id="btn-bulk-payroll">New Payroll Run</button>
+          <button class="btn btn-secondary" id="btn-payroll-rules" data-perm="manage_payroll">Payroll Rules</button>
+          <button class="btn btn-secondary" id="btn-payroll-liabilities">Liabilities</button>
+          <button class="btn btn-secondary" id="btn-payroll-bank-file">Bank File</button>
+          <button class="btn btn-secondary" id="btn-payroll-reports">Payslips &amp; Register</button>
+          <button class="btn btn-secondary" id="btn-payroll-year-end">Year-End</button>
         </div>
       </div>
 
-      <div class="table-wrap">
+      ${runs.length > 0 ? `
+      <h3 style="margin-bottom:8px; font-size:var(--font-size-md); font-weight:600;">Payroll Runs</h3>
+      <div class="table-wrap mb-4">
         <table>
           <thead>
-            <tr>
-              <th>Employee</th>
-              <th>Role</th>
-              <th>Period</th>
-              <th>Base Salary</th>
-              <th>Bonuses</th>
+            <tr><th>Run</th><th>Period</th><th>Employees</th><th>Gross</th><th>Net</th><th>Prepared By</th><th>Approved By</th><th>Status</th><th class="col-actions">Actions</th></tr>
+          </thead>
+          <tbody>
+            ${runs.map(r => `
+              <tr>
+                <td>${escHtml(r.run_number)}</td>
+                <td>${r.pay_period_start} – ${r.pay_period_end}</td>
+                <td>${r.employee_count}</td>
+                <td>${currency(r.total_gross)}</td>
+                <td><strong>${currency(r.total_net)}</strong></td>
+                <td>${escHtml(r.created_by_name) || '—'}</td>
+                <td>${escHtml(r.approved_by_name) || '—'}</td>
+                <td>${statusBadge(r.status)}</td>
+                <td><button class="btn btn-secondary btn-sm btn-view-run" data-id="${r.id}">Review</button></td>
+              </tr>
+            `).join('')}
+          </tbody>
+        </table>
+      </div>` : ''}
+
+      <div class="table-wrap">
+        <table>
+          <thead>
+            <tr>
+              <th>Employee</th>
+              <th>Role</th>
+              <th>Period</th>
+              <th>Base Salary</th>
+              <th>Gross</th>
+              <th>Deductions</th>
               <th>Net Pay</th>
               <th>Date</th>
               <th>Status</th>
@@ -3880,19 +4986,22 @@ async function renderPayroll(container) {
           </thead>
           <tbody>
             ${payrollRecords.length === 0
-              ? '<tr><td colspan="9" class="table-empty">No payroll records yet.</td></tr>'
+              ? '<tr><td colspan="10" class="table-empty">No payroll records yet.</td></tr>'
               : payrollRecords.map(p => `
                 <tr>
                   <td>${escHtml(p.employee_name) || 'Unknown'}</td>
                   <td>${escHtml(p.employee_role) || '—'}</td>
                   <td>${p.pay_period_start || '—'} – ${p.pay_period_end || '—'}</td>
                   <td>${currency(p.base_salary)}</td>
-                  <td>${currency(p.bonuses)}</td>
+                  <td>${currency(p.gross_salary)}</td>
+                  <td>${currency(p.total_deductions)}</td>
                   <td><strong>${currency(p.net_pay)}</strong></td>
                   <td>${p.payment_date}</td>
                   <td>${statusBadge(p.status)}</td>
                   <td>
                     <button class="btn btn-secondary btn-sm btn-payslip" data-id="${p.id}" title="Download Payslip">PDF</button>
+                    ${p.status === 'Pending' && !p.run_id ? `<button class="btn btn-primary btn-sm btn-pay-payroll" data-id="${p.id}" data-date="${p.payment_date || ''}" data-perm="manage_payroll">Mark Paid</button>` : ''}
+                    ${p.status !== 'Void' ? `<button class="btn btn-danger btn-sm btn-void-payroll" data-id="${p.id}" data-perm="manage_payroll">Void</button>` : ''}
                   </td>
                 </tr>
               `).join('')
@@ -3903,8 +5012,41 @@ async function renderPayroll(container) {
     </div>
   `;
 
-  $('#btn-add-payroll').onclick = () => openPayrollModal(employees);
-  $('#btn-bulk-payroll').onclick = () => openBulkPayrollModal();
+  $('#btn-add-payroll').onclick = () => openPayrollModal(employees, accounts);
+  $('#btn-bulk-payroll').onclick = () => openBulkPayrollModal(accounts);
+  $('#btn-payroll-rules').onclick = () => openPayrollRulesModal();
+  $('#btn-payroll-liabilities').onclick = () => openPayrollLiabilitiesModal(accounts);
+  $('#btn-payroll-bank-file').onclick = () => openPayrollBankFileModal();
+  $('#btn-payroll-reports').onclick = () => openPayrollReportsModal();
+  $('#btn-payroll-year-end').onclick = () => openYearEndModal();
+
+  $$('.btn-view-run').forEach(btn => {
+    btn.onclick = () => openPayrollRunModal(Number(btn.dataset.id));
+  });
+
+  $$('.btn-pay-payroll').forEach(btn => {
+    btn.onclick = async () => {
+      const paymentDate = prompt('Pay this payroll record? Net pay will be posted as a Salary expense.\n\nPayment date (YYYY-MM-DD):', btn.dataset.date || new Date().toISOString().split('T')[0]);
+      if (paymentDate === null) return;
+      try {
+        await invoke('pay_payroll', { id: Number(btn.dataset.id), paymentDate: paymentDate || null });
+        toast('Payroll paid');
+        renderPayroll($('#main-content'));
+      } catch (err) { toast(err, 'error'); }
+    };
+  });
+
+  $$('.btn-void-payroll').forEach(btn => {
+    btn.onclick = async () => {
+      const reason = prompt('Void this payroll record? Its salary posting will be reversed and outstanding liabilities cancelled.\n\nReason:');
+      if (reason === null) return;
+      try {
+        await invoke('void_payroll', { id: Number(btn.dataset.id), reason: reason || null });
+        toast('Payroll voided');
+        renderPayroll($('#main-content'));
+      } catch (err) { toast(err, 'error'); }
+    };
+  });
 
   $$('.btn-payslip').forEach(btn => {
     btn.onclick = async () => {
@@ -3920,7 +5062,7 @@ async function renderPayroll(container) {
   });
 }
 
-function openPayrollModal(employees) {
+function openPayrollModal(employees, accounts) {
   if (!employees || employees.length === 0) {
     toast('No employees found. Please add employees first.', 'error');
     setTimeout(() => { location.hash = 'employees'; }, 500);
@@ -3963,13 +5105,33 @@ function openPayrollModal(employees) {
             <option value="Pending">Pending</option>
           </select>
         </div>
+        <div class="form-group">
+          <label class="form-label">Pay From Account</label>
+          <select class="form-select" name="account_id">
+            ${accounts.map(a => `<option value="${a.id}">${escHtml(a.name)}</option>`).join('')}
+          </select>
+        </div>
+        <div class="form-group">
+          <label class="form-label">Overtime Pay</label>
+          <input class="form-input" name="overtime_pay" type="number" step="0.01" min="0" placeholder="From attendance" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Late Penalties</label>
+          <input class="form-input" name="late_penalties" type="number" step="0.01" min="0" placeholder="From attendance" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Absence Deductions</label>
+          <input class="form-input" name="absences" type="number" step="0.01" min="0" placeholder="From attendance" />
+        </div>
         <div class="form-group full-width">
           <label class="form-label">Notes</label>
           <textarea class="form-textarea" name="notes"></textarea>
         </div>
       </div>
+      <div id="payroll-preview" class="card mt-4" style="display:none"></div>
       <div class="form-actions">
         <button type="button" class="btn btn-secondary" onclick="closeModal()">Cancel</button>
+        <button type="button" class="btn btn-secondary" id="btn-preview-payroll">Preview</button>
         <button type="submit" class="btn btn-primary">Save Payroll</button>
       </div>
     </form>
@@ -3978,13 +5140,51 @@ function openPayrollModal(employees) {
   // Auto-fill base salary when employee is selected
   const empSelect = document.querySelector('#payroll-form select[name="employee_id"]');
   const salaryInput = document.querySelector('#payroll-form input[name="base_salary"]');
+  // An auto-filled salary is replaced by the prorated amount on preview; a typed one is kept
   function fillSalary() {
     const emp = employees.find(e => String(e.id) === empSelect.value);
     if (emp && emp.salary) salaryInput.value = emp.salary;
+    salaryInput.dataset.auto = '1';
   }
   empSelect.addEventListener('change', fillSalary);
+  salaryInput.addEventListener('input', () => { delete salaryInput.dataset.auto; });
   fillSalary();
 
+  // Fills overtime, late penalties and absences from attendance; they stay editable
+  $('#btn-preview-payroll').onclick = async () => {
+    const fd = new FormData($('#payroll-form'));
+    if (!fd.get('pay_period_start') || !fd.get('pay_period_end')) {
+      toast('Choose the pay period first', 'error');
+      return;
+    }
+    try {
+      const { record: r, attendance: a, proration } = await invoke('preview_payroll', {
+        employeeId: Number(fd.get('employee_id')),
+        baseSalary: salaryInput.dataset.auto ? null : Number(fd.get('base_salary') || 0),
+        bonuses: Number(fd.get('bonuses') || 0),
+        payPeriodStart: fd.get('pay_period_start'),
+        payPeriodEnd: fd.get('pay_period_end'),
+      });
+      const form = $('#payroll-form');
+      form.overtime_pay.value = a.overtime_pay;
+      form.late_penalties.value = a.late_penalties;
+      form.absences.value = a.absences;
+      if (salaryInput.dataset.auto) salaryInput.value = r.base_salary;
+      const box = $('#payroll-preview');
+      box.style.display = '';
+      box.innerHTML = `
+        ${proration < 1 ? `<p style="color:var(--text-secondary)">Employed for ${Math.round(proration * 100)}% of the period's working days; salary and allowances are prorated.</p>` : ''}
+        <p style="color:var(--text-secondary)">${a.working_days} working day(s): ${a.days_present} present, ${a.half_days} half, ${a.days_absent} absent,
+          ${a.paid_leave_days} paid / ${a.unpaid_leave_days} unpaid leave, ${a.late_days} late, ${a.overtime_hours} overtime hour(s)</p>
+        <div class="totals-row"><span>Gross</span><span>${currency(r.gross_salary)}</span></div>
+        ${(r.lines || []).filter(l => l.line_type !== 'Earning' && l.amount > 0).map(l => `<div class="totals-row"><span>${escHtml(l.name)}</span><span>-${currency(l.amount)}</span></div>`).join('')}
+        ${r.late_penalties > 0 ? `<div class="totals-row"><span>Late Penalties</span><span>-${currency(r.late_penalties)}</span></div>` : ''}
+        ${r.absences > 0 ? `<div class="totals-row"><span>Absences</span><span>-${currency(r.absences)}</span></div>` : ''}
+        <div class="totals-row grand-total"><span>Net Pay</span><span>${currency(r.net_pay)}</span></div>
+      `;
+    } catch (err) { toast(err, 'error'); }
+  };
+
   $('#payroll-form').onsubmit = async (e) => {
     e.preventDefault();
     const fd = new FormData(e.target);
@@ -3998,6 +5198,10 @@ function openPayrollModal(employees) {
         paymentDate: fd.get('payment_date'),
         status: fd.get('status'),
         notes: fd.get('notes') || null,
+        overtimePay: fd.get('overtime_pay') === '' ? null : Number(fd.get('overtime_pay')),
+        latePenalties: fd.get('late_penalties') === '' ? null : Number(fd.get('late_penalties')),
+        absences: fd.get('absences') === '' ? null : Number(fd.get('absences')),
+        accountId: Number(fd.get('account_id')),
       });
       toast('Payroll recorded');
       closeModal();
@@ -4006,11 +5210,11 @@ function openPayrollModal(employees) {
   };
 }
 
-function openBulkPayrollModal() {
+function openBulkPayrollModal(accounts) {
   const today = new Date().toISOString().split('T')[0];
-  openModal('Bulk Payroll Run', `
+  openModal('New Payroll Run', `
     <form id="bulk-payroll-form">
-      <p class="mb-4" style="color:var(--text-secondary)">Process payroll for all employees using their saved salary.</p>
+      <p class="mb-4" style="color:var(--text-secondary)">Drafts payroll for employees on the chosen pay frequency who were employed during the period, using their saved salary (prorated for joiners and leavers), with overtime, late penalties and absences taken from attendance. Review and adjust the draft, then have another payroll manager approve it before paying.</p>
       <div class="form-grid">
         <div class="form-group">
           <label class="form-label">Pay Period Start *</label>
@@ -4024,14 +5228,26 @@ function openBulkPayrollModal() {
           <label class="form-label">Payment Date *</label>
           <input class="form-input" name="payment_date" type="date" value="${today}" required />
         </div>
+        <div class="form-group">
+          <label class="form-label">Pay Frequency</label>
+          <select class="form-select" name="pay_frequency">
+            ${Object.entries(PAY_FREQUENCIES).map(([v, label]) => `<option value="${v}">${label}</option>`).join('')}
+          </select>
+        </div>
         <div class="form-group">
           <label class="form-label">Extra Bonuses</label>
           <input class="form-input" name="bonuses" type="number" step="0.01" value="0" />
         </div>
+        <div class="form-group">
+          <label class="form-label">Pay From Account</label>
+          <select class="form-select" name="account_id">
+            ${accounts.map(a => `<option value="${a.id}">${escHtml(a.name)}</option>`).join('')}
+          </select>
+        </div>
       </div>
       <div class="form-actions">
         <button type="button" class="btn btn-secondary" onclick="closeModal()">Cancel</button>
-        <button type="submit" class="btn btn-primary">Run Bulk Payroll</button>
+        <button type="submit" class="btn btn-primary">Draft Payroll Run</button>
       </div>
     </form>
   `);
@@ -4040,23 +5256,709 @@ function openBulkPayrollModal() {
     e.preventDefault();
     const fd = new FormData(e.target);
     try {
-      const count = await invoke('create_bulk_payroll', {
+      const runId = await invoke('create_payroll_run', {
         payPeriodStart: fd.get('pay_period_start'),
         payPeriodEnd: fd.get('pay_period_end'),
         paymentDate: fd.get('payment_date'),
         bonuses: Number(fd.get('bonuses') || 0),
+        accountId: Number(fd.get('account_id')),
+        payFrequency: fd.get('pay_frequency'),
       });
-      toast(`Payroll processed for ${count} employee(s)`);
-      closeModal();
+      toast('Payroll run drafted');
       renderPayroll($('#main-content'));
+      openPayrollRunModal(runId);
+    } catch (err) { toast(err, 'error'); }
+  };
+}
+
+async function openPayrollRunModal(runId) {
+  const run = await invoke('get_payroll_run_detail', { id: runId });
+  const isDraft = run.status === 'Draft';
+  const refresh = () => { renderPayroll($('#main-content')); openPayrollRunModal(runId); };
+
+  openModal(`Payroll Run ${run.run_number}`, `
+    <p class="mb-4" style="color:var(--text-secondary)">
+      ${run.pay_period_start} – ${run.pay_period_end}, paid ${run.payment_date} · ${statusBadge(run.status)}
+      · Prepared by ${escHtml(run.created_by_name) || '—'}${run.approved_by_name ? ` · Approved by ${escHtml(run.approved_by_name)}` : ''}
+      ${run.void_reason ? ` · Void: ${escHtml(run.void_reason)}` : ''}
+    </p>
+    <div class="table-wrap">
+      <table>
+        <thead>
+          <tr><th>Employee</th><th>Base</th><th>Overtime</th><th>Bonuses</th><th>Late</th><th>Absences</th><th>Gross</th><th>Deductions</th><th>Net</th>${isDraft ? '<th class="col-actions"></th>' : ''}</tr>
+        </thead>
+        <tbody>
+          ${(run.records || []).map(r => `
+            <tr data-id="${r.id}">
+              <td>${escHtml(r.employee_name)}</td>
+              ${isDraft ? `
+                <td><input class="form-input adj-base" type="number" step="0.01" min="0" value="${r.base_salary}" style="width:100px" /></td>
+                <td><input class="form-input adj-overtime" type="number" step="0.01" min="0" value="${r.overtime_pay}" style="width:90px" /></td>
+                <td><input class="form-input adj-bonuses" type="number" step="0.01" min="0" value="${r.bonuses}" style="width:90px" /></td>
+                <td><input class="form-input adj-late" type="number" step="0.01" min="0" value="${r.late_penalties}" style="width:80px" /></td>
+                <td><input class="form-input adj-absences" type="number" step="0.01" min="0" value="${r.absences}" style="width:90px" /></td>
+              ` : `
+                <td>${currency(r.base_salary)}</td>
+                <td>${currency(r.overtime_pay)}</td>
+                <td>${currency(r.bonuses)}</td>
+                <td>${currency(r.late_penalties)}</td>
+                <td>${currency(r.absences)}</td>
+              `}
+              <td>${currency(r.gross_salary)}</td>
+              <td>${currency(r.total_deductions)}</td>
+              <td><strong>${currency(r.net_pay)}</strong></td>
+              ${isDraft ? `<td>
+                <button class="btn btn-secondary btn-sm btn-adjust-record">Recalculate</button>
+                <button class="btn btn-danger btn-sm btn-icon btn-remove-record" title="Remove from run">&times;</button>
+              </td>` : ''}
+            </tr>
+          `).join('')}
+        </tbody>
+      </table>
+    </div>
+    <div class="totals-row grand-total mt-4"><span>Total Net Pay (${run.employee_count})</span><span>${currency(run.total_net)}</span></div>
+    <div class="form-actions">
+      <button type="button" class="btn btn-secondary" onclick="closeModal()">Close</button>
+      ${run.status !== 'Void' ? '<button type="button" class="btn btn-danger" id="btn-void-run">Void Run</button>' : ''}
+      ${isDraft ? '<button type="button" class="btn btn-primary" id="btn-approve-run">Approve</button>' : ''}
+      ${run.status === 'Approved' ? '<button type="button" class="btn btn-primary" id="btn-pay-run">Pay Run</button>' : ''}
+    </div>
+  `);
+  const modal = document.querySelector('.modal');
+  if (modal) modal.style.maxWidth = '1100px';
+
+  $$('.btn-adjust-record').forEach(btn => {
+    btn.onclick = async () => {
+      const tr = btn.closest('tr');
+      const num = cls => Number(tr.querySelector(cls).value || 0);
+      try {
+        await invoke('adjust_payroll_run_record', {
+          runId,
+          payrollId: Number(tr.dataset.id),
+          baseSalary: num('.adj-base'),
+          bonuses: num('.adj-bonuses'),
+          overtimePay: num('.adj-overtime'),
+          latePenalties: num('.adj-late'),
+          absences: num('.adj-absences'),
+        });
+        toast('Pay recalculated');
+        refresh();
+      } catch (err) { toast(err, 'error'); }
+    };
+  });
+  $$('.btn-remove-record').forEach(btn => {
+    btn.onclick = async () => {
+      try {
+        await invoke('remove_payroll_run_record', { runId, payrollId: Number(btn.closest('tr').dataset.id) });
+        refresh();
+      } catch (err) { toast(err, 'error'); }
+    };
+  });
+  $('#btn-approve-run')?.addEventListener('click', async () => {
+    try {
+      await invoke('approve_payroll_run', { id: runId });
+      toast('Payroll run approved');
+      refresh();
+    } catch (err) { toast(err, 'error'); }
+  });
+  $('#btn-pay-run')?.addEventListener('click', async () => {
+    if (!confirm(`Pay ${currency(run.total_net)} to ${run.employee_count} employee(s)? Salary expenses will be posted.`)) return;
+    try {
+      await invoke('pay_payroll_run', { id: runId });
+      toast('Payroll run paid');
+      refresh();
+    } catch (err) { toast(err, 'error'); }
+  });
+  $('#btn-void-run')?.addEventListener('click', async () => {
+    const reason = prompt('Void this payroll run? Any salary postings will be reversed.\n\nReason:');
+    if (reason === null) return;
+    try {
+      await invoke('void_payroll_run', { id: runId, reason: reason || null });
+      toast('Payroll run voided');
+      refresh();
+    } catch (err) { toast(err, 'error'); }
+  });
+}
+
+function openPayrollReportsModal() {
+  const now = new Date();
+  const monthStart = new Date(now.getFullYear(), now.getMonth(), 1).toISOString().split('T')[0];
+  const monthEnd = new Date(now.getFullYear(), now.getMonth() + 1, 0).toISOString().split('T')[0];
+
+  openModal('Payslips & Payroll Register', `
+    <form id="payroll-reports-form">
+      <div class="form-grid">
+        <div class="form-group">
+          <label class="form-label">Period Start *</label>
+          <input class="form-input" name="pay_period_start" type="date" required value="${monthStart}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Period End *</label>
+          <input class="form-input" name="pay_period_end" type="date" required value="${monthEnd}" />
+        </div>
+        <div class="form-group full-width">
+          <label class="form-label">Export</label>
+          <select class="form-select" name="output">
+            <option value="payslips-single">All payslips in one PDF</option>
+            <option value="payslips-folder">One payslip PDF per employee (choose a folder)</option>
+            <option value="register-PDF">Payroll register (PDF)</option>
+            <option value="register-XLSX">Payroll register (Excel)</option>
+          </select>
+        </div>
+      </div>
+      <div class="form-actions">
+        <button type="button" class="btn btn-secondary" onclick="closeModal()">Cancel</button>
+        <button type="submit" class="btn btn-primary">Export</button>
+      </div>
+    </form>
+  `);
+
+  $('#payroll-reports-form').onsubmit = async (e) => {
+    e.preventDefault();
+    const fd = new FormData(e.target);
+    const payPeriodStart = fd.get('pay_period_start');
+    const payPeriodEnd = fd.get('pay_period_end');
+    const [kind, option] = fd.get('output').split('-');
+    const { save, open } = window.__TAURI__.dialog;
+    try {
+      if (kind === 'payslips') {
+        const path = option === 'folder'
+          ? await open({ directory: true, multiple: false })
+          : await save({ defaultPath: `payslips-${payPeriodEnd}.pdf`, filters: [{ name: 'PDF', extensions: ['pdf'] }] });
+        if (!path) return;
+        const count = await invoke('export_payslips', { payPeriodStart, payPeriodEnd, mode: option, path });
+        toast(`${count} payslip${count !== 1 ? 's' : ''} exported`);
+      } else {
+        const ext = option === 'PDF' ? 'pdf' : 'xlsx';
+        const filePath = await save({
+          defaultPath: `payroll-register-${payPeriodEnd}.${ext}`,
+          filters: [option === 'PDF' ? { name: 'PDF', extensions: ['pdf'] } : { name: 'Excel', extensions: ['xlsx'] }],
+        });
+        if (!filePath) return;
+        const count = await invoke('export_payroll_register', { payPeriodStart, payPeriodEnd, format: option, filePath });
+        toast(`Payroll register exported (${count} employee${count !== 1 ? 's' : ''})`);
+      }
+      closeModal();
+    } catch (err) { toast(err, 'error'); }
+  };
+}
+
+const MONTH_NAMES = ['January', 'February', 'March', 'April', 'May', 'June', 'July', 'August', 'September', 'October', 'November', 'December'];
+
+function openYearEndModal() {
+  const thisYear = new Date().getFullYear();
+
+  openModal('Year-End Earnings', `
+    <form id="year-end-form">
+      <div class="form-grid">
+        <div class="form-group">
+          <label class="form-label">Tax Year *</label>
+          <input class="form-input" name="year" type="number" min="2000" max="2100" required value="${thisYear - 1}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Tax Year Starts In</label>
+          <select class="form-select" name="start_month">
+            ${MONTH_NAMES.map((m, i) => `<option value="${i + 1}">${m}</option>`).join('')}
+          </select>
+        </div>
+      </div>
+      <div class="form-actions">
+        <button type="submit" class="btn btn-secondary">Load</button>
+        <button type="button" class="btn btn-secondary" id="btn-year-end-xlsx">Summary (Excel)</button>
+        <button type="button" class="btn btn-secondary" id="btn-year-end-folder">Certificates (one PDF each)</button>
+        <button type="button" class="btn btn-primary" id="btn-year-end-pdf">Certificates (PDF)</button>
+      </div>
+    </form>
+    <div id="year-end-results" class="mt-4"></div>
+  `);
+  document.querySelector('.modal').style.maxWidth = '900px';
+
+  const form = $('#year-end-form');
+  const params = () => {
+    const fd = new FormData(form);
+    return { year: Number(fd.get('year')), startMonth: Number(fd.get('start_month')) };
+  };
+
+  const exportCertificates = async (mode, employeeId = null) => {
+    const { year, startMonth } = params();
+    const { save, open } = window.__TAURI__.dialog;
+    const path = mode === 'folder'
+      ? await open({ directory: true, multiple: false })
+      : await save({ defaultPath: `earnings-certificates-${year}.pdf`, filters: [{ name: 'PDF', extensions: ['pdf'] }] });
+    if (!path) return;
+    try {
+      const count = await invoke('export_earnings_certificates', { year, startMonth, employeeId, mode, path });
+      toast(`${count} certificate${count !== 1 ? 's' : ''} exported`);
+    } catch (err) { toast(err, 'error'); }
+  };
+
+  const load = async () => {
+    const { year, startMonth } = params();
+    const el = $('#year-end-results');
+    try {
+      const summaries = await invoke('get_year_end_summary', { year, startMonth });
+      if (summaries.length === 0) {
+        el.innerHTML = `<p style="color:var(--text-muted)">No paid payroll in this tax year.</p>`;
+        return;
+      }
+      const sum = (f) => summaries.reduce((t, s) => t + f(s), 0);
+      el.innerHTML = `
+        <p style="color:var(--text-muted);margin-bottom:8px">${summaries[0].tax_year_start} to ${summaries[0].tax_year_end}</p>
+        <div class="table-wrap">
+          <table>
+            <thead><tr><th>Employee</th><th>Payslips</th><th>Gross</th><th>Tax</th><th>Deductions</th><th>Net Pay</th><th>Employer</th><th></th></tr></thead>
+            <tbody>
+              ${summaries.map(s => `
+                <tr>
+                  <td>${escHtml(s.employee_name || '')}</td>
+                  <td>${s.payslips}</td>
+                  <td>${currency(s.gross_salary)}</td>
+                  <td>${currency(s.tax)}</td>
+                  <td>${currency(s.total_deductions)}</td>
+                  <td>${currency(s.net_pay)}</td>
+                  <td>${currency(s.employer_contributions)}</td>
+                  <td><button class="btn btn-sm btn-secondary btn-year-end-cert" data-id="${s.employee_id}">Certificate</button></td>
+                </tr>
+              `).join('')}
+              <tr class="totals-row grand-total">
+                <td>Total</td>
+                <td>${sum(s => s.payslips)}</td>
+                <td>${currency(sum(s => s.gross_salary))}</td>
+                <td>${currency(sum(s => s.tax))}</td>
+                <td>${currency(sum(s => s.total_deductions))}</td>
+                <td>${currency(sum(s => s.net_pay))}</td>
+                <td>${currency(sum(s => s.employer_contributions))}</td>
+                <td></td>
+              </tr>
+            </tbody>
+          </table>
+        </div>
+      `;
+      $$('.btn-year-end-cert').forEach(btn => {
+        btn.onclick = () => exportCertificates('single', Number(btn.dataset.id));
+      });
+    } catch (err) { toast(err, 'error'); }
+  };
+
+  form.onsubmit = (e) => { e.preventDefault(); load(); };
+  $('#btn-year-end-pdf').onclick = () => exportCertificates('single');
+  $('#btn-year-end-folder').onclick = () => exportCertificates('folder');
+  $('#btn-year-end-xlsx').onclick = async () => {
+    const { year, startMonth } = params();
+    const { save } = window.__TAURI__.dialog;
+    const filePath = await save({ defaultPath: `payroll-year-end-${year}.xlsx`, filters: [{ name: 'Excel', extensions: ['xlsx'] }] });
+    if (!filePath) return;
+    try {
+      const count = await invoke('export_year_end_xlsx', { year, startMonth, filePath });
+      toast(`Year-end summary exported (${count} employee${count !== 1 ? 's' : ''})`);
+    } catch (err) { toast(err, 'error'); }
+  };
+  load();
+}
+
+// ── Bank Payment Files ─────────────────────────────────
+
+function paymentFileFields() {
+  return `
+    <div class="form-group">
+      <label class="form-label">Payment Date *</label>
+      <input class="form-input" name="payment_date" type="date" required value="${new Date().toISOString().split('T')[0]}" />
+    </div>
+    <div class="form-group">
+      <label class="form-label">Format *</label>
+      <select class="form-select" name="format">
+        <option value="CSV">CSV</option>
+        <option value="pain.001">ISO 20022 (pain.001 XML)</option>
+      </select>
+    </div>
+    <div class="form-group">
+      <label class="form-label">Currency Code *</label>
+      <input class="form-input" name="currency" required maxlength="3" placeholder="LKR" style="text-transform:uppercase" />
+    </div>
+  `;
+}
+
+async function choosePaymentFilePath(format, name) {
+  const { save } = window.__TAURI__.dialog;
+  const xml = format === 'pain.001';
+  return save({
+    defaultPath: `${name}.${xml ? 'xml' : 'csv'}`,
+    filters: [xml ? { name: 'XML', extensions: ['xml'] } : { name: 'CSV', extensions: ['csv'] }],
+  });
+}
+
+function openPayrollBankFileModal() {
+  const now = new Date();
+  const monthStart = new Date(now.getFullYear(), now.getMonth(), 1).toISOString().split('T')[0];
+  const monthEnd = new Date(now.getFullYear(), now.getMonth() + 1, 0).toISOString().split('T')[0];
+
+  openModal('Salary Bank Payment File', `
+    <form id="payroll-bank-file-form">
+      <p class="mb-4" style="color:var(--text-secondary)">Net pay for payroll in the period, excluding void records and runs that are not yet approved. Employees need bank details, and pain.001 files also need the business bank details from Settings.</p>
+      <div class="form-grid">
+        <div class="form-group">
+          <label class="form-label">Period Start *</label>
+          <input class="form-input" name="pay_period_start" type="date" required value="${monthStart}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Period End *</label>
+          <input class="form-input" name="pay_period_end" type="date" required value="${monthEnd}" />
+        </div>
+        ${paymentFileFields()}
+      </div>
+      <div class="form-actions">
+        <button type="button" class="btn btn-secondary" onclick="closeModal()">Cancel</button>
+        <button type="submit" class="btn btn-primary">Export</button>
+      </div>
+    </form>
+  `);
+
+  $('#payroll-bank-file-form').onsubmit = async (e) => {
+    e.preventDefault();
+    const fd = new FormData(e.target);
+    const format = fd.get('format');
+    try {
+      const filePath = await choosePaymentFilePath(format, `salaries-${fd.get('pay_period_end')}`);
+      if (!filePath) return;
+      const total = await invoke('export_payroll_payment_file', {
+        payPeriodStart: fd.get('pay_period_start'),
+        payPeriodEnd: fd.get('pay_period_end'),
+        paymentDate: fd.get('payment_date'),
+        format,
+        currency: fd.get('currency').trim().toUpperCase(),
+        filePath,
+      });
+      toast(`Payment file saved (${currency(total)})`);
+      closeModal();
+    } catch (err) { toast(err, 'error'); }
+  };
+}
+
+function openSupplierBankFileModal(expenses) {
+  openModal('Supplier Bank Payment File', `
+    <form id="supplier-bank-file-form">
+      <p class="mb-4" style="color:var(--text-secondary)">Select the expenses to pay and enter each supplier's bank details. Amounts are taken from the transactions.</p>
+      <div class="table-wrap">
+        <table>
+          <thead>
+            <tr><th></th><th>Date</th><th>Description</th><th>Amount</th><th>Payee *</th><th>Bank *</th><th>Branch</th><th>Account No. *</th></tr>
+          </thead>
+          <tbody>
+            ${expenses.length === 0
+              ? '<tr><td colspan="8" class="table-empty">No expense transactions.</td></tr>'
+              : expenses.map(t => `
+                <tr data-id="${t.id}">
+                  <td><input type="checkbox" class="pay-select" /></td>
+                  <td>${t.date}</td>
+                  <td>${escHtml(t.description) || '—'}</td>
+                  <td>${currency(t.amount)}</td>
+                  <td><input class="form-input pay-payee" style="width:140px" /></td>
+                  <td><input class="form-input pay-bank" style="width:120px" /></td>
+                  <td><input class="form-input pay-branch" style="width:100px" /></td>
+                  <td><input class="form-input pay-account" style="width:130px" /></td>
+                </tr>
+              `).join('')}
+          </tbody>
+        </table>
+      </div>
+      <div class="form-grid mt-4">
+        ${paymentFileFields()}
+      </div>
+      <div class="form-actions">
+        <button type="button" class="btn btn-secondary" onclick="closeModal()">Cancel</button>
+        <button type="submit" class="btn btn-primary">Export</button>
+      </div>
+    </form>
+  `);
+  const modal = document.querySelector('.modal');
+  if (modal) modal.style.maxWidth = '1100px';
+
+  $('#supplier-bank-file-form').onsubmit = async (e) => {
+    e.preventDefault();
+    const fd = new FormData(e.target);
+    const format = fd.get('format');
+    const val = (tr, cls) => tr.querySelector(cls).value.trim() || null;
+    const payments = [...$$('#supplier-bank-file-form tbody tr[data-id]')]
+      .filter(tr => tr.querySelector('.pay-select').checked)
+      .map(tr => ({
+        payee_name: val(tr, '.pay-payee') || '',
+        bank_name: val(tr, '.pay-bank'),
+        bank_branch: val(tr, '.pay-branch'),
+        account_no: val(tr, '.pay-account'),
+        amount: 0,
+        reference: '',
+        remittance_info: null,
+        transaction_id: Number(tr.dataset.id),
+      }));
+    if (payments.length === 0) {
+      toast('Select at least one expense to pay', 'error');
+      return;
+    }
+    try {
+      const filePath = await choosePaymentFilePath(format, `supplier-payments-${fd.get('payment_date')}`);
+      if (!filePath) return;
+      const total = await invoke('export_supplier_payment_file', {
+        payments,
+        paymentDate: fd.get('payment_date'),
+        format,
+        currency: fd.get('currency').trim().toUpperCase(),
+        filePath,
+      });
+      toast(`Payment file saved (${currency(total)})`);
+      closeModal();
+    } catch (err) { toast(err, 'error'); }
+  };
+}
+
+async function openPayrollLiabilitiesModal(accounts) {
+  const liabilities = await invoke('get_payroll_liabilities', { status: 'Outstanding' });
+  const today = new Date().toISOString().split('T')[0];
+
+  openModal('Outstanding Payroll Liabilities', `
+    <p class="mb-4" style="color:var(--text-secondary)">Tax withheld, fund contributions and other deductions from paid payroll. Remitting records the payment as a Salary expense.</p>
+    <form id="remit-form">
+      <div class="table-wrap">
+        <table>
+          <thead>
+            <tr><th></th><th>Liability</th><th>Employee</th><th>Period End</th><th>Employee Share</th><th>Employer Share</th><th>Total</th></tr>
+          </thead>
+          <tbody>
+            ${liabilities.length === 0
+              ? '<tr><td colspan="7" class="table-empty">Nothing outstanding.</td></tr>'
+              : liabilities.map(l => `
+                <tr>
+                  <td><input type="checkbox" class="remit-check" value="${l.id}" data-amount="${l.amount}" /></td>
+                  <td>${escHtml(l.name)}</td>
+                  <td>${escHtml(l.employee_name) || '—'}</td>
+                  <td>${l.pay_period_end || '—'}</td>
+                  <td>${currency(l.employee_amount)}</td>
+                  <td>${currency(l.employer_amount)}</td>
+                  <td>${currency(l.amount)}</td>
+                </tr>
+              `).join('')}
+          </tbody>
+        </table>
+      </div>
+      <div class="form-grid mt-4">
+        <div class="form-group">
+          <label class="form-label">Pay From Account</label>
+          <select class="form-select" name="account_id">
+            ${accounts.map(a => `<option value="${a.id}">${escHtml(a.name)}</option>`).join('')}
+          </select>
+        </div>
+        <div class="form-group">
+          <label class="form-label">Date</label>
+          <input class="form-input" name="date" type="date" value="${today}" required />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Reference</label>
+          <input class="form-input" name="reference" placeholder="e.g. receipt number" />
+        </div>
+      </div>
+      <div class="form-actions">
+        <button type="button" class="btn btn-secondary" onclick="closeModal()">Close</button>
+        <button type="submit" class="btn btn-primary" id="btn-remit">Remit Selected</button>
+      </div>
+    </form>
+  `);
+  const modal = document.querySelector('.modal');
+  if (modal) modal.style.maxWidth = '800px';
+
+  const updateTotal = () => {
+    const total = [...$$('.remit-check')].filter(c => c.checked).reduce((sum, c) => sum + Number(c.dataset.amount), 0);
+    $('#btn-remit').textContent = total > 0 ? `Remit ${currency(total)}` : 'Remit Selected';
+  };
+  $$('.remit-check').forEach(c => c.addEventListener('change', updateTotal));
+
+  $('#remit-form').onsubmit = async (e) => {
+    e.preventDefault();
+    const fd = new FormData(e.target);
+    try {
+      await invoke('remit_payroll_liabilities', {
+        liabilityIds: [...$$('.remit-check')].filter(c => c.checked).map(c => Number(c.value)),
+        accountId: Number(fd.get('account_id')),
+        date: fd.get('date'),
+        reference: fd.get('reference') || null,
+      });
+      toast('Liabilities remitted');
+      openPayrollLiabilitiesModal(accounts);
+    } catch (err) { toast(err, 'error'); }
+  };
+}
+
+async function openPayrollRulesModal() {
+  const [components, brackets] = await Promise.all([
+    invoke('get_payroll_components'),
+    invoke('get_tax_brackets'),
+  ]);
+  const rateText = (c, rate) => c.calculation === 'Percent' ? `${rate}% of ${c.basis.toLowerCase()}` : currency(rate);
+
+  openModal('Payroll Rules', `
+    <h3 style="margin-bottom:8px; font-size:var(--font-size-md); font-weight:600;">Components</h3>
+    <div class="table-wrap">
+      <table>
+        <thead>
+          <tr><th>Name</th><th>Type</th><th>Employee</th><th>Employer</th><th>Status</th><th class="col-actions">Actions</th></tr>
+        </thead>
+        <tbody>
+          ${components.length === 0
+            ? '<tr><td colspan="6" class="table-empty">No components defined.</td></tr>'
+            : components.map(c => `
+              <tr>
+                <td>${escHtml(c.name)}${c.component_type === 'Earning' && !c.taxable ? ' <span class="text-muted">(non-taxable)</span>' : ''}</td>
+                <td>${c.component_type}</td>
+                <td>${c.component_type === 'Contribution' && !c.amount ? '—' : rateText(c, c.amount)}</td>
+                <td>${c.component_type === 'Contribution' ? rateText(c, c.employer_rate) : '—'}</td>
+                <td>${c.is_active ? '<span class="badge badge-paid">Active</span>' : '<span class="badge badge-cancelled">Inactive</span>'}</td>
+                <td>
+                  <button class="btn btn-secondary btn-sm btn-toggle-component" data-id="${c.id}" data-active="${c.is_active}">${c.is_active ? 'Disable' : 'Enable'}</button>
+                  <button class="btn btn-danger btn-sm btn-delete-component" data-id="${c.id}">Delete</button>
+                </td>
+              </tr>
+            `).join('')}
+        </tbody>
+      </table>
+    </div>
+
+    <form id="component-form" class="mt-4">
+      <div class="form-grid">
+        <div class="form-group">
+          <label class="form-label">Name *</label>
+          <input class="form-input" name="name" required placeholder="e.g. Transport allowance" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Type</label>
+          <select class="form-select" name="component_type">
+            <option value="Earning">Earning</option>
+            <option value="Deduction">Deduction</option>
+            <option value="Contribution">Contribution (employee + employer)</option>
+          </select>
+        </div>
+        <div class="form-group">
+          <label class="form-label">Calculation</label>
+          <select class="form-select" name="calculation">
+            <option value="Fixed">Fixed amount</option>
+            <option value="Percent">Percentage</option>
+          </select>
+        </div>
+        <div class="form-group">
+          <label class="form-label">Percentage of</label>
+          <select class="form-select" name="basis">
+            <option value="Basic">Basic salary</option>
+            <option value="Gross">Gross pay</option>
+          </select>
+        </div>
+        <div class="form-group">
+          <label class="form-label">Employee amount / rate</label>
+          <input class="form-input" name="amount" type="number" step="0.01" min="0" value="0" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Employer rate (contributions)</label>
+          <input class="form-input" name="employer_rate" type="number" step="0.01" min="0" value="0" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Taxable (earnings)</label>
+          <select class="form-select" name="taxable">
+            <option value="1">Yes</option>
+            <option value="0">No</option>
+          </select>
+        </div>
+      </div>
+      <div class="form-actions">
+        <button type="submit" class="btn btn-primary">Add Component</button>
+      </div>
+    </form>
+
+    <h3 style="margin:24px 0 8px; font-size:var(--font-size-md); font-weight:600;">Income Tax Brackets (per pay period)</h3>
+    <form id="brackets-form">
+      <table class="invoice-items-table">
+        <thead>
+          <tr><th>From</th><th>To (blank = no limit)</th><th>Rate %</th><th class="col-remove"></th></tr>
+        </thead>
+        <tbody id="brackets-body"></tbody>
+      </table>
+      <button type="button" class="btn btn-secondary btn-sm" id="btn-add-bracket">Add Bracket</button>
+      <div class="form-actions">
+        <button type="button" class="btn btn-secondary" onclick="closeModal()">Close</button>
+        <button type="submit" class="btn btn-primary">Save Brackets</button>
+      </div>
+    </form>
+  `);
+  const modal = document.querySelector('.modal');
+  if (modal) modal.style.maxWidth = '800px';
+
+  function addBracketRow(b = {}) {
+    const tr = document.createElement('tr');
+    tr.innerHTML = `
+      <td><input class="form-input bracket-lower" type="number" step="0.01" min="0" value="${b.lower_bound ?? 0}" /></td>
+      <td><input class="form-input bracket-upper" type="number" step="0.01" min="0" value="${b.upper_bound ?? ''}" /></td>
+      <td><input class="form-input bracket-rate" type="number" step="0.01" min="0" max="100" value="${b.rate ?? 0}" /></td>
+      <td><button type="button" class="btn btn-danger btn-sm btn-icon">&times;</button></td>
+    `;
+    tr.querySelector('button').onclick = () => tr.remove();
+    $('#brackets-body').appendChild(tr);
+  }
+  brackets.forEach(addBracketRow);
+  $('#btn-add-bracket').onclick = () => addBracketRow();
+
+  $('#brackets-form').onsubmit = async (e) => {
+    e.preventDefault();
+    try {
+      await invoke('save_tax_brackets', {
+        brackets: [...$$('#brackets-body tr')].map(tr => ({
+          id: null,
+          lower_bound: Number(tr.querySelector('.bracket-lower').value || 0),
+          upper_bound: tr.querySelector('.bracket-upper').value === '' ? null : Number(tr.querySelector('.bracket-upper').value),
+          rate: Number(tr.querySelector('.bracket-rate').value || 0),
+        })),
+      });
+      toast('Tax brackets saved');
+      openPayrollRulesModal();
+    } catch (err) { toast(err, 'error'); }
+  };
+
+  $('#component-form').onsubmit = async (e) => {
+    e.preventDefault();
+    const fd = new FormData(e.target);
+    try {
+      await invoke('create_payroll_component', {
+        name: fd.get('name'),
+        componentType: fd.get('component_type'),
+        calculation: fd.get('calculation'),
+        amount: Number(fd.get('amount') || 0),
+        employerRate: Number(fd.get('employer_rate') || 0),
+        basis: fd.get('basis'),
+        taxable: fd.get('taxable') === '1',
+      });
+      toast('Component added');
+      openPayrollRulesModal();
     } catch (err) { toast(err, 'error'); }
   };
+
+  $$('.btn-toggle-component').forEach(btn => {
+    btn.onclick = async () => {
+      try {
+        await invoke('update_payroll_component', { id: Number(btn.dataset.id), isActive: btn.dataset.active !== 'true' });
+        openPayrollRulesModal();
+      } catch (err) { toast(err, 'error'); }
+    };
+  });
+  $$('.btn-delete-component').forEach(btn => {
+    btn.onclick = async () => {
+      if (!confirm('Delete this payroll component? Existing payslips keep their amounts.')) return;
+      try {
+        await invoke('delete_payroll_component', { id: Number(btn.dataset.id) });
+        openPayrollRulesModal();
+      } catch (err) { toast(err, 'error'); }
+    };
+  });
 }
 
 // ── Employees ────────────────────────────────────────
 
 async function renderEmployees(container) {
   let employees = await invoke('get_employees');
+  const payAccess = hasPayrollAccess();
 
   container.innerHTML = `
     <div class="page-enter" data-perm="view_employees">
@@ -4065,7 +5967,7 @@ async function renderEmployees(container) {
           <h1 class="page-title">Employees</h1>
           <p class="page-subtitle">${employees.length} employee${employees.length !== 1 ? 's' : ''}</p>
         </div>
-        <button class="btn btn-primary" id="btn-add-employee"><svg xmlns="http://www.w3.org/2000/svg" width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" style="vertical-align:middle;margin-right:4px"><circle cx="12" cy="12" r="10"/><line x1="12" y1="8" x2="12" y2="16"/><line x1="8" y1="12" x2="16" y2="12"/></svg> Add Employee</button>
+        <button class="btn btn-primary" id="btn-add-employee" data-perm="create_employees"><svg xmlns="http://www.w3.org/2000/svg" width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round" style="vertical-align:middle;margin-right:4px"><circle cx="12" cy="12" r="10"/><line x1="12" y1="8" x2="12" y2="16"/><line x1="8" y1="12" x2="16" y2="12"/></svg> Add Employee</button>
       </div>
 
       <div class="table-wrap">
@@ -4074,24 +5976,34 @@ async function renderEmployees(container) {
             <tr>
               <th>Name</th>
               <th>Role</th>
+              <th>Department</th>
               <th>Email</th>
               <th>Phone</th>
-              <th>Salary</th>
+              ${payAccess ? '<th>Salary</th>' : ''}
+              <th>Status</th>
               <th class="col-actions">Actions</th>
             </tr>
           </thead>
           <tbody>
             ${employees.length === 0
-              ? '<tr><td colspan="6" class="table-empty">No employees yet. Add your first employee.</td></tr>'
+              ? `<tr><td colspan="${payAccess ? 8 : 7}" class="table-empty">No employees yet. Add your first employee.</td></tr>`
               : employees.map(e => `
                 <tr>
                   <td>${escHtml(e.name)}</td>
                   <td>${escHtml(e.role) || '—'}</td>
+                  <td>${escHtml(e.department) || '—'}</td>
                   <td>${escHtml(e.email) || '—'}</td>
                   <td>${escHtml(e.phone) || '—'}</td>
-                  <td>${currency(e.salary)}</td>
+                  ${payAccess ? `<td>${currency(e.salary)} <span style="color:var(--text-secondary); font-size:var(--font-size-xs)">${PAY_FREQUENCIES[e.pay_frequency] || ''}</span></td>` : ''}
+                  <td>
+                    <span class="badge ${{ Active: 'badge-paid', Suspended: 'badge-sent', Terminated: 'badge-cancelled' }[e.status] || ''}">${e.status}</span>
+                    ${e.exit_date ? `<div style="color:var(--text-secondary); font-size:var(--font-size-xs)">Last day ${e.exit_date}</div>` : ''}
+                  </td>
                   <td>
-                    <button class="btn btn-secondary btn-sm btn-icon edit-emp" data-id="${e.id}" data-json='${JSON.stringify(e).replace(/'/g, "&#39;")}'><svg xmlns="http://www.w3.org/2000/svg" width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><path d="M11 4H4a2 2 0 0 0-2 2v14a2 2 0 0 0 2 2h14a2 2 0 0 0 2-2v-7"/><path d="M18.5 2.5a2.121 2.121 0 0 1 3 3L12 15l-4 1 1-4 9.5-9.5z"/></svg></button>
+                    ${payAccess ? `
+                    <button class="btn btn-secondary btn-sm btn-attendance" data-id="${e.id}" data-name="${escHtml(e.name)}">Attendance</button>
+                    <button class="btn btn-secondary btn-sm btn-loans" data-id="${e.id}" data-name="${escHtml(e.name)}">Loans</button>` : ''}
+                    <button class="btn btn-secondary btn-sm btn-icon edit-emp" data-perm="edit_employees" data-id="${e.id}" data-json='${JSON.stringify(e).replace(/'/g, "&#39;")}'><svg xmlns="http://www.w3.org/2000/svg" width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><path d="M11 4H4a2 2 0 0 0-2 2v14a2 2 0 0 0 2 2h14a2 2 0 0 0 2-2v-7"/><path d="M18.5 2.5a2.121 2.121 0 0 1 3 3L12 15l-4 1 1-4 9.5-9.5z"/></svg></button>
                   </td>
                 </tr>
               `).join('')
@@ -4110,8 +6022,247 @@ async function renderEmployees(container) {
       openEmployeeModal(data);
     };
   });
+
+  $$('.btn-attendance').forEach(btn => {
+    btn.onclick = () => openAttendanceModal(Number(btn.dataset.id), btn.dataset.name);
+  });
+
+  $$('.btn-loans').forEach(btn => {
+    btn.onclick = () => openEmployeeLoansModal(Number(btn.dataset.id), btn.dataset.name);
+  });
 }
 
+async function openEmployeeLoansModal(employeeId, employeeName) {
+  const loans = await invoke('get_employee_loans', { employeeId });
+  const loanBadge = (status) => ({
+    'Active': 'badge-sent',
+    'Settled': 'badge-paid',
+    'Cancelled': 'badge-cancelled',
+  }[status] || '');
+
+  openModal(`Loans & Advances — ${escHtml(employeeName)}`, `
+    <div class="table-wrap">
+      <table>
+        <thead>
+          <tr><th>Type</th><th>Starts</th><th>Principal</th><th>Instalment</th><th>Recovered</th><th>Balance</th><th>Status</th><th class="col-actions"></th></tr>
+        </thead>
+        <tbody>
+          ${loans.length === 0
+            ? '<tr><td colspan="8" class="table-empty">No loans or advances.</td></tr>'
+            : loans.map(l => `
+              <tr>
+                <td>${l.loan_type}${l.notes ? `<div style="color:var(--text-secondary); font-size:var(--font-size-xs)">${escHtml(l.notes)}</div>` : ''}</td>
+                <td>${l.start_date}</td>
+                <td>${currency(l.principal)}</td>
+                <td>${currency(l.instalment)}</td>
+                <td>${currency(l.recovered)}</td>
+                <td><strong>${currency(l.balance)}</strong></td>
+                <td><span class="badge ${loanBadge(l.status)}">${l.status}</span></td>
+                <td>
+                  ${l.status === 'Active' ? `<button class="btn btn-secondary btn-sm btn-loan-status" data-id="${l.id}" data-status="Cancelled">Stop</button>` : ''}
+                  ${l.status === 'Cancelled' ? `<button class="btn btn-secondary btn-sm btn-loan-status" data-id="${l.id}" data-status="Active">Resume</button>` : ''}
+                  ${l.recovered === 0 ? `<button class="btn btn-danger btn-sm btn-icon btn-loan-delete" data-id="${l.id}" title="Delete">&times;</button>` : ''}
+                </td>
+              </tr>
+            `).join('')}
+        </tbody>
+      </table>
+    </div>
+
+    <form id="loan-form" class="card mt-4">
+      <h3 style="margin-bottom:12px; font-size:var(--font-size-md); font-weight:600;">New Loan or Advance</h3>
+      <div class="form-grid">
+        <div class="form-group">
+          <label class="form-label">Type *</label>
+          <select class="form-select" name="loan_type">
+            <option value="Advance">Salary Advance</option>
+            <option value="Loan">Loan</option>
+          </select>
+        </div>
+        <div class="form-group">
+          <label class="form-label">First Deduction From *</label>
+          <input class="form-input" name="start_date" type="date" required value="${new Date().toISOString().split('T')[0]}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Principal *</label>
+          <input class="form-input" name="principal" type="number" step="0.01" min="0.01" required />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Instalment per Pay Period *</label>
+          <input class="form-input" name="instalment" type="number" step="0.01" min="0.01" required />
+        </div>
+        <div class="form-group full-width">
+          <label class="form-label">Notes</label>
+          <input class="form-input" name="notes" />
+        </div>
+      </div>
+      <div class="form-actions">
+        <button type="button" class="btn btn-secondary" onclick="closeModal()">Close</button>
+        <button type="submit" class="btn btn-primary">Add</button>
+      </div>
+    </form>
+  `);
+  const modal = document.querySelector('.modal');
+  if (modal) modal.style.maxWidth = '800px';
+
+  $('#loan-form').onsubmit = async (e) => {
+    e.preventDefault();
+    const fd = new FormData(e.target);
+    try {
+      await invoke('create_employee_loan', {
+        employeeId,
+        loanType: fd.get('loan_type'),
+        principal: Number(fd.get('principal')),
+        instalment: Number(fd.get('instalment')),
+        startDate: fd.get('start_date'),
+        notes: fd.get('notes') || null,
+      });
+      toast('Loan recorded');
+      openEmployeeLoansModal(employeeId, employeeName);
+    } catch (err) { toast(err, 'error'); }
+  };
+
+  $$('.btn-loan-status').forEach(btn => {
+    btn.onclick = async () => {
+      try {
+        await invoke('update_employee_loan', { id: Number(btn.dataset.id), status: btn.dataset.status });
+        openEmployeeLoansModal(employeeId, employeeName);
+      } catch (err) { toast(err, 'error'); }
+    };
+  });
+
+  $$('.btn-loan-delete').forEach(btn => {
+    btn.onclick = async () => {
+      if (!confirm('Delete this loan?')) return;
+      try {
+        await invoke('delete_employee_loan', { id: Number(btn.dataset.id) });
+        openEmployeeLoansModal(employeeId, employeeName);
+      } catch (err) { toast(err, 'error'); }
+    };
+  });
+}
+
+async function openAttendanceModal(employeeId, employeeName, month = new Date().toISOString().slice(0, 7)) {
+  const [y, m] = month.split('-').map(Number);
+  const start = `${month}-01`;
+  const end = `${month}-${String(new Date(y, m, 0).getDate()).padStart(2, '0')}`;
+  const [records, leaveTypes, balances] = await Promise.all([
+    invoke('get_attendance', { employeeId, startDate: start, endDate: end }),
+    invoke('get_leave_types'),
+    invoke('get_leave_balances', { employeeId, year: y }),
+  ]);
+
+  openModal(`Attendance — ${employeeName}`, `
+    <div class="form-group" style="max-width:200px">
+      <label class="form-label">Month</label>
+      <input class="form-input" id="att-month" type="month" value="${month}" />
+    </div>
+
+    <div class="mb-4">
+      ${balances.filter(b => b.accrued > 0).map(b => `<span class="badge badge-sent" style="margin-right:6px">${escHtml(b.leave_type_name)}: ${b.balance} of ${b.accrued} day(s) left</span>`).join('')}
+    </div>
+
+    <div class="table-wrap">
+      <table>
+        <thead>
+          <tr><th>Date</th><th>Status</th><th>In</th><th>Out</th><th>Notes</th><th class="col-actions"></th></tr>
+        </thead>
+        <tbody>
+          ${records.length === 0
+            ? '<tr><td colspan="6" class="table-empty">No attendance recorded this month.</td></tr>'
+            : records.map(a => `
+              <tr>
+                <td>${a.date}</td>
+                <td>${a.status === 'Leave' ? `Leave (${escHtml(a.leave_type_name)})` : a.status === 'HalfDay' ? 'Half day' : a.status}</td>
+                <td>${a.check_in || '—'}</td>
+                <td>${a.check_out || '—'}</td>
+                <td>${escHtml(a.notes) || ''}</td>
+                <td><button class="btn btn-danger btn-sm btn-icon btn-delete-att" data-id="${a.id}">&times;</button></td>
+              </tr>
+            `).join('')}
+        </tbody>
+      </table>
+    </div>
+
+    <form id="attendance-form" class="mt-4">
+      <div class="form-grid">
+        <div class="form-group">
+          <label class="form-label">Date *</label>
+          <input class="form-input" name="date" type="date" required value="${new Date().toISOString().split('T')[0]}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Status</label>
+          <select class="form-select" name="status">
+            <option value="Present">Present</option>
+            <option value="Absent">Absent</option>
+            <option value="HalfDay">Half day</option>
+            <option value="Leave">Leave</option>
+            <option value="Holiday">Holiday (worked time is overtime)</option>
+          </select>
+        </div>
+        <div class="form-group">
+          <label class="form-label">Leave Type</label>
+          <select class="form-select" name="leave_type_id">
+            <option value="">—</option>
+            ${leaveTypes.filter(t => t.is_active).map(t => `<option value="${t.id}">${escHtml(t.name)}${t.is_paid ? '' : ' (unpaid)'}</option>`).join('')}
+          </select>
+        </div>
+        <div class="form-group">
+          <label class="form-label">Check In</label>
+          <input class="form-input" name="check_in" type="time" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Check Out</label>
+          <input class="form-input" name="check_out" type="time" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Notes</label>
+          <input class="form-input" name="notes" />
+        </div>
+      </div>
+      <div class="form-actions">
+        <button type="button" class="btn btn-secondary" onclick="closeModal()">Close</button>
+        <button type="submit" class="btn btn-primary">Save Day</button>
+      </div>
+    </form>
+  `);
+  const modal = document.querySelector('.modal');
+  if (modal) modal.style.maxWidth = '800px';
+
+  $('#att-month').onchange = (e) => {
+    if (e.target.value) openAttendanceModal(employeeId, employeeName, e.target.value);
+  };
+
+  $('#attendance-form').onsubmit = async (e) => {
+    e.preventDefault();
+    const fd = new FormData(e.target);
+    try {
+      await invoke('record_attendance', {
+        employeeId,
+        date: fd.get('date'),
+        status: fd.get('status'),
+        checkIn: fd.get('check_in') || null,
+        checkOut: fd.get('check_out') || null,
+        leaveTypeId: Number(fd.get('leave_type_id')) || null,
+        notes: fd.get('notes') || null,
+      });
+      toast('Attendance saved');
+      openAttendanceModal(employeeId, employeeName, fd.get('date').slice(0, 7));
+    } catch (err) { toast(err, 'error'); }
+  };
+
+  $$('.btn-delete-att').forEach(btn => {
+    btn.onclick = async () => {
+      try {
+        await invoke('delete_attendance', { id: Number(btn.dataset.id) });
+        openAttendanceModal(employeeId, employeeName, month);
+      } catch (err) { toast(err, 'error'); }
+    };
+  });
+}
+
+const PAY_FREQUENCIES = { Monthly: 'Monthly', BiWeekly: 'Bi-weekly', Weekly: 'Weekly' };
+
 function openEmployeeModal(data = null) {
   const isEdit = !!data;
   openModal(isEdit ? 'Edit Employee' : 'Add Employee', `
@@ -4125,6 +6276,24 @@ function openEmployeeModal(data = null) {
           <label class="form-label">Role</label>
           <input class="form-input" name="role" value="${escHtml(data?.role || '')}" />
         </div>
+        <div class="form-group">
+          <label class="form-label">Department</label>
+          <input class="form-input" name="department" value="${escHtml(data?.department || '')}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Status</label>
+          <select class="form-select" name="status">
+            ${['Active', 'Suspended', 'Terminated'].map(st => `<option value="${st}" ${(data?.status || 'Active') === st ? 'selected' : ''}>${st}</option>`).join('')}
+          </select>
+        </div>
+        <div class="form-group">
+          <label class="form-label">Join Date</label>
+          <input class="form-input" name="join_date" type="date" value="${data?.join_date || ''}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Exit Date (last day)</label>
+          <input class="form-input" name="exit_date" type="date" value="${data?.exit_date || ''}" />
+        </div>
         <div class="form-group">
           <label class="form-label">Email</label>
           <input class="form-input" name="email" type="email" value="${escHtml(data?.email || '')}" />
@@ -4133,14 +6302,48 @@ function openEmployeeModal(data = null) {
           <label class="form-label">Phone</label>
           <input class="form-input" name="phone" value="${escHtml(data?.phone || '')}" />
         </div>
-        <div class="form-group">
-          <label class="form-label">Salary *</label>
+        <div class="form-group pay-field">
+          <label class="form-label">Salary per Pay Period *</label>
           <input class="form-input" name="salary" type="number" step="0.01" min="0" required value="${data?.salary || ''}" />
         </div>
         <div class="form-group">
+          <label class="form-label">Pay Frequency</label>
+          <select class="form-select" name="pay_frequency">
+            ${Object.entries(PAY_FREQUENCIES).map(([v, label]) => `<option value="${v}" ${(data?.pay_frequency || 'Monthly') === v ? 'selected' : ''}>${label}</option>`).join('')}
+          </select>
+        </div>
+        <div class="form-group pay-field">
           <label class="form-label">Allowances</label>
           <input class="form-input" name="allowances" type="number" step="0.01" value="${data?.allowances || 0}" />
         </div>
+        <div class="form-group pay-field">
+          <label class="form-label">Overtime Rate (per hour)</label>
+          <input class="form-input" name="overtime_rate" type="number" step="0.01" min="0" value="${data?.overtime_rate || 0}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Standard Hours per Day</label>
+          <input class="form-input" name="standard_hours" type="number" step="0.25" min="0.25" max="24" value="${data?.standard_hours ?? 8}" />
+        </div>
+        <div class="form-group">
+          <label class="form-label">Shift Start</label>
+          <input class="form-input" name="shift_start" type="time" value="${data?.shift_start || ''}" />
+        </div>
+        <div class="form-group pay-field">
+          <label class="form-label">Late Penalty (per late arrival)</label>
+          <input class="form-input" name="late_penalty" type="number" step="0.01" min="0" value="${data?.late_penalty || 0}" />
+        </div>
+        <div class="form-group pay-field">
+          <label class="form-label">Bank</label>
+          <input class="form-input" name="bank_name" value="${escHtml(data?.bank_name || '')}" />
+        </div>
+        <div class="form-group pay-field">
+          <label class="form-label">Branch</label>
+          <input class="form-input" name="bank_branch" value="${escHtml(data?.bank_branch || '')}" />
+        </div>
+        <div class="form-group pay-field">
+          <label class="form-label">Account Number</label>
+          <input class="form-input" name="bank_account_no" value="${escHtml(data?.bank_account_no || '')}" />
+        </div>
       </div>
       <div class="form-actions">
         <button type="button" class="btn btn-secondary" onclick="closeModal()">Cancel</button>
@@ -4149,6 +6352,12 @@ function openEmployeeModal(data = null) {
     </form>
   `);
 
+  // Pay fields are hidden without payroll access; the backend keeps their stored values.
+  if (!hasPayrollAccess()) {
+    $$('#employee-form .pay-field').forEach(el => { el.style.display = 'none'; });
+    $('#employee-form [name="salary"]').required = false;
+  }
+
   $('#employee-form').onsubmit = async (e) => {
     e.preventDefault();
     const fd = new FormData(e.target);
@@ -4162,6 +6371,18 @@ function openEmployeeModal(data = null) {
           phone: fd.get('phone') || null,
           salary: Number(fd.get('salary')),
           allowances: Number(fd.get('allowances') || 0),
+          overtimeRate: Number(fd.get('overtime_rate') || 0),
+          standardHours: Number(fd.get('standard_hours') || 8),
+          shiftStart: fd.get('shift_start') || null,
+          latePenalty: Number(fd.get('late_penalty') || 0),
+          bankName: fd.get('bank_name').trim(),
+          bankBranch: fd.get('bank_branch').trim(),
+          bankAccountNo: fd.get('bank_account_no').trim(),
+          department: fd.get('department').trim(),
+          status: fd.get('status'),
+          joinDate: fd.get('join_date'),
+          exitDate: fd.get('exit_date'),
+          payFrequency: fd.get('pay_frequency'),
         });
       } else {
         await invoke('create_employee', {
@@ -4171,6 +6392,18 @@ function openEmployeeModal(data = null) {
           phone: fd.get('phone') || null,
           salary: Number(fd.get('salary')),
           allowances: Number(fd.get('allowances') || 0),
+          overtimeRate: Number(fd.get('overtime_rate') || 0),
+          standardHours: Number(fd.get('standard_hours') || 8),
+          shiftStart: fd.get('shift_start') || null,
+          latePenalty: Number(fd.get('late_penalty') || 0),
+          bankName: fd.get('bank_name').trim(),
+          bankBranch: fd.get('bank_branch').trim(),
+          bankAccountNo: fd.get('bank_account_no').trim(),
+          department: fd.get('department').trim(),
+          status: fd.get('status'),
+          joinDate: fd.get('join_date'),
+          exitDate: fd.get('exit_date'),
+          payFrequency: fd.get('pay_frequency'),
         });
       }
       toast(isEdit ? 'Employee updated' : 'Employee added');